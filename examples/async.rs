@@ -30,14 +30,15 @@ fn main() {
         write!(conout, "{}", sl_console::clear::CurrentLine).unwrap();
         write!(conout, "\r{:?}    <- This demonstrates the async read input char. Between each update a 100 ms. is waited, simply to demonstrate the async fashion. \n\r", evt).unwrap();
         match evt {
-            Ok(evt) => match evt {
-                Event::Key(key) => match key.code {
-                    KeyCode::Char('q') => break,
-                    KeyCode::Char('b') => blocking = !blocking,
-                    _ => {}
-                },
-                _ => {}
-            },
+            Ok(evt) => {
+                if let Event::Key(key) = evt {
+                    match key.code {
+                        KeyCode::Char('q') => break,
+                        KeyCode::Char('b') => blocking = !blocking,
+                        _ => {}
+                    }
+                }
+            }
             Err(err) if err.kind() == io::ErrorKind::WouldBlock => {
                 // Just means no data was ready (non-blocking)
             }
@@ -27,7 +27,7 @@ fn main() {
                 KeyCode::Char('q') => break,
                 _ => (),
             },
-            Event::Mouse(me) => match me {
+            Event::Mouse(me, _) => match me {
                 MouseEvent::Press(_, x, y) => {
                     write!(console, "{}x", sl_console::cursor::Goto(x, y)).unwrap();
                     //print!("{}x", sl_console::cursor::Goto(x, y));
@@ -41,12 +41,15 @@ fn main() {
                     log::info!("Key: {:?}.", key);
                 }
             },
-            Event::Mouse(me) => {
-                log::info!("Mouse Event: {:?}.", me);
+            Event::Mouse(me, mods) => {
+                log::info!("Mouse Event: {:?}, mods: {:?}.", me, mods);
             }
             Event::Unsupported(uns) => {
                 log::info!("Unsupported: {:?}.", uns);
             }
+            evt => {
+                log::info!("Event: {:?}.", evt);
+            }
         }
     }
 }
@@ -0,0 +1,7 @@
+use sl_console::*;
+
+fn main() {
+    con_init().unwrap();
+    let mut conout = conout().into_raw_mode().unwrap();
+    diagnostics::key_inspector(&mut conin(), &mut conout).unwrap();
+}
@@ -150,6 +150,9 @@ author:
     ticki.
 "#;
 
+/// Where the game counts terminal positions from.
+const ORIGIN: Origin = Origin::Zero;
+
 /// The game state.
 struct Game<R: ConsoleRead, W: Write> {
     /// Width of the grid.
@@ -202,7 +205,9 @@ fn init(difficulty: u8, w: u16, h: u16, rand_seed: u64) {
     // Grab the locked conin, in theory this will be faster but it is waiting
     // on input so is probably pointless.  We could not save conin in Game and
     // just use conin().get_key() or conin().get_event() as well.
-    let conin = conin().lock();
+    let mut conin = conin().lock();
+    // Count mouse positions from 0 like the board, see Game::cell.
+    conin.set_origin(ORIGIN);
     write!(conout, "{}", clear::All).unwrap();
 
     // Set the initial game state.
@@ -244,7 +249,7 @@ impl<R: ConsoleRead, W: Write> Drop for Game<R, W> {
             "{}{}{}",
             clear::All,
             style::Reset,
-            cursor::Goto(1, 1)
+            ORIGIN.goto(0, 0)
         )
         .is_err()
         {}
@@ -256,6 +261,27 @@ impl<R: ConsoleRead, W: Write> Drop for Game<R, W> {
 }
 
 impl<R: ConsoleRead, W: Write> Game<R, W> {
+    /// Go to cell (x, y), the board sits inside a one cell frame.
+    fn cell(x: u16, y: u16) -> Goto {
+        ORIGIN.goto(x + 1, y + 1)
+    }
+
+    /// The cell under the terminal position (a, b), if any.
+    fn cell_at(&self, a: u16, b: u16) -> Option<(u16, u16)> {
+        let (x, y) = (a.checked_sub(1)?, b.checked_sub(1)?);
+        if x < self.width && y < self.height {
+            Some((x, y))
+        } else {
+            None
+        }
+    }
+
+    /// Place the cursor on the current cell.
+    fn place_cursor(&mut self) {
+        write!(self.conout, "{}", Self::cell(self.x, self.y)).unwrap();
+        self.conout.flush().unwrap();
+    }
+
     /// Get the grid position of a given coordinate.
     fn pos(&self, x: u16, y: u16) -> usize {
         if x == u16::MAX || y == u16::MAX {
@@ -310,7 +336,7 @@ impl<R: ConsoleRead, W: Write> Game<R, W> {
             write!(
                 self.conout,
                 "{}{}{}{}{}",
-                cursor::Goto(x + 2, y + 2),
+                Self::cell(x, y),
                 Bg(Red),
                 Fg(Black),
                 MINE,
@@ -366,22 +392,22 @@ impl<R: ConsoleRead, W: Write> Game<R, W> {
                     Char('q') => return false,
                     _ => {}
                 },
-                Event::Mouse(me) => match me {
+                Event::Mouse(me, _) => match me {
                     MouseEvent::Press(MouseButton::Left, a, b) => {
-                        if a > 1 && a < (self.width + 2) && b > 1 && b < (self.height + 2) {
-                            self.x = a - 2;
-                            self.y = b - 2;
-                            cursor::goto(self.x + 2, self.y + 2).unwrap();
+                        if let Some((x, y)) = self.cell_at(a, b) {
+                            self.x = x;
+                            self.y = y;
+                            self.place_cursor();
                             if !self.click() {
                                 return false;
                             }
                         }
                     }
                     MouseEvent::Press(MouseButton::Right, a, b) => {
-                        if a > 1 && a < (self.width + 2) && b > 1 && b < (self.height + 2) {
-                            self.x = a - 2;
-                            self.y = b - 2;
-                            cursor::goto(self.x + 2, self.y + 2).unwrap();
+                        if let Some((x, y)) = self.cell_at(a, b) {
+                            self.x = x;
+                            self.y = y;
+                            self.place_cursor();
                             self.toggle_flag(self.x, self.y);
                         }
                     }
@@ -390,7 +416,7 @@ impl<R: ConsoleRead, W: Write> Game<R, W> {
                 _ => {}
             }
             // Make sure the cursor is placed on the current position.
-            cursor::goto(self.x + 2, self.y + 2).unwrap();
+            self.place_cursor();
             if self.chicken_dinner() {
                 return self.game_over(true);
             }
@@ -425,7 +451,7 @@ impl<R: ConsoleRead, W: Write> Game<R, W> {
     /// This will display the starting grid, and fill the old grid with random mines.
     fn reset(&mut self) {
         // Reset the cursor.
-        write!(self.conout, "{}", cursor::Goto(1, 1)).unwrap();
+        write!(self.conout, "{}", ORIGIN.goto(0, 0)).unwrap();
 
         // Write the upper part of the frame.
         self.conout.write(TOP_LEFT_CORNER.as_bytes()).unwrap();
@@ -456,7 +482,7 @@ impl<R: ConsoleRead, W: Write> Game<R, W> {
         }
         self.conout.write(BOTTOM_RIGHT_CORNER.as_bytes()).unwrap();
 
-        write!(self.conout, "{}", cursor::Goto(self.x + 2, self.y + 2)).unwrap();
+        write!(self.conout, "{}", Self::cell(self.x, self.y)).unwrap();
         self.conout.flush().unwrap();
 
         // Reset the grid.
@@ -508,7 +534,7 @@ impl<R: ConsoleRead, W: Write> Game<R, W> {
 
         self.get_mut(x, y).revealed = true;
 
-        write!(self.conout, "{}", cursor::Goto(x + 2, y + 2)).unwrap();
+        write!(self.conout, "{}", Self::cell(x, y)).unwrap();
 
         if v == 0 {
             // If the cell is free, simply put a space on the position.
@@ -545,7 +571,7 @@ impl<R: ConsoleRead, W: Write> Game<R, W> {
 
     /// Print the point count.
     fn print_points(&mut self) {
-        write!(self.conout, "{}", cursor::Goto(3, self.height + 2)).unwrap();
+        write!(self.conout, "{}", ORIGIN.goto(2, self.height + 1)).unwrap();
         self.conout
             .write(self.points.to_string().as_bytes())
             .unwrap();
@@ -553,11 +579,11 @@ impl<R: ConsoleRead, W: Write> Game<R, W> {
 
     /// Reveal all the fields, printing where the mines were.
     fn reveal_all(&mut self) {
-        write!(self.conout, "{}", cursor::Goto(1, 1)).unwrap();
+        write!(self.conout, "{}", ORIGIN.goto(0, 0)).unwrap();
 
         for y in 0..self.height {
             for x in 0..self.width {
-                write!(self.conout, "{}", cursor::Goto(x + 2, y + 2)).unwrap();
+                write!(self.conout, "{}", Self::cell(x, y)).unwrap();
                 let cell = self.get(x, y);
                 if cell.mine {
                     write!(
@@ -592,16 +618,26 @@ impl<R: ConsoleRead, W: Write> Game<R, W> {
         let termheight = termsize.map(|(_, h)| h).or_else(|| Some(6)).unwrap();
         //Goto bottom left corner
         if won {
-            write!(self.conout, "{}YOU WON!", cursor::Goto(1, termheight - 6)).unwrap();
+            write!(
+                self.conout,
+                "{}YOU WON!",
+                ORIGIN.goto(0, termheight.saturating_sub(7))
+            )
+            .unwrap();
         } else {
             write!(
                 self.conout,
                 "{}YOU LOST :(",
-                cursor::Goto(1, termheight - 6)
+                ORIGIN.goto(0, termheight.saturating_sub(7))
             )
             .unwrap();
         }
-        write!(self.conout, "{}", cursor::Goto(1, termheight - 5)).unwrap();
+        write!(
+            self.conout,
+            "{}",
+            ORIGIN.goto(0, termheight.saturating_sub(6))
+        )
+        .unwrap();
         self.conout.write(GAME_OVER.as_bytes()).unwrap();
         self.conout.flush().unwrap();
 
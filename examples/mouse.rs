@@ -25,8 +25,11 @@ fn main() {
                 KeyCode::Char('q') => break,
                 _ => {}
             },
-            Event::Mouse(me) => match me {
-                MouseEvent::Press(_, a, b) | MouseEvent::Release(a, b) | MouseEvent::Hold(a, b) => {
+            Event::Mouse(me, _) => match me {
+                MouseEvent::Press(_, a, b)
+                | MouseEvent::Release(_, a, b)
+                | MouseEvent::Hold(_, a, b)
+                | MouseEvent::Moved(a, b) => {
                     write!(console, "{}", cursor::Goto(a, b)).unwrap();
                     let (x, y) = cursor_pos().unwrap();
                     write!(
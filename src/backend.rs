@@ -0,0 +1,71 @@
+//! The system layer under the console.
+//!
+//! [`ConsoleIn`](crate::console::ConsoleIn) and
+//! [`ConsoleOut`](crate::console::ConsoleOut) do all the event parsing,
+//! timeouts, wake ups and raw mode bookkeeping on top of a [`SysBackend`],
+//! which only moves bytes and switches the terminal mode.  The console
+//! singletons use [`PlatformBackend`] (`/dev/tty` on unix, `CONIN$` and
+//! `CONOUT$` on Windows), other backends (a mock for tests, a network
+//! stream, a pseudo terminal) get the same behavior through
+//! [`Console::open`](crate::console::Console::open).
+
+use std::io::{self, Read, Write};
+use std::sync::Arc;
+use std::time::Duration;
+
+/// The console of the platform, the backend of [`conin`](crate::conin)
+/// and [`conout`](crate::conout).
+pub type PlatformBackend = crate::sys::console::SysConsoleIn;
+
+/// Interrupts a [`SysBackend::poll_or_wake`] from another thread.
+pub trait Wake: Send + Sync {
+    /// Make the current or next poll_or_wake return.
+    fn wake(&self) -> io::Result<()>;
+}
+
+/// The input side of a console backend, it also opens and switches the mode
+/// of its output side.
+///
+/// `read` (from `Read`) must not block, it is only called after a poll
+/// reported input.
+pub trait SysBackend: Read + Send + Sized + 'static {
+    /// The output side.
+    type Out: Write + Send;
+
+    /// Open the input and output.
+    fn open() -> io::Result<(Self, Self::Out)>;
+
+    /// Wait up to `timeout` (forever if None) for input.
+    ///
+    /// Returns true if input is ready, false if timed out.
+    fn poll(&mut self, timeout: Option<Duration>) -> bool;
+
+    /// Wait up to `timeout` (forever if None) for input, a wake up or a
+    /// watched descriptor.
+    ///
+    /// Returns true if input is ready, false if woken, timed out or only
+    /// watched descriptors are ready (see
+    /// [`take_ready`](SysBackend::take_ready)).
+    fn poll_or_wake(&mut self, timeout: Option<Duration>) -> bool;
+
+    /// Read, blocking until there is input.
+    fn read_block(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.poll(None);
+        self.read(buf)
+    }
+
+    /// Return a handle that interrupts poll_or_wake.
+    fn waker(&self) -> Arc<dyn Wake>;
+
+    /// Discard pending wake ups.
+    fn clear_wake(&mut self) {}
+
+    /// Return and clear the tokens of the watched descriptors found ready
+    /// by the last poll_or_wake.
+    fn take_ready(&mut self) -> Vec<u64> {
+        Vec::new()
+    }
+
+    /// Switch the terminal in (true) or out of (false) raw mode.
+    fn set_raw_mode(&self, out: &mut Self::Out, mode: bool) -> io::Result<()>;
+}
@@ -0,0 +1,133 @@
+//! Ringing the terminal bell.
+//!
+//! [`audible`] beeps, [`visual`] flashes the screen instead by briefly
+//! switching it to reverse video (DECSCNM).  Widgets that signal an error
+//! call [`ring`], which follows the [`BellStyle`] the user chose with
+//! [`set_preference`] so the choice is made once for the whole program.
+//!
+//! # Example
+//!
+//! ```rust,no_run
+//! use sl_console::bell::{self, BellStyle};
+//! use sl_console::*;
+//!
+//!     con_init().unwrap();
+//!     bell::set_preference(BellStyle::Visual);
+//!     // Flashes the screen.
+//!     bell::ring(&mut conout()).unwrap();
+//! ```
+
+use std::fmt;
+use std::io::{self, Write};
+use std::thread;
+use std::time::Duration;
+
+use lazy_static::lazy_static;
+use parking_lot::Mutex;
+
+use crate::console::conout_r;
+
+/// How long [`ring`] flashes the screen for.
+pub const FLASH_DURATION: Duration = Duration::from_millis(100);
+
+lazy_static! {
+    static ref PREFERENCE: Mutex<BellStyle> = Mutex::new(BellStyle::default());
+}
+
+/// The BEL character, rings the terminal's bell.
+#[derive(Copy, Clone, PartialEq, Eq)]
+pub struct Bell;
+
+impl fmt::Display for Bell {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("\x07")
+    }
+}
+
+derive_csi_sequence!(
+    "Show the screen in reverse video (DECSCNM).",
+    ReverseScreen,
+    "?5h"
+);
+derive_csi_sequence!(
+    "Show the screen in normal video (DECSCNM).",
+    NormalScreen,
+    "?5l"
+);
+
+/// How to get the user's attention.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, Default)]
+pub enum BellStyle {
+    /// Beep.
+    #[default]
+    Audible,
+    /// Flash the screen.
+    Visual,
+    /// Do nothing.
+    None,
+}
+
+/// Set how [`ring`] gets the user's attention, the default is
+/// [`BellStyle::Audible`].
+pub fn set_preference(style: BellStyle) {
+    *PREFERENCE.lock() = style;
+}
+
+/// Return how [`ring`] gets the user's attention.
+pub fn preference() -> BellStyle {
+    *PREFERENCE.lock()
+}
+
+/// Ring the console's bell.
+pub fn audible() -> io::Result<()> {
+    let mut conout = conout_r()?.lock();
+    write!(conout, "{}", Bell)?;
+    conout.flush()
+}
+
+/// Flash the screen of `conout` for `duration`.
+///
+/// The screen is switched to reverse video and back, blocking for
+/// `duration` in between.  Terminals without DECSCNM show nothing.
+pub fn visual<W: Write>(conout: &mut W, duration: Duration) -> io::Result<()> {
+    write!(conout, "{}", ReverseScreen)?;
+    conout.flush()?;
+    thread::sleep(duration);
+    write!(conout, "{}", NormalScreen)?;
+    conout.flush()
+}
+
+/// Get the user's attention on `conout` the way the user prefers, see
+/// [`set_preference`].
+pub fn ring<W: Write>(conout: &mut W) -> io::Result<()> {
+    match preference() {
+        BellStyle::Audible => {
+            write!(conout, "{}", Bell)?;
+            conout.flush()
+        }
+        BellStyle::Visual => visual(conout, FLASH_DURATION),
+        BellStyle::None => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_ring() {
+        let mut out = Vec::new();
+        visual(&mut out, Duration::ZERO).unwrap();
+        assert_eq!(out, b"\x1B[?5h\x1B[?5l");
+
+        let mut out = Vec::new();
+        assert_eq!(preference(), BellStyle::Audible);
+        ring(&mut out).unwrap();
+        set_preference(BellStyle::None);
+        ring(&mut out).unwrap();
+        set_preference(BellStyle::Visual);
+        ring(&mut out).unwrap();
+        set_preference(BellStyle::Audible);
+        assert_eq!(out, b"\x07\x1B[?5h\x1B[?5l");
+    }
+}
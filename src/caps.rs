@@ -0,0 +1,1287 @@
+//! Detect what the attached terminal supports.
+//!
+//! [`detect`] combines the environment (`TERM`, `COLORTERM`, the locale and
+//! a few terminal specific variables) with the replies to a handful of
+//! queries (primary device attributes, DECRQM mode reports and the kitty
+//! keyboard flags) into one [`TerminalCaps`].  The result is cached, use
+//! [`caps`] to get the cached value (detecting it on first use) instead of
+//! querying the terminal again.  [`terminal_version`] asks for the name and
+//! version of the terminal, for working around quirks of a particular one.
+//!
+//! # Overrides
+//!
+//! Users can correct a mis-detected terminal with these environment
+//! variables, they are read once (see [`overrides`]) and win over anything
+//! detected:
+//!
+//! - `SL_CONSOLE_FORCE_COLOR`: the color level, one of `none` (or `0`), `16`,
+//!   `256` or `truecolor` (or `24bit`).
+//! - `SL_CONSOLE_NO_MOUSE`: if set (and not `0`) report no mouse support and
+//!   do not turn mouse reporting on.
+//! - `SL_CONSOLE_TERM_QUIRKS`: the terminal name used to pick the built in
+//!   key quirk table instead of `$TERM`, `none` to use no table.
+//! - `SL_CONSOLE_DIALECT`: the output [`Dialect`], one of `xterm`, `vt100`,
+//!   `screen` or `tmux`.
+//!
+//! # Example
+//!
+//! ```rust,no_run
+//! use sl_console::caps::{self, ColorLevel};
+//! use sl_console::*;
+//!
+//!     con_init().unwrap();
+//!     let caps = caps::caps().unwrap();
+//!     if caps.colors >= ColorLevel::TrueColor {
+//!         println!("24 bit color");
+//!     }
+//! ```
+
+use std::env;
+use std::io::{self, Write};
+use std::ops;
+use std::str;
+use std::time::Duration;
+
+use lazy_static::lazy_static;
+use parking_lot::Mutex;
+
+use crate::clock::{Clock, Deadline, SYSTEM_CLOCK};
+use crate::color::{AnsiValue, Rgb};
+use crate::console::{conin_r, conout_r, ConsoleRead, ConsoleWrite};
+use crate::event::{CsiSequence, DeviceAttributes, Event, ModeReport};
+
+/// How long to wait for the terminal to answer a query.
+pub(crate) const QUERY_TIMEOUT: Duration = Duration::from_millis(200);
+
+/// Modes asked about with DECRQM, mouse protocols and synchronized output.
+const QUERY_MODES: &[u16] = &[1000, 1005, 1006, 1015, 1016, 2026];
+
+derive_csi_sequence!(
+    "Ask for the primary device attributes (DA1), the terminal replies with an [`Event::DeviceAttributes`].",
+    ReportPrimaryAttributes,
+    "c"
+);
+derive_csi_sequence!(
+    "Ask for the secondary device attributes (DA2), the terminal replies with an [`Event::DeviceAttributes`].",
+    ReportSecondaryAttributes,
+    ">c"
+);
+derive_csi_sequence!(
+    "Ask for the terminal's name and version (XTVERSION), the terminal replies with an [`Event::Dcs`], see [`terminal_version`].",
+    ReportVersion,
+    ">0q"
+);
+
+lazy_static! {
+    static ref CACHED_CAPS: Mutex<Option<TerminalCaps>> = Mutex::new(None);
+    static ref OVERRIDES: Overrides = Overrides::from_env();
+}
+
+/// User overrides for detected capabilities and behavior.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Overrides {
+    /// Color level from `SL_CONSOLE_FORCE_COLOR`.
+    pub color: Option<ColorLevel>,
+    /// `SL_CONSOLE_NO_MOUSE` is set, never enable mouse reporting.
+    pub no_mouse: bool,
+    /// Terminal name for the key quirk table from `SL_CONSOLE_TERM_QUIRKS`.
+    pub term_quirks: Option<String>,
+    /// Output dialect from `SL_CONSOLE_DIALECT`.
+    pub dialect: Option<Dialect>,
+}
+
+impl Overrides {
+    /// Read the overrides from the environment.
+    pub fn from_env() -> Self {
+        Self::from_vars(|name| env::var(name).ok())
+    }
+
+    /// Read the overrides from the variables returned by `var`.
+    pub fn from_vars<F>(var: F) -> Self
+    where
+        F: Fn(&str) -> Option<String>,
+    {
+        let color =
+            var("SL_CONSOLE_FORCE_COLOR").and_then(|v| match v.to_ascii_lowercase().as_str() {
+                "0" | "none" => Some(ColorLevel::None),
+                "16" => Some(ColorLevel::Ansi16),
+                "256" => Some(ColorLevel::Ansi256),
+                "truecolor" | "24bit" => Some(ColorLevel::TrueColor),
+                _ => None,
+            });
+        let no_mouse = var("SL_CONSOLE_NO_MOUSE").is_some_and(|v| !v.is_empty() && v != "0");
+        let term_quirks = var("SL_CONSOLE_TERM_QUIRKS").filter(|v| !v.is_empty());
+        let dialect =
+            var("SL_CONSOLE_DIALECT").and_then(|v| match v.to_ascii_lowercase().as_str() {
+                "xterm" => Some(Dialect::Xterm),
+                "vt100" => Some(Dialect::Vt100),
+                "screen" => Some(Dialect::Screen),
+                "tmux" => Some(Dialect::Tmux),
+                _ => None,
+            });
+        Overrides {
+            color,
+            no_mouse,
+            term_quirks,
+            dialect,
+        }
+    }
+
+    /// Apply the overrides to `caps`.
+    pub fn apply(&self, caps: &mut TerminalCaps) {
+        if let Some(dialect) = self.dialect {
+            caps.set_dialect(dialect);
+        }
+        if let Some(color) = self.color {
+            caps.colors = color;
+        }
+        if self.no_mouse {
+            caps.mouse = MouseProtocols::default();
+        }
+    }
+}
+
+/// The overrides from the environment, read the first time this (or
+/// [`con_init`](crate::con_init)) is called.
+pub fn overrides() -> &'static Overrides {
+    &OVERRIDES
+}
+
+/// The number of colors the terminal can display.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum ColorLevel {
+    /// No color support (dumb terminal).
+    None,
+    /// The 16 ANSI colors.
+    Ansi16,
+    /// The 256 color palette.
+    Ansi256,
+    /// 24 bit RGB color.
+    TrueColor,
+}
+
+/// How much of unicode the terminal can display.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum UnicodeLevel {
+    /// Only ASCII is safe to output.
+    Ascii,
+    /// The terminal uses UTF-8.
+    Unicode,
+}
+
+/// The family of terminal the output is written for.
+///
+/// Beyond the individual capabilities this decides how output is shaped
+/// for terminals that are not xterm compatible or for a multiplexer
+/// between the application and the terminal, see
+/// [`TerminalCaps::set_dialect`].
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, Default)]
+pub enum Dialect {
+    /// An xterm compatible terminal, output is written as is.
+    #[default]
+    Xterm,
+    /// A DEC VT100 (or a terminal emulating only it): no colors, no mouse
+    /// and ASCII only.
+    Vt100,
+    /// GNU screen: at most 256 colors, sequences screen does not understand
+    /// are passed through to the outer terminal.
+    Screen,
+    /// tmux: graphics sequences are passed through to the outer terminal.
+    Tmux,
+}
+
+impl Dialect {
+    /// True if `seq` (one complete escape sequence) has to be passed
+    /// through the multiplexer to reach the terminal.
+    ///
+    /// tmux drops APC strings (kitty graphics), screen also drops OSC 52
+    /// (clipboard) and DCS strings (sixel).
+    pub(crate) fn needs_passthrough(&self, seq: &[u8]) -> bool {
+        match (self, seq.get(1)) {
+            (Dialect::Tmux, Some(b'_')) => true,
+            (Dialect::Screen, Some(b'_')) | (Dialect::Screen, Some(b'P')) => true,
+            (Dialect::Screen, Some(b']')) => seq[2..].starts_with(b"52;"),
+            _ => false,
+        }
+    }
+
+    /// Write `seq` wrapped in the multiplexer's passthrough DCS, as is for
+    /// a dialect without one.
+    ///
+    /// tmux takes the sequence with every ESC doubled.  screen passes the
+    /// body of a DCS on as is but would end it at the first ST in `seq`, so
+    /// the sequence is split into one DCS per ESC (and per 760 bytes, the
+    /// most screen accepts).
+    pub(crate) fn write_passthrough<W: Write>(&self, out: &mut W, seq: &[u8]) -> io::Result<()> {
+        match self {
+            Dialect::Tmux => {
+                out.write_all(b"\x1bPtmux;")?;
+                for part in seq.split_inclusive(|b| *b == b'\x1b') {
+                    out.write_all(part)?;
+                    if part.last() == Some(&b'\x1b') {
+                        out.write_all(b"\x1b")?;
+                    }
+                }
+                out.write_all(b"\x1b\\")
+            }
+            Dialect::Screen => {
+                for part in seq.split_inclusive(|b| *b == b'\x1b') {
+                    for chunk in part.chunks(760) {
+                        out.write_all(b"\x1bP")?;
+                        out.write_all(chunk)?;
+                        out.write_all(b"\x1b\\")?;
+                    }
+                }
+                Ok(())
+            }
+            Dialect::Xterm | Dialect::Vt100 => out.write_all(seq),
+        }
+    }
+}
+
+/// The mouse reporting protocols the terminal supports.
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq, Hash)]
+pub struct MouseProtocols {
+    /// Basic button reporting (mode 1000).
+    pub x10: bool,
+    /// UTF-8 encoded coordinates (mode 1005).
+    pub utf8: bool,
+    /// SGR extended reporting (mode 1006).
+    pub sgr: bool,
+    /// urxvt extended reporting (mode 1015).
+    pub urxvt: bool,
+    /// SGR reporting with pixel coordinates (mode 1016).
+    pub sgr_pixels: bool,
+}
+
+/// The capabilities of a terminal.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TerminalCaps {
+    /// The terminal name from `$TERM`.
+    pub term: String,
+    /// The colors the terminal can display.
+    pub colors: ColorLevel,
+    /// How much of unicode the terminal can display.
+    pub unicode: UnicodeLevel,
+    /// The mouse protocols the terminal supports.
+    pub mouse: MouseProtocols,
+    /// The terminal supports the kitty keyboard protocol.
+    pub kitty_keyboard: bool,
+    /// The terminal supports synchronized output (mode 2026).
+    pub synchronized_output: bool,
+    /// The terminal supports OSC 8 hyperlinks.
+    pub hyperlinks: bool,
+    /// The terminal supports curly and colored underlines (SGR 4:3, 58).
+    pub styled_underline: bool,
+    /// The terminal shows blinking text (SGR 5 and 6).
+    pub blink: bool,
+    /// The terminal has alternate fonts (SGR 11 to 19).
+    pub alternate_fonts: bool,
+    /// The terminal supports sixel graphics.
+    pub sixel: bool,
+    /// The parameters of the primary device attributes reply, empty if the
+    /// terminal did not answer.
+    pub device_attributes: Vec<u16>,
+    /// How output is shaped for the terminal, see
+    /// [`set_dialect`](TerminalCaps::set_dialect).
+    pub dialect: Dialect,
+}
+
+impl Default for TerminalCaps {
+    /// The capabilities of a basic terminal: 16 colors, ASCII only, basic
+    /// mouse reporting.
+    fn default() -> Self {
+        TerminalCaps {
+            term: String::new(),
+            colors: ColorLevel::Ansi16,
+            unicode: UnicodeLevel::Ascii,
+            mouse: MouseProtocols {
+                x10: true,
+                ..Default::default()
+            },
+            kitty_keyboard: false,
+            synchronized_output: false,
+            hyperlinks: false,
+            styled_underline: false,
+            blink: true,
+            alternate_fonts: false,
+            sixel: false,
+            device_attributes: Vec::new(),
+            dialect: Dialect::Xterm,
+        }
+    }
+}
+
+impl TerminalCaps {
+    /// Capabilities guessed from the environment only, no queries are sent.
+    ///
+    /// The [`overrides`] are applied.
+    pub fn from_env() -> Self {
+        let mut caps = Self::from_vars(|name| env::var(name).ok());
+        overrides().apply(&mut caps);
+        caps
+    }
+
+    /// Capabilities guessed from the variables returned by `var`.
+    ///
+    /// This is [`TerminalCaps::from_env`] with a custom lookup, `var` is
+    /// called with a variable name and returns its value if set.
+    pub fn from_vars<F>(var: F) -> Self
+    where
+        F: Fn(&str) -> Option<String>,
+    {
+        let term = var("TERM").unwrap_or_default();
+        let colorterm = var("COLORTERM").unwrap_or_default();
+        let program = var("TERM_PROGRAM").unwrap_or_default();
+        let vte_version: u32 = var("VTE_VERSION").and_then(|v| v.parse().ok()).unwrap_or(0);
+        let windows_terminal = var("WT_SESSION").is_some();
+        let kitty = term.contains("kitty") || var("KITTY_WINDOW_ID").is_some();
+        let wezterm = program == "WezTerm";
+        let iterm = program == "iTerm.app";
+
+        let colors = if term == "dumb" {
+            ColorLevel::None
+        } else if colorterm == "truecolor"
+            || colorterm == "24bit"
+            || term.ends_with("-direct")
+            || kitty
+            || wezterm
+            || iterm
+            || windows_terminal
+        {
+            ColorLevel::TrueColor
+        } else if term.contains("256color") {
+            ColorLevel::Ansi256
+        } else {
+            ColorLevel::Ansi16
+        };
+
+        let locale = var("LC_ALL")
+            .filter(|v| !v.is_empty())
+            .or_else(|| var("LC_CTYPE").filter(|v| !v.is_empty()))
+            .or_else(|| var("LANG"))
+            .unwrap_or_default()
+            .to_ascii_lowercase();
+        let unicode = if cfg!(windows) || locale.contains("utf-8") || locale.contains("utf8") {
+            UnicodeLevel::Unicode
+        } else {
+            UnicodeLevel::Ascii
+        };
+
+        let modern = kitty || wezterm || iterm || windows_terminal || vte_version >= 5000;
+        let mouse = if term == "dumb" {
+            MouseProtocols::default()
+        } else {
+            MouseProtocols {
+                x10: true,
+                sgr: modern || term.starts_with("xterm"),
+                ..Default::default()
+            }
+        };
+
+        // VTE only blinks since 0.52.
+        let blink = term != "dumb" && (vte_version == 0 || vte_version >= 5200);
+        let alternate_fonts = term.starts_with("mlterm");
+
+        let dialect = if var("TMUX").is_some() || term.starts_with("tmux") {
+            Dialect::Tmux
+        } else if var("STY").is_some() || term.starts_with("screen") {
+            Dialect::Screen
+        } else if term.starts_with("vt1") || term.starts_with("vt2") {
+            Dialect::Vt100
+        } else {
+            Dialect::Xterm
+        };
+
+        let mut caps = TerminalCaps {
+            term,
+            colors,
+            unicode,
+            mouse,
+            kitty_keyboard: false,
+            synchronized_output: false,
+            hyperlinks: modern || program == "vscode",
+            styled_underline: kitty || wezterm || iterm || vte_version >= 5200,
+            blink,
+            alternate_fonts,
+            sixel: false,
+            device_attributes: Vec::new(),
+            dialect: Dialect::Xterm,
+        };
+        caps.set_dialect(dialect);
+        caps
+    }
+
+    /// Set the dialect and drop the capabilities it can not have.
+    ///
+    /// Under [`Dialect::Vt100`] there are no colors, mouse reporting or
+    /// unicode and none of the newer extensions.  Under [`Dialect::Screen`]
+    /// colors are limited to 256 since `COLORTERM` is usually inherited from
+    /// the outer terminal while screen itself does not pass 24 bit colors.
+    /// A [`CapabilityWriter`] uses the dialect to pass sequences through a
+    /// multiplexer.
+    pub fn set_dialect(&mut self, dialect: Dialect) {
+        self.dialect = dialect;
+        match dialect {
+            Dialect::Vt100 => {
+                self.colors = ColorLevel::None;
+                self.unicode = UnicodeLevel::Ascii;
+                self.mouse = MouseProtocols::default();
+                self.kitty_keyboard = false;
+                self.synchronized_output = false;
+                self.hyperlinks = false;
+                self.styled_underline = false;
+                self.alternate_fonts = false;
+                self.sixel = false;
+            }
+            Dialect::Screen => self.colors = self.colors.min(ColorLevel::Ansi256),
+            Dialect::Xterm | Dialect::Tmux => {}
+        }
+    }
+
+    /// Update the capabilities from a query reply.
+    ///
+    /// Returns true if `reply` was the primary device attributes reply, this
+    /// is asked for last so once it arrives all the replies have been seen.
+    pub fn apply_reply(&mut self, reply: &CsiSequence) -> bool {
+        if let Some(report) = reply.mode_report() {
+            if report.private && report.state.is_recognized() {
+                match report.mode {
+                    1000 => self.mouse.x10 = true,
+                    1005 => self.mouse.utf8 = true,
+                    1006 => self.mouse.sgr = true,
+                    1015 => self.mouse.urxvt = true,
+                    1016 => self.mouse.sgr_pixels = true,
+                    2026 => self.synchronized_output = true,
+                    _ => {}
+                }
+            }
+            return false;
+        }
+        match (reply.private, reply.final_byte) {
+            (Some(b'?'), b'u') => {
+                self.kitty_keyboard = true;
+                false
+            }
+            (_, b'c') => reply
+                .device_attributes()
+                .is_some_and(|attributes| self.apply_attributes(&attributes)),
+            _ => false,
+        }
+    }
+
+    /// Update the capabilities from a device attributes reply.
+    ///
+    /// Returns true if it was the primary device attributes reply, see
+    /// [`apply_reply`](TerminalCaps::apply_reply).
+    pub fn apply_attributes(&mut self, attributes: &DeviceAttributes) -> bool {
+        if attributes.secondary {
+            return false;
+        }
+        self.device_attributes = attributes.params.clone();
+        self.sixel = attributes.sixel();
+        true
+    }
+}
+
+/// Query the terminal and return its capabilities, the result is also
+/// cached for [`caps`].
+///
+/// The queries are written to `conout` and the replies read from `conin`,
+/// the console should be in raw mode or the replies will be echoed.  Any
+/// other input that arrives while waiting for the replies is discarded.  If
+/// the terminal does not answer then the capabilities are guessed from the
+/// environment.
+pub fn detect<R, W>(conin: &mut R, conout: &mut W) -> io::Result<TerminalCaps>
+where
+    R: ConsoleRead,
+    W: Write,
+{
+    let mut caps = TerminalCaps::from_env();
+    let mut query = String::new();
+    for mode in QUERY_MODES {
+        query.push_str(&format!("\x1b[?{}$p", mode));
+    }
+    // Kitty keyboard flags.
+    query.push_str("\x1b[?u");
+    // A terminal that does not answer leaves the guesses from the environment.
+    let _ = query_until_da1(
+        conin,
+        conout,
+        &query,
+        &SYSTEM_CLOCK,
+        |event, _| match event {
+            Event::Csi(reply) => {
+                caps.apply_reply(reply);
+            }
+            Event::DeviceAttributes(attributes) => {
+                caps.apply_attributes(attributes);
+            }
+            _ => {}
+        },
+    );
+    overrides().apply(&mut caps);
+
+    *CACHED_CAPS.lock() = Some(caps.clone());
+    Ok(caps)
+}
+
+/// Ask the terminal for the state of the DEC private `modes` (DECRQM).
+///
+/// The queries are written to `conout` and the replies read from `conin`,
+/// the console should be in raw mode.  Other input that arrives while
+/// waiting is discarded.  Returns the reports received, an error if the
+/// terminal did not answer in time.
+pub fn query_modes<R, W>(
+    conin: &mut R,
+    conout: &mut W,
+    modes: &[u16],
+) -> io::Result<Vec<ModeReport>>
+where
+    R: ConsoleRead,
+    W: Write,
+{
+    query_modes_with(conin, conout, modes, &SYSTEM_CLOCK)
+}
+
+fn query_modes_with<R, W>(
+    conin: &mut R,
+    conout: &mut W,
+    modes: &[u16],
+    clock: &dyn Clock,
+) -> io::Result<Vec<ModeReport>>
+where
+    R: ConsoleRead,
+    W: Write,
+{
+    let mut query = String::new();
+    for mode in modes {
+        query.push_str(&format!("\x1b[?{}$p", mode));
+    }
+    let mut reports = Vec::new();
+    let answered = query_until_da1(conin, conout, &query, clock, |event, _| {
+        if let Event::Csi(reply) = event {
+            if let Some(report) = reply.mode_report().filter(|report| report.private) {
+                reports.push(report);
+            }
+        }
+    })?;
+    if answered {
+        Ok(reports)
+    } else {
+        Err(io::Error::new(
+            io::ErrorKind::TimedOut,
+            "Mode query timed out.",
+        ))
+    }
+}
+
+/// Ask the terminal for its name and version (XTVERSION), for example
+/// `XTerm(367)` or `kitty(0.26.5)`.
+///
+/// The query is written to `conout` and the reply read from `conin`, the
+/// console should be in raw mode.  Other input that arrives while waiting
+/// is discarded.  Returns None if the terminal answered the device
+/// attributes query sent after it but not this one (it does not support
+/// XTVERSION), an error if the terminal did not answer in time.
+pub fn terminal_version<R, W>(conin: &mut R, conout: &mut W) -> io::Result<Option<String>>
+where
+    R: ConsoleRead,
+    W: Write,
+{
+    terminal_version_with(conin, conout, &SYSTEM_CLOCK)
+}
+
+fn terminal_version_with<R, W>(
+    conin: &mut R,
+    conout: &mut W,
+    clock: &dyn Clock,
+) -> io::Result<Option<String>>
+where
+    R: ConsoleRead,
+    W: Write,
+{
+    let mut version = None;
+    let answered = query_until_da1(conin, conout, ReportVersion.as_ref(), clock, |event, _| {
+        if let Event::Dcs(body) = event {
+            if version.is_none() {
+                version = parse_version(body);
+            }
+        }
+    })?;
+    if answered {
+        Ok(version)
+    } else {
+        Err(io::Error::new(
+            io::ErrorKind::TimedOut,
+            "Version query timed out.",
+        ))
+    }
+}
+
+/// Write `query` followed by a primary device attributes (DA1) request and
+/// pass every event read to `on_event` until the DA1 reply arrives.
+///
+/// Every terminal answers DA1 so it marks the end of the replies, it is
+/// passed to `on_event` as well.  Returns false if it did not arrive within
+/// [`QUERY_TIMEOUT`], an error if writing or reading failed.
+pub(crate) fn query_until_da1<R, W, F>(
+    conin: &mut R,
+    conout: &mut W,
+    query: &str,
+    clock: &dyn Clock,
+    mut on_event: F,
+) -> io::Result<bool>
+where
+    R: ConsoleRead,
+    W: Write,
+    F: FnMut(&Event, &[u8]),
+{
+    write!(conout, "{}\x1b[c", query)?;
+    conout.flush()?;
+
+    let deadline = Deadline::new(clock, QUERY_TIMEOUT);
+    while let Some(remaining) = deadline.remaining() {
+        match conin.get_event_and_raw(Some(remaining)) {
+            Some(Ok((event, raw))) => {
+                on_event(&event, &raw);
+                let primary = match &event {
+                    Event::DeviceAttributes(attributes) => !attributes.secondary,
+                    Event::Csi(reply) => reply
+                        .device_attributes()
+                        .is_some_and(|attributes| !attributes.secondary),
+                    _ => false,
+                };
+                if primary {
+                    return Ok(true);
+                }
+            }
+            Some(Err(err)) if err.kind() == io::ErrorKind::WouldBlock => break,
+            Some(Err(err)) => return Err(err),
+            None => break,
+        }
+    }
+    Ok(false)
+}
+
+/// The name and version from the body of an XTVERSION reply (`>|name`).
+pub(crate) fn parse_version(body: &[u8]) -> Option<String> {
+    body.strip_prefix(b">|")
+        .map(|version| String::from_utf8_lossy(version).into_owned())
+}
+
+/// Return the cached capabilities, detecting them with the console first if
+/// needed.
+///
+/// The console is put in raw mode for the detection if it is not already.
+pub fn caps() -> io::Result<TerminalCaps> {
+    if let Some(caps) = cached() {
+        return Ok(caps);
+    }
+    let mut conout = conout_r()?.lock();
+    let mut conin = conin_r()?.lock();
+    let was_raw = conout.set_raw_mode(true)?;
+    let caps = detect(&mut conin, &mut conout);
+    conout.set_raw_mode(was_raw)?;
+    caps
+}
+
+/// Return the cached capabilities if they have been detected.
+pub fn cached() -> Option<TerminalCaps> {
+    CACHED_CAPS.lock().clone()
+}
+
+/// Replace the cached capabilities, for instance to override something
+/// detected wrong.  None will cause the next call to [`caps`] to detect
+/// again.
+pub fn set_cached(caps: Option<TerminalCaps>) {
+    *CACHED_CAPS.lock() = caps;
+}
+
+/// Where the [`CapabilityWriter`] is in the output stream.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum FilterState {
+    /// Plain text.
+    Ground,
+    /// Just after an ESC.
+    Escape,
+    /// Inside a CSI sequence.
+    Csi,
+    /// Inside an OSC string.
+    Osc,
+    /// Inside a DCS string.
+    Dcs,
+    /// Inside an APC string.
+    Apc,
+    /// An ESC inside an OSC or DCS string (maybe the start of ST).
+    StringEscape,
+}
+
+/// Output wrapper that removes or rewrites sequences the terminal can not
+/// handle.
+///
+/// Applications can write the richest form of their output and it will
+/// degrade for the terminal described by the [`TerminalCaps`]:
+///
+/// - OSC 8 hyperlinks are removed leaving the link text as plain text.
+/// - Curly/dotted/dashed underlines become a plain underline and underline
+///   colors are removed.
+/// - Blinking text is shown without blinking.
+/// - Alternate font selections are removed.
+/// - Sixel images are replaced with a placeholder.
+/// - 24 bit and 256 colors are changed to the nearest color the terminal
+///   can display.
+/// - Under a multiplexer sequences it would drop are wrapped in its
+///   passthrough DCS, see [`Dialect`].
+///
+/// Sequences split across writes are held until they are complete.
+pub struct CapabilityWriter<W: Write> {
+    /// The output target.
+    output: W,
+    caps: TerminalCaps,
+    placeholder: String,
+    state: FilterState,
+    /// The bytes of the sequence being read.
+    pending: Vec<u8>,
+}
+
+impl<W: Write> CapabilityWriter<W> {
+    /// Wrap `output`, filtering for a terminal with `caps`.
+    pub fn new(output: W, caps: TerminalCaps) -> Self {
+        CapabilityWriter {
+            output,
+            caps,
+            placeholder: "[image]".to_string(),
+            state: FilterState::Ground,
+            pending: Vec::new(),
+        }
+    }
+
+    /// Set the text written in place of an image the terminal can not show.
+    pub fn set_placeholder(&mut self, placeholder: &str) {
+        self.placeholder = placeholder.to_string();
+    }
+
+    /// The capabilities output is filtered for.
+    pub fn caps(&self) -> &TerminalCaps {
+        &self.caps
+    }
+
+    /// Consume the wrapper and return the output target, any incomplete
+    /// sequence is dropped.
+    pub fn into_inner(self) -> W {
+        self.output
+    }
+
+    /// Write the sequence in pending (if allowed) and return to ground.
+    fn finish_sequence(&mut self) -> io::Result<()> {
+        let seq = std::mem::take(&mut self.pending);
+        self.state = FilterState::Ground;
+        match seq.get(1) {
+            Some(b'[') if seq.last() == Some(&b'm') => {
+                if let Some(params) = degrade_sgr(&seq[2..seq.len() - 1], &self.caps) {
+                    if !params.is_empty() {
+                        self.output.write_all(b"\x1b[")?;
+                        self.output.write_all(&params)?;
+                        self.output.write_all(b"m")?;
+                    }
+                    return Ok(());
+                }
+            }
+            Some(b']') if !self.caps.hyperlinks && seq[2..].starts_with(b"8;") => return Ok(()),
+            Some(b'P') if !self.caps.sixel && is_sixel(&seq[2..]) => {
+                return self.output.write_all(self.placeholder.as_bytes());
+            }
+            _ if self.caps.dialect.needs_passthrough(&seq) => {
+                return self.caps.dialect.write_passthrough(&mut self.output, &seq);
+            }
+            _ => {}
+        }
+        self.output.write_all(&seq)
+    }
+}
+
+/// True if the body of a DCS string is sixel data (params then q).
+fn is_sixel(body: &[u8]) -> bool {
+    body.iter().find(|b| !(b.is_ascii_digit() || **b == b';')) == Some(&b'q')
+}
+
+/// An extended color from an SGR sequence.
+#[derive(Debug, Copy, Clone)]
+enum SgrColor {
+    Ansi(u8),
+    Rgb(Rgb),
+}
+
+impl SgrColor {
+    /// Parse the arguments after 38/48/58, `5;n` or `2;r;g;b` (with an
+    /// optional color space id before r in the colon form).
+    fn parse(args: &[&str]) -> Option<SgrColor> {
+        match args {
+            ["5", n] => n.parse().ok().map(SgrColor::Ansi),
+            ["2", r, g, b] | ["2", _, r, g, b] => Some(SgrColor::Rgb(Rgb(
+                r.parse().ok()?,
+                g.parse().ok()?,
+                b.parse().ok()?,
+            ))),
+            _ => None,
+        }
+    }
+}
+
+/// The SGR parameters for one of the 16 basic colors.
+fn basic_color(fg: bool, index: u8) -> String {
+    let base = match (fg, index < 8) {
+        (true, true) => 30,
+        (false, true) => 40,
+        (true, false) => 90 - 8,
+        (false, false) => 100 - 8,
+    };
+    (base + index as u16).to_string()
+}
+
+/// The SGR parameters for `color` on a terminal with `level` colors, an
+/// empty string if the terminal has no colors or None if it can be used
+/// as is.
+fn degrade_color(fg: bool, color: SgrColor, level: ColorLevel) -> Option<String> {
+    let base = if fg { "38" } else { "48" };
+    match (color, level) {
+        (_, ColorLevel::None) => Some(String::new()),
+        (SgrColor::Rgb(rgb), ColorLevel::Ansi256) => {
+            Some(format!("{};5;{}", base, AnsiValue::nearest(rgb).0))
+        }
+        (SgrColor::Rgb(rgb), ColorLevel::Ansi16) => {
+            Some(basic_color(fg, AnsiValue::nearest_16(rgb).0))
+        }
+        (SgrColor::Ansi(index), ColorLevel::Ansi16) if index < 16 => Some(basic_color(fg, index)),
+        (SgrColor::Ansi(index), ColorLevel::Ansi16) => Some(basic_color(
+            fg,
+            AnsiValue::nearest_16(AnsiValue(index).to_rgb()).0,
+        )),
+        _ => None,
+    }
+}
+
+/// Rewrite the parameters of an SGR sequence for the terminal: colors are
+/// reduced to what it can display and styled underlines, underline colors,
+/// blinking or alternate fonts are removed if not supported.
+///
+/// Returns None if nothing needed to change.
+fn degrade_sgr(params: &[u8], caps: &TerminalCaps) -> Option<Vec<u8>> {
+    let params = str::from_utf8(params).ok()?;
+    let groups: Vec<&str> = params.split(';').collect();
+    let mut out: Vec<String> = Vec::with_capacity(groups.len());
+    let mut changed = false;
+    let mut i = 0;
+    while i < groups.len() {
+        let group = groups[i];
+        i += 1;
+        let mut sub = group.split(':');
+        let code = sub.next().unwrap_or_default();
+        match code {
+            // Extended colors, also skip over the color arguments so they
+            // are not read as attributes.
+            "38" | "48" | "58" => {
+                let (original, args) = if group.contains(':') {
+                    (group.to_string(), sub.collect::<Vec<_>>())
+                } else {
+                    let count = match groups.get(i).copied() {
+                        Some("5") => 2,
+                        Some("2") => 4,
+                        _ => 0,
+                    };
+                    let end = (i + count).min(groups.len());
+                    let args = groups[i..end].to_vec();
+                    i = end;
+                    (groups[i - args.len() - 1..i].join(";"), args)
+                };
+                let replacement = if code == "58" {
+                    if caps.styled_underline && caps.colors != ColorLevel::None {
+                        None
+                    } else {
+                        Some(String::new())
+                    }
+                } else {
+                    SgrColor::parse(&args)
+                        .and_then(|color| degrade_color(code == "38", color, caps.colors))
+                };
+                match replacement {
+                    Some(replacement) => {
+                        changed = true;
+                        if !replacement.is_empty() {
+                            out.push(replacement);
+                        }
+                    }
+                    None => out.push(original),
+                }
+            }
+            "59" if !caps.styled_underline => changed = true,
+            "5" | "6" | "25" if !caps.blink => changed = true,
+            _ if !caps.alternate_fonts && matches!(code.parse::<u8>(), Ok(10..=19)) => {
+                changed = true;
+            }
+            "4" if !caps.styled_underline && group.contains(':') => {
+                changed = true;
+                out.push(if group == "4:0" { "24" } else { "4" }.to_string());
+            }
+            _ if caps.colors == ColorLevel::None
+                && matches!(
+                    code.parse::<u8>(),
+                    Ok(30..=37 | 40..=47 | 90..=97 | 100..=107)
+                ) =>
+            {
+                changed = true;
+            }
+            _ => out.push(group.to_string()),
+        }
+    }
+    if changed {
+        Some(out.join(";").into_bytes())
+    } else {
+        None
+    }
+}
+
+impl<W: Write> ops::Deref for CapabilityWriter<W> {
+    type Target = W;
+
+    fn deref(&self) -> &W {
+        &self.output
+    }
+}
+
+impl<W: Write> ops::DerefMut for CapabilityWriter<W> {
+    fn deref_mut(&mut self) -> &mut W {
+        &mut self.output
+    }
+}
+
+impl<W: Write> Write for CapabilityWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let mut text_start = 0;
+        let mut i = 0;
+        while i < buf.len() {
+            let b = buf[i];
+            let text = self.state == FilterState::Ground && b != b'\x1b';
+            match self.state {
+                FilterState::Ground => {
+                    if b == b'\x1b' {
+                        self.output.write_all(&buf[text_start..i])?;
+                        self.state = FilterState::Escape;
+                        self.pending.push(b);
+                    }
+                }
+                FilterState::Escape => {
+                    self.pending.push(b);
+                    match b {
+                        b'[' => self.state = FilterState::Csi,
+                        b']' => self.state = FilterState::Osc,
+                        b'P' => self.state = FilterState::Dcs,
+                        b'_' => self.state = FilterState::Apc,
+                        _ => self.finish_sequence()?,
+                    }
+                }
+                FilterState::Csi => {
+                    self.pending.push(b);
+                    if (0x40..=0x7e).contains(&b) {
+                        self.finish_sequence()?;
+                    }
+                }
+                FilterState::Osc | FilterState::Dcs | FilterState::Apc => {
+                    self.pending.push(b);
+                    if b == b'\x1b' {
+                        self.state = FilterState::StringEscape;
+                    } else if b == b'\x07' && self.pending[1] == b']' {
+                        self.finish_sequence()?;
+                    }
+                }
+                FilterState::StringEscape => {
+                    if b == b'\\' {
+                        self.pending.push(b);
+                        self.finish_sequence()?;
+                    } else {
+                        // Not ST so the string was never terminated, pass it
+                        // on and handle the ESC as the start of a new
+                        // sequence.
+                        self.pending.pop();
+                        self.output.write_all(&self.pending)?;
+                        self.pending.clear();
+                        self.pending.push(b'\x1b');
+                        self.state = FilterState::Escape;
+                        continue;
+                    }
+                }
+            }
+            if !text {
+                text_start = i + 1;
+            }
+            i += 1;
+        }
+        if self.state == FilterState::Ground {
+            self.output.write_all(&buf[text_start..])?;
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.output.flush()
+    }
+}
+
+impl<W: ConsoleWrite> ConsoleWrite for CapabilityWriter<W> {
+    fn set_raw_mode(&mut self, mode: bool) -> io::Result<bool> {
+        self.output.set_raw_mode(mode)
+    }
+
+    fn is_raw_mode(&self) -> bool {
+        self.output.is_raw_mode()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::event::parse_event;
+
+    fn vars(vars: &[(&str, &str)]) -> TerminalCaps {
+        TerminalCaps::from_vars(|name| {
+            vars.iter()
+                .find(|(n, _)| *n == name)
+                .map(|(_, v)| v.to_string())
+        })
+    }
+
+    fn reply(caps: &mut TerminalCaps, raw: &[u8]) -> bool {
+        let mut iter = raw[1..].iter().map(|b| Ok(*b));
+        match parse_event(raw[0], &mut iter).unwrap() {
+            Event::Csi(csi) => caps.apply_reply(&csi),
+            Event::DeviceAttributes(attributes) => caps.apply_attributes(&attributes),
+            evt => panic!("not a csi reply: {:?}", evt),
+        }
+    }
+
+    #[test]
+    fn test_from_vars() {
+        let caps = vars(&[("TERM", "xterm-256color"), ("LANG", "en_US.UTF-8")]);
+        assert_eq!(caps.colors, ColorLevel::Ansi256);
+        assert_eq!(caps.unicode, UnicodeLevel::Unicode);
+        assert!(caps.mouse.sgr);
+        assert!(!caps.hyperlinks);
+
+        let caps = vars(&[("TERM", "xterm"), ("COLORTERM", "truecolor")]);
+        assert_eq!(caps.colors, ColorLevel::TrueColor);
+
+        let caps = vars(&[("TERM", "dumb")]);
+        assert_eq!(caps.colors, ColorLevel::None);
+        assert_eq!(caps.mouse, MouseProtocols::default());
+
+        let caps = vars(&[("TERM", "xterm-kitty")]);
+        assert!(caps.hyperlinks);
+        assert!(caps.styled_underline);
+        assert!(caps.blink);
+
+        let caps = vars(&[("TERM", "xterm-256color"), ("VTE_VERSION", "5000")]);
+        assert!(!caps.blink);
+        assert!(!caps.alternate_fonts);
+        assert!(vars(&[("TERM", "mlterm")]).alternate_fonts);
+    }
+
+    fn filter(caps: TerminalCaps, writes: &[&[u8]]) -> Vec<u8> {
+        let mut writer = CapabilityWriter::new(Vec::new(), caps);
+        for buf in writes {
+            writer.write_all(buf).unwrap();
+        }
+        writer.into_inner()
+    }
+
+    #[test]
+    fn test_capability_writer() {
+        let basic = TerminalCaps::default();
+        let link: &[u8] = b"see \x1b]8;;https://example.com\x1b\\here\x1b]8;;\x07.";
+        assert_eq!(filter(basic.clone(), &[link]), b"see here.".to_vec());
+        assert_eq!(
+            filter(basic.clone(), &[b"a\x1b[4:3;58;2;1;2;3;1mb\x1b[59;4:0m"]),
+            b"a\x1b[4;1mb\x1b[24m".to_vec()
+        );
+        // Only underline color, nothing left to write.
+        assert_eq!(filter(basic.clone(), &[b"a\x1b[58:5:3mb"]), b"ab".to_vec());
+        // 4 here is a blue argument, not underline.
+        let rgb: &[u8] = b"\x1b[38;2;1;4;3m\x1b[1m";
+        let truecolor = TerminalCaps {
+            colors: ColorLevel::TrueColor,
+            ..TerminalCaps::default()
+        };
+        assert_eq!(filter(truecolor, &[rgb]), rgb.to_vec());
+        assert_eq!(
+            filter(basic.clone(), &[b"x\x1bP0;1q#0;2;0;0;0~~\x1b\\y"]),
+            b"x[image]y".to_vec()
+        );
+        // Split across writes.
+        assert_eq!(
+            filter(
+                basic.clone(),
+                &[b"a\x1b", b"]8;;u", b"rl\x1b", b"\\b\x1b[4:", b"3mc"]
+            ),
+            b"ab\x1b[4mc".to_vec()
+        );
+        let no_blink = TerminalCaps {
+            blink: false,
+            ..TerminalCaps::default()
+        };
+        assert_eq!(
+            filter(no_blink, &[b"a\x1b[1;5mb\x1b[6mc\x1b[25m"]),
+            b"a\x1b[1mbc".to_vec()
+        );
+        assert_eq!(
+            filter(basic.clone(), &[b"a\x1b[13;1mb\x1b[10m"]),
+            b"a\x1b[1mb".to_vec()
+        );
+
+        let mut modern = TerminalCaps::default();
+        modern.hyperlinks = true;
+        modern.styled_underline = true;
+        modern.sixel = true;
+        assert_eq!(filter(modern, &[link]), link.to_vec());
+    }
+
+    #[test]
+    fn test_capability_writer_colors() {
+        let colors = |colors| TerminalCaps {
+            colors,
+            ..TerminalCaps::default()
+        };
+        let rgb: &[u8] = b"\x1b[1;38;2;250;10;10;48:2::0:0:0m";
+        assert_eq!(
+            filter(colors(ColorLevel::Ansi256), &[rgb]),
+            b"\x1b[1;38;5;196;48;5;16m".to_vec()
+        );
+        assert_eq!(
+            filter(colors(ColorLevel::Ansi16), &[rgb]),
+            b"\x1b[1;91;40m".to_vec()
+        );
+        assert_eq!(
+            filter(colors(ColorLevel::Ansi16), &[b"\x1b[38;5;4;48;5;231m"]),
+            b"\x1b[34;107m".to_vec()
+        );
+        assert_eq!(
+            filter(colors(ColorLevel::None), &[rgb, b"\x1b[31;49m"]),
+            b"\x1b[1m\x1b[49m".to_vec()
+        );
+    }
+
+    #[test]
+    fn test_overrides() {
+        let overrides = Overrides::from_vars(|name| match name {
+            "SL_CONSOLE_FORCE_COLOR" => Some("256".to_string()),
+            "SL_CONSOLE_NO_MOUSE" => Some("1".to_string()),
+            _ => None,
+        });
+        assert_eq!(overrides.color, Some(ColorLevel::Ansi256));
+        assert!(overrides.no_mouse);
+        assert_eq!(overrides.term_quirks, None);
+        let mut caps = vars(&[("TERM", "xterm-kitty")]);
+        overrides.apply(&mut caps);
+        assert_eq!(caps.colors, ColorLevel::Ansi256);
+        assert_eq!(caps.mouse, MouseProtocols::default());
+
+        let overrides = Overrides::from_vars(|name| match name {
+            "SL_CONSOLE_NO_MOUSE" => Some("0".to_string()),
+            _ => None,
+        });
+        assert_eq!(overrides, Overrides::default());
+
+        let overrides = Overrides::from_vars(|name| match name {
+            "SL_CONSOLE_DIALECT" => Some("VT100".to_string()),
+            _ => None,
+        });
+        let mut caps = vars(&[("TERM", "xterm-kitty")]);
+        overrides.apply(&mut caps);
+        assert_eq!(caps.dialect, Dialect::Vt100);
+        assert_eq!(caps.colors, ColorLevel::None);
+        assert!(!caps.hyperlinks);
+    }
+
+    #[test]
+    fn test_dialect() {
+        assert_eq!(vars(&[("TERM", "xterm")]).dialect, Dialect::Xterm);
+        assert_eq!(vars(&[("TERM", "vt220")]).dialect, Dialect::Vt100);
+        let caps = vars(&[("TERM", "screen-256color"), ("COLORTERM", "truecolor")]);
+        assert_eq!(caps.dialect, Dialect::Screen);
+        assert_eq!(caps.colors, ColorLevel::Ansi256);
+        let caps = vars(&[
+            ("TERM", "screen-256color"),
+            ("TMUX", "/tmp/tmux-1000/default,1,0"),
+            ("COLORTERM", "truecolor"),
+        ]);
+        assert_eq!(caps.dialect, Dialect::Tmux);
+        assert_eq!(caps.colors, ColorLevel::TrueColor);
+
+        let copy: &[u8] = b"a\x1b]52;c;aGk=\x07b";
+        let image: &[u8] = b"\x1b_Gf=100;AAAA\x1b\\";
+        let mut tmux = TerminalCaps::default();
+        tmux.set_dialect(Dialect::Tmux);
+        assert_eq!(filter(tmux.clone(), &[copy]), copy.to_vec());
+        assert_eq!(
+            filter(tmux, &[image]),
+            b"\x1bPtmux;\x1b\x1b_Gf=100;AAAA\x1b\x1b\\\x1b\\".to_vec()
+        );
+        let mut screen = TerminalCaps::default();
+        screen.set_dialect(Dialect::Screen);
+        assert_eq!(
+            filter(screen.clone(), &[copy]),
+            b"a\x1bP\x1b\x1b\\\x1bP]52;c;aGk=\x07\x1b\\b".to_vec()
+        );
+        // No sixel support, still replaced.
+        assert_eq!(filter(screen, &[b"\x1bPq#0~\x1b\\"]), b"[image]".to_vec());
+        assert_eq!(filter(TerminalCaps::default(), &[image]), image.to_vec());
+    }
+
+    #[test]
+    fn test_apply_reply() {
+        let mut caps = vars(&[("TERM", "xterm")]);
+        assert!(!reply(&mut caps, b"\x1b[?2026;2$y"));
+        assert!(caps.synchronized_output);
+        assert!(!reply(&mut caps, b"\x1b[?1016;0$y"));
+        assert!(!caps.mouse.sgr_pixels);
+        assert!(!reply(&mut caps, b"\x1b[?1015;2$y"));
+        assert!(caps.mouse.urxvt);
+        assert!(!reply(&mut caps, b"\x1b[?1u"));
+        assert!(caps.kitty_keyboard);
+        assert!(reply(&mut caps, b"\x1b[?62;4;22c"));
+        assert!(caps.sixel);
+        assert_eq!(caps.device_attributes, vec![62, 4, 22]);
+    }
+
+    #[test]
+    fn test_query_modes() {
+        let clock = crate::clock::TestClock::new();
+        let mut out = Vec::new();
+        let mut conin = &b"\x1b[?1000;1$y\x1b[?1006;2$y\x1b[?62c"[..];
+        let reports = query_modes_with(&mut conin, &mut out, &[1000, 1006], &clock).unwrap();
+        assert_eq!(out, b"\x1b[?1000$p\x1b[?1006$p\x1b[c");
+        assert_eq!(reports.len(), 2);
+        assert_eq!(reports[1].mode, 1006);
+
+        let mut conin = crate::clock::SilentConsole { clock: &clock };
+        let err = query_modes_with(&mut conin, &mut out, &[1000], &clock).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::TimedOut);
+    }
+
+    #[test]
+    fn test_terminal_version() {
+        let clock = crate::clock::TestClock::new();
+        let mut out = Vec::new();
+        let mut conin = &b"x\x1bP>|XTerm(367)\x1b\\\x1b[?62;22c"[..];
+        let version = terminal_version_with(&mut conin, &mut out, &clock).unwrap();
+        assert_eq!(out, b"\x1b[>0q\x1b[c");
+        assert_eq!(version.as_deref(), Some("XTerm(367)"));
+
+        let mut conin = &b"\x1bP1$r0m\x1b\\\x1b[?1;2c"[..];
+        let version = terminal_version_with(&mut conin, &mut out, &clock).unwrap();
+        assert_eq!(version, None);
+
+        let mut conin = crate::clock::SilentConsole { clock: &clock };
+        let err = terminal_version_with(&mut conin, &mut out, &clock).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::TimedOut);
+    }
+}
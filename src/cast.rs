@@ -0,0 +1,289 @@
+//! Record and play terminal output as asciinema casts.
+//!
+//! A [`Recorder`] wraps a writer, passes the output through unchanged and
+//! records it with timestamps in the
+//! [asciinema v2](https://docs.asciinema.org/manual/asciicast/v2/) format,
+//! which can be played with `asciinema play` or embedded in documentation.
+//! [`play`] writes a recorded cast back out with its original timing.
+//!
+//! # Example
+//!
+//! ```rust,no_run
+//! use sl_console::cast::{self, Recorder};
+//! use sl_console::*;
+//! use std::fs::File;
+//! use std::io::{BufReader, Write};
+//!
+//!     con_init().unwrap();
+//!     let (cols, rows) = terminal_size().unwrap();
+//!     let file = File::create("demo.cast").unwrap();
+//!     let mut out = Recorder::new(conout(), file, cols, rows).unwrap();
+//!     write!(out, "{}Hello", clear::All).unwrap();
+//!     out.flush().unwrap();
+//!     drop(out);
+//!
+//!     let cast = BufReader::new(File::open("demo.cast").unwrap());
+//!     cast::play(cast, &mut conout(), 1.0).unwrap();
+//! ```
+
+use std::io::{self, BufRead, Write};
+use std::str;
+use std::thread;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use crate::clock::{Clock, SYSTEM_CLOCK};
+
+/// A writer that records everything written through it as a cast.
+pub struct Recorder<W: Write, C: Write> {
+    inner: W,
+    cast: C,
+    clock: &'static dyn Clock,
+    start: Instant,
+    // The start of a UTF-8 character split across writes.
+    partial: Vec<u8>,
+}
+
+impl<W: Write, C: Write> Recorder<W, C> {
+    /// Record the output written to `inner` into `cast`, `width` and
+    /// `height` are the terminal size stored in the header.
+    ///
+    /// Writes the cast header immediately.
+    pub fn new(inner: W, cast: C, width: u16, height: u16) -> io::Result<Self> {
+        Recorder::with_clock(inner, cast, width, height, &SYSTEM_CLOCK)
+    }
+
+    pub(crate) fn with_clock(
+        inner: W,
+        mut cast: C,
+        width: u16,
+        height: u16,
+        clock: &'static dyn Clock,
+    ) -> io::Result<Self> {
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |time| time.as_secs());
+        writeln!(
+            cast,
+            "{{\"version\": 2, \"width\": {}, \"height\": {}, \"timestamp\": {}}}",
+            width, height, timestamp
+        )?;
+        Ok(Recorder {
+            inner,
+            cast,
+            clock,
+            start: clock.now(),
+            partial: Vec::new(),
+        })
+    }
+
+    /// Return the wrapped writer and the cast.
+    ///
+    /// The end of a UTF-8 character split by the last write is not
+    /// recorded.
+    pub fn into_inner(self) -> (W, C) {
+        (self.inner, self.cast)
+    }
+
+    fn record(&mut self, bytes: &[u8]) -> io::Result<()> {
+        let mut data = std::mem::take(&mut self.partial);
+        data.extend_from_slice(bytes);
+        // Keep an incomplete character for the next write.
+        if let Err(err) = str::from_utf8(&data) {
+            if err.error_len().is_none() {
+                self.partial = data.split_off(err.valid_up_to());
+            }
+        }
+        if data.is_empty() {
+            return Ok(());
+        }
+        let elapsed = self.clock.now().saturating_duration_since(self.start);
+        writeln!(
+            self.cast,
+            "[{:.6}, \"o\", \"{}\"]",
+            elapsed.as_secs_f64(),
+            json_escape(&String::from_utf8_lossy(&data))
+        )
+    }
+}
+
+impl<W: Write, C: Write> Write for Recorder<W, C> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let len = self.inner.write(buf)?;
+        self.record(&buf[..len])?;
+        Ok(len)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()?;
+        self.cast.flush()
+    }
+}
+
+fn json_escape(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    for ch in text.chars() {
+        match ch {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            ch if (ch as u32) < 0x20 || ch == '\x7F' => {
+                out.push_str(&format!("\\u{:04x}", ch as u32))
+            }
+            ch => out.push(ch),
+        }
+    }
+    out
+}
+
+fn invalid(msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, format!("Invalid cast: {}", msg))
+}
+
+/// Parse the four hex digits of a `\u` escape.
+fn hex4(chars: &mut str::CharIndices) -> io::Result<u32> {
+    let digits: String = chars.take(4).map(|(_, ch)| ch).collect();
+    u32::from_str_radix(&digits, 16).map_err(|_| invalid("bad \\u escape"))
+}
+
+/// Parse a JSON string starting after the opening quote, returns the string
+/// and the rest after the closing quote.
+fn parse_json_string(text: &str) -> io::Result<(String, &str)> {
+    let mut out = String::new();
+    let mut chars = text.char_indices();
+    while let Some((i, ch)) = chars.next() {
+        match ch {
+            '"' => return Ok((out, &text[i + 1..])),
+            '\\' => {
+                let escaped = chars
+                    .next()
+                    .ok_or_else(|| invalid("unterminated string"))?
+                    .1;
+                out.push(match escaped {
+                    'n' => '\n',
+                    'r' => '\r',
+                    't' => '\t',
+                    'b' => '\x08',
+                    'f' => '\x0C',
+                    'u' => {
+                        let mut code = hex4(&mut chars)?;
+                        // A UTF-16 surrogate pair.
+                        if (0xD800..0xDC00).contains(&code) {
+                            if chars.next().map(|c| c.1) != Some('\\')
+                                || chars.next().map(|c| c.1) != Some('u')
+                            {
+                                return Err(invalid("unpaired surrogate"));
+                            }
+                            let low = hex4(&mut chars)?;
+                            code = 0x10000
+                                + ((code - 0xD800) << 10)
+                                + (low.wrapping_sub(0xDC00) & 0x3FF);
+                        }
+                        char::from_u32(code).unwrap_or(char::REPLACEMENT_CHARACTER)
+                    }
+                    ch => ch,
+                });
+            }
+            ch => out.push(ch),
+        }
+    }
+    Err(invalid("unterminated string"))
+}
+
+/// Parse an event line, returns the time, the event type and the data.
+fn parse_event(line: &str) -> io::Result<(f64, String, String)> {
+    let line = line
+        .trim()
+        .strip_prefix('[')
+        .ok_or_else(|| invalid("event is not an array"))?;
+    let (time, rest) = line
+        .split_once(',')
+        .ok_or_else(|| invalid("missing event type"))?;
+    let time: f64 = time.trim().parse().map_err(|_| invalid("bad time"))?;
+    let rest = rest
+        .trim_start()
+        .strip_prefix('"')
+        .ok_or_else(|| invalid("missing event type"))?;
+    let (kind, rest) = parse_json_string(rest)?;
+    let rest = rest
+        .trim_start()
+        .strip_prefix(',')
+        .and_then(|rest| rest.trim_start().strip_prefix('"'))
+        .ok_or_else(|| invalid("missing event data"))?;
+    let (data, _) = parse_json_string(rest)?;
+    Ok((time, kind, data))
+}
+
+/// Write the output recorded in `cast` to `out` with the recorded timing.
+///
+/// `speed` scales the playback rate, 2.0 plays twice as fast.  Events
+/// other than output are skipped.
+pub fn play<R: BufRead, W: Write>(cast: R, out: &mut W, speed: f64) -> io::Result<()> {
+    let start = Instant::now();
+    let mut lines = cast.lines();
+    let header = lines.next().transpose()?.unwrap_or_default();
+    if !header.trim_start().starts_with('{') {
+        return Err(invalid("missing header"));
+    }
+    for line in lines {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let (time, kind, data) = parse_event(&line)?;
+        if kind != "o" {
+            continue;
+        }
+        let due = Duration::from_secs_f64((time / speed).max(0.0));
+        if let Some(wait) = due.checked_sub(start.elapsed()) {
+            out.flush()?;
+            thread::sleep(wait);
+        }
+        out.write_all(data.as_bytes())?;
+    }
+    out.flush()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::clock::TestClock;
+
+    #[test]
+    fn test_recorder() {
+        let clock: &'static TestClock = Box::leak(Box::new(TestClock::new()));
+        let mut rec = Recorder::with_clock(Vec::new(), Vec::new(), 80, 24, clock).unwrap();
+        rec.write_all(b"\x1B[1mhi \"there\"\n").unwrap();
+        clock.advance(Duration::from_millis(1500));
+        // A character split across writes is recorded once complete.
+        rec.write_all(b"\\\xE6\xBC").unwrap();
+        rec.write_all(b"\xA2").unwrap();
+        let (out, cast) = rec.into_inner();
+        assert_eq!(out, "\x1B[1mhi \"there\"\n\\漢".as_bytes());
+
+        let cast = String::from_utf8(cast).unwrap();
+        let lines: Vec<&str> = cast.lines().collect();
+        assert!(lines[0].starts_with("{\"version\": 2, \"width\": 80, \"height\": 24,"));
+        assert_eq!(
+            &lines[1..],
+            [
+                "[0.000000, \"o\", \"\\u001b[1mhi \\\"there\\\"\\n\"]",
+                "[1.500000, \"o\", \"\\\\\"]",
+                "[1.500000, \"o\", \"漢\"]",
+            ]
+        );
+
+        let mut played = Vec::new();
+        play(cast.as_bytes(), &mut played, 1000.0).unwrap();
+        assert_eq!(played, out);
+    }
+
+    #[test]
+    fn test_parse_event() {
+        let (time, kind, data) = parse_event(r#"[0.25, "o", "\ud83d\ude00\t\u00e9"]"#).unwrap();
+        assert_eq!((time, kind.as_str(), data.as_str()), (0.25, "o", "😀\té"));
+        assert!(parse_event(r#"[0.25, "o", "open"#).is_err());
+        assert!(play(&b"[1.0, \"o\", \"x\"]\n"[..], &mut Vec::new(), 1.0).is_err());
+    }
+}
@@ -0,0 +1,205 @@
+//! Run a program in a terminal inside the console.
+//!
+//! A [`ChildTerminal`] runs a command in its own pseudo terminal, forwards
+//! the user's input and resizes to it and keeps its screen in a
+//! [`Scraper`], the core of an embedded terminal widget (a multiplexer
+//! pane, a terminal inside an editor).  The output is also kept as a byte
+//! stream for recorders or for passing straight through.
+//!
+//! Requires the `pty` feature and is only available on unix.
+//!
+//! # Example
+//!
+//! ```rust,no_run
+//! use sl_console::child::ChildTerminal;
+//! use sl_console::*;
+//! use std::io::Write;
+//! use std::process::Command;
+//! use std::time::Duration;
+//!
+//!     con_init().unwrap();
+//!     let mut conin = conin().lock();
+//!     let mut conout = conout();
+//!     let mut child = ChildTerminal::spawn(&mut Command::new("top"), (80, 24)).unwrap();
+//!     child.follow_resizes().unwrap();
+//!     conout.raw_mode_on().unwrap();
+//!     while child.read_output(Some(Duration::from_millis(10))).unwrap() {
+//!         conout.write_all(&child.take_output()).unwrap();
+//!         conout.flush().unwrap();
+//!         if let Some(Ok((event, raw))) = conin.get_event_and_raw(Some(Duration::ZERO)) {
+//!             child.forward(&event, &raw).unwrap();
+//!         }
+//!     }
+//!     conout.raw_mode_off().unwrap();
+//! ```
+
+use std::fs::File;
+use std::io::{self, Write};
+use std::os::unix::io::{AsRawFd, RawFd};
+use std::process::{Child, Command, ExitStatus};
+use std::sync::mpsc::Receiver;
+use std::time::Duration;
+
+use crate::event::Event;
+use crate::resize::resize_channel;
+use crate::scrape::Scraper;
+use crate::sys::pty::{read_master, set_size, spawn};
+
+/// A program running in its own pseudo terminal.
+pub struct ChildTerminal {
+    master: File,
+    child: Child,
+    screen: Scraper,
+    // Output read since the last take_output.
+    output: Vec<u8>,
+    eof: bool,
+    resized: Option<Receiver<(u16, u16)>>,
+}
+
+impl ChildTerminal {
+    /// Spawn `command` in a new pseudo terminal of `size` (columns, rows).
+    ///
+    /// The terminal is the command's stdin, stdout, stderr and controlling
+    /// terminal.
+    pub fn spawn(command: &mut Command, size: (u16, u16)) -> io::Result<Self> {
+        let (cols, rows) = size;
+        let (master, child) = spawn(command, cols, rows)?;
+        Ok(ChildTerminal {
+            master,
+            child,
+            screen: Scraper::new(cols, rows),
+            output: Vec::new(),
+            eof: false,
+            resized: None,
+        })
+    }
+
+    /// Resize the child's terminal along with the console from now on.
+    ///
+    /// The new sizes are applied by [`read_output`](ChildTerminal::read_output).
+    /// Returns an error if the resize watcher could not be started.
+    pub fn follow_resizes(&mut self) -> io::Result<()> {
+        self.resized = Some(resize_channel()?);
+        Ok(())
+    }
+
+    /// Resize the terminal, the child gets SIGWINCH.
+    pub fn resize(&mut self, cols: u16, rows: u16) -> io::Result<()> {
+        set_size(self.master.as_raw_fd(), cols, rows)?;
+        self.screen.resize(cols, rows);
+        Ok(())
+    }
+
+    /// Write `input` to the terminal as if it was typed.
+    pub fn send(&mut self, input: &[u8]) -> io::Result<()> {
+        self.master.write_all(input)?;
+        self.master.flush()
+    }
+
+    /// Pass a console event on to the child.
+    ///
+    /// Keys, mouse events and input that did not parse are sent as the
+    /// bytes they were read from (`raw`), the rest (ticks, wake ups and
+    /// replies to the console's own queries) are not the child's.  Returns
+    /// true if the event was sent.
+    pub fn forward(&mut self, event: &Event, raw: &[u8]) -> io::Result<bool> {
+        match event {
+            Event::Key(_)
+            | Event::Grapheme(_)
+            | Event::Mouse(..)
+            | Event::MousePixels(..)
+            | Event::Unsupported(_)
+            | Event::Garbled(_)
+                if !raw.is_empty() =>
+            {
+                self.send(raw)?;
+                Ok(true)
+            }
+            _ => Ok(false),
+        }
+    }
+
+    /// Apply pending resizes and read the child's output, waiting at most
+    /// `timeout` (forever if None) for some.
+    ///
+    /// Returns false once the child closed the terminal (usually because
+    /// it exited).
+    pub fn read_output(&mut self, timeout: Option<Duration>) -> io::Result<bool> {
+        if let Some(size) = self.resized.as_ref().and_then(|r| r.try_iter().last()) {
+            self.resize(size.0, size.1)?;
+        }
+        if self.eof {
+            return Ok(false);
+        }
+        let start = self.output.len();
+        if !read_master(&mut self.master, timeout, &mut self.output)? {
+            self.eof = true;
+        }
+        self.screen.write_all(&self.output[start..])?;
+        Ok(!self.eof)
+    }
+
+    /// The child's screen.
+    pub fn screen(&self) -> &Scraper {
+        &self.screen
+    }
+
+    /// Return the output read since the last call.
+    pub fn take_output(&mut self) -> Vec<u8> {
+        std::mem::take(&mut self.output)
+    }
+
+    /// The child process.
+    pub fn child(&mut self) -> &mut Child {
+        &mut self.child
+    }
+
+    /// Wait for the child to exit, reading the rest of its output.
+    pub fn wait(&mut self) -> io::Result<ExitStatus> {
+        while self.read_output(None)? {}
+        self.child.wait()
+    }
+}
+
+impl Drop for ChildTerminal {
+    fn drop(&mut self) {
+        if let Ok(None) = self.child.try_wait() {
+            let _ = self.child.kill();
+            let _ = self.child.wait();
+        }
+    }
+}
+
+/// The master side of the terminal, readable when the child wrote output,
+/// for [`ConsoleIn::watch_fd`](crate::console::ConsoleIn::watch_fd).
+impl AsRawFd for ChildTerminal {
+    fn as_raw_fd(&self) -> RawFd {
+        self.master.as_raw_fd()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::event::{Key, KeyCode};
+
+    #[test]
+    fn test_child_terminal() {
+        let mut command = Command::new("sh");
+        command.args([
+            "-c",
+            "stty -echo; printf 'name? '; read name; printf '\\033[2;3Hhi %s' \"$name\"",
+        ]);
+        let mut child = ChildTerminal::spawn(&mut command, (20, 3)).unwrap();
+        while !child.screen().lines()[0].starts_with("name?") {
+            assert!(child.read_output(Some(Duration::from_secs(5))).unwrap());
+        }
+        let key = Event::Key(Key::new(KeyCode::Char('x')));
+        assert!(child.forward(&key, b"x").unwrap());
+        assert!(!child.forward(&Event::Tick, b"").unwrap());
+        child.send(b"\r").unwrap();
+        assert!(child.wait().unwrap().success());
+        assert_eq!(child.screen().lines(), vec!["name?", "  hi x", ""]);
+        assert!(child.take_output().ends_with(b"hi x"));
+    }
+}
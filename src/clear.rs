@@ -1,7 +1,5 @@
 //! Clearing the screen.
 
-use std::fmt;
-
 derive_csi_sequence!("Clear the entire screen.", All, "2J");
 derive_csi_sequence!("Clear everything after the cursor.", AfterCursor, "J");
 derive_csi_sequence!("Clear everything before the cursor.", BeforeCursor, "1J");
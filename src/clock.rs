@@ -0,0 +1,142 @@
+//! Time source for timeouts.
+//!
+//! Code that waits with a timeout reads the time through a [`Clock`] so
+//! tests can substitute a [`TestClock`] that only moves when told to,
+//! instead of depending on real sleeps.
+
+use std::time::{Duration, Instant};
+
+/// A source of the current time.
+pub(crate) trait Clock: Send + Sync {
+    /// The current time.
+    fn now(&self) -> Instant;
+}
+
+/// The real time.
+#[derive(Debug, Default, Copy, Clone)]
+pub(crate) struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> Instant {
+        Instant::now()
+    }
+}
+
+/// The clock used outside of tests.
+pub(crate) static SYSTEM_CLOCK: SystemClock = SystemClock;
+
+/// The time a wait has to end by.
+pub(crate) struct Deadline<'a> {
+    clock: &'a dyn Clock,
+    end: Instant,
+}
+
+impl<'a> Deadline<'a> {
+    /// A deadline `timeout` from now.
+    pub(crate) fn new(clock: &'a dyn Clock, timeout: Duration) -> Self {
+        Deadline {
+            clock,
+            end: clock.now() + timeout,
+        }
+    }
+
+    /// The time left, None once the deadline has been reached.
+    pub(crate) fn remaining(&self) -> Option<Duration> {
+        self.end
+            .checked_duration_since(self.clock.now())
+            .filter(|remaining| !remaining.is_zero())
+    }
+}
+
+#[cfg(test)]
+pub(crate) use self::testing::*;
+
+#[cfg(test)]
+mod testing {
+    use super::*;
+    use crate::console::ConsoleRead;
+    use crate::event::Event;
+    use parking_lot::Mutex;
+    use std::io;
+
+    /// A clock that only moves when advanced.
+    pub(crate) struct TestClock {
+        now: Mutex<Instant>,
+    }
+
+    impl TestClock {
+        pub(crate) fn new() -> Self {
+            TestClock {
+                now: Mutex::new(Instant::now()),
+            }
+        }
+
+        pub(crate) fn advance(&self, by: Duration) {
+            *self.now.lock() += by;
+        }
+    }
+
+    impl Clock for TestClock {
+        fn now(&self) -> Instant {
+            *self.now.lock()
+        }
+    }
+
+    /// Console input that never has data, every wait advances `clock` by
+    /// the full timeout.
+    pub(crate) struct SilentConsole<'a> {
+        pub(crate) clock: &'a TestClock,
+    }
+
+    impl<'a> SilentConsole<'a> {
+        fn wait(&self, timeout: Option<Duration>) -> io::Error {
+            self.clock
+                .advance(timeout.expect("silent console waited forever"));
+            io::Error::new(io::ErrorKind::WouldBlock, "timed out")
+        }
+    }
+
+    impl<'a> ConsoleRead for SilentConsole<'a> {
+        fn get_event_and_raw(
+            &mut self,
+            timeout: Option<Duration>,
+        ) -> Option<io::Result<(Event, Vec<u8>)>> {
+            Some(Err(self.wait(timeout)))
+        }
+
+        fn poll(&mut self, timeout: Option<Duration>) -> bool {
+            self.wait(timeout);
+            false
+        }
+
+        fn read_timeout(
+            &mut self,
+            _buf: &mut [u8],
+            timeout: Option<Duration>,
+        ) -> io::Result<usize> {
+            Err(self.wait(timeout))
+        }
+    }
+
+    impl<'a> io::Read for SilentConsole<'a> {
+        fn read(&mut self, _buf: &mut [u8]) -> io::Result<usize> {
+            Err(self.wait(None))
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_deadline() {
+        let clock = TestClock::new();
+        let deadline = Deadline::new(&clock, Duration::from_millis(100));
+        assert_eq!(deadline.remaining(), Some(Duration::from_millis(100)));
+        clock.advance(Duration::from_millis(60));
+        assert_eq!(deadline.remaining(), Some(Duration::from_millis(40)));
+        clock.advance(Duration::from_millis(40));
+        assert_eq!(deadline.remaining(), None);
+    }
+}
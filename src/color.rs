@@ -1,5 +1,7 @@
 //! Color managemement.
 //!
+//! Colors are written following the [style policy](crate::style::set_policy).
+//!
 //! # Example
 //!
 //! ```rust
@@ -14,6 +16,8 @@ use numtoa::NumToA;
 use std::fmt;
 use std::fmt::Debug;
 
+use crate::style;
+
 /// A terminal color.
 pub trait Color: Debug {
     /// Write the foreground version of this color.
@@ -127,6 +131,94 @@ impl AnsiValue {
     }
 }
 
+/// The default xterm colors for the 16 basic ANSI colors.
+const ANSI16_RGB: [Rgb; 16] = [
+    Rgb(0, 0, 0),
+    Rgb(205, 0, 0),
+    Rgb(0, 205, 0),
+    Rgb(205, 205, 0),
+    Rgb(0, 0, 238),
+    Rgb(205, 0, 205),
+    Rgb(0, 205, 205),
+    Rgb(229, 229, 229),
+    Rgb(127, 127, 127),
+    Rgb(255, 0, 0),
+    Rgb(0, 255, 0),
+    Rgb(255, 255, 0),
+    Rgb(92, 92, 255),
+    Rgb(255, 0, 255),
+    Rgb(0, 255, 255),
+    Rgb(255, 255, 255),
+];
+
+/// The channel values of the 6x6x6 color cube.
+const CUBE_LEVELS: [u8; 6] = [0, 95, 135, 175, 215, 255];
+
+/// Squared distance between two colors.
+fn distance(a: Rgb, b: Rgb) -> u32 {
+    let d = |x: u8, y: u8| (x as i32 - y as i32).pow(2) as u32;
+    d(a.0, b.0) + d(a.1, b.1) + d(a.2, b.2)
+}
+
+/// Index of the cube level closest to `c`.
+fn nearest_cube_level(c: u8) -> u8 {
+    match c {
+        0..=47 => 0,
+        48..=114 => 1,
+        _ => (c - 35) / 40,
+    }
+}
+
+impl AnsiValue {
+    /// The RGB color of this value using the default xterm palette (the
+    /// first 16 colors are often changed by user themes).
+    pub fn to_rgb(self) -> Rgb {
+        match self.0 {
+            0..=15 => ANSI16_RGB[self.0 as usize],
+            16..=231 => {
+                let i = self.0 - 16;
+                Rgb(
+                    CUBE_LEVELS[(i / 36) as usize],
+                    CUBE_LEVELS[(i / 6 % 6) as usize],
+                    CUBE_LEVELS[(i % 6) as usize],
+                )
+            }
+            _ => {
+                let gray = 8 + 10 * (self.0 - 232);
+                Rgb(gray, gray, gray)
+            }
+        }
+    }
+
+    /// The color in the 256 color palette closest to `rgb`.
+    ///
+    /// Only the color cube and grayscale ramp (16-255) are considered since
+    /// the first 16 colors vary between terminals.
+    pub fn nearest(rgb: Rgb) -> AnsiValue {
+        let Rgb(r, g, b) = rgb;
+        let cube = AnsiValue::rgb(
+            nearest_cube_level(r),
+            nearest_cube_level(g),
+            nearest_cube_level(b),
+        );
+        let average = (r as u32 + g as u32 + b as u32) / 3;
+        let gray = AnsiValue::grayscale((average.saturating_sub(3) / 10).min(23) as u8);
+        if distance(gray.to_rgb(), rgb) < distance(cube.to_rgb(), rgb) {
+            gray
+        } else {
+            cube
+        }
+    }
+
+    /// The basic color (0-15) closest to `rgb`.
+    pub fn nearest_16(rgb: Rgb) -> AnsiValue {
+        let index = (0..16)
+            .min_by_key(|i| distance(ANSI16_RGB[*i], rgb))
+            .unwrap_or(0);
+        AnsiValue(index as u8)
+    }
+}
+
 impl AnsiValue {
     /// Returns the ANSI sequence as a string.
     pub fn fg_string(self) -> String {
@@ -185,6 +277,118 @@ impl Rgb {
     }
 }
 
+impl Rgb {
+    /// The relative luminance of this color (0.0 for black to 1.0 for
+    /// white) as defined by WCAG 2.
+    pub fn luminance(self) -> f64 {
+        fn channel(c: u8) -> f64 {
+            let c = c as f64 / 255.0;
+            if c <= 0.03928 {
+                c / 12.92
+            } else {
+                ((c + 0.055) / 1.055).powf(2.4)
+            }
+        }
+        0.2126 * channel(self.0) + 0.7152 * channel(self.1) + 0.0722 * channel(self.2)
+    }
+
+    /// Linearly interpolate between this color and `other`, `t` 0.0 is this
+    /// color and 1.0 is `other` (clamped to that range).
+    pub fn lerp(self, other: Rgb, t: f64) -> Rgb {
+        let t = t.clamp(0.0, 1.0);
+        let mix = |a: u8, b: u8| (a as f64 + (b as f64 - a as f64) * t).round() as u8;
+        Rgb(
+            mix(self.0, other.0),
+            mix(self.1, other.1),
+            mix(self.2, other.2),
+        )
+    }
+}
+
+/// `steps` colors evenly spaced from `from` to `to` (both included).
+pub fn gradient(from: Rgb, to: Rgb, steps: usize) -> Vec<Rgb> {
+    match steps {
+        0 => Vec::new(),
+        1 => vec![from],
+        _ => (0..steps)
+            .map(|i| from.lerp(to, i as f64 / (steps - 1) as f64))
+            .collect(),
+    }
+}
+
+/// Text with a foreground gradient from the first color to the second, one
+/// step per character.
+///
+/// The foreground is reset after the text.
+///
+/// # Example
+///
+/// ```rust
+/// use sl_console::color::{GradientText, Rgb};
+///
+///     println!("{}", GradientText("Loading...", Rgb(255, 0, 0), Rgb(0, 0, 255)));
+/// ```
+#[derive(Debug, Clone, Copy)]
+pub struct GradientText<'a>(pub &'a str, pub Rgb, pub Rgb);
+
+impl<'a> fmt::Display for GradientText<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let steps = self.0.chars().count();
+        for (ch, color) in self.0.chars().zip(gradient(self.1, self.2, steps)) {
+            write!(f, "{}{}", Fg(color), ch)?;
+        }
+        if style::enabled() {
+            f.write_str(RESET_FG)?;
+        }
+        Ok(())
+    }
+}
+
+/// The WCAG contrast ratio between two colors, from 1.0 (no contrast) to
+/// 21.0 (black on white).
+pub fn contrast_ratio(a: Rgb, b: Rgb) -> f64 {
+    let (la, lb) = (a.luminance(), b.luminance());
+    let (light, dark) = if la > lb { (la, lb) } else { (lb, la) };
+    (light + 0.05) / (dark + 0.05)
+}
+
+/// Black or white, whichever is more readable on `bg`.
+pub fn contrasting_fg(bg: Rgb) -> Rgb {
+    let (black, white) = (Rgb(0, 0, 0), Rgb(255, 255, 255));
+    if contrast_ratio(black, bg) >= contrast_ratio(white, bg) {
+        black
+    } else {
+        white
+    }
+}
+
+/// Return `fg` adjusted so it has at least a contrast `ratio` against `bg`.
+///
+/// If `fg` already has enough contrast it is returned unchanged, otherwise
+/// it is moved towards black or white (whichever is more readable on `bg`)
+/// just far enough.  WCAG asks for 4.5 for normal text and 3.0 for large
+/// text, ratios that can not be reached return black or white.
+pub fn ensure_contrast(fg: Rgb, bg: Rgb, ratio: f64) -> Rgb {
+    if contrast_ratio(fg, bg) >= ratio {
+        return fg;
+    }
+    let target = contrasting_fg(bg);
+    if contrast_ratio(target, bg) < ratio {
+        return target;
+    }
+    // Binary search for the smallest mix that reaches the ratio.
+    let (mut low, mut high) = (0.0, 1.0);
+    for _ in 0..16 {
+        let mid = (low + high) / 2.0;
+        if contrast_ratio(fg.lerp(target, mid), bg) >= ratio {
+            high = mid;
+        } else {
+            low = mid;
+        }
+    }
+    fg.lerp(target, high)
+}
+
 impl Color for Rgb {
     #[inline]
     fn write_fg(&self, f: &mut fmt::Formatter) -> fmt::Result {
@@ -233,6 +437,9 @@ pub struct Fg<C: Color>(pub C);
 
 impl<C: Color> fmt::Display for Fg<C> {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if !style::enabled() {
+            return Ok(());
+        }
         self.0.write_fg(f)
     }
 }
@@ -243,6 +450,66 @@ pub struct Bg<C: Color>(pub C);
 
 impl<C: Color> fmt::Display for Bg<C> {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if !style::enabled() {
+            return Ok(());
+        }
         self.0.write_bg(f)
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_contrast() {
+        let (black, white) = (Rgb(0, 0, 0), Rgb(255, 255, 255));
+        assert!((contrast_ratio(black, white) - 21.0).abs() < 0.01);
+        assert!((contrast_ratio(white, white) - 1.0).abs() < 0.01);
+        assert_eq!(contrasting_fg(Rgb(255, 255, 0)), black);
+        assert_eq!(contrasting_fg(Rgb(0, 0, 128)), white);
+
+        let bg = Rgb(0x26, 0x4f, 0x78);
+        let fg = Rgb(0x40, 0x60, 0x80);
+        assert!(contrast_ratio(fg, bg) < 4.5);
+        let fixed = ensure_contrast(fg, bg, 4.5);
+        assert!(contrast_ratio(fixed, bg) >= 4.5);
+        assert_ne!(fixed, white);
+        assert_eq!(ensure_contrast(white, bg, 4.5), white);
+        assert_eq!(ensure_contrast(fg, Rgb(128, 128, 128), 30.0), black);
+    }
+
+    #[test]
+    fn test_ansi_rgb() {
+        assert_eq!(AnsiValue(1).to_rgb(), Rgb(205, 0, 0));
+        assert_eq!(AnsiValue(16).to_rgb(), Rgb(0, 0, 0));
+        assert_eq!(AnsiValue(196).to_rgb(), Rgb(255, 0, 0));
+        assert_eq!(AnsiValue(110).to_rgb(), Rgb(135, 175, 215));
+        assert_eq!(AnsiValue(232).to_rgb(), Rgb(8, 8, 8));
+        assert_eq!(AnsiValue(255).to_rgb(), Rgb(238, 238, 238));
+        for i in 16..=255 {
+            assert_eq!(AnsiValue::nearest(AnsiValue(i).to_rgb()).0, i);
+        }
+        assert_eq!(AnsiValue::nearest(Rgb(250, 10, 10)).0, 196);
+        assert_eq!(AnsiValue::nearest(Rgb(100, 101, 99)).0, 241);
+        assert_eq!(AnsiValue::nearest_16(Rgb(250, 10, 10)).0, 9);
+        assert_eq!(AnsiValue::nearest_16(Rgb(10, 10, 10)).0, 0);
+    }
+
+    #[test]
+    fn test_gradient() {
+        let (black, white) = (Rgb(0, 0, 0), Rgb(255, 255, 255));
+        assert_eq!(black.lerp(white, 0.5), Rgb(128, 128, 128));
+        assert_eq!(black.lerp(white, 2.0), white);
+        assert!(gradient(black, white, 0).is_empty());
+        assert_eq!(gradient(black, white, 1), vec![black]);
+        assert_eq!(
+            gradient(Rgb(0, 0, 0), Rgb(100, 0, 200), 3),
+            vec![Rgb(0, 0, 0), Rgb(50, 0, 100), Rgb(100, 0, 200)]
+        );
+        assert_eq!(
+            GradientText("ab", black, white).to_string(),
+            "\x1b[38;2;0;0;0ma\x1b[38;2;255;255;255mb\x1b[39m"
+        );
+    }
+}
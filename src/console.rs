@@ -12,34 +12,50 @@
 //! con_init() fails then calls to conin()/conout() will panic.  It is ok to
 //! call conin_r()/conout_r() but you will have to deal with the error and
 //! conin()/conout() will always work if con_init() was successful.
+//!
+//! The same input and output logic runs on other backends (see
+//! [`backend`](crate::backend)) through a [`Console`].
 
 use std::cell::RefCell;
+use std::collections::VecDeque;
 use std::io::{self, Read, Write};
-use std::time::Duration;
+use std::process::{Command, ExitStatus};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
 
 use lazy_static::lazy_static;
 use parking_lot::*;
 
-use crate::event::Event;
-use crate::input::event_and_raw;
+use crate::backend::{PlatformBackend, SysBackend, Wake};
+use crate::clock::{Clock, Deadline, SYSTEM_CLOCK};
+use crate::cursor::Origin;
+use crate::event::{Event, ParseOptions};
+use crate::input::{
+    coalesce_mouse, event_and_raw, is_combining_base, merge_combining, RawEvent, RepeatDetector,
+};
+use crate::metrics;
+use crate::quirks::KeyQuirks;
+use crate::raw::RawModeExt;
+use crate::resize;
 use crate::sys::console::*;
 
+#[cfg(feature = "tokio")]
+pub mod tokio;
+
+/// Default time to wait for the rest of an escape sequence once it has started.
+const DEFAULT_SEQUENCE_TIMEOUT: Duration = Duration::from_millis(50);
+
 fn make_tty_in() -> io::Result<ReentrantMutex<RefCell<ConsoleIn>>> {
-    let syscon = open_syscon_in()?;
-    Ok(ReentrantMutex::new(RefCell::new(ConsoleIn {
-        syscon,
-        leftover: None,
-        blocking: true,
-        read_timeout: None,
-    })))
+    let mut conin = ConsoleIn::new(open_syscon_in()?);
+    conin.key_quirks = KeyQuirks::from_env();
+    Ok(ReentrantMutex::new(RefCell::new(conin)))
 }
 
 fn make_tty_out() -> io::Result<ReentrantMutex<RefCell<ConsoleOut>>> {
-    let syscon = open_syscon_out()?;
-    Ok(ReentrantMutex::new(RefCell::new(ConsoleOut {
-        syscon,
-        raw_mode: false,
-    })))
+    Ok(ReentrantMutex::new(RefCell::new(ConsoleOut::new(
+        open_syscon_out()?,
+    ))))
 }
 
 lazy_static! {
@@ -143,6 +159,37 @@ pub fn conout() -> Conout {
     }
 }
 
+/// Run `command` with the terminal handed over to it and wait for it to
+/// exit.
+///
+/// The terminal is restored to its normal state for the child as with
+/// [`cooked_scope`](RawModeExt::cooked_scope) (raw mode, mouse reporting,
+/// alternate screen and cursor visibility).  On unix the child runs in its
+/// own process group which is made the foreground job of the tty, the way a
+/// job control shell starts commands, so it gets the keyboard signals
+/// (Ctrl-C) instead of this process.  Afterwards the tty is taken back and
+/// the saved modes re-established.  The same happens when the child is
+/// stopped (Ctrl-Z), the status returned then has a `stopped_signal` (see
+/// `std::os::unix::process::ExitStatusExt`) and the child is left stopped
+/// in its process group.
+///
+/// # Example
+///
+/// ```rust,no_run
+/// use std::process::Command;
+/// use sl_console::console::run_child;
+/// use sl_console::*;
+///
+///     con_init().unwrap();
+///     let status = run_child(Command::new("vi").arg("notes.txt")).unwrap();
+/// ```
+pub fn run_child(command: &mut Command) -> io::Result<ExitStatus> {
+    let mut conout = conout_r()?.lock();
+    conout.cooked_scope(|| {
+        conout_r().and_then(|conout| conout.lock().inner.borrow().syscon.run_child(command))
+    })?
+}
+
 /// Console output trait.
 pub trait ConsoleWrite: Write {
     /// Switch the raw mode, true enters raw mode and false exits raw mode.
@@ -183,6 +230,38 @@ pub trait ConsoleRead: Read {
     /// to read.
     /// Returns a Err of kind WouldBlock if it times out.
     fn read_timeout(&mut self, buf: &mut [u8], timeout: Option<Duration>) -> io::Result<usize>;
+
+    /// Put an event and its bytes back, the next get_event_and_raw returns
+    /// it before any new input.  Events put back are returned last in,
+    /// first out.
+    ///
+    /// Readers that can not put events back return an error of kind
+    /// Unsupported, which is the default.
+    fn unget_event_and_raw(&mut self, event: Event, raw: Vec<u8>) -> io::Result<()> {
+        let _ = (event, raw);
+        Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            "Events can not be put back on this reader.",
+        ))
+    }
+
+    /// Wait for the next event until `token` is cancelled.
+    ///
+    /// Returns an error of kind Interrupted once the token is cancelled,
+    /// from any thread, without waiting for input.  The console readers
+    /// are woken by the cancel, other readers check the token every
+    /// [`CANCEL_CHECK_INTERVAL`] by default.
+    fn get_event_cancellable(&mut self, token: &CancelToken) -> Option<io::Result<Event>> {
+        loop {
+            if token.is_cancelled() {
+                return Some(Err(cancelled()));
+            }
+            match self.get_event_and_raw(Some(CANCEL_CHECK_INTERVAL)) {
+                Some(Err(err)) if err.kind() == io::ErrorKind::WouldBlock => {}
+                res => return res.map(|res| res.map(|(event, _)| event)),
+            }
+        }
+    }
 }
 
 /// Represents the input side of the tty/console terminal.
@@ -230,6 +309,14 @@ impl ConsoleRead for Conin {
     fn read_timeout(&mut self, buf: &mut [u8], timeout: Option<Duration>) -> io::Result<usize> {
         self.lock().read_timeout(buf, timeout)
     }
+
+    fn unget_event_and_raw(&mut self, event: Event, raw: Vec<u8>) -> io::Result<()> {
+        self.lock().unget_event_and_raw(event, raw)
+    }
+
+    fn get_event_cancellable(&mut self, token: &CancelToken) -> Option<io::Result<Event>> {
+        self.lock().get_event_cancellable(token)
+    }
 }
 
 impl Read for Conin {
@@ -292,12 +379,546 @@ impl Write for Conout {
 ///
 /// This is a singleton that aquires a lock to access the console (similiar to
 /// Stdin).  It should be used to access the tty/terminal to avoid conflicts
-/// and other issues.
-pub struct ConsoleIn {
-    syscon: SysConsoleIn,
+/// and other issues.  Other backends are read through a [`Console`].
+pub struct ConsoleIn<B: SysBackend = PlatformBackend> {
+    syscon: B,
     leftover: Option<u8>,
-    blocking: bool,
-    read_timeout: Option<Duration>,
+    sequence_timeout: Duration,
+    parse_options: ParseOptions,
+    key_quirks: KeyQuirks,
+    ticker: Ticker,
+    idle: IdleTimer,
+    clock: &'static dyn Clock,
+    /// Events sent by wakers, waiting to be returned.
+    woken: Arc<Mutex<VecDeque<Event>>>,
+    /// Tokens of watched descriptors that are ready, waiting to be returned.
+    io_ready: VecDeque<u64>,
+    /// Cleared to remove the resize handler delivering resize events.
+    resize_events: Option<Arc<AtomicBool>>,
+    repeat: RepeatDetector,
+    combine_timeout: Option<Duration>,
+    coalesce_mouse: bool,
+    origin: Origin,
+    /// An event read while looking for combining marks or coalescing mouse
+    /// events, returned next.
+    pending: RawEvent,
+    /// When the first byte of the next event arrived, only tracked for the
+    /// metrics.
+    arrived: Option<Instant>,
+    filter: Option<EventFilter>,
+    /// Events put back, returned next (from the front).
+    unread: VecDeque<(Event, Vec<u8>)>,
+    /// Set while a cancellable read waits.
+    cancel: Option<CancelToken>,
+}
+
+/// A callback that sees every event before it is returned, see
+/// [`ConsoleIn::set_event_filter`].
+pub type EventFilter = Box<dyn FnMut(Event) -> Option<Event> + Send>;
+
+/// A handle that can interrupt a blocked get_event or poll from another
+/// thread.
+///
+/// Get one with [`ConsoleIn::waker`], it can be cloned and sent to other
+/// threads.  The event it sends makes a waiting poll return true, so an
+/// application can wait for console input, network or timer wake ups in one
+/// blocking call and read the event that woke it.
+///
+/// # Example
+///
+/// ```rust,no_run
+/// use std::thread;
+/// use sl_console::event::Event;
+/// use sl_console::input::ConsoleReadExt;
+/// use sl_console::*;
+///
+///     con_init().unwrap();
+///     let mut conin = conin().lock();
+///     let waker = conin.waker();
+///     thread::spawn(move || {
+///         // ... do some work ...
+///         waker.wake(Event::Custom(1)).unwrap();
+///     });
+///     // Returns the key pressed or Event::Custom(1) when the work is done.
+///     let _event = conin.get_event();
+/// ```
+#[derive(Clone)]
+pub struct Waker {
+    woken: Arc<Mutex<VecDeque<Event>>>,
+    sys: Arc<dyn Wake>,
+}
+
+impl Waker {
+    /// Queue `event` and wake up the console input, `event` is returned by
+    /// the current (or next) get_event.
+    pub fn wake(&self, event: Event) -> io::Result<()> {
+        self.woken.lock().push_back(event);
+        self.sys.wake()
+    }
+}
+
+/// How often readers that can not be woken check a [`CancelToken`].
+pub const CANCEL_CHECK_INTERVAL: Duration = Duration::from_millis(50);
+
+/// A shutdown signal for reads blocked in
+/// [`get_event_cancellable`](ConsoleRead::get_event_cancellable).
+///
+/// Clones share the signal, cancel one from another thread to unblock the
+/// readers waiting on any of them.  A cancelled token stays cancelled until
+/// [`reset`](CancelToken::reset).
+#[derive(Clone, Default)]
+pub struct CancelToken {
+    inner: Arc<CancelInner>,
+}
+
+#[derive(Default)]
+struct CancelInner {
+    cancelled: AtomicBool,
+    /// The backends of the readers waiting on the token.
+    waiting: Mutex<Vec<Arc<dyn Wake>>>,
+}
+
+impl CancelToken {
+    /// A token that is not cancelled.
+    pub fn new() -> Self {
+        CancelToken::default()
+    }
+
+    /// Cancel the token and wake the readers waiting on it.
+    pub fn cancel(&self) {
+        self.inner.cancelled.store(true, Ordering::SeqCst);
+        for wake in self.inner.waiting.lock().iter() {
+            let _ = wake.wake();
+        }
+    }
+
+    /// True once the token is cancelled.
+    pub fn is_cancelled(&self) -> bool {
+        self.inner.cancelled.load(Ordering::SeqCst)
+    }
+
+    /// Make the token usable again after a cancel.
+    pub fn reset(&self) {
+        self.inner.cancelled.store(false, Ordering::SeqCst);
+    }
+
+    fn register(&self, wake: &Arc<dyn Wake>) {
+        self.inner.waiting.lock().push(wake.clone());
+    }
+
+    fn unregister(&self, wake: &Arc<dyn Wake>) {
+        self.inner
+            .waiting
+            .lock()
+            .retain(|waiting| !Arc::ptr_eq(waiting, wake));
+    }
+}
+
+/// The error returned by a cancelled read.
+fn cancelled() -> io::Error {
+    io::Error::new(io::ErrorKind::Interrupted, "Console read cancelled.")
+}
+
+impl<B: SysBackend> ConsoleIn<B> {
+    fn new(syscon: B) -> Self {
+        ConsoleIn {
+            syscon,
+            leftover: None,
+            sequence_timeout: DEFAULT_SEQUENCE_TIMEOUT,
+            parse_options: ParseOptions::default(),
+            key_quirks: KeyQuirks::default(),
+            ticker: Ticker::default(),
+            idle: IdleTimer::default(),
+            clock: &SYSTEM_CLOCK,
+            woken: Arc::new(Mutex::new(VecDeque::new())),
+            io_ready: VecDeque::new(),
+            resize_events: None,
+            repeat: RepeatDetector::default(),
+            combine_timeout: None,
+            coalesce_mouse: false,
+            origin: Origin::One,
+            pending: None,
+            arrived: None,
+            filter: None,
+            unread: VecDeque::new(),
+            cancel: None,
+        }
+    }
+
+    /// Set how long to wait for the rest of a partially read escape sequence.
+    ///
+    /// Once the first byte of an event has been read any further bytes have
+    /// to arrive within this timeout, if they do not the bytes read so far
+    /// are returned as an Unsupported event.  This keeps a truncated sequence
+    /// from blocking input forever.  Defaults to 50ms.
+    pub fn set_sequence_timeout(&mut self, timeout: Duration) {
+        self.sequence_timeout = timeout;
+    }
+
+    /// Return the current escape sequence timeout.
+    pub fn sequence_timeout(&self) -> Duration {
+        self.sequence_timeout
+    }
+
+    /// Set the options used to parse input into events.
+    pub fn set_parse_options(&mut self, options: ParseOptions) {
+        self.parse_options = options;
+    }
+
+    /// Return the options used to parse input into events.
+    pub fn parse_options(&self) -> ParseOptions {
+        self.parse_options
+    }
+
+    /// Set the table used to normalize terminal specific key sequences.
+    ///
+    /// Defaults to the built in table for the terminal in `$TERM`.
+    pub fn set_key_quirks(&mut self, quirks: KeyQuirks) {
+        self.key_quirks = quirks;
+    }
+
+    /// Return the table used to normalize terminal specific key sequences.
+    pub fn key_quirks(&self) -> &KeyQuirks {
+        &self.key_quirks
+    }
+
+    /// Emit an `Event::Tick` every `tick` from get_event (and friends).
+    ///
+    /// A pending tick is returned before any input and a read waiting for
+    /// input returns early with the tick when it is due, so animations can
+    /// be driven from the same loop as input.  If ticks are not collected
+    /// in time the missed ones are dropped rather than delivered in a
+    /// burst.  None (the default) turns ticks off.
+    pub fn set_tick(&mut self, tick: Option<Duration>) {
+        self.ticker.set(tick, self.clock.now());
+    }
+
+    /// Return the tick interval.
+    pub fn tick(&self) -> Option<Duration> {
+        self.ticker.interval
+    }
+
+    /// Emit an `Event::Idle` from get_event (and friends) once no input
+    /// arrived for `idle`.
+    ///
+    /// The event holds how long the console has been idle and is emitted
+    /// once per idle period, the next input (or [`reset_idle`]) starts a
+    /// new one.  Like ticks a read waiting for input returns early with the
+    /// event, so auto-save or dimming need no timer thread.  None (the
+    /// default) turns idle events off.
+    ///
+    /// [`reset_idle`]: ConsoleIn::reset_idle
+    pub fn set_idle(&mut self, idle: Option<Duration>) {
+        self.idle.set(idle, self.clock.now());
+    }
+
+    /// Return the idle period.
+    pub fn idle(&self) -> Option<Duration> {
+        self.idle.after
+    }
+
+    /// Count activity the console does not see (for example output from a
+    /// background job) as input, restarting the idle period.
+    pub fn reset_idle(&mut self) {
+        self.idle.activity(self.clock.now());
+    }
+
+    /// Return a handle other threads can use to send events that interrupt
+    /// a blocked get_event or poll.
+    pub fn waker(&self) -> Waker {
+        Waker {
+            woken: self.woken.clone(),
+            sys: self.syscon.waker(),
+        }
+    }
+
+    /// Deliver an `Event::Resize` with the new size from get_event (and
+    /// friends) after every resize of the terminal.
+    ///
+    /// The resizes come from the [`resize`](crate::resize) watcher (SIGWINCH
+    /// on unix), a read waiting for input returns with the event.  Only the
+    /// latest size is kept if several resizes happen before the event is
+    /// read.  Off by default, returns an error if the watcher could not be
+    /// started.
+    pub fn set_resize_events(&mut self, on: bool) -> io::Result<()> {
+        match (on, &self.resize_events) {
+            (true, None) => {
+                let enabled = Arc::new(AtomicBool::new(true));
+                let handler_enabled = enabled.clone();
+                let waker = self.waker();
+                resize::register(Box::new(move |cols, rows| {
+                    if !handler_enabled.load(Ordering::Relaxed) {
+                        return false;
+                    }
+                    waker
+                        .woken
+                        .lock()
+                        .retain(|event| !matches!(event, Event::Resize(..)));
+                    // The console may be gone, the handler goes with it.
+                    waker.wake(Event::Resize(cols, rows)).is_ok()
+                }))?;
+                self.resize_events = Some(enabled);
+            }
+            (false, Some(enabled)) => {
+                enabled.store(false, Ordering::Relaxed);
+                self.resize_events = None;
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    /// True if resizes are delivered as events.
+    pub fn resize_events(&self) -> bool {
+        self.resize_events.is_some()
+    }
+
+    /// Flag key events as repeats if the same key arrives again within
+    /// `threshold`.
+    ///
+    /// Terminals do not report held keys, they just send the key again at
+    /// the keyboard repeat rate (usually 25-50ms apart after an initial
+    /// delay of a few hundred ms).  With a threshold a little above the
+    /// repeat interval the repeats of a held key get a `Key::kind` of
+    /// `Repeat`, the first press (and the first repeat after the initial
+    /// delay) do not.
+    /// None (the default) never flags repeats.
+    pub fn set_repeat_threshold(&mut self, threshold: Option<Duration>) {
+        self.repeat.set_threshold(threshold);
+    }
+
+    /// Return the key repeat threshold.
+    pub fn repeat_threshold(&self) -> Option<Duration> {
+        self.repeat.threshold()
+    }
+
+    /// Merge a character followed by combining marks into one
+    /// `Event::Grapheme`.
+    ///
+    /// Some terminals send dead key and compose results as the base
+    /// character followed by separate combining marks (`e` then U+0301
+    /// instead of `é`).  With a timeout set, after a plain character key
+    /// any combining marks arriving within `timeout` of each other are
+    /// merged with it.  This delays every plain character by up to the
+    /// timeout so keep it short (a few ms).  None (the default) returns the
+    /// marks as separate key events.
+    pub fn set_combine_timeout(&mut self, timeout: Option<Duration>) {
+        self.combine_timeout = timeout;
+    }
+
+    /// Return the combining mark timeout.
+    pub fn combine_timeout(&self) -> Option<Duration> {
+        self.combine_timeout
+    }
+
+    /// Coalesce mouse drags, moves and wheel turns.
+    ///
+    /// When on, a mouse event followed by already queued events of the same
+    /// kind (a drag with the same button, a move or a turn of the same
+    /// wheel, with the same modifiers) is dropped in favour of the latest
+    /// one, whose raw bytes are returned.  This keeps an application that
+    /// renders slower than the mouse reports from lagging behind a drag.
+    /// Only input that already arrived is merged, reads are never delayed.
+    /// Off by default.
+    pub fn set_coalesce_mouse(&mut self, coalesce: bool) {
+        self.coalesce_mouse = coalesce;
+    }
+
+    /// True if mouse events are coalesced.
+    pub fn coalesce_mouse(&self) -> bool {
+        self.coalesce_mouse
+    }
+
+    /// Set where the mouse and cursor position events read from this console
+    /// (and [`cursor_pos`](crate::cursor::cursor_pos)) count from, the
+    /// default is [`Origin::One`] like the terminal.  Write positions with
+    /// [`Origin::goto`] to match.
+    pub fn set_origin(&mut self, origin: Origin) {
+        self.origin = origin;
+    }
+
+    /// Return where coordinates read from this console count from.
+    pub fn origin(&self) -> Origin {
+        self.origin
+    }
+
+    /// Pass every event through `filter` before it is returned from
+    /// get_event (and friends, the `Events` and `Keys` iterators included).
+    ///
+    /// The filter can return the event, return a different one (the raw
+    /// bytes stay those of the original) or return None to swallow it, the
+    /// read then goes on waiting for the next event within its timeout.
+    /// Use it for handlers that apply everywhere, such as redrawing on
+    /// Ctrl+L, or to log input.  Events put back with
+    /// [`unget_event_and_raw`](ConsoleRead::unget_event_and_raw) are not
+    /// filtered again.  Replaces any filter set before.
+    pub fn set_event_filter<F>(&mut self, filter: F)
+    where
+        F: FnMut(Event) -> Option<Event> + Send + 'static,
+    {
+        self.filter = Some(Box::new(filter));
+    }
+
+    /// Remove the event filter, returns it.
+    pub fn clear_event_filter(&mut self) -> Option<EventFilter> {
+        self.filter.take()
+    }
+
+    /// True if an event filter is set.
+    pub fn has_event_filter(&self) -> bool {
+        self.filter.is_some()
+    }
+
+    /// Read, waiting up to timeout (forever if None) for input or a waker.
+    ///
+    /// Returns a WouldBlock error if woken or timed out.
+    fn read_or_wake(&mut self, buf: &mut [u8], timeout: Option<Duration>) -> io::Result<usize> {
+        if self.syscon.poll_or_wake(timeout) {
+            self.syscon.read(buf)
+        } else {
+            Err(io::Error::new(
+                io::ErrorKind::WouldBlock,
+                "Timed out or woken on console read.",
+            ))
+        }
+    }
+}
+
+impl ConsoleIn {
+    /// Report `fd` as an `Event::IoReady(token)` when it is readable.
+    ///
+    /// get_event (and friends) wait for the descriptor along with the
+    /// console input.  Readiness is level triggered, the event is returned
+    /// again on every call until the data is read from `fd`.  Registering a
+    /// token again replaces its descriptor.  Returns an error if the
+    /// descriptor is too large to wait on.
+    #[cfg(unix)]
+    pub fn watch_fd(&mut self, fd: std::os::unix::io::RawFd, token: u64) -> io::Result<()> {
+        self.syscon.watch(fd, token)
+    }
+
+    /// Stop watching the descriptor registered with `token`.
+    #[cfg(unix)]
+    pub fn unwatch_fd(&mut self, token: u64) {
+        self.io_ready.retain(|t| *t != token);
+        self.syscon.unwatch(token);
+    }
+}
+
+impl<B: SysBackend> Drop for ConsoleIn<B> {
+    fn drop(&mut self) {
+        // Remove the resize handler on the next resize.
+        if let Some(enabled) = &self.resize_events {
+            enabled.store(false, Ordering::Relaxed);
+        }
+    }
+}
+
+/// Schedules the tick events.
+#[derive(Debug, Default)]
+struct Ticker {
+    interval: Option<Duration>,
+    next: Option<Instant>,
+}
+
+impl Ticker {
+    fn set(&mut self, interval: Option<Duration>, now: Instant) {
+        self.interval = interval;
+        self.next = interval.map(|interval| now + interval);
+    }
+
+    /// If a tick is due schedule the next one and return true.
+    fn take(&mut self, now: Instant) -> bool {
+        if let (Some(interval), Some(next)) = (self.interval, self.next) {
+            if now >= next {
+                let next = next + interval;
+                self.next = Some(if next > now { next } else { now + interval });
+                return true;
+            }
+        }
+        false
+    }
+
+    /// How long until the next tick, None if ticks are off.
+    fn until(&self, now: Instant) -> Option<Duration> {
+        self.next.map(|next| next.saturating_duration_since(now))
+    }
+}
+
+/// Schedules the idle events.
+#[derive(Debug, Default)]
+struct IdleTimer {
+    after: Option<Duration>,
+    last_activity: Option<Instant>,
+    // True once the event for the current idle period was emitted.
+    fired: bool,
+}
+
+impl IdleTimer {
+    fn set(&mut self, after: Option<Duration>, now: Instant) {
+        self.after = after;
+        self.activity(now);
+    }
+
+    /// Input arrived, start a new idle period.
+    fn activity(&mut self, now: Instant) {
+        self.last_activity = Some(now);
+        self.fired = false;
+    }
+
+    /// If the idle event is due mark it emitted and return how long the
+    /// console has been idle.
+    fn take(&mut self, now: Instant) -> Option<Duration> {
+        let idle = now.saturating_duration_since(self.last_activity?);
+        if self.fired || idle < self.after? {
+            return None;
+        }
+        self.fired = true;
+        Some(idle)
+    }
+
+    /// How long until the idle event, None if it is off or was emitted.
+    fn until(&self, now: Instant) -> Option<Duration> {
+        if self.fired {
+            return None;
+        }
+        let due = self.last_activity? + self.after?;
+        Some(due.saturating_duration_since(now))
+    }
+}
+
+/// Reader used while parsing a single event.
+///
+/// The first read waits as long as the caller asked for, after that the rest
+/// of the event has to arrive within the sequence timeout.
+struct EventReader<'a, B: SysBackend> {
+    conin: &'a mut ConsoleIn<B>,
+    timeout: Option<Duration>,
+    started: bool,
+    // When the last read returned data, only tracked for the metrics.
+    last_read: Option<Instant>,
+}
+
+impl<'a, B: SysBackend> Read for EventReader<'a, B> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let timeout = if self.started {
+            Some(self.conin.sequence_timeout)
+        } else {
+            self.timeout
+        };
+        let res = if self.started {
+            self.conin.read_timeout(buf, timeout)
+        } else {
+            self.started = true;
+            self.conin.read_or_wake(buf, timeout)
+        };
+        if matches!(res, Ok(len) if len > 0) && metrics::is_enabled() {
+            let now = self.conin.clock.now();
+            self.conin.arrived.get_or_insert(now);
+            self.last_read = Some(now);
+        }
+        res
+    }
 }
 
 /// A locked console input device.
@@ -305,50 +926,412 @@ pub struct ConsoleInLock<'a> {
     inner: ReentrantMutexGuard<'a, RefCell<ConsoleIn>>,
 }
 
+impl<'a> ConsoleInLock<'a> {
+    /// Set how long to wait for the rest of a partially read escape sequence.
+    ///
+    /// See [`ConsoleIn::set_sequence_timeout`].
+    pub fn set_sequence_timeout(&mut self, timeout: Duration) {
+        self.inner.borrow_mut().set_sequence_timeout(timeout);
+    }
+
+    /// Return the current escape sequence timeout.
+    pub fn sequence_timeout(&self) -> Duration {
+        self.inner.borrow().sequence_timeout()
+    }
+
+    /// Set the options used to parse input into events.
+    pub fn set_parse_options(&mut self, options: ParseOptions) {
+        self.inner.borrow_mut().set_parse_options(options);
+    }
+
+    /// Return the options used to parse input into events.
+    pub fn parse_options(&self) -> ParseOptions {
+        self.inner.borrow().parse_options()
+    }
+
+    /// Set the table used to normalize terminal specific key sequences.
+    pub fn set_key_quirks(&mut self, quirks: KeyQuirks) {
+        self.inner.borrow_mut().set_key_quirks(quirks);
+    }
+
+    /// Return a copy of the table used to normalize terminal specific key
+    /// sequences.
+    pub fn key_quirks(&self) -> KeyQuirks {
+        self.inner.borrow().key_quirks().clone()
+    }
+
+    /// Emit an `Event::Tick` every `tick`.
+    ///
+    /// See [`ConsoleIn::set_tick`].
+    pub fn set_tick(&mut self, tick: Option<Duration>) {
+        self.inner.borrow_mut().set_tick(tick);
+    }
+
+    /// Return the tick interval.
+    pub fn tick(&self) -> Option<Duration> {
+        self.inner.borrow().tick()
+    }
+
+    /// Emit an `Event::Idle` once no input arrived for `idle`.
+    ///
+    /// See [`ConsoleIn::set_idle`].
+    pub fn set_idle(&mut self, idle: Option<Duration>) {
+        self.inner.borrow_mut().set_idle(idle);
+    }
+
+    /// Return the idle period.
+    pub fn idle(&self) -> Option<Duration> {
+        self.inner.borrow().idle()
+    }
+
+    /// Restart the idle period.
+    ///
+    /// See [`ConsoleIn::reset_idle`].
+    pub fn reset_idle(&mut self) {
+        self.inner.borrow_mut().reset_idle();
+    }
+
+    /// Pass every event through `filter` before it is returned.
+    ///
+    /// See [`ConsoleIn::set_event_filter`].
+    pub fn set_event_filter<F>(&mut self, filter: F)
+    where
+        F: FnMut(Event) -> Option<Event> + Send + 'static,
+    {
+        self.inner.borrow_mut().set_event_filter(filter);
+    }
+
+    /// Remove the event filter, returns it.
+    pub fn clear_event_filter(&mut self) -> Option<EventFilter> {
+        self.inner.borrow_mut().clear_event_filter()
+    }
+
+    /// True if an event filter is set.
+    pub fn has_event_filter(&self) -> bool {
+        self.inner.borrow().has_event_filter()
+    }
+
+    /// Return a handle that can interrupt a blocked get_event or poll.
+    ///
+    /// See [`ConsoleIn::waker`].
+    pub fn waker(&self) -> Waker {
+        self.inner.borrow().waker()
+    }
+
+    /// Deliver an `Event::Resize` after every resize of the terminal.
+    ///
+    /// See [`ConsoleIn::set_resize_events`].
+    pub fn set_resize_events(&mut self, on: bool) -> io::Result<()> {
+        self.inner.borrow_mut().set_resize_events(on)
+    }
+
+    /// True if resizes are delivered as events.
+    pub fn resize_events(&self) -> bool {
+        self.inner.borrow().resize_events()
+    }
+
+    /// Flag key events as repeats if the same key arrives again within
+    /// `threshold`.
+    ///
+    /// See [`ConsoleIn::set_repeat_threshold`].
+    pub fn set_repeat_threshold(&mut self, threshold: Option<Duration>) {
+        self.inner.borrow_mut().set_repeat_threshold(threshold);
+    }
+
+    /// Return the key repeat threshold.
+    pub fn repeat_threshold(&self) -> Option<Duration> {
+        self.inner.borrow().repeat_threshold()
+    }
+
+    /// Merge a character followed by combining marks into one
+    /// `Event::Grapheme`.
+    ///
+    /// See [`ConsoleIn::set_combine_timeout`].
+    pub fn set_combine_timeout(&mut self, timeout: Option<Duration>) {
+        self.inner.borrow_mut().set_combine_timeout(timeout);
+    }
+
+    /// Return the combining mark timeout.
+    pub fn combine_timeout(&self) -> Option<Duration> {
+        self.inner.borrow().combine_timeout()
+    }
+
+    /// Coalesce mouse drags, moves and wheel turns.
+    ///
+    /// See [`ConsoleIn::set_coalesce_mouse`].
+    pub fn set_coalesce_mouse(&mut self, coalesce: bool) {
+        self.inner.borrow_mut().set_coalesce_mouse(coalesce);
+    }
+
+    /// True if mouse events are coalesced.
+    pub fn coalesce_mouse(&self) -> bool {
+        self.inner.borrow().coalesce_mouse()
+    }
+
+    /// Set where coordinates read from this console count from.
+    ///
+    /// See [`ConsoleIn::set_origin`].
+    pub fn set_origin(&mut self, origin: Origin) {
+        self.inner.borrow_mut().set_origin(origin);
+    }
+
+    /// Return where coordinates read from this console count from.
+    pub fn origin(&self) -> Origin {
+        self.inner.borrow().origin()
+    }
+
+    /// Report `fd` as an `Event::IoReady(token)` when it is readable.
+    ///
+    /// See [`ConsoleIn::watch_fd`].
+    #[cfg(unix)]
+    pub fn watch_fd(&mut self, fd: std::os::unix::io::RawFd, token: u64) -> io::Result<()> {
+        self.inner.borrow_mut().watch_fd(fd, token)
+    }
+
+    /// Stop watching the descriptor registered with `token`.
+    #[cfg(unix)]
+    pub fn unwatch_fd(&mut self, token: u64) {
+        self.inner.borrow_mut().unwatch_fd(token);
+    }
+}
+
 /// Represents the output side of the tty/console terminal.
 ///
 /// This is a singleton that aquires a lock to access the console (similiar to
 /// Stdin).  It should be used to access the tty/terminal to avoid conflicts
-/// and other issues.
-pub struct ConsoleOut {
-    syscon: SysConsoleOut,
+/// and other issues.  Other backends are written through a [`Console`].
+pub struct ConsoleOut<B: SysBackend = PlatformBackend> {
+    syscon: B::Out,
     raw_mode: bool,
 }
 
+impl<B: SysBackend> ConsoleOut<B> {
+    fn new(syscon: B::Out) -> Self {
+        ConsoleOut {
+            syscon,
+            raw_mode: false,
+        }
+    }
+
+    /// Switch raw mode on the terminal `conin` reads.
+    fn switch_raw_mode(&mut self, conin: &ConsoleIn<B>, mode: bool) -> io::Result<bool> {
+        let prev_mode = self.raw_mode;
+        if prev_mode != mode {
+            conin.syscon.set_raw_mode(&mut self.syscon, mode)?;
+            self.raw_mode = mode;
+        }
+        Ok(prev_mode)
+    }
+}
+
 /// A locked console output device.
 pub struct ConsoleOutLock<'a> {
     inner: ReentrantMutexGuard<'a, RefCell<ConsoleOut>>,
 }
 
-impl ConsoleRead for ConsoleIn {
+impl<B: SysBackend> ConsoleIn<B> {
+    /// Count the event in the metrics, `last_read` is when the last byte of
+    /// input was read.
+    fn record_metrics(&mut self, res: &RawEvent, last_read: Option<Instant>) {
+        let arrived = self.arrived.take();
+        match res {
+            Some(Ok((event, raw))) => {
+                let now = self.clock.now();
+                let latency = arrived.map_or(Duration::ZERO, |arrived| now - arrived);
+                metrics::record_event(event, raw, latency);
+                // The leftover byte arrived with the last read.
+                if self.leftover.is_some() {
+                    self.arrived = last_read;
+                }
+            }
+            Some(Err(err)) if err.kind() == io::ErrorKind::WouldBlock => self.arrived = arrived,
+            Some(Err(_)) => metrics::record_failure(),
+            None => {}
+        }
+    }
+
+    /// Read the next event, ticks, woken and io events included.
+    fn next_event(&mut self, timeout: Option<Duration>) -> RawEvent {
+        loop {
+            if let Some(event) = self.woken.lock().pop_front() {
+                self.syscon.clear_wake();
+                return Some(Ok((event, Vec::new())));
+            }
+            self.io_ready.extend(self.syscon.take_ready());
+            if let Some(token) = self.io_ready.pop_front() {
+                return Some(Ok((Event::IoReady(token), Vec::new())));
+            }
+            let now = self.clock.now();
+            if self.ticker.take(now) {
+                return Some(Ok((Event::Tick, Vec::new())));
+            }
+            if let Some(idle) = self.idle.take(now) {
+                return Some(Ok((Event::Idle(idle), Vec::new())));
+            }
+            // Do not wait past the next tick or idle event.
+            let until = match (self.ticker.until(now), self.idle.until(now)) {
+                (Some(tick), Some(idle)) => Some(tick.min(idle)),
+                (tick, idle) => tick.or(idle),
+            };
+            let (timeout, timer_wait) = match until {
+                Some(until) => match timeout {
+                    Some(timeout) if timeout <= until => (Some(timeout), false),
+                    _ => (Some(until), true),
+                },
+                None => (timeout, false),
+            };
+            let mut leftover = self.leftover.take();
+            let options = self.parse_options;
+            let mut reader = EventReader {
+                started: leftover.is_some(),
+                conin: self,
+                timeout,
+                last_read: None,
+            };
+            let res = event_and_raw(&mut reader, &mut leftover, &options);
+            let last_read = reader.last_read;
+            self.leftover = leftover;
+            if metrics::is_enabled() {
+                self.record_metrics(&res, last_read);
+            }
+            match res {
+                Some(Err(err)) if err.kind() == io::ErrorKind::WouldBlock => {
+                    if !self.woken.lock().is_empty() || !self.io_ready.is_empty() {
+                        continue;
+                    }
+                    self.io_ready.extend(self.syscon.take_ready());
+                    if !self.io_ready.is_empty() {
+                        continue;
+                    }
+                    if self.cancel.as_ref().is_some_and(CancelToken::is_cancelled) {
+                        return Some(Err(cancelled()));
+                    }
+                    // A wake up with no event, the event was already taken.
+                    self.syscon.clear_wake();
+                    if timer_wait {
+                        continue;
+                    }
+                    if timeout.is_none() {
+                        continue;
+                    }
+                    return Some(Err(err));
+                }
+                res => {
+                    if let Some(Ok(_)) = res {
+                        self.idle.activity(self.clock.now());
+                    }
+                    return res.map(|res| {
+                        res.map(|(event, raw)| {
+                            let event = match self.key_quirks.normalize(event, &raw) {
+                                Event::Mouse(mouse, mods) => {
+                                    let origin = self.origin;
+                                    Event::Mouse(
+                                        mouse.map_position(|x, y| origin.from_terminal(x, y)),
+                                        mods,
+                                    )
+                                }
+                                Event::CursorPos(x, y) => {
+                                    let (x, y) = self.origin.from_terminal(x, y);
+                                    Event::CursorPos(x, y)
+                                }
+                                event => event,
+                            };
+                            (self.repeat.mark(event, self.clock.now()), raw)
+                        })
+                    });
+                }
+            }
+        }
+    }
+}
+
+impl<B: SysBackend> ConsoleIn<B> {
+    /// Read the next event with combining marks merged and mouse events
+    /// coalesced, before the filter.
+    fn next_merged(&mut self, timeout: Option<Duration>) -> RawEvent {
+        let res = match self.pending.take() {
+            Some(res) => Some(res),
+            None => self.next_event(timeout),
+        };
+        match (res, self.combine_timeout) {
+            (Some(Ok((Event::Key(key), raw))), Some(wait)) if is_combining_base(&key) => {
+                let (merged, pending) =
+                    merge_combining((Event::Key(key), raw), || self.next_event(Some(wait)));
+                self.pending = pending;
+                Some(Ok(merged))
+            }
+            (Some(Ok(first @ (Event::Mouse(..), _))), _)
+            | (Some(Ok(first @ (Event::MousePixels(..), _))), _)
+                if self.coalesce_mouse =>
+            {
+                let (latest, pending) =
+                    coalesce_mouse(first, || self.next_event(Some(Duration::ZERO)));
+                self.pending = pending;
+                Some(Ok(latest))
+            }
+            (res, _) => res,
+        }
+    }
+}
+
+impl<B: SysBackend> ConsoleRead for ConsoleIn<B> {
     fn get_event_and_raw(
         &mut self,
         timeout: Option<Duration>,
     ) -> Option<io::Result<(Event, Vec<u8>)>> {
-        let old_block = self.blocking;
-        let old_timeout = self.read_timeout.take();
-        if timeout.is_none() {
-            self.blocking = true;
-        } else {
-            self.blocking = false;
-            self.read_timeout = timeout;
+        if let Some(unread) = self.unread.pop_front() {
+            return Some(Ok(unread));
+        }
+        let deadline = timeout.map(|timeout| Deadline::new(self.clock, timeout));
+        let mut timeout = timeout;
+        loop {
+            let res = self.next_merged(timeout);
+            let filter = match &mut self.filter {
+                Some(filter) => filter,
+                None => return res,
+            };
+            match res {
+                Some(Ok((event, raw))) => {
+                    if let Some(event) = filter(event) {
+                        return Some(Ok((event, raw)));
+                    }
+                }
+                res => return res,
+            }
+            // Swallowed, wait for the next event in the time left.
+            if let Some(deadline) = &deadline {
+                timeout = deadline.remaining();
+                if timeout.is_none() {
+                    return Some(Err(io::Error::new(
+                        io::ErrorKind::WouldBlock,
+                        "Timed out on console read.",
+                    )));
+                }
+            }
         }
-        let mut leftover = self.leftover.take();
-        let mut guard = scopeguard::guard(self, |s| {
-            s.blocking = old_block;
-            s.read_timeout = old_timeout;
-        });
-        let res = event_and_raw(&mut *guard, &mut leftover);
-        guard.leftover = leftover;
-        res
     }
 
     fn poll(&mut self, timeout: Option<Duration>) -> bool {
-        if let Some(timeout) = timeout {
-            self.syscon.poll_timeout(timeout)
-        } else {
-            self.syscon.poll();
-            true
+        let deadline = timeout.map(|timeout| Deadline::new(self.clock, timeout));
+        let mut timeout = timeout;
+        loop {
+            if !self.unread.is_empty() || !self.woken.lock().is_empty() {
+                return true;
+            }
+            if self.syscon.poll_or_wake(timeout) {
+                return true;
+            }
+            if !self.woken.lock().is_empty() {
+                return true;
+            }
+            // Timed out, or a wake up whose event was already taken.
+            self.syscon.clear_wake();
+            if let Some(deadline) = &deadline {
+                timeout = deadline.remaining();
+                if timeout.is_none() {
+                    return false;
+                }
+            }
         }
     }
 
@@ -366,30 +1349,31 @@ impl ConsoleRead for ConsoleIn {
             self.syscon.read_block(buf)
         }
     }
+
+    fn unget_event_and_raw(&mut self, event: Event, raw: Vec<u8>) -> io::Result<()> {
+        self.unread.push_front((event, raw));
+        Ok(())
+    }
+
+    fn get_event_cancellable(&mut self, token: &CancelToken) -> Option<io::Result<Event>> {
+        let wake = self.syscon.waker();
+        token.register(&wake);
+        self.cancel = Some(token.clone());
+        let res = if token.is_cancelled() {
+            Some(Err(cancelled()))
+        } else {
+            self.get_event_and_raw(None)
+                .map(|res| res.map(|(event, _)| event))
+        };
+        self.cancel = None;
+        token.unregister(&wake);
+        res
+    }
 }
 
-impl Read for ConsoleIn {
+impl<B: SysBackend> Read for ConsoleIn<B> {
     fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
-        if self.blocking {
-            self.syscon.read_block(buf)
-        } else {
-            let mut do_read = true;
-            if let Some(timeout) = self.read_timeout {
-                do_read = self.poll(Some(timeout));
-            }
-            if do_read {
-                // Assume we may be reading an CSI or something so allow a small
-                // window for more data.
-                self.read_timeout = Some(Duration::from_millis(10));
-                self.syscon.read(buf)
-            } else {
-                self.read_timeout = None;
-                Err(io::Error::new(
-                    io::ErrorKind::WouldBlock,
-                    "Timed out on console read.",
-                ))
-            }
-        }
+        self.syscon.read_block(buf)
     }
 }
 
@@ -408,6 +1392,14 @@ impl<'a> ConsoleRead for ConsoleInLock<'a> {
     fn read_timeout(&mut self, buf: &mut [u8], timeout: Option<Duration>) -> io::Result<usize> {
         self.inner.borrow_mut().read_timeout(buf, timeout)
     }
+
+    fn unget_event_and_raw(&mut self, event: Event, raw: Vec<u8>) -> io::Result<()> {
+        self.inner.borrow_mut().unget_event_and_raw(event, raw)
+    }
+
+    fn get_event_cancellable(&mut self, token: &CancelToken) -> Option<io::Result<Event>> {
+        self.inner.borrow_mut().get_event_cancellable(token)
+    }
 }
 
 impl<'a> Read for ConsoleInLock<'a> {
@@ -418,25 +1410,17 @@ impl<'a> Read for ConsoleInLock<'a> {
 
 impl ConsoleWrite for ConsoleOut {
     fn set_raw_mode(&mut self, mode: bool) -> io::Result<bool> {
-        let prev_mode = self.raw_mode;
-        if self.raw_mode != mode {
-            if let Some(conin) = conin_r()?.try_lock() {
-                if mode {
-                    self.syscon
-                        .activate_raw_mode(&conin.inner.borrow().syscon)?;
-                } else {
-                    self.syscon.suspend_raw_mode(&conin.inner.borrow().syscon)?;
-                }
-                self.raw_mode = mode;
-                Ok(prev_mode)
-            } else {
-                Err(io::Error::new(
-                    io::ErrorKind::WouldBlock,
-                    "Conin is already locked.",
-                ))
-            }
+        if self.raw_mode == mode {
+            return Ok(mode);
+        }
+        if let Some(conin) = conin_r()?.try_lock() {
+            let conin = conin.inner.borrow();
+            self.switch_raw_mode(&conin, mode)
         } else {
-            Ok(prev_mode)
+            Err(io::Error::new(
+                io::ErrorKind::WouldBlock,
+                "Conin is already locked.",
+            ))
         }
     }
 
@@ -445,7 +1429,7 @@ impl ConsoleWrite for ConsoleOut {
     }
 }
 
-impl Write for ConsoleOut {
+impl<B: SysBackend> Write for ConsoleOut<B> {
     fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
         self.syscon.write(buf)
     }
@@ -475,6 +1459,109 @@ impl<'a> Write for ConsoleOutLock<'a> {
     }
 }
 
+/// The input and output of a console on any [`SysBackend`].
+///
+/// The event parsing, timeouts, wakers and raw mode handling are the ones
+/// [`conin`] and [`conout`] use, only the system calls are the backend's.
+/// Raw mode is switched off when the console is dropped.
+pub struct Console<B: SysBackend> {
+    input: ConsoleIn<B>,
+    output: ConsoleOut<B>,
+}
+
+impl<B: SysBackend> Console<B> {
+    /// Open the console of backend `B`.
+    pub fn open() -> io::Result<Self> {
+        let (input, output) = B::open()?;
+        Ok(Console::new(input, output))
+    }
+
+    /// A console on an already opened backend.
+    pub fn new(input: B, output: B::Out) -> Self {
+        Console {
+            input: ConsoleIn::new(input),
+            output: ConsoleOut::new(output),
+        }
+    }
+
+    /// The input side, for its settings.
+    pub fn input(&self) -> &ConsoleIn<B> {
+        &self.input
+    }
+
+    /// The input side, for its settings.
+    pub fn input_mut(&mut self) -> &mut ConsoleIn<B> {
+        &mut self.input
+    }
+
+    /// The backend's output.
+    pub fn output(&self) -> &B::Out {
+        &self.output.syscon
+    }
+
+    /// The backend's output.
+    pub fn output_mut(&mut self) -> &mut B::Out {
+        &mut self.output.syscon
+    }
+}
+
+impl<B: SysBackend> Drop for Console<B> {
+    fn drop(&mut self) {
+        let _ = self.output.switch_raw_mode(&self.input, false);
+    }
+}
+
+impl<B: SysBackend> ConsoleRead for Console<B> {
+    fn get_event_and_raw(
+        &mut self,
+        timeout: Option<Duration>,
+    ) -> Option<io::Result<(Event, Vec<u8>)>> {
+        self.input.get_event_and_raw(timeout)
+    }
+
+    fn poll(&mut self, timeout: Option<Duration>) -> bool {
+        self.input.poll(timeout)
+    }
+
+    fn read_timeout(&mut self, buf: &mut [u8], timeout: Option<Duration>) -> io::Result<usize> {
+        self.input.read_timeout(buf, timeout)
+    }
+
+    fn unget_event_and_raw(&mut self, event: Event, raw: Vec<u8>) -> io::Result<()> {
+        self.input.unget_event_and_raw(event, raw)
+    }
+
+    fn get_event_cancellable(&mut self, token: &CancelToken) -> Option<io::Result<Event>> {
+        self.input.get_event_cancellable(token)
+    }
+}
+
+impl<B: SysBackend> Read for Console<B> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.input.read(buf)
+    }
+}
+
+impl<B: SysBackend> ConsoleWrite for Console<B> {
+    fn set_raw_mode(&mut self, mode: bool) -> io::Result<bool> {
+        self.output.switch_raw_mode(&self.input, mode)
+    }
+
+    fn is_raw_mode(&self) -> bool {
+        self.output.raw_mode
+    }
+}
+
+impl<B: SysBackend> Write for Console<B> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.output.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.output.flush()
+    }
+}
+
 #[cfg(unix)]
 mod unix_impl {
     use super::*;
@@ -512,6 +1599,36 @@ mod unix_impl {
             self.inner.borrow_mut().as_raw_fd()
         }
     }
+
+    /// Registers the tty, it is readable when input arrives.
+    ///
+    /// Readiness is edge triggered, on each readable event read with a zero
+    /// timeout until the read returns a WouldBlock error (the console can
+    /// hold events already read from the tty).
+    #[cfg(feature = "mio")]
+    impl mio::event::Source for Conin {
+        fn register(
+            &mut self,
+            registry: &mio::Registry,
+            token: mio::Token,
+            interests: mio::Interest,
+        ) -> io::Result<()> {
+            mio::unix::SourceFd(&self.as_raw_fd()).register(registry, token, interests)
+        }
+
+        fn reregister(
+            &mut self,
+            registry: &mio::Registry,
+            token: mio::Token,
+            interests: mio::Interest,
+        ) -> io::Result<()> {
+            mio::unix::SourceFd(&self.as_raw_fd()).reregister(registry, token, interests)
+        }
+
+        fn deregister(&mut self, registry: &mio::Registry) -> io::Result<()> {
+            mio::unix::SourceFd(&self.as_raw_fd()).deregister(registry)
+        }
+    }
 }
 
 #[cfg(windows)]
@@ -551,11 +1668,47 @@ mod windows_impl {
             self.inner.borrow_mut().as_raw_handle()
         }
     }
+
+    /// Registers a mio waker that the console reader thread wakes when
+    /// input arrives, the console is reported readable whatever the
+    /// interests.
+    ///
+    /// On each readable event read with a zero timeout until the read
+    /// returns a WouldBlock error (the console can hold events already
+    /// read).
+    #[cfg(feature = "mio")]
+    impl mio::event::Source for Conin {
+        fn register(
+            &mut self,
+            registry: &mio::Registry,
+            token: mio::Token,
+            _interests: mio::Interest,
+        ) -> io::Result<()> {
+            let waker = mio::Waker::new(registry, token)?;
+            self.lock().inner.borrow().syscon.set_notify(Some(waker));
+            Ok(())
+        }
+
+        fn reregister(
+            &mut self,
+            registry: &mio::Registry,
+            token: mio::Token,
+            interests: mio::Interest,
+        ) -> io::Result<()> {
+            self.register(registry, token, interests)
+        }
+
+        fn deregister(&mut self, _registry: &mio::Registry) -> io::Result<()> {
+            self.lock().inner.borrow().syscon.set_notify(None);
+            Ok(())
+        }
+    }
 }
 
 #[cfg(test)]
 mod test {
     use super::*;
+    use crate::event::{Key, KeyCode};
 
     #[test]
     fn test_async_stdin() {
@@ -573,4 +1726,273 @@ mod test {
             }
         }
     }
+
+    /// A backend reading from a buffer, raw mode switches are written to
+    /// the output.
+    struct Mock(VecDeque<u8>);
+
+    struct NoWake;
+
+    impl Wake for NoWake {
+        fn wake(&self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    impl Read for Mock {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            self.0.read(buf)
+        }
+    }
+
+    impl SysBackend for Mock {
+        type Out = Vec<u8>;
+
+        fn open() -> io::Result<(Self, Vec<u8>)> {
+            Ok((Mock(VecDeque::new()), Vec::new()))
+        }
+
+        fn poll(&mut self, _timeout: Option<Duration>) -> bool {
+            !self.0.is_empty()
+        }
+
+        fn poll_or_wake(&mut self, _timeout: Option<Duration>) -> bool {
+            !self.0.is_empty()
+        }
+
+        fn waker(&self) -> Arc<dyn Wake> {
+            Arc::new(NoWake)
+        }
+
+        fn set_raw_mode(&self, out: &mut Vec<u8>, mode: bool) -> io::Result<()> {
+            write!(out, "<raw {}>", mode)
+        }
+    }
+
+    #[test]
+    fn test_backend() {
+        let mut con = Console::new(Mock(b"a\x1B[A".to_vec().into()), Vec::new());
+        con.input_mut().waker().wake(Event::Custom(3)).unwrap();
+        let mut next = || {
+            con.get_event_and_raw(Some(Duration::from_millis(10)))
+                .unwrap()
+                .map(|(event, _)| event)
+        };
+        assert_eq!(next().unwrap(), Event::Custom(3));
+        assert_eq!(next().unwrap(), Event::Key(Key::new(KeyCode::Char('a'))));
+        assert_eq!(next().unwrap(), Event::Key(Key::new(KeyCode::Up)));
+        assert_eq!(next().unwrap_err().kind(), io::ErrorKind::WouldBlock);
+
+        assert!(!con.set_raw_mode(true).unwrap());
+        assert!(con.set_raw_mode(true).unwrap());
+        write!(con, "x").unwrap();
+        assert!(con.set_raw_mode(false).unwrap());
+        assert_eq!(con.output().as_slice(), b"<raw true>x<raw false>");
+    }
+
+    #[test]
+    fn test_waker_interrupts_poll() {
+        let mut con = Console::new(Mock(VecDeque::new()), Vec::new());
+        let timeout = Some(Duration::from_millis(10));
+        assert!(!con.poll(timeout));
+        con.input_mut().waker().wake(Event::Custom(5)).unwrap();
+        assert!(con.poll(timeout));
+        assert_eq!(
+            con.get_event_and_raw(timeout).unwrap().unwrap().0,
+            Event::Custom(5)
+        );
+        assert!(!con.poll(timeout));
+    }
+
+    #[test]
+    fn test_cancel_token() {
+        let mut con = Console::new(Mock(b"a".to_vec().into()), Vec::new());
+        let token = CancelToken::new();
+        assert_eq!(
+            con.get_event_cancellable(&token).unwrap().unwrap(),
+            Event::Key(Key::new(KeyCode::Char('a')))
+        );
+        token.clone().cancel();
+        assert!(token.is_cancelled());
+        let err = con.get_event_cancellable(&token).unwrap().unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::Interrupted);
+        let err = (&b"b"[..])
+            .get_event_cancellable(&token)
+            .unwrap()
+            .unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::Interrupted);
+        token.reset();
+        assert!(!token.is_cancelled());
+        assert!(token.inner.waiting.lock().is_empty());
+    }
+
+    #[test]
+    fn test_event_filter() {
+        let mut con = Console::new(Mock(b"a\x1B[Ab".to_vec().into()), Vec::new());
+        con.input_mut().set_event_filter(|event| match event {
+            Event::Key(Key {
+                code: KeyCode::Char('a'),
+                ..
+            }) => None,
+            Event::Key(Key {
+                code: KeyCode::Up, ..
+            }) => Some(Event::Key(Key::new(KeyCode::Down))),
+            event => Some(event),
+        });
+        assert!(con.input().has_event_filter());
+        let mut next = || {
+            con.get_event_and_raw(Some(Duration::from_millis(10)))
+                .unwrap()
+                .unwrap()
+        };
+        assert_eq!(
+            next(),
+            (Event::Key(Key::new(KeyCode::Down)), b"\x1B[A".to_vec())
+        );
+        assert_eq!(next().0, Event::Key(Key::new(KeyCode::Char('b'))));
+        assert!(con.input_mut().clear_event_filter().is_some());
+        assert!(!con.input().has_event_filter());
+    }
+
+    #[test]
+    fn test_origin() {
+        use crate::event::{MouseButton, MouseEvent};
+
+        let input = b"\x1B[<0;3;4M\x1B[5;7R\x1B[<0;3;4M";
+        let mut con = Console::new(Mock(input.to_vec().into()), Vec::new());
+        con.input_mut().set_origin(Origin::Zero);
+        assert_eq!(con.input().origin(), Origin::Zero);
+        let mut next = || {
+            con.get_event_and_raw(Some(Duration::from_millis(10)))
+                .unwrap()
+                .unwrap()
+                .0
+        };
+        assert_eq!(
+            next(),
+            Event::Mouse(MouseEvent::Press(MouseButton::Left, 2, 3), None)
+        );
+        assert_eq!(next(), Event::CursorPos(6, 4));
+        con.input_mut().set_origin(Origin::One);
+        assert_eq!(
+            con.get_event_and_raw(Some(Duration::from_millis(10)))
+                .unwrap()
+                .unwrap()
+                .0,
+            Event::Mouse(MouseEvent::Press(MouseButton::Left, 3, 4), None)
+        );
+    }
+
+    #[test]
+    fn test_coalesce_mouse() {
+        use crate::event::{MouseButton, MouseEvent};
+
+        let input = b"\x1B[<32;1;1M\x1B[<32;2;1M\x1B[<32;3;1M\x1B[<0;3;1m\
+            \x1B[<65;1;1M\x1B[<65;1;1M\x1B[<64;1;1M";
+        let mut con = Console::new(Mock(input.to_vec().into()), Vec::new());
+        con.input_mut().set_coalesce_mouse(true);
+        assert!(con.input().coalesce_mouse());
+        let mut next = || {
+            con.get_event_and_raw(Some(Duration::from_millis(10)))
+                .unwrap()
+                .unwrap()
+        };
+        assert_eq!(
+            next(),
+            (
+                Event::Mouse(MouseEvent::Hold(MouseButton::Left, 3, 1), None),
+                b"\x1B[<32;3;1M".to_vec()
+            )
+        );
+        assert_eq!(
+            next().0,
+            Event::Mouse(MouseEvent::Release(Some(MouseButton::Left), 3, 1), None)
+        );
+        assert_eq!(
+            next().0,
+            Event::Mouse(MouseEvent::Press(MouseButton::WheelDown, 1, 1), None)
+        );
+        assert_eq!(
+            next().0,
+            Event::Mouse(MouseEvent::Press(MouseButton::WheelUp, 1, 1), None)
+        );
+    }
+
+    #[test]
+    fn test_peek_unget_and_drain() {
+        use crate::input::ConsoleReadExt;
+
+        let mut con = Console::new(Mock(b"ab".to_vec().into()), Vec::new());
+        let a = Event::Key(Key::new(KeyCode::Char('a')));
+        let timeout = Some(Duration::from_millis(10));
+        assert_eq!(con.peek_event(timeout).unwrap().unwrap(), a);
+        assert_eq!(con.peek_event(timeout).unwrap().unwrap(), a);
+        assert!(con.poll(timeout));
+        assert_eq!(
+            con.get_event_and_raw(timeout).unwrap().unwrap(),
+            (a, b"a".to_vec())
+        );
+        con.unget_event(Event::Custom(1)).unwrap();
+        con.unget_event(Event::Custom(2)).unwrap();
+        assert_eq!(con.get_event().unwrap().unwrap(), Event::Custom(2));
+        assert_eq!(con.get_event().unwrap().unwrap(), Event::Custom(1));
+        assert_eq!(
+            con.get_event().unwrap().unwrap(),
+            Event::Key(Key::new(KeyCode::Char('b')))
+        );
+
+        con.unget_event(Event::Tick).unwrap();
+        assert_eq!(con.drain_events(), vec![Event::Tick]);
+        let mut con = Console::new(Mock(b"x\x1B[Ay".to_vec().into()), Vec::new());
+        assert_eq!(con.drain_events().len(), 3);
+        assert!(con.drain_events().is_empty());
+
+        let err = (&b""[..]).unget_event(Event::Tick).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::Unsupported);
+    }
+
+    #[test]
+    fn test_ticker() {
+        let clock = crate::clock::TestClock::new();
+        let ms = Duration::from_millis;
+        let mut ticker = Ticker::default();
+        assert!(!ticker.take(clock.now()));
+        assert_eq!(ticker.until(clock.now()), None);
+
+        ticker.set(Some(ms(100)), clock.now());
+        assert_eq!(ticker.until(clock.now()), Some(ms(100)));
+        clock.advance(ms(99));
+        assert!(!ticker.take(clock.now()));
+        clock.advance(ms(1));
+        assert!(ticker.take(clock.now()));
+        assert_eq!(ticker.until(clock.now()), Some(ms(100)));
+        // Missed ticks are dropped, not delivered in a burst.
+        clock.advance(ms(350));
+        assert!(ticker.take(clock.now()));
+        assert!(!ticker.take(clock.now()));
+        assert_eq!(ticker.until(clock.now()), Some(ms(100)));
+    }
+
+    #[test]
+    fn test_idle_timer() {
+        let clock = crate::clock::TestClock::new();
+        let ms = Duration::from_millis;
+        let mut idle = IdleTimer::default();
+        assert_eq!(idle.take(clock.now()), None);
+        assert_eq!(idle.until(clock.now()), None);
+
+        idle.set(Some(ms(100)), clock.now());
+        clock.advance(ms(60));
+        assert_eq!(idle.until(clock.now()), Some(ms(40)));
+        idle.activity(clock.now());
+        clock.advance(ms(99));
+        assert_eq!(idle.take(clock.now()), None);
+        clock.advance(ms(5));
+        assert_eq!(idle.take(clock.now()), Some(ms(104)));
+        // Once per idle period.
+        assert_eq!(idle.take(clock.now()), None);
+        assert_eq!(idle.until(clock.now()), None);
+        idle.activity(clock.now());
+        assert_eq!(idle.until(clock.now()), Some(ms(100)));
+    }
 }
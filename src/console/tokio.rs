@@ -0,0 +1,307 @@
+//! Async console input and output for tokio.
+//!
+//! [`conin`] and [`conout`] return the console for use from tokio tasks.
+//! [`AsyncConin`] implements `AsyncRead` and reads events with
+//! [`get_event`](AsyncConin::get_event), [`AsyncConout`] implements
+//! `AsyncWrite`.  On unix the tty is registered with tokio's reactor, on
+//! Windows a thread reads the console and sends the events over a channel.
+//! Either way no task blocks waiting for input.
+//!
+//! Must be called from within a tokio runtime with IO enabled.  Requires
+//! the `tokio` feature.
+//!
+//! # Example
+//!
+//! ```rust,no_run
+//! use sl_console::console::tokio::{conin, conout};
+//! use sl_console::event::{Event, Key, KeyCode};
+//! use tokio::io::AsyncWriteExt;
+//!
+//! async fn echo() -> std::io::Result<()> {
+//!     sl_console::con_init()?;
+//!     let mut conin = conin()?;
+//!     let mut conout = conout()?;
+//!     while let Some(event) = conin.get_event().await {
+//!         match event? {
+//!             Event::Key(Key {
+//!                 code: KeyCode::Esc, ..
+//!             }) => break,
+//!             event => {
+//!                 let line = format!("{:?}\r\n", event);
+//!                 conout.write_all(line.as_bytes()).await?;
+//!             }
+//!         }
+//!     }
+//!     Ok(())
+//! }
+//! ```
+
+use std::io::{self, Write};
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+use ::tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+
+use crate::console::{conin_r, Conin};
+use crate::event::Event;
+
+/// Async read side of the tty/console, see [`conin`].
+pub struct AsyncConin {
+    inner: imp::Input,
+}
+
+/// Async write side of the tty/console, see [`conout`].
+pub struct AsyncConout {
+    inner: imp::Output,
+}
+
+/// Return the read side of the tty/console for async use.
+///
+/// Reading the console through [`crate::conin`] at the same time mixes up
+/// the input, use one or the other.
+pub fn conin() -> io::Result<AsyncConin> {
+    Ok(AsyncConin {
+        inner: imp::Input::new(conin_r()?)?,
+    })
+}
+
+/// Return the write side of the tty/console for async use.
+pub fn conout() -> io::Result<AsyncConout> {
+    Ok(AsyncConout {
+        inner: imp::Output::open()?,
+    })
+}
+
+impl AsyncConin {
+    /// Wait for the next event, None at the end of input.
+    pub async fn get_event(&mut self) -> Option<io::Result<Event>> {
+        self.get_event_and_raw()
+            .await
+            .map(|res| res.map(|(event, _)| event))
+    }
+
+    /// Wait for the next event and the bytes that define it, None at the
+    /// end of input.
+    pub async fn get_event_and_raw(&mut self) -> Option<io::Result<(Event, Vec<u8>)>> {
+        self.inner.get_event_and_raw().await
+    }
+}
+
+impl AsyncRead for AsyncConin {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        self.get_mut().inner.poll_read(cx, buf)
+    }
+}
+
+impl AsyncWrite for AsyncConout {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        self.get_mut().inner.poll_write(cx, buf)
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Poll::Ready(self.get_mut().inner.flush())
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Poll::Ready(self.get_mut().inner.flush())
+    }
+}
+
+#[cfg(unix)]
+mod imp {
+    use std::fs::File;
+    use std::time::Duration;
+
+    use ::tokio::io::unix::AsyncFd;
+
+    use super::*;
+    use crate::console::ConsoleRead;
+    use crate::sys::console::open_syscon_out_nonblock;
+
+    /// The tty registered with the reactor, it is already non-blocking.
+    pub(super) struct Input(AsyncFd<Conin>);
+
+    impl Input {
+        pub(super) fn new(conin: Conin) -> io::Result<Self> {
+            Ok(Input(AsyncFd::new(conin)?))
+        }
+
+        pub(super) async fn get_event_and_raw(&mut self) -> Option<io::Result<(Event, Vec<u8>)>> {
+            loop {
+                // The tty starts out ready, so events the console already
+                // holds are returned before waiting.
+                let mut guard = match self.0.readable_mut().await {
+                    Ok(guard) => guard,
+                    Err(err) => return Some(Err(err)),
+                };
+                match guard
+                    .get_inner_mut()
+                    .get_event_and_raw(Some(Duration::ZERO))
+                {
+                    Some(Err(err)) if err.kind() == io::ErrorKind::WouldBlock => {
+                        guard.clear_ready()
+                    }
+                    res => return res,
+                }
+            }
+        }
+
+        pub(super) fn poll_read(
+            &mut self,
+            cx: &mut Context<'_>,
+            buf: &mut ReadBuf<'_>,
+        ) -> Poll<io::Result<()>> {
+            loop {
+                let mut guard = match self.0.poll_read_ready_mut(cx) {
+                    Poll::Ready(Ok(guard)) => guard,
+                    Poll::Ready(Err(err)) => return Poll::Ready(Err(err)),
+                    Poll::Pending => return Poll::Pending,
+                };
+                let unfilled = buf.initialize_unfilled();
+                match guard
+                    .get_inner_mut()
+                    .read_timeout(unfilled, Some(Duration::ZERO))
+                {
+                    Ok(len) => {
+                        buf.advance(len);
+                        return Poll::Ready(Ok(()));
+                    }
+                    Err(err) if err.kind() == io::ErrorKind::WouldBlock => guard.clear_ready(),
+                    Err(err) => return Poll::Ready(Err(err)),
+                }
+            }
+        }
+    }
+
+    /// The tty opened non-blocking and registered with the reactor, so a
+    /// write to a slow terminal waits without blocking the runtime.
+    pub(super) struct Output(AsyncFd<File>);
+
+    impl Output {
+        pub(super) fn open() -> io::Result<Self> {
+            Ok(Output(AsyncFd::new(open_syscon_out_nonblock()?)?))
+        }
+
+        pub(super) fn flush(&mut self) -> io::Result<()> {
+            self.0.get_mut().flush()
+        }
+
+        pub(super) fn poll_write(
+            &mut self,
+            cx: &mut Context<'_>,
+            buf: &[u8],
+        ) -> Poll<io::Result<usize>> {
+            loop {
+                let mut guard = match self.0.poll_write_ready_mut(cx) {
+                    Poll::Ready(Ok(guard)) => guard,
+                    Poll::Ready(Err(err)) => return Poll::Ready(Err(err)),
+                    Poll::Pending => return Poll::Pending,
+                };
+                match guard.get_inner_mut().write(buf) {
+                    Err(err) if err.kind() == io::ErrorKind::WouldBlock => guard.clear_ready(),
+                    res => return Poll::Ready(res),
+                }
+            }
+        }
+    }
+}
+
+#[cfg(windows)]
+mod imp {
+    use std::thread;
+
+    use ::tokio::sync::mpsc::{unbounded_channel, UnboundedReceiver};
+
+    use super::*;
+    use crate::console::{conout_r, Conout, ConsoleRead, Waker};
+
+    /// Events read by a thread, with the bytes of one not yet read through
+    /// AsyncRead.
+    pub(super) struct Input {
+        events: UnboundedReceiver<io::Result<(Event, Vec<u8>)>>,
+        raw: Vec<u8>,
+        waker: Waker,
+    }
+
+    impl Input {
+        pub(super) fn new(mut conin: Conin) -> io::Result<Self> {
+            let waker = conin.lock().waker();
+            let (send, events) = unbounded_channel();
+            thread::Builder::new()
+                .name("sl-console-tokio".to_string())
+                .spawn(move || {
+                    while let Some(res) = conin.get_event_and_raw(None) {
+                        let failed = res.is_err();
+                        if send.send(res).is_err() || failed {
+                            break;
+                        }
+                    }
+                })?;
+            Ok(Input {
+                events,
+                raw: Vec::new(),
+                waker,
+            })
+        }
+
+        pub(super) async fn get_event_and_raw(&mut self) -> Option<io::Result<(Event, Vec<u8>)>> {
+            self.events.recv().await
+        }
+
+        pub(super) fn poll_read(
+            &mut self,
+            cx: &mut Context<'_>,
+            buf: &mut ReadBuf<'_>,
+        ) -> Poll<io::Result<()>> {
+            if self.raw.is_empty() {
+                match self.events.poll_recv(cx) {
+                    Poll::Ready(Some(Ok((_, raw)))) => self.raw = raw,
+                    Poll::Ready(Some(Err(err))) => return Poll::Ready(Err(err)),
+                    Poll::Ready(None) => return Poll::Ready(Ok(())),
+                    Poll::Pending => return Poll::Pending,
+                }
+            }
+            let len = self.raw.len().min(buf.remaining());
+            buf.put_slice(&self.raw[..len]);
+            self.raw.drain(..len);
+            Poll::Ready(Ok(()))
+        }
+    }
+
+    impl Drop for Input {
+        fn drop(&mut self) {
+            // Wake the thread so it sees the channel closed and ends.
+            self.events.close();
+            let _ = self.waker.wake(Event::Tick);
+        }
+    }
+
+    /// Console writes do not block for long, they are made directly.
+    pub(super) struct Output(Conout);
+
+    impl Output {
+        pub(super) fn open() -> io::Result<Self> {
+            Ok(Output(conout_r()?))
+        }
+
+        pub(super) fn flush(&mut self) -> io::Result<()> {
+            self.0.flush()
+        }
+
+        pub(super) fn poll_write(
+            &mut self,
+            _cx: &mut Context<'_>,
+            buf: &[u8],
+        ) -> Poll<io::Result<usize>> {
+            Poll::Ready(self.0.write(buf))
+        }
+    }
+}
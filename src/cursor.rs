@@ -1,18 +1,80 @@
 //! Cursor movement.
-
+//!
+//! # Coordinates
+//!
+//! The terminal counts columns and rows from 1.  An application that would
+//! rather count from 0 sets [`Origin::Zero`] on its console with
+//! [`ConsoleIn::set_origin`](crate::console::ConsoleIn::set_origin), then
+//! the mouse and cursor position events read from that console (and
+//! [`cursor_pos`]) are 0 based, and writes positions with
+//! [`Origin::goto`].  The conversion happens only where they meet the
+//! terminal.
+//!
+//! ```rust
+//! use sl_console::cursor::Origin;
+//!
+//! assert_eq!(Origin::Zero.goto(0, 0).to_string(), "\x1B[1;1H");
+//! assert_eq!(Origin::One.goto(1, 1).to_string(), "\x1B[1;1H");
+//! ```
+
+use crate::clock::{Clock, Deadline, SYSTEM_CLOCK};
 use crate::console::*;
+use crate::event::Event;
+use crate::grid::CellStyle;
 use numtoa::NumToA;
 use std::fmt;
 use std::io::{self, Error, ErrorKind, Write};
 use std::ops;
-use std::time::{Duration, Instant};
+use std::time::Duration;
 
 /// The timeout of an escape code control sequence, in milliseconds.
 const CONTROL_SEQUENCE_TIMEOUT: u64 = 100;
 
+/// Where the application's coordinates start.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, Default)]
+pub enum Origin {
+    /// The top left cell is (1, 1), as for the terminal.
+    #[default]
+    One,
+    /// The top left cell is (0, 0).
+    Zero,
+}
+
+impl Origin {
+    /// Convert application coordinates to the terminal's (one based).
+    pub fn to_terminal(self, x: u16, y: u16) -> (u32, u32) {
+        match self {
+            Origin::One => (x as u32, y as u32),
+            Origin::Zero => (x as u32 + 1, y as u32 + 1),
+        }
+    }
+
+    /// Convert terminal coordinates (one based) to the application's.
+    pub fn from_terminal(self, x: u16, y: u16) -> (u16, u16) {
+        match self {
+            Origin::One => (x, y),
+            Origin::Zero => (x.saturating_sub(1), y.saturating_sub(1)),
+        }
+    }
+
+    /// [`Goto`] the application's (x, y).
+    pub fn goto(self, x: u16, y: u16) -> Goto {
+        match self {
+            Origin::One => Goto(x, y),
+            Origin::Zero => Goto(x.saturating_add(1), y.saturating_add(1)),
+        }
+    }
+}
+
 derive_csi_sequence!("Hide the cursor.", Hide, "?25l");
 derive_csi_sequence!("Show the cursor.", Show, "?25h");
 
+derive_csi_sequence!(
+    "Ask for the cursor position, the terminal replies with an [`Event::CursorPos`](crate::event::Event::CursorPos).",
+    ReportPosition,
+    "6n"
+);
+
 derive_csi_sequence!("Restore the cursor.", Restore, "u");
 derive_csi_sequence!("Save the cursor.", Save, "s");
 
@@ -49,7 +111,7 @@ derive_csi_sequence!("Change the cursor style to steady bar", SteadyBar, "\x36 q
 ///
 /// ANSI escapes are very poorly designed, and one of the many odd aspects is being one-based. This
 /// can be quite strange at first, but it is not that big of an obstruction once you get used to
-/// it.
+/// it.  Or count from 0 with [`Origin::Zero`] and [`Origin::goto`].
 ///
 /// # Example
 ///
@@ -168,10 +230,9 @@ pub fn goto(x: u16, y: u16) -> io::Result<()> {
     Ok(())
 }
 
-/// Return the current cursor position.
+/// Return the current cursor position, counted from the console's
+/// [`origin`](ConsoleIn::set_origin).
 pub fn cursor_pos() -> io::Result<(u16, u16)> {
-    let delimiter = b'R';
-
     {
         let mut conout = conout_r()?.lock();
         // Where is the cursor?
@@ -180,54 +241,68 @@ pub fn cursor_pos() -> io::Result<(u16, u16)> {
         conout.flush()?;
     }
 
+    // The console already counts the reply from the origin.
     let mut conin = conin_r()?.lock();
-    let mut buf: [u8; 1] = [0];
-    let mut read_chars = Vec::new();
-
-    let timeout = Duration::from_millis(CONTROL_SEQUENCE_TIMEOUT);
-    let now = Instant::now();
-    while buf[0] != delimiter && now.elapsed() < timeout {
-        match conin.read_timeout(&mut buf, Some(timeout - now.elapsed())) {
-            Ok(1) => {
-                read_chars.push(buf[0]);
-            }
-            Ok(0) => {
-                return Err(io::Error::new(
-                    io::ErrorKind::UnexpectedEof,
-                    "Unexpected EOF.",
-                ));
+    read_cursor_pos(&mut conin, &SYSTEM_CLOCK)
+}
+
+/// Read the reply to a cursor position query.
+///
+/// Other events read while waiting for it, keys typed before the reply
+/// arrived for instance, are put back in order for the next read.
+fn read_cursor_pos<R: ConsoleRead>(conin: &mut R, clock: &dyn Clock) -> io::Result<(u16, u16)> {
+    let mut other = Vec::new();
+    let deadline = Deadline::new(clock, Duration::from_millis(CONTROL_SEQUENCE_TIMEOUT));
+    let res = loop {
+        let remaining = match deadline.remaining() {
+            Some(remaining) => remaining,
+            None => {
+                break Err(Error::new(
+                    ErrorKind::Other,
+                    "Cursor position detection timed out.",
+                ))
             }
-            Ok(_) => {}
-            Err(err) if err.kind() == io::ErrorKind::WouldBlock => {}
-            Err(err) => return Err(err),
+        };
+        match conin.get_event_and_raw(Some(remaining)) {
+            Some(Ok((Event::CursorPos(x, y), _))) => break Ok((x, y)),
+            Some(Ok(event)) => other.push(event),
+            Some(Err(err)) if err.kind() == ErrorKind::WouldBlock => {}
+            Some(Err(err)) => break Err(err),
+            None => break Err(Error::new(ErrorKind::UnexpectedEof, "Unexpected EOF.")),
         }
+    };
+    // Put back last first.  Readers that can not put events back lose them.
+    for (event, raw) in other.into_iter().rev() {
+        let _ = conin.unget_event_and_raw(event, raw);
     }
+    res
+}
 
-    if read_chars.pop().unwrap_or(b'\0') == delimiter && !read_chars.is_empty() {
-        // The answer will look like `ESC [ Cy ; Cx R`.
-        // The pop in the if removes and verifies the delimiter
-        if let Ok(read_str) = String::from_utf8(read_chars) {
-            if let Some(beg) = read_str.rfind('[') {
-                let coords: String = read_str.chars().skip(beg + 1).collect();
-                let mut nums = coords.split(';');
-                if let (Some(cy), Some(cx)) = (nums.next(), nums.next()) {
-                    if let (Ok(cy), Ok(cx)) = (cy.parse::<u16>(), cx.parse::<u16>()) {
-                        return Ok((cx, cy));
-                    }
-                }
-            }
-        }
-        return Err(Error::new(
-            ErrorKind::Other,
-            "Failed to parse coords from chars read from console.",
-        ));
-    }
-    Err(Error::new(
-        ErrorKind::Other,
-        "Cursor position detection timed out.",
-    ))
+/// Extension to `ConsoleWrite` for drawing a string at a position.
+///
+/// Each call builds the whole sequence first and writes it with one
+/// `write_all`, the output is not flushed.  Positions are counted like
+/// [`Goto`].
+pub trait PrintAtExt: ConsoleWrite {
+    /// Write `text` starting at (x, y).
+    fn print_at(&mut self, x: u16, y: u16, text: &str) -> io::Result<()> {
+        let mut buf = Goto(x, y).to_string();
+        buf.push_str(text);
+        self.write_all(buf.as_bytes())
+    }
+
+    /// Write `text` starting at (x, y) in `style`, then reset the style.
+    fn print_styled_at(&mut self, x: u16, y: u16, style: CellStyle, text: &str) -> io::Result<()> {
+        let mut buf = Goto(x, y).to_string();
+        buf.push_str(&style.transition(None));
+        buf.push_str(text);
+        buf.push_str("\x1B[m");
+        self.write_all(buf.as_bytes())
+    }
 }
 
+impl<W: ConsoleWrite + ?Sized> PrintAtExt for W {}
+
 /// Hide the cursor for the lifetime of this struct.
 /// It will hide the cursor on creation with from() and show it back on drop().
 pub struct HideCursor<W: ConsoleWrite> {
@@ -282,3 +357,135 @@ impl<W: ConsoleWrite> ConsoleWrite for HideCursor<W> {
         self.output.is_raw_mode()
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::clock::{SilentConsole, TestClock};
+    use crate::event::{Key, KeyCode, MouseButton, MouseEvent};
+    use crate::grid::{Attrs, CellColor};
+
+    struct Output(Vec<u8>);
+
+    impl Write for Output {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.write(buf)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    impl ConsoleWrite for Output {
+        fn set_raw_mode(&mut self, _mode: bool) -> io::Result<bool> {
+            Ok(false)
+        }
+
+        fn is_raw_mode(&self) -> bool {
+            false
+        }
+    }
+
+    #[test]
+    fn test_print_at() {
+        let mut out = Output(Vec::new());
+        out.print_at(3, 2, "hi").unwrap();
+        let style = CellStyle {
+            fg: CellColor::Ansi(1),
+            attrs: Attrs::BOLD,
+            ..Default::default()
+        };
+        out.print_styled_at(1, 1, style, "x").unwrap();
+        assert_eq!(
+            out.0,
+            b"\x1B[2;3Hhi\x1B[1;1H\x1b[0;1;38;5;1mx\x1B[m".to_vec()
+        );
+    }
+
+    /// Reads from a buffer and can put events back.
+    struct Unget<'a> {
+        input: &'a [u8],
+        unread: Vec<(Event, Vec<u8>)>,
+    }
+
+    impl<'a> ConsoleRead for Unget<'a> {
+        fn get_event_and_raw(
+            &mut self,
+            timeout: Option<Duration>,
+        ) -> Option<io::Result<(Event, Vec<u8>)>> {
+            match self.unread.pop() {
+                Some(unread) => Some(Ok(unread)),
+                None => self.input.get_event_and_raw(timeout),
+            }
+        }
+
+        fn poll(&mut self, timeout: Option<Duration>) -> bool {
+            !self.unread.is_empty() || self.input.poll(timeout)
+        }
+
+        fn read_timeout(&mut self, buf: &mut [u8], timeout: Option<Duration>) -> io::Result<usize> {
+            self.input.read_timeout(buf, timeout)
+        }
+
+        fn unget_event_and_raw(&mut self, event: Event, raw: Vec<u8>) -> io::Result<()> {
+            self.unread.push((event, raw));
+            Ok(())
+        }
+    }
+
+    impl<'a> io::Read for Unget<'a> {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            self.input.read(buf)
+        }
+    }
+
+    #[test]
+    fn test_read_cursor_pos() {
+        let clock = TestClock::new();
+        assert_eq!(
+            read_cursor_pos(&mut &b"\x1B[12;34R"[..], &clock).unwrap(),
+            (34, 12)
+        );
+        // Keys typed before the reply are kept.
+        let mut conin = Unget {
+            input: b"ab\x1B[3;4Rc",
+            unread: Vec::new(),
+        };
+        assert_eq!(read_cursor_pos(&mut conin, &clock).unwrap(), (4, 3));
+        let keys: Vec<_> = (0..3)
+            .map(|_| conin.get_event_and_raw(None).unwrap().unwrap())
+            .collect();
+        assert_eq!(
+            keys,
+            vec![
+                (Event::Key(Key::new(KeyCode::Char('a'))), b"a".to_vec()),
+                (Event::Key(Key::new(KeyCode::Char('b'))), b"b".to_vec()),
+                (Event::Key(Key::new(KeyCode::Char('c'))), b"c".to_vec()),
+            ]
+        );
+        let start = clock.now();
+        let err = read_cursor_pos(&mut SilentConsole { clock: &clock }, &clock).unwrap_err();
+        assert_eq!(err.to_string(), "Cursor position detection timed out.");
+        assert_eq!(
+            clock.now() - start,
+            Duration::from_millis(CONTROL_SEQUENCE_TIMEOUT)
+        );
+    }
+
+    #[test]
+    fn test_origin() {
+        assert_eq!(Origin::One.to_terminal(3, 4), (3, 4));
+        assert_eq!(Origin::Zero.to_terminal(0, 65535), (1, 65536));
+        assert_eq!(Origin::One.from_terminal(3, 4), (3, 4));
+        assert_eq!(Origin::Zero.from_terminal(1, 5), (0, 4));
+        assert_eq!(Origin::Zero.from_terminal(0, 0), (0, 0));
+        assert_eq!(Origin::Zero.goto(0, 4).to_string(), "\x1B[5;1H");
+        assert_eq!(Origin::One.goto(3, 4).to_string(), "\x1B[4;3H");
+        let mouse = MouseEvent::Hold(MouseButton::Left, 5, 7);
+        assert_eq!(
+            mouse.map_position(|x, y| Origin::Zero.from_terminal(x, y)),
+            MouseEvent::Hold(MouseButton::Left, 4, 6)
+        );
+    }
+}
@@ -0,0 +1,526 @@
+//! Log the escape sequences written to a terminal.
+//!
+//! [`DebugWriter`] wraps a writer and passes every byte through unchanged
+//! while decoding the output and logging each escape sequence with a
+//! readable name (through the `log` crate with the `sl_console::debug`
+//! target).  Escape sequences are logged at debug level and the text
+//! between them at trace level, so it shows exactly what rendering code
+//! emits without a terminal recorder.  `DebugWriter` needs the `log`
+//! feature, [`describe_escape`] is always available.
+//!
+//! # Example
+//!
+//! ```rust,no_run
+//! use sl_console::debug::DebugWriter;
+//! use sl_console::*;
+//! use std::io::Write;
+//!
+//!     con_init().unwrap();
+//!     let mut out = DebugWriter::new(conout());
+//!     // Logs `cursor::Goto(1, 1)`, `style::Bold` and the text.
+//!     write!(out, "{}{}Hello", cursor::Goto(1, 1), style::Bold).unwrap();
+//!     out.flush().unwrap();
+//! ```
+
+use crate::input::MOUSE_MODES;
+
+#[cfg(feature = "log")]
+pub use self::writer::DebugWriter;
+
+const COLOR_NAMES: [&str; 16] = [
+    "Black",
+    "Red",
+    "Green",
+    "Yellow",
+    "Blue",
+    "Magenta",
+    "Cyan",
+    "White",
+    "LightBlack",
+    "LightRed",
+    "LightGreen",
+    "LightYellow",
+    "LightBlue",
+    "LightMagenta",
+    "LightCyan",
+    "LightWhite",
+];
+
+const CURSOR_STYLES: [&str; 7] = [
+    "BlinkingBlock",
+    "BlinkingBlock",
+    "SteadyBlock",
+    "BlinkingUnderline",
+    "SteadyUnderline",
+    "BlinkingBar",
+    "SteadyBar",
+];
+
+/// A piece of decoded output.
+#[derive(Debug, PartialEq, Eq)]
+pub(crate) enum Piece<'a> {
+    Text(&'a [u8]),
+    Escape(&'a [u8]),
+}
+
+/// Splits output into text and escape sequences, escape sequences split
+/// across writes are held until they are complete.
+#[derive(Debug, Default)]
+pub(crate) struct Decoder {
+    pending: Vec<u8>,
+}
+
+impl Decoder {
+    pub(crate) fn feed<F: FnMut(Piece)>(&mut self, bytes: &[u8], mut emit: F) {
+        let mut joined = std::mem::take(&mut self.pending);
+        let mut bytes = if joined.is_empty() {
+            bytes
+        } else {
+            joined.extend_from_slice(bytes);
+            &joined[..]
+        };
+        while !bytes.is_empty() {
+            bytes = self.split_one(bytes, &mut emit);
+        }
+    }
+
+    /// Emit the first piece of `bytes` and return the rest.
+    fn split_one<'a, F: FnMut(Piece)>(&mut self, bytes: &'a [u8], emit: &mut F) -> &'a [u8] {
+        if bytes[0] != 0x1B {
+            let end = bytes.iter().position(|b| *b == 0x1B).unwrap_or(bytes.len());
+            emit(Piece::Text(&bytes[..end]));
+            return &bytes[end..];
+        }
+        match escape_len(bytes) {
+            Some(len) => {
+                emit(Piece::Escape(&bytes[..len]));
+                &bytes[len..]
+            }
+            None => {
+                self.pending.extend_from_slice(bytes);
+                &[]
+            }
+        }
+    }
+}
+
+/// The length of the escape sequence at the start of `bytes`, None if it is
+/// incomplete.
+fn escape_len(bytes: &[u8]) -> Option<usize> {
+    match bytes.get(1)? {
+        b'[' => {
+            for (i, b) in bytes.iter().enumerate().skip(2) {
+                match b {
+                    0x20..=0x3F => {}
+                    0x40..=0x7E => return Some(i + 1),
+                    // Malformed, end the sequence before the stray byte.
+                    _ => return Some(i),
+                }
+            }
+            None
+        }
+        // String sequences end with BEL or ST (ESC \).
+        b']' | b'P' | b'_' | b'^' => {
+            for (i, b) in bytes.iter().enumerate().skip(2) {
+                if *b == 0x07 {
+                    return Some(i + 1);
+                }
+                if *b == 0x1B {
+                    return match bytes.get(i + 1)? {
+                        b'\\' => Some(i + 2),
+                        _ => Some(i),
+                    };
+                }
+            }
+            None
+        }
+        // Intermediate byte, such as a character set selection `ESC ( B`.
+        0x20..=0x2F => bytes.get(2).map(|_| 3),
+        _ => Some(2),
+    }
+}
+
+fn param(params: &[&str], index: usize, default: u16) -> u16 {
+    match params.get(index).and_then(|p| p.parse().ok()) {
+        Some(0) | None => default,
+        Some(value) => value,
+    }
+}
+
+fn color_name(value: u16) -> String {
+    match COLOR_NAMES.get(value as usize) {
+        Some(name) => (*name).to_string(),
+        None => format!("AnsiValue({})", value),
+    }
+}
+
+/// Describe the SGR parameters, one name per attribute.
+fn describe_sgr(params: &[&str]) -> String {
+    if params.is_empty() {
+        return "style::Reset".to_string();
+    }
+    let mut names = Vec::new();
+    let mut iter = params.iter().map(|p| p.parse::<u16>().unwrap_or(0));
+    while let Some(code) = iter.next() {
+        let name = match code {
+            0 => "style::Reset".to_string(),
+            1 => "style::Bold".to_string(),
+            2 => "style::Faint".to_string(),
+            3 => "style::Italic".to_string(),
+            4 => "style::Underline".to_string(),
+            5 => "style::Blink".to_string(),
+            6 => "style::RapidBlink".to_string(),
+            7 => "style::Invert".to_string(),
+            8 => "style::Conceal".to_string(),
+            9 => "style::CrossedOut".to_string(),
+            10..=19 => format!("style::Font({})", code - 10),
+            21 => "double underline".to_string(),
+            22 => "style::NoBold".to_string(),
+            23 => "style::NoItalic".to_string(),
+            24 => "style::NoUnderline".to_string(),
+            25 => "style::NoBlink".to_string(),
+            27 => "style::NoInvert".to_string(),
+            28 => "style::Reveal".to_string(),
+            29 => "style::NoCrossedOut".to_string(),
+            51 => "style::Framed".to_string(),
+            53 => "style::Overline".to_string(),
+            55 => "style::NoOverline".to_string(),
+            30..=37 => format!("color::Fg({})", color_name(code - 30)),
+            90..=97 => format!("color::Fg({})", color_name(code - 90 + 8)),
+            40..=47 => format!("color::Bg({})", color_name(code - 40)),
+            100..=107 => format!("color::Bg({})", color_name(code - 100 + 8)),
+            39 => "color::Fg(Reset)".to_string(),
+            49 => "color::Bg(Reset)".to_string(),
+            38 | 48 => {
+                let layer = if code == 38 { "Fg" } else { "Bg" };
+                match iter.next() {
+                    Some(5) => {
+                        format!("color::{}({})", layer, color_name(iter.next().unwrap_or(0)))
+                    }
+                    Some(2) => {
+                        let r = iter.next().unwrap_or(0);
+                        let g = iter.next().unwrap_or(0);
+                        let b = iter.next().unwrap_or(0);
+                        format!("color::{}(Rgb({}, {}, {}))", layer, r, g, b)
+                    }
+                    _ => format!("unknown color {}", code),
+                }
+            }
+            _ => format!("SGR {}", code),
+        };
+        names.push(name);
+    }
+    names.join(", ")
+}
+
+fn describe_private_mode(mode: &str, set: bool) -> String {
+    if mode.parse().is_ok_and(|mode| MOUSE_MODES.contains(&mode)) {
+        return format!("mouse mode {} {}", mode, if set { "on" } else { "off" });
+    }
+    let name = match (mode, set) {
+        ("5", true) => "bell::ReverseScreen",
+        ("5", false) => "bell::NormalScreen",
+        ("25", true) => "cursor::Show",
+        ("25", false) => "cursor::Hide",
+        ("1049", true) => "screen::ToAlternateScreen",
+        ("1049", false) => "screen::ToMainScreen",
+        ("2026", true) => "begin synchronized update",
+        ("2026", false) => "end synchronized update",
+        ("2004", true) => "bracketed paste on",
+        ("2004", false) => "bracketed paste off",
+        ("1004", true) => "focus reports on",
+        ("1004", false) => "focus reports off",
+        _ => {
+            return format!(
+                "{} private mode {}",
+                if set { "set" } else { "reset" },
+                mode
+            );
+        }
+    };
+    name.to_string()
+}
+
+fn describe_csi(seq: &[u8]) -> String {
+    let body = String::from_utf8_lossy(&seq[2..seq.len() - 1]);
+    let final_byte = seq[seq.len() - 1] as char;
+    let (private, body) = match body.chars().next() {
+        Some(c @ '<'..='?') => (Some(c), &body[1..]),
+        _ => (None, &body[..]),
+    };
+    let param_end = body
+        .find(|c: char| (' '..='/').contains(&c))
+        .unwrap_or(body.len());
+    let intermediates = &body[param_end..];
+    let params: Vec<&str> = if param_end == 0 {
+        Vec::new()
+    } else {
+        body[..param_end].split(';').collect()
+    };
+    match (private, intermediates, final_byte) {
+        (None, "", 'H') | (None, "", 'f') => {
+            format!(
+                "cursor::Goto({}, {})",
+                param(&params, 1, 1),
+                param(&params, 0, 1)
+            )
+        }
+        (None, "", 'A') => format!("cursor::Up({})", param(&params, 0, 1)),
+        (None, "", 'B') => format!("cursor::Down({})", param(&params, 0, 1)),
+        (None, "", 'C') => format!("cursor::Right({})", param(&params, 0, 1)),
+        (None, "", 'D') => format!("cursor::Left({})", param(&params, 0, 1)),
+        (None, "", 'S') => format!("scroll::Up({})", param(&params, 0, 1)),
+        (None, "", 'T') => format!("scroll::Down({})", param(&params, 0, 1)),
+        (None, "", 'J') => match param(&params, 0, 0) {
+            0 => "clear::AfterCursor".to_string(),
+            1 => "clear::BeforeCursor".to_string(),
+            2 => "clear::All".to_string(),
+            3 => "clear scrollback".to_string(),
+            n => format!("clear screen {}", n),
+        },
+        (None, "", 'K') => match param(&params, 0, 0) {
+            0 => "clear::UntilNewline".to_string(),
+            1 => "clear line before cursor".to_string(),
+            2 => "clear::CurrentLine".to_string(),
+            n => format!("clear line {}", n),
+        },
+        (None, "", 's') => "cursor::Save".to_string(),
+        (None, "", 'u') => "cursor::Restore".to_string(),
+        (None, "", 'm') => describe_sgr(&params),
+        (None, "", 'n') if param(&params, 0, 0) == 6 => "cursor position query".to_string(),
+        (None, "", 'n') if param(&params, 0, 0) == 5 => "status query".to_string(),
+        (None, "", 'c') => "device attributes query".to_string(),
+        (None, "", 't') => match param(&params, 0, 0) {
+            1 => "window::Deiconify".to_string(),
+            2 => "window::Iconify".to_string(),
+            11 => "window::ReportState".to_string(),
+            13 => "window::ReportPosition".to_string(),
+            14 => "window::ReportSizePixels".to_string(),
+            18 => "window::ReportSizeChars".to_string(),
+            22 => "window::PushTitle".to_string(),
+            23 => "window::PopTitle".to_string(),
+            n => format!("window operation {}", n),
+        },
+        (None, " ", 'q') => match CURSOR_STYLES.get(param(&params, 0, 0) as usize) {
+            Some(style) => format!("cursor::{}", style),
+            None => "unknown cursor style".to_string(),
+        },
+        (Some('?'), "", 'h') | (Some('?'), "", 'l') => params
+            .iter()
+            .map(|mode| describe_private_mode(mode, final_byte == 'h'))
+            .collect::<Vec<_>>()
+            .join(", "),
+        (Some('?'), "$", 'p') => format!("query private mode {}", params.join(";")),
+        (Some('>'), "", 'u') => format!("push keyboard flags {}", param(&params, 0, 0)),
+        (Some('<'), "", 'u') => "pop keyboard flags".to_string(),
+        (Some('?'), "", 'u') => "keyboard flags query".to_string(),
+        _ => "unknown CSI".to_string(),
+    }
+}
+
+fn describe_osc(seq: &[u8]) -> String {
+    let body = String::from_utf8_lossy(&seq[2..]);
+    let command = body
+        .split(|c: char| !c.is_ascii_digit())
+        .next()
+        .unwrap_or("");
+    match command {
+        "0" | "2" => "set title".to_string(),
+        "1" => "set icon name".to_string(),
+        "8" => "hyperlink".to_string(),
+        "10" => "foreground color".to_string(),
+        "11" => "background color".to_string(),
+        "52" => "clipboard".to_string(),
+        _ => format!("OSC {}", command),
+    }
+}
+
+/// Return a readable name for the escape sequence `seq`.
+///
+/// Sequences this crate writes are named after the type that writes them
+/// (for example `cursor::Goto(3, 4)` or `style::Bold, color::Fg(Red)`),
+/// others get a short description.
+pub fn describe_escape(seq: &[u8]) -> String {
+    if seq.len() < 2 || seq[0] != 0x1B {
+        return "not an escape sequence".to_string();
+    }
+    match seq[1] {
+        b'[' if seq.len() > 2 => describe_csi(seq),
+        b']' => describe_osc(seq),
+        b'P' => "DCS".to_string(),
+        b'7' => "save cursor (DEC)".to_string(),
+        b'8' => "restore cursor (DEC)".to_string(),
+        b'c' => "reset terminal".to_string(),
+        b'=' => "application keypad".to_string(),
+        b'>' => "normal keypad".to_string(),
+        b'(' => "select character set".to_string(),
+        b => format!("ESC {}", b as char),
+    }
+}
+
+#[cfg(feature = "log")]
+mod writer {
+    use std::io::{self, Write};
+    use std::ops;
+
+    use super::{describe_escape, Decoder, Piece};
+    use crate::console::ConsoleWrite;
+
+    const LOG_TARGET: &str = "sl_console::debug";
+
+    fn log_piece(piece: Piece) {
+        match piece {
+            Piece::Text(text) => {
+                log::trace!(target: LOG_TARGET, "text {:?}", String::from_utf8_lossy(text))
+            }
+            Piece::Escape(seq) => log::debug!(
+                target: LOG_TARGET,
+                "{} {:?}",
+                describe_escape(seq),
+                String::from_utf8_lossy(seq)
+            ),
+        }
+    }
+
+    /// A writer that logs the escape sequences written through it.
+    ///
+    /// Every byte is passed to the wrapped writer unchanged.
+    pub struct DebugWriter<W: Write> {
+        inner: W,
+        decoder: Decoder,
+    }
+
+    impl<W: Write> DebugWriter<W> {
+        /// Wrap `inner`.
+        pub fn new(inner: W) -> Self {
+            DebugWriter {
+                inner,
+                decoder: Decoder::default(),
+            }
+        }
+
+        /// Return the wrapped writer.
+        pub fn into_inner(self) -> W {
+            self.inner
+        }
+    }
+
+    impl<W: Write> ops::Deref for DebugWriter<W> {
+        type Target = W;
+
+        fn deref(&self) -> &W {
+            &self.inner
+        }
+    }
+
+    impl<W: Write> ops::DerefMut for DebugWriter<W> {
+        fn deref_mut(&mut self) -> &mut W {
+            &mut self.inner
+        }
+    }
+
+    impl<W: Write> Write for DebugWriter<W> {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            let len = self.inner.write(buf)?;
+            if log::log_enabled!(target: LOG_TARGET, log::Level::Debug) {
+                self.decoder.feed(&buf[..len], log_piece);
+            }
+            Ok(len)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            self.inner.flush()
+        }
+    }
+
+    impl<W: ConsoleWrite> ConsoleWrite for DebugWriter<W> {
+        fn set_raw_mode(&mut self, mode: bool) -> io::Result<bool> {
+            self.inner.set_raw_mode(mode)
+        }
+
+        fn is_raw_mode(&self) -> bool {
+            self.inner.is_raw_mode()
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::color::{self, Bg, Fg, Rgb};
+    use crate::{clear, cursor, screen, style};
+
+    fn describe<D: std::fmt::Display>(escape: D) -> String {
+        describe_escape(escape.to_string().as_bytes())
+    }
+
+    #[test]
+    fn test_describe_escape() {
+        assert_eq!(describe(cursor::Goto(3, 4)), "cursor::Goto(3, 4)");
+        assert_eq!(describe(cursor::Left(2)), "cursor::Left(2)");
+        assert_eq!(describe(cursor::Hide), "cursor::Hide");
+        assert_eq!(describe(cursor::SteadyBar), "cursor::SteadyBar");
+        assert_eq!(describe(clear::All), "clear::All");
+        assert_eq!(describe(clear::UntilNewline), "clear::UntilNewline");
+        assert_eq!(
+            describe(screen::ToAlternateScreen),
+            "screen::ToAlternateScreen"
+        );
+        assert_eq!(describe(style::Reset), "style::Reset");
+        assert_eq!(describe(style::Bold), "style::Bold");
+        assert_eq!(describe(Fg(color::Red)), "color::Fg(Red)");
+        assert_eq!(
+            describe(Bg(color::AnsiValue(200))),
+            "color::Bg(AnsiValue(200))"
+        );
+        assert_eq!(describe(Fg(Rgb(1, 2, 3))), "color::Fg(Rgb(1, 2, 3))");
+        assert_eq!(
+            describe_escape(b"\x1B[1;31;49m"),
+            "style::Bold, color::Fg(Red), color::Bg(Reset)"
+        );
+        assert_eq!(
+            describe_escape(b"\x1B[?1000;1006h"),
+            "mouse mode 1000 on, mouse mode 1006 on"
+        );
+        assert_eq!(describe_escape(b"\x1B]0;title\x07"), "set title");
+        assert_eq!(describe_escape(b"\x1B[5x"), "unknown CSI");
+    }
+
+    #[test]
+    fn test_decoder() {
+        let mut decoder = Decoder::default();
+        let mut pieces = Vec::new();
+        let mut feed = |decoder: &mut Decoder, bytes: &[u8]| {
+            decoder.feed(bytes, |piece| {
+                pieces.push(match piece {
+                    Piece::Text(text) => (false, text.to_vec()),
+                    Piece::Escape(seq) => (true, seq.to_vec()),
+                })
+            })
+        };
+        feed(&mut decoder, b"ab\x1B[1");
+        feed(&mut decoder, b";2Hcd\x1B]0;t");
+        feed(&mut decoder, b"\x1B\\\x1B(B\x1B7");
+        assert_eq!(
+            pieces,
+            vec![
+                (false, b"ab".to_vec()),
+                (true, b"\x1B[1;2H".to_vec()),
+                (false, b"cd".to_vec()),
+                (true, b"\x1B]0;t\x1B\\".to_vec()),
+                (true, b"\x1B(B".to_vec()),
+                (true, b"\x1B7".to_vec()),
+            ]
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "log")]
+    fn test_debug_writer() {
+        use std::io::Write;
+
+        let mut out = DebugWriter::new(Vec::new());
+        write!(out, "{}hi{}", cursor::Goto(1, 2), style::Reset).unwrap();
+        assert_eq!(out.into_inner(), b"\x1B[2;1Hhi\x1B[m");
+    }
+}
@@ -0,0 +1,447 @@
+//! Find out what a terminal supports by asking it.
+//!
+//! [`probe`] sends a battery of queries (device attributes, status and
+//! cursor reports, the default colors, mode reports, the kitty keyboard
+//! flags and the terminal version) and returns a [`ProbeReport`] of what the
+//! terminal answered.  The report prints in a form suitable for pasting into
+//! a bug report and also holds the [`TerminalCaps`] derived from the
+//! answers.
+//!
+//! [`key_inspector`] shows the bytes each key sends and how they were
+//! parsed, see the `key_inspector` example.
+//!
+//! Problems the crate can not return as an error (input that fails to
+//! parse is returned as [`Event::Unsupported`]) are passed as a
+//! [`Diagnostic`] to the handler set with [`set_diagnostics_handler`].  The
+//! default handler, [`log_diagnostic`], logs them with the `log` crate if
+//! the `log` feature is enabled (it is by default).
+//!
+//! # Example
+//!
+//! ```rust,no_run
+//! use sl_console::*;
+//!
+//!     con_init().unwrap();
+//!     let mut conout = conout();
+//!     conout.raw_mode_on().unwrap();
+//!     let report = diagnostics::probe(&mut conin(), &mut conout);
+//!     conout.raw_mode_off().unwrap();
+//!     println!("{}", report.unwrap());
+//! ```
+
+use std::fmt;
+use std::io::{self, Write};
+use std::str;
+use std::time::Duration;
+
+use lazy_static::lazy_static;
+use parking_lot::RwLock;
+
+use crate::caps::{overrides, parse_version, TerminalCaps};
+use crate::clock::{Clock, Deadline, SYSTEM_CLOCK};
+use crate::color::Rgb;
+use crate::console::ConsoleRead;
+use crate::event::{Event, Key, KeyCode, KeyMod, ModeReport, ParseEventError};
+use crate::theme::parse_color_report;
+
+/// How long to wait for all the answers.
+const PROBE_TIMEOUT: Duration = Duration::from_millis(500);
+
+/// The DEC private modes asked about.
+const PROBE_MODES: &[u16] = &[
+    25, 1000, 1002, 1003, 1004, 1005, 1006, 1015, 1016, 1049, 2004, 2026,
+];
+
+lazy_static! {
+    static ref HANDLER: RwLock<fn(Diagnostic)> = RwLock::new(log_diagnostic);
+}
+
+/// A problem that could not be returned to the caller.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Diagnostic {
+    /// Input that failed to parse, it was returned as
+    /// [`Event::Unsupported`].
+    ParseFailure {
+        /// Why it failed.
+        error: String,
+        /// Why it failed, None if reading the input failed.
+        reason: Option<ParseEventError>,
+        /// The input that failed.
+        bytes: Vec<u8>,
+    },
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Diagnostic::ParseFailure { error, bytes, .. } => write!(
+                f,
+                "Failed to parse event: {} ({:?})",
+                error,
+                String::from_utf8_lossy(bytes)
+            ),
+        }
+    }
+}
+
+/// Send diagnostics to `handler` instead of the default
+/// [`log_diagnostic`], for instance to route them to the application's
+/// telemetry or to ignore them with `|_| {}`.
+pub fn set_diagnostics_handler(handler: fn(Diagnostic)) {
+    *HANDLER.write() = handler;
+}
+
+/// The default diagnostics handler, logs `diagnostic` as an error with the
+/// `log` crate, does nothing without the `log` feature.
+pub fn log_diagnostic(diagnostic: Diagnostic) {
+    #[cfg(feature = "log")]
+    log::error!("{}", diagnostic);
+    #[cfg(not(feature = "log"))]
+    let _ = diagnostic;
+}
+
+/// Pass `diagnostic` to the handler.
+pub(crate) fn report(diagnostic: Diagnostic) {
+    let handler = *HANDLER.read();
+    handler(diagnostic);
+}
+
+/// What a terminal answered to the queries sent by [`probe`], None (or
+/// empty) for queries it did not answer.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ProbeReport {
+    /// The primary device attributes (DA1) parameters.
+    pub primary_attributes: Option<Vec<u16>>,
+    /// The secondary device attributes (DA2) parameters, usually the
+    /// terminal type, firmware version and ROM cartridge number.
+    pub secondary_attributes: Option<Vec<u16>>,
+    /// The version string (XTVERSION).
+    pub version: Option<String>,
+    /// True if the device status report (DSR) said the terminal is OK.
+    pub status_ok: Option<bool>,
+    /// The cursor position report (x, y), one based.
+    pub cursor_position: Option<(u16, u16)>,
+    /// The default foreground color (OSC 10).
+    pub foreground: Option<Rgb>,
+    /// The default background color (OSC 11).
+    pub background: Option<Rgb>,
+    /// The kitty keyboard protocol flags.
+    pub kitty_keyboard: Option<u16>,
+    /// The mode reports (DECRPM) received.
+    pub modes: Vec<ModeReport>,
+    /// The input received while waiting that was not an answer.
+    pub unexpected: Vec<Vec<u8>>,
+    /// The capabilities from the environment updated with the answers,
+    /// with the [`overrides`](crate::caps::overrides) applied.
+    pub caps: TerminalCaps,
+}
+
+impl ProbeReport {
+    fn new() -> Self {
+        ProbeReport {
+            primary_attributes: None,
+            secondary_attributes: None,
+            version: None,
+            status_ok: None,
+            cursor_position: None,
+            foreground: None,
+            background: None,
+            kitty_keyboard: None,
+            modes: Vec::new(),
+            unexpected: Vec::new(),
+            caps: TerminalCaps::from_env(),
+        }
+    }
+
+    /// Record one reply.  Returns Some(true) for the primary device
+    /// attributes, which are asked for last, and None if `event` is not an
+    /// answer.
+    fn apply(&mut self, event: Event) -> Option<bool> {
+        match event {
+            Event::CursorPos(x, y) => {
+                self.cursor_position = Some((x, y));
+                Some(false)
+            }
+            Event::Csi(reply) => {
+                let last = self.caps.apply_reply(&reply);
+                if let Some(report) = reply.mode_report() {
+                    self.modes.push(report);
+                    return Some(false);
+                }
+                match (reply.private, reply.final_byte) {
+                    (Some(b'?'), b'u') => {
+                        self.kitty_keyboard = Some(reply.params.first().copied().unwrap_or(0))
+                    }
+                    (None, b'n') => self.status_ok = Some(reply.params.first() == Some(&0)),
+                    _ => return None,
+                }
+                Some(last)
+            }
+            Event::DeviceAttributes(attributes) if attributes.secondary => {
+                self.secondary_attributes = Some(attributes.params);
+                Some(false)
+            }
+            Event::DeviceAttributes(attributes) => {
+                let last = self.caps.apply_attributes(&attributes);
+                self.primary_attributes = Some(attributes.params);
+                Some(last)
+            }
+            Event::Osc { code, payload } if code == 10 || code == 11 => {
+                let color = str::from_utf8(&payload).ok().and_then(parse_color_report);
+                if code == 10 {
+                    self.foreground = color;
+                } else {
+                    self.background = color;
+                }
+                Some(false)
+            }
+            Event::Dcs(body) if body.starts_with(b">|") => {
+                self.version = parse_version(&body);
+                Some(false)
+            }
+            _ => None,
+        }
+    }
+}
+
+fn show<T: fmt::Debug>(f: &mut fmt::Formatter, name: &str, value: &Option<T>) -> fmt::Result {
+    match value {
+        Some(value) => writeln!(f, "{}: {:?}", name, value),
+        None => writeln!(f, "{}: no answer", name),
+    }
+}
+
+impl fmt::Display for ProbeReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "TERM: {}", self.caps.term)?;
+        show(f, "primary device attributes", &self.primary_attributes)?;
+        show(f, "secondary device attributes", &self.secondary_attributes)?;
+        show(f, "version", &self.version)?;
+        show(f, "status ok", &self.status_ok)?;
+        show(f, "cursor position", &self.cursor_position)?;
+        show(f, "foreground", &self.foreground)?;
+        show(f, "background", &self.background)?;
+        show(f, "kitty keyboard flags", &self.kitty_keyboard)?;
+        for mode in PROBE_MODES {
+            match self.modes.iter().find(|report| report.mode == *mode) {
+                Some(report) => writeln!(f, "mode {}: {:?}", mode, report.state)?,
+                None => writeln!(f, "mode {}: no answer", mode)?,
+            }
+        }
+        for raw in &self.unexpected {
+            writeln!(f, "unexpected input: {:?}", String::from_utf8_lossy(raw))?;
+        }
+        Ok(())
+    }
+}
+
+/// Send the diagnostic queries to `conout` and collect the answers from
+/// `conin`.
+///
+/// The console should be in raw mode or the answers will be echoed.  Waits
+/// until the terminal answers the primary device attributes query, which
+/// is sent last, or half a second.  Returns an error only if writing the
+/// queries or reading fails.
+pub fn probe<R, W>(conin: &mut R, conout: &mut W) -> io::Result<ProbeReport>
+where
+    R: ConsoleRead,
+    W: Write,
+{
+    probe_with(conin, conout, &SYSTEM_CLOCK)
+}
+
+fn probe_with<R, W>(conin: &mut R, conout: &mut W, clock: &dyn Clock) -> io::Result<ProbeReport>
+where
+    R: ConsoleRead,
+    W: Write,
+{
+    let mut queries = String::new();
+    for mode in PROBE_MODES {
+        queries.push_str(&format!("\x1B[?{}$p", mode));
+    }
+    // Secondary attributes, version, status, cursor position, colors and
+    // kitty flags then the primary attributes.
+    queries.push_str("\x1B[>c\x1B[>q\x1B[5n\x1B[6n\x1B]10;?\x1B\\\x1B]11;?\x1B\\\x1B[?u\x1B[c");
+    conout.write_all(queries.as_bytes())?;
+    conout.flush()?;
+
+    let mut report = ProbeReport::new();
+    let deadline = Deadline::new(clock, PROBE_TIMEOUT);
+    while let Some(remaining) = deadline.remaining() {
+        match conin.get_event_and_raw(Some(remaining)) {
+            Some(Ok((event, raw))) => match report.apply(event) {
+                Some(true) => break,
+                Some(false) => {}
+                None => report.unexpected.push(raw),
+            },
+            Some(Err(err)) if err.kind() == io::ErrorKind::WouldBlock => break,
+            Some(Err(err)) => return Err(err),
+            None => break,
+        }
+    }
+    overrides().apply(&mut report.caps);
+    Ok(report)
+}
+
+/// Name the part of the input parser that handles the event that starts
+/// with `raw`, for reports about keys that are not recognized.
+pub fn parser_branch(raw: &[u8]) -> &'static str {
+    match raw {
+        [] => "no input",
+        [0x1B] => "lone ESC (Esc key)",
+        [0x1B, b'O', ..] => "SS3 (ESC O)",
+        [0x1B, b'[', b'[', ..] => "Linux console function key (ESC [ [)",
+        [0x1B, b'[', b'<', ..] => "SGR mouse (ESC [ <)",
+        [0x1B, b'[', b'M', ..] => "X10 mouse (ESC [ M)",
+        [0x1B, b'[', b'?' | b'>' | b'=', ..] => "private CSI (ESC [ ? > =), a query reply",
+        [0x1B, b'[', .., b'~'] => "CSI special key (ESC [ ... ~)",
+        [0x1B, b'[', .., b'u'] => "CSI u key (kitty or fixterms)",
+        [0x1B, b'[', .., b'M'] => "urxvt mouse (ESC [ ... M)",
+        [0x1B, b'[', .., b'A'..=b'H' | b'P'..=b'S' | b'Z'] => "CSI cursor or function key",
+        [0x1B, b'[', ..] => "CSI sequence",
+        [0x1B, b']', ..] => "OSC (ESC ])",
+        [0x1B, b'P', _, ..] => "DCS (ESC P)",
+        [0x1B, ..] => "ESC prefix (Alt)",
+        [0x9B, ..] => "8-bit CSI",
+        [0x80..=0x9F, ..] => "C1 control",
+        [0x00..=0x1F | 0x7F, ..] => "C0 control (Ctrl key)",
+        [0x20..=0x7E] => "ASCII character",
+        _ => "UTF-8 character",
+    }
+}
+
+/// Show what the terminal sends for each key until Ctrl+C is pressed.
+///
+/// For every event read from `conin` a line with the raw bytes, the parser
+/// branch (see [`parser_branch`]) and the parsed event is written to
+/// `conout`.  The console should be in raw mode so keys are not echoed or
+/// line buffered.
+pub fn key_inspector<R, W>(conin: &mut R, conout: &mut W) -> io::Result<()>
+where
+    R: ConsoleRead,
+    W: Write,
+{
+    write!(
+        conout,
+        "Press keys to see what the terminal sends, Ctrl+C to quit.\r\n"
+    )?;
+    conout.flush()?;
+    while let Some(res) = conin.get_event_and_raw(None) {
+        let (event, raw) = res?;
+        let hex: Vec<String> = raw.iter().map(|b| format!("{:02X}", b)).collect();
+        write!(
+            conout,
+            "{:<24} {:?} | {} | {:?}\r\n",
+            hex.join(" "),
+            String::from_utf8_lossy(&raw),
+            parser_branch(&raw),
+            event
+        )?;
+        conout.flush()?;
+        if let Event::Key(Key {
+            code: KeyCode::Char('c'),
+            mods: Some(KeyMod::Ctrl),
+            ..
+        }) = event
+        {
+            break;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::clock::{SilentConsole, TestClock};
+    use crate::event::{parse_event, ModeState};
+    use parking_lot::Mutex;
+
+    lazy_static! {
+        static ref SEEN: Mutex<Vec<Diagnostic>> = Mutex::new(Vec::new());
+    }
+
+    #[test]
+    fn test_diagnostics_handler() {
+        set_diagnostics_handler(|diagnostic| SEEN.lock().push(diagnostic));
+        let mut iter = b"[<0;1M".iter().map(|b| Ok(*b));
+        let event = parse_event(b'\x1B', &mut iter).unwrap();
+        // Input that is only cut off is held, not reported.
+        let mut parser = crate::event::EventParser::new();
+        assert!(parser.advance(b"\x1B[1;5").is_empty());
+        assert_eq!(parser.advance(b"A").len(), 1);
+        assert_eq!(crate::event::parse_events(b"a\x1B[1;").1, 1);
+        set_diagnostics_handler(log_diagnostic);
+        assert_eq!(event, Event::Unsupported(b"\x1B[<0;1M".to_vec()));
+        // Other tests may report failures at the same time.
+        let seen = SEEN.lock();
+        assert!(!seen.iter().any(|diagnostic| match diagnostic {
+            Diagnostic::ParseFailure { bytes, .. } => bytes.starts_with(b"\x1B[1;"),
+        }));
+        let failure = seen
+            .iter()
+            .find(|diagnostic| match diagnostic {
+                Diagnostic::ParseFailure { bytes, .. } => bytes == b"\x1B[<0;1M",
+            })
+            .unwrap();
+        assert!(failure.to_string().starts_with("Failed to parse event: "));
+        assert!(matches!(
+            failure,
+            Diagnostic::ParseFailure {
+                reason: Some(ParseEventError::InvalidMouse),
+                ..
+            }
+        ));
+    }
+
+    #[test]
+    fn test_probe() {
+        let clock = TestClock::new();
+        let mut out = Vec::new();
+        let replies: &[u8] = b"\x1B[?1006;1$y\x1B[>41;354;0c\x1BP>|XTerm(354)\x1B\\\x1B[0n\
+            \x1B[3;5R\x1B]11;rgb:0000/8080/ffff\x1B\\\x1B[?1uX\x1B[?62;4c";
+        let report = probe_with(&mut &replies[..], &mut out, &clock).unwrap();
+        assert!(out.ends_with(b"\x1B[?u\x1B[c"));
+        assert_eq!(
+            report.modes,
+            vec![ModeReport {
+                mode: 1006,
+                private: true,
+                state: ModeState::Set
+            }]
+        );
+        assert_eq!(report.secondary_attributes, Some(vec![41, 354, 0]));
+        assert_eq!(report.version.as_deref(), Some("XTerm(354)"));
+        assert_eq!(report.status_ok, Some(true));
+        assert_eq!(report.cursor_position, Some((5, 3)));
+        assert_eq!(report.foreground, None);
+        assert_eq!(report.background, Some(Rgb(0, 128, 255)));
+        assert_eq!(report.kitty_keyboard, Some(1));
+        assert_eq!(report.unexpected, vec![b"X".to_vec()]);
+        assert_eq!(report.primary_attributes, Some(vec![62, 4]));
+        assert!(report.caps.kitty_keyboard && report.caps.sixel && report.caps.mouse.sgr);
+        let text = report.to_string();
+        assert!(text.contains("foreground: no answer\n"));
+        assert!(text.contains("mode 1006: Set\n"));
+
+        let report = probe_with(&mut SilentConsole { clock: &clock }, &mut out, &clock).unwrap();
+        assert_eq!(report.primary_attributes, None);
+    }
+
+    #[test]
+    fn test_key_inspector() {
+        let mut out = Vec::new();
+        key_inspector(&mut &b"a\x1B[A\x1B[<0;1;2M\x03b"[..], &mut out).unwrap();
+        let out = String::from_utf8(out).unwrap();
+        let lines: Vec<&str> = out.split("\r\n").collect();
+        assert_eq!(lines.len(), 6);
+        assert!(lines[1].starts_with("61 "));
+        assert!(lines[1].contains("| ASCII character | Key("));
+        assert!(lines[2].starts_with("1B 5B 41 "));
+        assert!(lines[2].contains("| CSI cursor or function key | Key("));
+        assert!(lines[3].contains("| SGR mouse (ESC [ <) | Mouse("));
+        assert!(lines[4].contains("| C0 control (Ctrl key) |"));
+        // Stopped at Ctrl+C.
+        assert_eq!(lines[5], "");
+    }
+}
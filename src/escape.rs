@@ -0,0 +1,164 @@
+//! A common interface for escape sequences.
+//!
+//! Every sequence type in the crate (cursor movement, colors, styles,
+//! clears, scrolling and screen switches) implements [`Escape`], so
+//! sequences can be written into a fixed buffer without allocating or be
+//! handled generically alongside their `Display` implementation.
+//!
+//! # Example
+//!
+//! ```rust
+//! use sl_console::escape::Escape;
+//! use sl_console::{clear, cursor};
+//!
+//! let mut buf = [0u8; 64];
+//! let mut len = clear::All.write_into(&mut buf);
+//! len += cursor::Goto(3, 4).write_into(&mut buf[len..]);
+//! assert_eq!(&buf[..len], b"\x1B[2J\x1B[4;3H");
+//! ```
+
+use std::borrow::Cow;
+use std::fmt::{self, Write};
+
+use crate::color::{Bg, Color, Fg};
+use crate::{bell, cursor, scroll, style};
+
+/// The longest color sequence, `ESC [ 38;2;255;255;255m`.
+const MAX_COLOR_LEN: usize = 19;
+
+/// The longest sequence with one u16 parameter.
+const MAX_ONE_PARAM_LEN: usize = 8;
+
+/// An escape sequence.
+pub trait Escape: fmt::Display {
+    /// The most bytes [`write_into`](Escape::write_into) will write.
+    fn len_hint(&self) -> usize;
+
+    /// Write the sequence to the start of `buf` and return the number of
+    /// bytes written.
+    ///
+    /// Panics if `buf` is shorter than the sequence, a buffer of
+    /// [`len_hint`](Escape::len_hint) bytes is always long enough.
+    fn write_into(&self, buf: &mut [u8]) -> usize;
+
+    /// The sequence as bytes, borrowed for sequences without parameters.
+    fn as_bytes(&self) -> Cow<'static, [u8]> {
+        let mut buf = vec![0; self.len_hint()];
+        let len = self.write_into(&mut buf);
+        buf.truncate(len);
+        Cow::Owned(buf)
+    }
+}
+
+/// A `fmt::Write` into a byte slice that fails instead of growing.
+struct SliceWriter<'a> {
+    buf: &'a mut [u8],
+    len: usize,
+}
+
+impl<'a> Write for SliceWriter<'a> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        let end = self.len + s.len();
+        if end > self.buf.len() {
+            return Err(fmt::Error);
+        }
+        self.buf[self.len..end].copy_from_slice(s.as_bytes());
+        self.len = end;
+        Ok(())
+    }
+}
+
+/// Format `value` into the start of `buf` without allocating, returns the
+/// number of bytes written.
+pub(crate) fn write_display<D: fmt::Display + ?Sized>(value: &D, buf: &mut [u8]) -> usize {
+    let mut writer = SliceWriter { buf, len: 0 };
+    write!(writer, "{}", value).expect("escape sequence longer than the buffer");
+    writer.len
+}
+
+macro_rules! impl_escape {
+    ($len:expr, $($name:ty),*) => {
+        $(
+            impl Escape for $name {
+                fn len_hint(&self) -> usize {
+                    $len
+                }
+
+                fn write_into(&self, buf: &mut [u8]) -> usize {
+                    write_display(self, buf)
+                }
+            }
+        )*
+    };
+}
+
+impl_escape!(
+    MAX_ONE_PARAM_LEN,
+    cursor::Left,
+    cursor::Right,
+    cursor::Up,
+    cursor::Down,
+    scroll::Up,
+    scroll::Down
+);
+// ESC [ y ; x H
+impl_escape!(14, cursor::Goto);
+// ESC [ 1 n m
+impl_escape!(5, style::Font);
+impl_escape!(1, bell::Bell);
+
+impl<C: Color> Escape for Fg<C> {
+    fn len_hint(&self) -> usize {
+        MAX_COLOR_LEN
+    }
+
+    fn write_into(&self, buf: &mut [u8]) -> usize {
+        write_display(self, buf)
+    }
+}
+
+impl<C: Color> Escape for Bg<C> {
+    fn len_hint(&self) -> usize {
+        MAX_COLOR_LEN
+    }
+
+    fn write_into(&self, buf: &mut [u8]) -> usize {
+        write_display(self, buf)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::color::{self, Rgb};
+    use crate::screen;
+
+    fn bytes<E: Escape>(escape: E) -> Vec<u8> {
+        let mut buf = vec![0; escape.len_hint()];
+        let len = escape.write_into(&mut buf);
+        assert_eq!(&buf[..len], escape.to_string().as_bytes());
+        assert_eq!(escape.as_bytes(), &buf[..len]);
+        buf.truncate(len);
+        buf
+    }
+
+    #[test]
+    fn test_escape() {
+        assert_eq!(bytes(cursor::Goto(65535, 65535)), b"\x1B[65535;65535H");
+        assert_eq!(bytes(cursor::Left(65535)), b"\x1B[65535D");
+        assert_eq!(bytes(scroll::Down(2)), b"\x1B[2T");
+        assert_eq!(bytes(Fg(Rgb(255, 255, 255))), b"\x1B[38;2;255;255;255m");
+        assert_eq!(bytes(Bg(color::AnsiValue(200))), b"\x1B[48;5;200m");
+        assert_eq!(bytes(Fg(color::Reset)), b"\x1B[39m");
+        assert_eq!(bytes(style::Bold), b"\x1B[1m");
+        assert_eq!(bytes(style::Font(9)), b"\x1B[19m");
+        assert_eq!(bytes(screen::ToAlternateScreen), b"\x1B[?1049h");
+        assert!(matches!(cursor::Hide.as_bytes(), Cow::Borrowed(_)));
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_short_buffer() {
+        cursor::Goto(10, 10).write_into(&mut [0; 4]);
+    }
+}
@@ -1,21 +1,301 @@
 //! Mouse and key events.
 
 use std::io::{Error, ErrorKind};
-use std::{io, str};
+use std::time::Duration;
+use std::{fmt, io, ops, str};
+
+use crate::diagnostics::{self, Diagnostic};
 
 /// An event reported by the terminal.
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Event {
     /// A key press.
     Key(Key),
-    /// A mouse button press, release or wheel use at specific coordinates.
-    Mouse(MouseEvent),
+    /// A character followed by combining marks, merged into one grapheme,
+    /// see [`ConsoleIn::set_combine_timeout`](crate::console::ConsoleIn::set_combine_timeout).
+    Grapheme(String),
+    /// A mouse button press, release or wheel use at specific coordinates,
+    /// with the modifier keys held.  Terminals usually keep Shift clicks for
+    /// themselves (selection) and some report no modifiers at all.
+    Mouse(MouseEvent, Option<KeyMod>),
+    /// A mouse event with pixel coordinates, reported instead of
+    /// [`Event::Mouse`] for SGR reports when
+    /// [`ParseOptions::sgr_pixels`] is set (mode 1016).
+    MousePixels(MouseEvent, Option<KeyMod>),
+    /// A cursor position report (x, y), the reply to
+    /// [`cursor::ReportPosition`](crate::cursor::ReportPosition).
+    ///
+    /// A report in row 1 at columns 2 to 16 reads the same as F3 with
+    /// modifiers (`CSI 1 ; mods R`) and is reported as that key.
+    CursorPos(u16, u16),
+    /// A device attributes reply (DA1 or DA2), see
+    /// [`caps::ReportPrimaryAttributes`](crate::caps::ReportPrimaryAttributes).
+    DeviceAttributes(DeviceAttributes),
+    /// A CSI sequence that is not a key or mouse event, these are usually
+    /// replies to queries sent to the terminal.
+    Csi(CsiSequence),
+    /// An operating system command (OSC), usually a reply to a query such
+    /// as the window title, a palette color or the clipboard.  Terminated
+    /// by BEL or ST.
+    Osc {
+        /// The numeric command before the first ';'.
+        code: u16,
+        /// The data after the first ';' without the terminator.
+        payload: Vec<u8>,
+    },
+    /// A device control string (DCS), the bytes between the introducer and
+    /// the terminator.
+    Dcs(Vec<u8>),
+    /// The tick interval elapsed, see
+    /// [`ConsoleIn::set_tick`](crate::console::ConsoleIn::set_tick).
+    Tick,
+    /// No input arrived for the idle period, holds how long the console has
+    /// been idle, see
+    /// [`ConsoleIn::set_idle`](crate::console::ConsoleIn::set_idle).
+    Idle(Duration),
+    /// The terminal was resized to (columns, rows), see
+    /// [`ConsoleIn::set_resize_events`](crate::console::ConsoleIn::set_resize_events).
+    Resize(u16, u16),
+    /// A user event sent with a [`Waker`](crate::console::Waker).
+    Custom(u64),
+    /// A file descriptor registered with
+    /// [`ConsoleIn::watch_fd`](crate::console::ConsoleIn::watch_fd) is
+    /// readable, holds its token.
+    IoReady(u64),
     /// An event that cannot currently be evaluated.
     Unsupported(Vec<u8>),
+    /// A malformed sequence and the bytes after it up to the next byte that
+    /// can start an event, see [`ParseOptions::resync`].
+    Garbled(Vec<u8>),
+}
+
+/// A parsed CSI sequence: `CSI [private] params [intermediates] final`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct CsiSequence {
+    /// Private marker (`?`, `>` or `=`) before the parameters, if any.
+    pub private: Option<u8>,
+    /// The semicolon separated parameters, an empty parameter is 0.
+    pub params: Vec<u16>,
+    /// Intermediate bytes (0x20-0x2F) between the parameters and final byte.
+    pub intermediates: Vec<u8>,
+    /// The final byte (0x40-0x7E).
+    pub final_byte: u8,
+}
+
+impl CsiSequence {
+    /// If this is a mode report (DECRPM) return it.
+    ///
+    /// This is the reply to a DECRQM request and looks like
+    /// `CSI ? Ps ; Pm $ y` for DEC private modes or `CSI Ps ; Pm $ y` for
+    /// ANSI modes.
+    pub fn mode_report(&self) -> Option<ModeReport> {
+        if self.final_byte != b'y' || self.intermediates != b"$" || self.params.len() != 2 {
+            return None;
+        }
+        let private = match self.private {
+            Some(b'?') => true,
+            None => false,
+            Some(_) => return None,
+        };
+        let state = match self.params[1] {
+            0 => ModeState::NotRecognized,
+            1 => ModeState::Set,
+            2 => ModeState::Reset,
+            3 => ModeState::PermanentlySet,
+            4 => ModeState::PermanentlyReset,
+            _ => return None,
+        };
+        Some(ModeReport {
+            mode: self.params[0],
+            private,
+            state,
+        })
+    }
+
+    /// If this is a device attributes reply (DA1 `CSI ? ... c` or DA2
+    /// `CSI > ... c`) return it.
+    pub fn device_attributes(&self) -> Option<DeviceAttributes> {
+        if self.final_byte != b'c' || !self.intermediates.is_empty() {
+            return None;
+        }
+        let secondary = match self.private {
+            Some(b'?') => false,
+            Some(b'>') => true,
+            _ => return None,
+        };
+        Some(DeviceAttributes {
+            secondary,
+            params: self.params.clone(),
+        })
+    }
+
+    /// If this is a window report (XTWINOPS reply) return it.
+    pub fn window_report(&self) -> Option<WindowReport> {
+        if self.final_byte != b't' || self.private.is_some() || !self.intermediates.is_empty() {
+            return None;
+        }
+        match self.params[..] {
+            [1] => Some(WindowReport::Iconified(false)),
+            [2] => Some(WindowReport::Iconified(true)),
+            [3, y, x] => Some(WindowReport::Position(x, y)),
+            [4, height, width] => Some(WindowReport::SizePixels(width, height)),
+            [8, rows, cols] => Some(WindowReport::SizeChars(cols, rows)),
+            _ => None,
+        }
+    }
+}
+
+/// A terminal mode report (DECRPM).
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ModeReport {
+    /// The mode number.
+    pub mode: u16,
+    /// True for a DEC private mode (`CSI ? ...`), false for an ANSI mode.
+    pub private: bool,
+    /// The state the terminal reported for the mode.
+    pub state: ModeState,
+}
+
+/// The state of a terminal mode from a mode report.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum ModeState {
+    /// The terminal does not know the mode.
+    NotRecognized,
+    /// The mode is set.
+    Set,
+    /// The mode is reset.
+    Reset,
+    /// The mode is set and can not be changed.
+    PermanentlySet,
+    /// The mode is reset and can not be changed.
+    PermanentlyReset,
+}
+
+impl ModeState {
+    /// True if the terminal recognized the mode.
+    pub fn is_recognized(self) -> bool {
+        self != ModeState::NotRecognized
+    }
+
+    /// True if the mode is set (permanently or not).
+    pub fn is_set(self) -> bool {
+        matches!(self, ModeState::Set | ModeState::PermanentlySet)
+    }
+}
+
+/// A device attributes reply.
+///
+/// The primary attributes (DA1, `CSI ? level ; attributes c`) give the
+/// terminal's conformance level and the features it has, the secondary
+/// attributes (DA2, `CSI > type ; version ; ... c`) identify the terminal
+/// and its firmware version.  Every terminal answers DA1, which is why it is
+/// sent after other queries to mark the end of their replies.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct DeviceAttributes {
+    /// True for the secondary attributes, false for the primary ones.
+    pub secondary: bool,
+    /// The parameters of the reply.
+    pub params: Vec<u16>,
+}
+
+impl DeviceAttributes {
+    /// The conformance level of a primary reply: 1 for a VT100 class
+    /// terminal, 2 to 5 for the VT200 to VT500 classes.
+    pub fn level(&self) -> Option<u16> {
+        match self.params.first()? {
+            _ if self.secondary => None,
+            level @ 61..=65 => Some(level - 60),
+            _ => Some(1),
+        }
+    }
+
+    /// The attribute list of a primary reply, the parameters after the
+    /// level, empty for a secondary reply.
+    pub fn attributes(&self) -> &[u16] {
+        match self.params.split_first() {
+            Some((_, attributes)) if !self.secondary => attributes,
+            _ => &[],
+        }
+    }
+
+    /// True if the primary attributes list `attribute`.
+    pub fn has(&self, attribute: u16) -> bool {
+        self.attributes().contains(&attribute)
+    }
+
+    /// 132 column mode (attribute 1).
+    pub fn columns_132(&self) -> bool {
+        self.has(1)
+    }
+
+    /// Sixel graphics (attribute 4).
+    pub fn sixel(&self) -> bool {
+        self.has(4)
+    }
+
+    /// Selective erase (attribute 6).
+    pub fn selective_erase(&self) -> bool {
+        self.has(6)
+    }
+
+    /// Horizontal scrolling (attribute 21).
+    pub fn horizontal_scrolling(&self) -> bool {
+        self.has(21)
+    }
+
+    /// ANSI color (attribute 22).
+    pub fn ansi_color(&self) -> bool {
+        self.has(22)
+    }
+
+    /// Rectangular editing (attribute 28).
+    pub fn rectangular_editing(&self) -> bool {
+        self.has(28)
+    }
+
+    /// The terminal type of a secondary reply (0 for a VT100, 1 for a
+    /// VT220, 41 for xterm, others are used by other emulators).
+    pub fn terminal_type(&self) -> Option<u16> {
+        self.params.first().copied().filter(|_| self.secondary)
+    }
+
+    /// The firmware (emulator) version of a secondary reply.
+    pub fn version(&self) -> Option<u16> {
+        self.params.get(1).copied().filter(|_| self.secondary)
+    }
+}
+
+/// A window report, the reply to an XTWINOPS query (see
+/// [`window`](crate::window)).
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum WindowReport {
+    /// The window is iconified (true) or not, `CSI 1 t` or `CSI 2 t`.
+    Iconified(bool),
+    /// The position of the window's top left corner in pixels (x, y),
+    /// `CSI 3 ; y ; x t`.
+    Position(u16, u16),
+    /// The size of the text area in pixels (width, height),
+    /// `CSI 4 ; height ; width t`.
+    SizePixels(u16, u16),
+    /// The size of the text area in characters (columns, rows),
+    /// `CSI 8 ; rows ; cols t`.
+    SizeChars(u16, u16),
 }
 
 /// A mouse related event.
+///
+/// Coordinates are counted from the console's
+/// [`origin`](crate::console::ConsoleIn::set_origin) when read from it,
+/// one-based by default.
 #[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum MouseEvent {
     /// A mouse button was pressed.
     ///
@@ -23,16 +303,49 @@ pub enum MouseEvent {
     Press(MouseButton, u16, u16),
     /// A mouse button was released.
     ///
-    /// The coordinates are one-based.
-    Release(u16, u16),
-    /// A mouse button is held over the given coordinates.
+    /// The coordinates are one-based.  The button is only known for terminals
+    /// using the SGR (1006) encoding, the X10 and urxvt encodings do not say
+    /// which button was released and report None.
+    Release(Option<MouseButton>, u16, u16),
+    /// A mouse button is held (dragged) over the given coordinates.
     ///
-    /// The coordinates are one-based.
-    Hold(u16, u16),
+    /// The coordinates are one-based.  The button will always be Left for
+    /// terminals that do not report which button is held.
+    Hold(MouseButton, u16, u16),
+    /// The mouse moved over the given coordinates with no button held.
+    ///
+    /// The coordinates are one-based.  Only reported with any-motion tracking
+    /// (1003), see [`MouseMode::Motion`](crate::input::MouseMode::Motion).
+    Moved(u16, u16),
+}
+
+impl MouseEvent {
+    /// The event with its coordinates converted by `f`.
+    pub(crate) fn map_position<F: Fn(u16, u16) -> (u16, u16)>(self, f: F) -> Self {
+        match self {
+            MouseEvent::Press(button, x, y) => {
+                let (x, y) = f(x, y);
+                MouseEvent::Press(button, x, y)
+            }
+            MouseEvent::Release(button, x, y) => {
+                let (x, y) = f(x, y);
+                MouseEvent::Release(button, x, y)
+            }
+            MouseEvent::Hold(button, x, y) => {
+                let (x, y) = f(x, y);
+                MouseEvent::Hold(button, x, y)
+            }
+            MouseEvent::Moved(x, y) => {
+                let (x, y) = f(x, y);
+                MouseEvent::Moved(x, y)
+            }
+        }
+    }
 }
 
 /// A mouse button.
 #[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum MouseButton {
     /// The left mouse button.
     Left,
@@ -48,6 +361,18 @@ pub enum MouseButton {
     ///
     /// This event is typically only used with Mouse::Press.
     WheelDown,
+    /// Mouse wheel is tilted (or a touchpad scrolled) left.
+    ///
+    /// This event is typically only used with Mouse::Press.
+    WheelLeft,
+    /// Mouse wheel is tilted (or a touchpad scrolled) right.
+    ///
+    /// This event is typically only used with Mouse::Press.
+    WheelRight,
+    /// The back (fourth, button 8 in xterm) mouse button.
+    Back,
+    /// The forward (fifth, button 9 in xterm) mouse button.
+    Forward,
 }
 
 #[derive(Debug, Copy, Clone, Hash, PartialEq, Eq)]
@@ -64,12 +389,23 @@ pub enum MouseButton {
 /// - Shift+Insert is not supported
 /// - Some terminals do not support modifier keys and certain
 /// non alpha-numeric keys
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Key {
     /// any key that could be pressed
     pub code: KeyCode,
     /// any key modifier ctrl + alt + shift (excluding capital letters w/ shift) that could be
     /// pressed.
     pub mods: Option<KeyMod>,
+    /// Whether the key was pressed, repeated or released.  Only terminals
+    /// using the kitty keyboard protocol with
+    /// [`KeyboardFlags::REPORT_EVENTS`](crate::input::KeyboardFlags::REPORT_EVENTS)
+    /// report repeats and releases, for others repeats can be detected with
+    /// [`ConsoleIn::set_repeat_threshold`](crate::console::ConsoleIn::set_repeat_threshold).
+    pub kind: KeyEventKind,
+    /// The key in the base (US) layout when it differs from `code`, only
+    /// reported by terminals using the kitty keyboard protocol with
+    /// [`KeyboardFlags::ALTERNATE_KEYS`](crate::input::KeyboardFlags::ALTERNATE_KEYS).
+    pub alternate: Option<KeyCode>,
 }
 
 impl Key {
@@ -80,6 +416,8 @@ impl Key {
         Self {
             code: key,
             mods: None,
+            kind: KeyEventKind::Press,
+            alternate: None,
         }
     }
 
@@ -90,13 +428,38 @@ impl Key {
         Self {
             code: key,
             mods: Some(mods),
+            kind: KeyEventKind::Press,
+            alternate: None,
         }
     }
+
+    /// The key to match layout independent shortcuts against, the base
+    /// layout key if the terminal reported one otherwise `code`.
+    ///
+    /// For example Ctrl+Z on a German keyboard (where Z is on the US Y key)
+    /// has a `code` of 'z' and a `layout_code` of 'y'.
+    pub fn layout_code(&self) -> KeyCode {
+        self.alternate.unwrap_or(self.code)
+    }
+}
+
+/// What happened to a key.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum KeyEventKind {
+    /// The key was pressed.
+    #[default]
+    Press,
+    /// The key is held and auto-repeated.
+    Repeat,
+    /// The key was released.
+    Release,
 }
 
 /// A key.
 #[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
 #[non_exhaustive]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum KeyCode {
     /// Backspace.
     Backspace,
@@ -124,8 +487,22 @@ pub enum KeyCode {
     Insert,
     /// Function keys.
     ///
-    /// Only function keys 1 through 12 are supported.
+    /// F1 to F12 are reported by all terminals, F13 to F20 by terminals that
+    /// send the VT220 codes for them (rxvt) and F13 to F35 with the kitty
+    /// keyboard protocol.  xterm sends F13 to F24 as Shift or Ctrl with F1
+    /// to F12.
     F(u8),
+    /// Enter on the keypad.
+    ///
+    /// Terminals only tell keypad keys apart in application keypad mode
+    /// (`ESC =`) or with the kitty keyboard protocol, otherwise they send
+    /// the same as the main keys.
+    KpEnter,
+    /// A keypad key that types a character: the digits and `+ - * / . , =`.
+    ///
+    /// Only reported when the keypad can be told apart, see
+    /// [`KpEnter`](KeyCode::KpEnter).
+    Kp(char),
     /// Normal character.
     Char(char),
     /// Null byte.
@@ -134,25 +511,429 @@ pub enum KeyCode {
     Esc,
 }
 
-/// Key combinations for keys besides Alt(char) and Ctrl(char) in
-#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
-pub enum KeyMod {
+/// The modifier keys held with a key, combine with `|`.
+///
+/// The bits are those of the kitty keyboard protocol.  Legacy encodings only
+/// report Shift, Alt and Ctrl, Super, Hyper and Meta come from terminals
+/// using the kitty protocol (or `CSI u`).  The names of the combinations,
+/// such as `KeyMod::AltCtrl`, were variants when this was an enum and are
+/// kept so matching on them still works.
+#[derive(Copy, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct KeyMod(u8);
+
+#[allow(non_upper_case_globals)]
+impl KeyMod {
+    /// Shift modifier key
+    /// Note that capital letters do not note the `shift` modifier.
+    pub const Shift: KeyMod = KeyMod(1);
     /// Alt modifier key
-    Alt,
+    pub const Alt: KeyMod = KeyMod(1 << 1);
     /// Ctrl modifier key
     /// Note that certain keys may not be modifiable with `ctrl`, due to limitations of terminals.
-    Ctrl,
-    /// Shift modifier key
-    /// Note that capital letters do not note the `shift` modifier.
-    Shift,
+    pub const Ctrl: KeyMod = KeyMod(1 << 2);
+    /// Super (Windows, Command) modifier key
+    pub const Super: KeyMod = KeyMod(1 << 3);
+    /// Hyper modifier key
+    pub const Hyper: KeyMod = KeyMod(1 << 4);
+    /// Meta modifier key, most terminals send Alt for it.
+    pub const Meta: KeyMod = KeyMod(1 << 5);
     /// Alt + Ctrl
-    AltCtrl,
+    pub const AltCtrl: KeyMod = KeyMod(Self::Alt.0 | Self::Ctrl.0);
     /// Alt + Shift
-    AltShift,
+    pub const AltShift: KeyMod = KeyMod(Self::Alt.0 | Self::Shift.0);
     /// Ctrl + Shift
-    CtrlShift,
+    pub const CtrlShift: KeyMod = KeyMod(Self::Ctrl.0 | Self::Shift.0);
     /// Alt + Ctrl + Shift
-    AltCtrlShift,
+    pub const AltCtrlShift: KeyMod = KeyMod(Self::Alt.0 | Self::Ctrl.0 | Self::Shift.0);
+
+    const NAMES: [&'static str; 6] = ["Shift", "Alt", "Ctrl", "Super", "Hyper", "Meta"];
+
+    /// The modifiers from their bits, None if no known modifier is set.
+    pub fn from_bits(bits: u8) -> Option<KeyMod> {
+        match bits & 0b11_1111 {
+            0 => None,
+            bits => Some(KeyMod(bits)),
+        }
+    }
+
+    /// The bits of the modifiers.
+    pub fn bits(self) -> u8 {
+        self.0
+    }
+
+    /// True if all the modifiers in `other` are held.
+    pub fn contains(self, other: KeyMod) -> bool {
+        self.0 & other.0 == other.0
+    }
+
+    /// These modifiers without those in `other`, None if none are left.
+    pub fn without(self, other: KeyMod) -> Option<KeyMod> {
+        KeyMod::from_bits(self.0 & !other.0)
+    }
+}
+
+impl ops::BitOr for KeyMod {
+    type Output = KeyMod;
+
+    fn bitor(self, rhs: KeyMod) -> KeyMod {
+        KeyMod(self.0 | rhs.0)
+    }
+}
+
+impl ops::BitOrAssign for KeyMod {
+    fn bitor_assign(&mut self, rhs: KeyMod) {
+        self.0 |= rhs.0;
+    }
+}
+
+/// Prints the held modifiers joined with `+`, such as `Alt+Ctrl`.
+impl fmt::Debug for KeyMod {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut first = true;
+        for (bit, name) in KeyMod::NAMES.iter().enumerate() {
+            if self.0 & 1 << bit != 0 {
+                if !first {
+                    f.write_str("+")?;
+                }
+                f.write_str(name)?;
+                first = false;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// The modifiers in the order they are written in key names.
+const KEY_MOD_ORDER: [(KeyMod, &str); 6] = [
+    (KeyMod::Ctrl, "Ctrl"),
+    (KeyMod::Alt, "Alt"),
+    (KeyMod::Shift, "Shift"),
+    (KeyMod::Super, "Super"),
+    (KeyMod::Hyper, "Hyper"),
+    (KeyMod::Meta, "Meta"),
+];
+
+/// Prints the held modifiers the way key names write them, such as
+/// `Ctrl+Alt`.
+impl fmt::Display for KeyMod {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut first = true;
+        for (key_mod, name) in KEY_MOD_ORDER.iter() {
+            if self.contains(*key_mod) {
+                if !first {
+                    f.write_str("+")?;
+                }
+                f.write_str(name)?;
+                first = false;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Prints the name of the key as [`Key`] parses it: `F5`, `PageUp`, `x`,
+/// `Space`, `Enter`.  `+` is written `Plus` and keypad keys start with `Kp`.
+impl fmt::Display for KeyCode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            KeyCode::Backspace => f.write_str("Backspace"),
+            KeyCode::Left => f.write_str("Left"),
+            KeyCode::Right => f.write_str("Right"),
+            KeyCode::Up => f.write_str("Up"),
+            KeyCode::Down => f.write_str("Down"),
+            KeyCode::Home => f.write_str("Home"),
+            KeyCode::End => f.write_str("End"),
+            KeyCode::PageUp => f.write_str("PageUp"),
+            KeyCode::PageDown => f.write_str("PageDown"),
+            KeyCode::BackTab => f.write_str("BackTab"),
+            KeyCode::Delete => f.write_str("Delete"),
+            KeyCode::Insert => f.write_str("Insert"),
+            KeyCode::F(n) => write!(f, "F{}", n),
+            KeyCode::KpEnter => f.write_str("KpEnter"),
+            KeyCode::Kp('+') => f.write_str("KpPlus"),
+            KeyCode::Kp(ch) => write!(f, "Kp{}", ch),
+            KeyCode::Char(' ') => f.write_str("Space"),
+            KeyCode::Char('\n') => f.write_str("Enter"),
+            KeyCode::Char('\t') => f.write_str("Tab"),
+            KeyCode::Char('+') => f.write_str("Plus"),
+            KeyCode::Char(ch) => write!(f, "{}", ch),
+            KeyCode::Null => f.write_str("Null"),
+            KeyCode::Esc => f.write_str("Esc"),
+        }
+    }
+}
+
+/// Prints the modifiers and the key joined with `+`, such as
+/// `Ctrl+Shift+F5` or `Alt+x`.  The kind of key event is not included.
+impl fmt::Display for Key {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if let Some(mods) = self.mods {
+            write!(f, "{}+", mods)?;
+        }
+        write!(f, "{}", self.code)
+    }
+}
+
+/// The error returned when a key name does not parse, holds the name.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct ParseKeyError(pub String);
+
+impl fmt::Display for ParseKeyError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Invalid key name: {:?}", self.0)
+    }
+}
+
+impl std::error::Error for ParseKeyError {}
+
+/// Parse a key name like `Ctrl+Shift+F5`, `Alt+x` or `ctrl+plus`, the format
+/// [`Display`](fmt::Display) writes.
+///
+/// Modifier and key names are not case sensitive (a single character key
+/// is), the modifiers may come in any order and some common alternatives
+/// are accepted: `Control`, `Escape`, `Return`, `Del`, `Ins`, `PgUp` and
+/// `PgDn`.  A trailing `+` is the plus key, so `Ctrl++` works too.  A
+/// letter with Ctrl is lower case, as terminals report it, so `Ctrl+X` is
+/// the same as `Ctrl+x`.
+///
+/// ```rust
+/// use sl_console::event::{Key, KeyCode, KeyMod};
+///
+/// let key: Key = "Ctrl+Shift+F5".parse().unwrap();
+/// assert_eq!(key, Key::new_mod(KeyCode::F(5), KeyMod::CtrlShift));
+/// assert_eq!(key.to_string(), "Ctrl+Shift+F5");
+/// assert_eq!("alt+x".parse::<Key>().unwrap().to_string(), "Alt+x");
+/// ```
+impl str::FromStr for Key {
+    type Err = ParseKeyError;
+
+    fn from_str(s: &str) -> Result<Key, ParseKeyError> {
+        let err = || ParseKeyError(s.to_string());
+        let (mods_part, code_part) = match s.strip_suffix('+') {
+            Some(rest) if rest.is_empty() || rest.ends_with('+') => {
+                (rest.strip_suffix('+').unwrap_or(rest), "+")
+            }
+            _ => s.rsplit_once('+').unwrap_or(("", s)),
+        };
+        let mut mods: Option<KeyMod> = None;
+        if !mods_part.is_empty() {
+            for name in mods_part.split('+') {
+                let key_mod = match name.to_ascii_lowercase().as_str() {
+                    "ctrl" | "control" => KeyMod::Ctrl,
+                    "alt" => KeyMod::Alt,
+                    "shift" => KeyMod::Shift,
+                    "super" => KeyMod::Super,
+                    "hyper" => KeyMod::Hyper,
+                    "meta" => KeyMod::Meta,
+                    _ => return Err(err()),
+                };
+                mods = Some(mods.map_or(key_mod, |mods| mods | key_mod));
+            }
+        }
+        let code = match parse_key_name(code_part).ok_or_else(err)? {
+            // Terminals report Ctrl with a letter in lower case.
+            KeyCode::Char(ch) if mods.is_some_and(|mods| mods.contains(KeyMod::Ctrl)) => {
+                KeyCode::Char(ch.to_ascii_lowercase())
+            }
+            code => code,
+        };
+        Ok(Key {
+            code,
+            mods,
+            kind: KeyEventKind::Press,
+            alternate: None,
+        })
+    }
+}
+
+/// The key code for the key part of a key name.
+fn parse_key_name(name: &str) -> Option<KeyCode> {
+    let mut chars = name.chars();
+    if let (Some(ch), None) = (chars.next(), chars.next()) {
+        return Some(KeyCode::Char(ch));
+    }
+    let lower = name.to_ascii_lowercase();
+    Some(match lower.as_str() {
+        "backspace" => KeyCode::Backspace,
+        "left" => KeyCode::Left,
+        "right" => KeyCode::Right,
+        "up" => KeyCode::Up,
+        "down" => KeyCode::Down,
+        "home" => KeyCode::Home,
+        "end" => KeyCode::End,
+        "pageup" | "pgup" => KeyCode::PageUp,
+        "pagedown" | "pgdn" => KeyCode::PageDown,
+        "backtab" => KeyCode::BackTab,
+        "delete" | "del" => KeyCode::Delete,
+        "insert" | "ins" => KeyCode::Insert,
+        "kpenter" => KeyCode::KpEnter,
+        "kpplus" => KeyCode::Kp('+'),
+        "space" => KeyCode::Char(' '),
+        "enter" | "return" => KeyCode::Char('\n'),
+        "tab" => KeyCode::Char('\t'),
+        "plus" => KeyCode::Char('+'),
+        "null" => KeyCode::Null,
+        "esc" | "escape" => KeyCode::Esc,
+        _ => {
+            if let Some(n) = lower.strip_prefix('f') {
+                return n
+                    .parse()
+                    .ok()
+                    .filter(|n| (1..=35).contains(n))
+                    .map(KeyCode::F);
+            }
+            let mut chars = lower.strip_prefix("kp")?.chars();
+            match (chars.next(), chars.next()) {
+                (Some(ch), None) if ch.is_ascii() && b"0123456789./*-+=,".contains(&(ch as u8)) => {
+                    KeyCode::Kp(ch)
+                }
+                _ => return None,
+            }
+        }
+    })
+}
+
+/// What to do with input that is not valid UTF-8.
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq, Hash)]
+pub enum Utf8Policy {
+    /// Log an error and report all the bytes read as an Unsupported event.
+    #[default]
+    Error,
+    /// Report the invalid bytes as a U+FFFD replacement character key.
+    Replace,
+    /// Report only the invalid bytes as an Unsupported event.
+    Raw,
+}
+
+/// How to treat the 8-bit C1 control bytes (0x80-0x9F).
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq, Hash)]
+pub enum C1Mode {
+    /// Only 0x9B is a control (CSI), the other C1 bytes are data.
+    #[default]
+    CsiOnly,
+    /// C1 bytes are controls: CSI (0x9B), SS3 (0x8F), DCS (0x90) and OSC
+    /// (0x9D) introduce sequences, the others are reported as Unsupported.
+    ///
+    /// Only use this for terminals that send 8-bit controls, it is not
+    /// compatible with UTF-8 input.
+    Controls,
+    /// All C1 bytes are data (decoded as UTF-8).
+    Data,
+}
+
+/// The encoding of X10 style (`CSI M Cb Cx Cy`) mouse reports.
+///
+/// Set this to match the mouse modes enabled, see
+/// [`MouseCapture::mouse_encoding`](crate::input::MouseCapture::mouse_encoding).
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq, Hash)]
+pub enum MouseEncoding {
+    /// Guess between X10 and UTF-8 from the bytes received.
+    #[default]
+    Auto,
+    /// Every value is a single byte.
+    X10,
+    /// Values over 95 are two byte UTF-8 characters (mode 1005).
+    Utf8,
+}
+
+/// Options that control how input bytes are parsed into events.
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq, Hash)]
+pub struct ParseOptions {
+    /// How to handle input that is not valid UTF-8.
+    pub utf8: Utf8Policy,
+    /// Report a NUL byte as Ctrl+Space instead of KeyCode::Null.
+    ///
+    /// Terminals send NUL for Ctrl+Space (and Ctrl+@) so this is usually what
+    /// was typed.  Leave it off if genuine NUL bytes are expected (pastes or
+    /// piped data).  Alt+Ctrl+Space is reported when the NUL follows an ESC.
+    pub nul_as_ctrl_space: bool,
+    /// How to treat 8-bit C1 control bytes.
+    pub c1: C1Mode,
+    /// How X10 style (`CSI M`) mouse reports are encoded.
+    pub mouse_encoding: MouseEncoding,
+    /// SGR (`CSI <`) mouse reports are in pixels (mode 1016), report them
+    /// as [`Event::MousePixels`], see
+    /// [`MouseCapture::sgr_pixels`](crate::input::MouseCapture::sgr_pixels).
+    pub sgr_pixels: bool,
+    /// Recover from garbage input (binary data written to the terminal).
+    ///
+    /// After input that can not be parsed the bytes that follow are skipped
+    /// up to the next ESC or printable ASCII character and everything is
+    /// reported as one [`Event::Garbled`], instead of an Unsupported event
+    /// followed by a run of bogus control keys.
+    pub resync: bool,
+}
+
+/// Why input could not be parsed into an event.
+///
+/// Parse failures are reported as [`Event::Unsupported`] and sent to the
+/// [diagnostics](crate::diagnostics) handler with this reason.  It converts
+/// to an `io::Error` (of kind UnexpectedEof for a truncated sequence and
+/// InvalidData otherwise) and can be recovered from one with
+/// [`ParseEventError::from_io`].
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum ParseEventError {
+    /// The input ended in the middle of an escape sequence.
+    TruncatedSequence,
+    /// Input that is not valid UTF-8, with [`Utf8Policy::Error`].
+    InvalidUtf8,
+    /// A CSI sequence that is not a known key or mouse report.
+    UnknownCsi,
+    /// An SS3 (`ESC O`) sequence that is not a known key.
+    UnknownSs3,
+    /// A CSI sequence with a malformed parameter or intermediate byte.
+    InvalidCsi,
+    /// A malformed key sequence.
+    InvalidKey,
+    /// A malformed mouse report.
+    InvalidMouse,
+    /// A malformed OSC or DCS string.
+    InvalidControlString,
+    /// An 8-bit C1 control that does not start a sequence, with
+    /// [`C1Mode::Controls`].
+    UnsupportedC1,
+}
+
+impl ParseEventError {
+    /// The parse error `err` was made from, None if it is some other error.
+    pub fn from_io(err: &io::Error) -> Option<ParseEventError> {
+        err.get_ref()?.downcast_ref::<ParseEventError>().copied()
+    }
+}
+
+impl fmt::Display for ParseEventError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            ParseEventError::TruncatedSequence => "Escape sequence was truncated",
+            ParseEventError::InvalidUtf8 => "Input character is not valid UTF-8",
+            ParseEventError::UnknownCsi => "Unknown csi sequence",
+            ParseEventError::UnknownSs3 => "Unknown escape code after ESC O",
+            ParseEventError::InvalidCsi => "Invalid parameter or intermediate byte in csi sequence",
+            ParseEventError::InvalidKey => "Failed to parse key escape code",
+            ParseEventError::InvalidMouse => "Failed to parse mouse report",
+            ParseEventError::InvalidControlString => "Invalid OSC or DCS string",
+            ParseEventError::UnsupportedC1 => "Unsupported C1 control",
+        })
+    }
+}
+
+impl std::error::Error for ParseEventError {}
+
+impl From<ParseEventError> for io::Error {
+    fn from(err: ParseEventError) -> io::Error {
+        let kind = match err {
+            ParseEventError::TruncatedSequence => ErrorKind::UnexpectedEof,
+            _ => ErrorKind::InvalidData,
+        };
+        io::Error::new(kind, err)
+    }
+}
+
+/// True if `byte` can start an event after garbage input: an ESC,
+/// printable ASCII or a tab, carriage return or line feed.
+pub(crate) fn is_resync_point(byte: u8) -> bool {
+    matches!(byte, b'\x1B' | b'\t' | b'\r' | b'\n' | 0x20..=0x7E)
 }
 
 /// Parse an Event from `item` and possibly subsequent bytes through `iter`.
@@ -160,84 +941,288 @@ pub fn parse_event<I>(item: u8, iter: &mut I) -> io::Result<Event>
 where
     I: Iterator<Item = io::Result<u8>>,
 {
-    fn inner_parse_event<I>(item: u8, iter: &mut I) -> io::Result<Event>
-    where
-        I: Iterator<Item = io::Result<u8>>,
-    {
-        match item {
-            b'\x9B' => {
-                // proposed CSI extension mentioned at bottom of page:
-                // http://www.leonerd.org.uk/hacks/fixterms/
-                parse_csi(iter)
+    parse_event_opts(item, iter, &ParseOptions::default(), &mut None)
+}
+
+/// Parse every complete event in `bytes`.
+///
+/// Returns the events and the number of bytes they used.  Parsing stops at
+/// an event that is cut off by the end of `bytes` (including a trailing ESC,
+/// which could start a sequence), the rest should be passed again once more
+/// input is available (or use an [`EventParser`], which keeps it).  Never
+/// blocks, does no IO and does not panic on any input so it can be used to
+/// process a large paste or recorded input in one go, or as a fuzzing
+/// target.
+///
+/// ```rust
+/// use sl_console::event::{parse_events, Event, Key, KeyCode};
+///
+/// let (events, used) = parse_events(b"a\x1B[A\x1B[1;");
+/// assert_eq!(
+///     events,
+///     vec![
+///         Event::Key(Key::new(KeyCode::Char('a'))),
+///         Event::Key(Key::new(KeyCode::Up)),
+///     ]
+/// );
+/// assert_eq!(used, 4);
+/// ```
+pub fn parse_events(bytes: &[u8]) -> (Vec<Event>, usize) {
+    parse_events_opts(bytes, &ParseOptions::default())
+}
+
+/// [`parse_events`] with `options`.
+pub(crate) fn parse_events_opts(bytes: &[u8], options: &ParseOptions) -> (Vec<Event>, usize) {
+    let mut events = Vec::new();
+    let mut used = 0;
+    // The start of the last event if the parser looked at the byte after it.
+    let mut peeked = None;
+    while used < bytes.len() {
+        let mut rest = bytes[used + 1..].iter();
+        let mut truncated = false;
+        let mut unread = None;
+        let event = {
+            let mut iter = std::iter::from_fn(|| match rest.next() {
+                Some(byte) => Some(Ok(*byte)),
+                None => {
+                    truncated = true;
+                    None
+                }
+            });
+            try_parse_event(bytes[used], &mut iter, options, &mut unread)
+        };
+        let event = match event {
+            Ok(event) if !truncated => event,
+            Err(error) if !truncated => {
+                parse_failure(&error, bytes[used..bytes.len() - rest.len()].to_vec())
             }
-            b'\x1B' => {
-                // This is an escape character, leading a control sequence.
-                Ok(match iter.next() {
-                    Some(Ok(b'O')) => {
+            // The input ran out, not a failure, the bytes are parsed again
+            // once the rest is here.
+            _ => {
+                // Hold the event that peeked at this byte too, it may parse
+                // differently once the rest is here.
+                if let Some(start) = peeked {
+                    events.pop();
+                    used = start;
+                }
+                break;
+            }
+        };
+        let start = used;
+        used = bytes.len() - rest.len() - unread.map_or(0, |_| 1);
+        peeked = unread.map(|_| start);
+        match event {
+            Event::Unsupported(_) if options.resync => {
+                used += bytes[used..]
+                    .iter()
+                    .take_while(|b| !is_resync_point(**b))
+                    .count();
+                events.push(Event::Garbled(bytes[start..used].to_vec()));
+            }
+            event => events.push(event),
+        }
+    }
+    (events, used)
+}
+
+/// A push based event parser.
+///
+/// Feed it input as it arrives with [`advance`](EventParser::advance), it
+/// keeps the start of an event that is cut off until the rest arrives.  It
+/// never reads or blocks so it works with any transport or async runtime.
+/// Since a lone ESC could start a sequence it is held until more input
+/// arrives, call [`flush`](EventParser::flush) when none came within the
+/// escape timeout (or the input ended) to report it as the Esc key.
+///
+/// ```rust
+/// use sl_console::event::{Event, EventParser, Key, KeyCode};
+///
+/// let mut parser = EventParser::new();
+/// assert_eq!(parser.advance(b"\x1B["), vec![]);
+/// assert_eq!(parser.advance(b"Ax"), vec![
+///     Event::Key(Key::new(KeyCode::Up)),
+///     Event::Key(Key::new(KeyCode::Char('x'))),
+/// ]);
+/// assert_eq!(parser.advance(b"\x1B"), vec![]);
+/// assert_eq!(parser.flush(), vec![Event::Key(Key::new(KeyCode::Esc))]);
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct EventParser {
+    options: ParseOptions,
+    pending: Vec<u8>,
+}
+
+impl EventParser {
+    /// A parser using the default options.
+    pub fn new() -> Self {
+        EventParser::default()
+    }
+
+    /// A parser using `options`.
+    pub fn with_options(options: ParseOptions) -> Self {
+        EventParser {
+            options,
+            pending: Vec::new(),
+        }
+    }
+
+    /// Set the options used to parse input into events.
+    pub fn set_options(&mut self, options: ParseOptions) {
+        self.options = options;
+    }
+
+    /// Return the options used to parse input into events.
+    pub fn options(&self) -> ParseOptions {
+        self.options
+    }
+
+    /// Parse `bytes` after any input held from before, returns the complete
+    /// events.
+    pub fn advance(&mut self, bytes: &[u8]) -> Vec<Event> {
+        self.pending.extend_from_slice(bytes);
+        let (events, used) = parse_events_opts(&self.pending, &self.options);
+        self.pending.drain(..used);
+        events
+    }
+
+    /// The input held for an event that is not complete yet.
+    pub fn pending(&self) -> &[u8] {
+        &self.pending
+    }
+
+    /// Parse the held input as it is, no more of it is coming: a lone ESC
+    /// is the Esc key and a cut off sequence is Unsupported.
+    pub fn flush(&mut self) -> Vec<Event> {
+        let pending = std::mem::take(&mut self.pending);
+        let mut events = Vec::new();
+        let mut used = 0;
+        while used < pending.len() {
+            if pending[used..] == [b'\x1B'] {
+                events.push(Event::Key(Key::new(KeyCode::Esc)));
+                break;
+            }
+            let mut rest = pending[used + 1..].iter();
+            let mut unread = None;
+            let event = {
+                let mut iter = rest.by_ref().map(|byte| Ok(*byte));
+                parse_event_opts(pending[used], &mut iter, &self.options, &mut unread)
+            };
+            let start = used;
+            used = pending.len() - rest.len() - unread.map_or(0, |_| 1);
+            events
+                .push(event.unwrap_or_else(|_| Event::Unsupported(pending[start..used].to_vec())));
+        }
+        events
+    }
+}
+
+/// Parse an Event from `item` and subsequent bytes, returning the error for
+/// input that does not parse (or ran out) without reporting it.
+fn try_parse_event<I>(
+    item: u8,
+    iter: &mut I,
+    options: &ParseOptions,
+    unread: &mut Option<u8>,
+) -> io::Result<Event>
+where
+    I: Iterator<Item = io::Result<u8>>,
+{
+    match item {
+        b'\x9B' if options.c1 != C1Mode::Data => {
+            // proposed CSI extension mentioned at bottom of page:
+            // http://www.leonerd.org.uk/hacks/fixterms/
+            parse_csi(iter, options)
+        }
+        b'\x80'..=b'\x9F' if options.c1 == C1Mode::Controls => match item {
+            b'\x8F' => parse_ss3(iter),
+            b'\x90' => Ok(Event::Dcs(parse_string_sequence(iter, false)?)),
+            b'\x9D' => parse_osc(iter),
+            _ => Err(ParseEventError::UnsupportedC1.into()),
+        },
+        b'\x1B' => {
+            // This is an escape character, leading a control sequence.
+            Ok(match iter.next() {
+                Some(Ok(b'O')) => parse_ss3(iter)?,
+                Some(Ok(b'[')) => {
+                    // This is a CSI sequence.
+                    parse_csi(iter, options)?
+                }
+                Some(Ok(c)) => {
+                    if c == b']' || c == b'P' {
+                        // OSC or DCS, unless the next byte can not start
+                        // one and this is Alt+] or Alt+Shift+P.
                         match iter.next() {
-                            // F1-F4
-                            Some(Ok(val @ b'P'..=b'S')) => {
-                                Event::Key(Key::new(KeyCode::F(1 + val - b'P')))
-                            }
-                            Some(Ok(b'5')) => match iter.next() {
-                                Some(Ok(val @ b'P'..=b'S')) => Event::Key(Key::new_mod(
-                                    KeyCode::F(1 + val - b'P'),
-                                    KeyMod::Ctrl,
-                                )),
-                                _ => {
-                                    return Err(Error::new(
-                                        ErrorKind::Other,
-                                        "Unknown escape code after ESC O 5",
-                                    ))
-                                }
-                            },
-                            _ => {
-                                return Err(Error::new(
-                                    ErrorKind::Other,
-                                    "Unknown escape code after ESC O",
-                                ))
+                            Some(Ok(next)) if starts_control_string(c, next) => {
+                                let mut iter = std::iter::once(Ok(next)).chain(iter);
+                                return match c {
+                                    b']' => parse_osc(&mut iter),
+                                    _ => Ok(Event::Dcs(parse_string_sequence(&mut iter, false)?)),
+                                };
                             }
+                            Some(Ok(next)) => *unread = Some(next),
+                            _ => {}
                         }
                     }
-                    Some(Ok(b'[')) => {
-                        // This is a CSI sequence.
-                        parse_csi(iter)?
-                    }
-                    Some(Ok(c)) => {
-                        let ch = parse_utf8_char(c, iter)?;
-                        match c {
-                            b'\x01'..=b'\x1A' => Event::Key(Key::new_mod(
-                                KeyCode::Char((ch as u8 - 0x1 + b'a') as char),
-                                KeyMod::AltCtrl,
-                            )),
-                            _ => {
-                                Event::Key(Key::new_mod(parse_libtickit_key_codes(c), KeyMod::Alt))
-                            }
+                    let ch = match parse_char(c, iter, options, unread)? {
+                        Ok(ch) => ch,
+                        Err(bytes) => return Ok(Event::Unsupported(bytes)),
+                    };
+                    match c {
+                        b'\0' if options.nul_as_ctrl_space => {
+                            Event::Key(Key::new_mod(KeyCode::Char(' '), KeyMod::AltCtrl))
                         }
+                        b'\x01'..=b'\x1A' => Event::Key(Key::new_mod(
+                            KeyCode::Char((ch as u8 - 0x1 + b'a') as char),
+                            KeyMod::AltCtrl,
+                        )),
+                        // Meta prefix with a shifted char, keep the case.
+                        _ if ch.is_uppercase() => {
+                            Event::Key(Key::new_mod(KeyCode::Char(ch), KeyMod::AltShift))
+                        }
+                        _ if c.is_ascii() => {
+                            Event::Key(Key::new_mod(parse_libtickit_key_codes(c), KeyMod::Alt))
+                        }
+                        _ => Event::Key(Key::new_mod(KeyCode::Char(ch), KeyMod::Alt)),
                     }
-                    Some(Err(_)) | None => {
-                        return Err(Error::new(ErrorKind::Other, "Could not parse an event"))
-                    }
-                })
-            }
-            b'\n' | b'\r' => Ok(Event::Key(Key::new(KeyCode::Char('\n')))),
-            b'\t' => Ok(Event::Key(Key::new(KeyCode::Char('\t')))),
-            b'\x7F' => Ok(Event::Key(Key::new(KeyCode::Backspace))),
-            c @ b'\x01'..=b'\x1A' => Ok(Event::Key(Key::new_mod(
-                KeyCode::Char((c as u8 - 0x1 + b'a') as char),
-                KeyMod::Ctrl,
-            ))),
-            c @ b'\x1C'..=b'\x1F' => Ok(Event::Key(Key::new_mod(
-                KeyCode::Char((c as u8 - 0x1C + b'4') as char),
-                KeyMod::Ctrl,
-            ))),
-            b'\0' => Ok(Event::Key(Key::new(KeyCode::Null))),
-            c => Ok({
-                let ch = parse_utf8_char(c, iter)?;
-                Event::Key(Key::new(KeyCode::Char(ch)))
-            }),
+                }
+                Some(Err(_)) | None => return Err(ParseEventError::TruncatedSequence.into()),
+            })
+        }
+        b'\n' | b'\r' => Ok(Event::Key(Key::new(KeyCode::Char('\n')))),
+        b'\t' => Ok(Event::Key(Key::new(KeyCode::Char('\t')))),
+        b'\x7F' => Ok(Event::Key(Key::new(KeyCode::Backspace))),
+        c @ b'\x01'..=b'\x1A' => Ok(Event::Key(Key::new_mod(
+            KeyCode::Char((c as u8 - 0x1 + b'a') as char),
+            KeyMod::Ctrl,
+        ))),
+        c @ b'\x1C'..=b'\x1F' => Ok(Event::Key(Key::new_mod(
+            KeyCode::Char((c as u8 - 0x1C + b'4') as char),
+            KeyMod::Ctrl,
+        ))),
+        b'\0' if options.nul_as_ctrl_space => {
+            Ok(Event::Key(Key::new_mod(KeyCode::Char(' '), KeyMod::Ctrl)))
         }
+        b'\0' => Ok(Event::Key(Key::new(KeyCode::Null))),
+        c => Ok(match parse_char(c, iter, options, unread)? {
+            Ok(ch) => Event::Key(Key::new(KeyCode::Char(ch))),
+            Err(bytes) => Event::Unsupported(bytes),
+        }),
     }
+}
+
+/// Parse an Event from `item` and subsequent bytes using `options`.
+///
+/// If the parser had to read a byte that belongs to the next event it is
+/// placed in `unread`.
+pub(crate) fn parse_event_opts<I>(
+    item: u8,
+    iter: &mut I,
+    options: &ParseOptions,
+    unread: &mut Option<u8>,
+) -> io::Result<Event>
+where
+    I: Iterator<Item = io::Result<u8>>,
+{
     let mut control_seq = vec![item];
     let result = {
         let mut iter = iter.inspect(|k| {
@@ -245,16 +1230,19 @@ where
                 control_seq.push(*k);
             }
         });
-        inner_parse_event(item, &mut iter)
+        try_parse_event(item, &mut iter, options, unread)
     };
+    Ok(result.unwrap_or_else(|error| parse_failure(&error, control_seq)))
+}
 
-    match result {
-        Ok(event) => Ok(event),
-        Err(error) => {
-            log::error!("Failed to parse event: {}", error);
-            Ok(Event::Unsupported(control_seq))
-        }
-    }
+/// Report the input that failed to parse, returned as Unsupported.
+fn parse_failure(error: &io::Error, bytes: Vec<u8>) -> Event {
+    diagnostics::report(Diagnostic::ParseFailure {
+        reason: ParseEventError::from_io(error),
+        error: error.to_string(),
+        bytes: bytes.clone(),
+    });
+    Event::Unsupported(bytes)
 }
 
 fn next_char<I, T>(iter: &mut I) -> Option<T>
@@ -268,6 +1256,202 @@ where
     None
 }
 
+/// Parses an SS3 sequence, just after reading ^[O
+fn parse_ss3<I>(iter: &mut I) -> io::Result<Event>
+where
+    I: Iterator<Item = Result<u8, Error>>,
+{
+    Ok(match iter.next() {
+        // F1-F4
+        Some(Ok(val @ b'P'..=b'S')) => Event::Key(Key::new(KeyCode::F(1 + val - b'P'))),
+        // The keypad in application keypad mode.
+        Some(Ok(val)) if SS3_KEYPAD.contains(&val) => {
+            let idx = SS3_KEYPAD.iter().position(|key| *key == val).unwrap_or(0);
+            Event::Key(Key::new(parse_keypad_key_code(idx)))
+        }
+        Some(Ok(b'5')) => match iter.next() {
+            Some(Ok(val @ b'P'..=b'S')) => {
+                Event::Key(Key::new_mod(KeyCode::F(1 + val - b'P'), KeyMod::Ctrl))
+            }
+            _ => return Err(ParseEventError::UnknownSs3.into()),
+        },
+        _ => return Err(ParseEventError::UnknownSs3.into()),
+    })
+}
+
+/// True if `next` can follow the 7-bit OSC (`ESC ]`) or DCS (`ESC P`)
+/// introducer `c` in a reply: OSC codes are numbers and DCS replies start
+/// with parameters (`1$r`, `>|`) or `!|`.
+fn starts_control_string(c: u8, next: u8) -> bool {
+    match c {
+        b']' => next.is_ascii_digit(),
+        _ => matches!(next, b'0'..=b'?' | b'!'),
+    }
+}
+
+/// Read the body of a control string (OSC, DCS) up to its terminator.
+///
+/// The terminator is ST (ESC \\ or 0x9C) or BEL if `allow_bel` is set (used
+/// by OSC), it is not included in the returned bytes.
+fn parse_string_sequence<I>(iter: &mut I, allow_bel: bool) -> io::Result<Vec<u8>>
+where
+    I: Iterator<Item = Result<u8, Error>>,
+{
+    let mut buf = Vec::new();
+    loop {
+        match next_char(iter).ok_or_else(truncated_error)? {
+            b'\x07' if allow_bel => return Ok(buf),
+            b'\x9C' => return Ok(buf),
+            b'\x1B' => {
+                return match next_char(iter).ok_or_else(truncated_error)? {
+                    b'\\' => Ok(buf),
+                    _ => Err(ParseEventError::InvalidControlString.into()),
+                }
+            }
+            c => buf.push(c),
+        }
+    }
+}
+
+/// Parses an OSC sequence, just after reading the introducer.
+fn parse_osc<I>(iter: &mut I) -> io::Result<Event>
+where
+    I: Iterator<Item = Result<u8, Error>>,
+{
+    let mut body = parse_string_sequence(iter, true)?;
+    let split = body.iter().position(|b| *b == b';').unwrap_or(body.len());
+    let code = str::from_utf8(&body[..split])
+        .ok()
+        .and_then(|code| code.parse::<u16>().ok())
+        .ok_or_else(|| Error::from(ParseEventError::InvalidControlString))?;
+    let payload = if split < body.len() {
+        body.split_off(split + 1)
+    } else {
+        Vec::new()
+    };
+    Ok(Event::Osc { code, payload })
+}
+
+/// Build a CsiSequence from the bytes between the CSI (and private marker)
+/// and the final byte.
+fn parse_csi_sequence(private: Option<u8>, buf: &[u8], final_byte: u8) -> io::Result<CsiSequence> {
+    let split = buf
+        .iter()
+        .position(|b| (0x20..=0x2F).contains(b))
+        .unwrap_or(buf.len());
+    let (params, intermediates) = buf.split_at(split);
+    if !intermediates.iter().all(|b| (0x20..=0x2F).contains(b)) {
+        return Err(ParseEventError::InvalidCsi.into());
+    }
+    let params = if params.is_empty() {
+        Vec::new()
+    } else {
+        let mut nums = Vec::new();
+        for param in params.split(|b| *b == b';') {
+            if param.is_empty() {
+                nums.push(0);
+            } else {
+                let num = str::from_utf8(param)
+                    .ok()
+                    .and_then(|p| p.parse::<u16>().ok())
+                    .ok_or_else(|| Error::from(ParseEventError::InvalidCsi))?;
+                nums.push(num);
+            }
+        }
+        nums
+    };
+    Ok(CsiSequence {
+        private,
+        params,
+        intermediates: intermediates.to_vec(),
+        final_byte,
+    })
+}
+
+/// The modifier keys held in the button byte of a mouse report.
+///
+/// Shift, meta and ctrl are bits 4, 8 and 16, in the same order as the bits
+/// of the xterm key modifier parameter.
+fn mouse_mods(cb: u16) -> Option<KeyMod> {
+    parse_key_mods(1 + (cb >> 2 & 0b111))
+}
+
+/// Decode the button byte of an SGR (1006) mouse report.
+///
+/// Cb is a bit field: the low two bits are the button, 4/8/16 are the
+/// shift/meta/ctrl modifiers, 32 flags motion (a drag, or a move with no
+/// button when the button bits are 3), 64 flags the wheel and 128 the extra
+/// buttons.
+fn parse_sgr_mouse(cb: u16, cx: u16, cy: u16, release: bool) -> io::Result<MouseEvent> {
+    let motion = cb & 32 != 0;
+    let button = match (cb & 0b1100_0000, cb & 0b11) {
+        (0, 0) => MouseButton::Left,
+        (0, 1) => MouseButton::Middle,
+        (0, 2) => MouseButton::Right,
+        (0, 3) if motion => return Ok(MouseEvent::Moved(cx, cy)),
+        (0, 3) => return Ok(MouseEvent::Release(None, cx, cy)),
+        (64, 0) => MouseButton::WheelUp,
+        (64, 1) => MouseButton::WheelDown,
+        (64, 2) => MouseButton::WheelLeft,
+        (64, 3) => MouseButton::WheelRight,
+        (128, 0) => MouseButton::Back,
+        (128, 1) => MouseButton::Forward,
+        _ => return Err(ParseEventError::InvalidMouse.into()),
+    };
+    Ok(if motion {
+        MouseEvent::Hold(button, cx, cy)
+    } else if release {
+        MouseEvent::Release(Some(button), cx, cy)
+    } else {
+        MouseEvent::Press(button, cx, cy)
+    })
+}
+
+/// Read one value of an X10 style mouse report.
+///
+/// Values are normally a single byte but terminals in UTF-8 extended mode
+/// (1005) send values over 127 as a two byte UTF-8 character.  Since a plain
+/// X10 byte can look like a UTF-8 lead byte we look at the following byte when
+/// `peek` is true and keep it in `pending` if it turns out to not be a
+/// continuation byte.  The last value of a report can not peek (it would eat
+/// the next event) so a lead byte there is always treated as UTF-8.  If the
+/// encoding is known no guessing is needed.
+fn next_mouse_value<I>(
+    iter: &mut I,
+    pending: &mut Option<u8>,
+    encoding: MouseEncoding,
+    peek: bool,
+) -> Option<u16>
+where
+    I: Iterator<Item = Result<u8, Error>>,
+{
+    let lead = match pending.take() {
+        Some(b) => b,
+        None => next_char(iter)?,
+    };
+    if encoding == MouseEncoding::X10 || !(0xC2..=0xDF).contains(&lead) {
+        return Some(lead as u16);
+    }
+    if !peek || encoding == MouseEncoding::Utf8 {
+        return match next_char(iter)? {
+            cont @ 0x80..=0xBF => Some(((lead as u16 & 0x1F) << 6) | (cont as u16 & 0x3F)),
+            _ => None,
+        };
+    }
+    match next_char(iter) {
+        Some(cont @ 0x80..=0xBF) => Some(((lead as u16 & 0x1F) << 6) | (cont as u16 & 0x3F)),
+        next => {
+            *pending = next;
+            Some(lead as u16)
+        }
+    }
+}
+
+/// Error for a sequence that ended (or timed out) before its final byte.
+fn truncated_error() -> Error {
+    ParseEventError::TruncatedSequence.into()
+}
+
 /// Reference for parse_special_key_code, parse_other_special_key_code, and parse_key_mods
 /// https://invisible-island.net/xterm/ctlseqs/ctlseqs.html
 fn parse_special_key_code(code: u8) -> Option<KeyCode> {
@@ -280,7 +1464,9 @@ fn parse_special_key_code(code: u8) -> Option<KeyCode> {
         6 => KeyCode::PageDown,
         v @ 11..=15 => KeyCode::F(v - 10),
         v @ 17..=21 => KeyCode::F(v - 11),
-        v @ 23..=24 => KeyCode::F(v - 12),
+        v @ 23..=26 => KeyCode::F(v - 12),
+        v @ 28..=29 => KeyCode::F(v - 13),
+        v @ 31..=34 => KeyCode::F(v - 14),
         _ => return None,
     };
     Some(code)
@@ -312,31 +1498,79 @@ fn parse_libtickit_key_codes(code: u8) -> KeyCode {
     }
 }
 
-fn parse_key_mods(mods: u8) -> Option<KeyMod> {
-    let mods = match mods {
-        2 => KeyMod::Shift,
-        3 => KeyMod::Alt,
-        4 => KeyMod::AltShift,
-        5 => KeyMod::Ctrl,
-        6 => KeyMod::CtrlShift,
-        7 => KeyMod::AltCtrl,
-        8 => KeyMod::AltCtrlShift,
-        _ => return None,
+/// The application keypad mode SS3 final bytes of the keypad keys, in the
+/// order of the kitty keypad key codes: the digits then `. / * - +`, Enter,
+/// `=` and `,`.
+const SS3_KEYPAD: &[u8; 18] = b"pqrstuvwxynojmkMXl";
+
+/// The keypad key at `idx` (less than 18) in the kitty keypad key order.
+fn parse_keypad_key_code(idx: usize) -> KeyCode {
+    match b"0123456789./*-+\n=,"[idx] {
+        b'\n' => KeyCode::KpEnter,
+        ch => KeyCode::Kp(ch as char),
+    }
+}
+
+fn parse_kitty_key_code(code: &str) -> Option<KeyCode> {
+    let code = code.parse::<u32>().ok()?;
+    match code {
+        0..=255 => Some(parse_libtickit_key_codes(code as u8)),
+        // F13 to F35 and the keypad, from the private use area.
+        57376..=57398 => Some(KeyCode::F((code - 57363) as u8)),
+        57399..=57416 => Some(parse_keypad_key_code((code - 57399) as usize)),
+        _ => char::from_u32(code).map(KeyCode::Char),
+    }
+}
+
+/// Parse the parameters of a `CSI ... u` key:
+/// `code[:shifted[:base]][;mods[:event]]`.
+///
+/// The base layout key becomes `Key::alternate` and the event type
+/// `Key::kind`.
+fn parse_csi_u(params: &str) -> Option<Event> {
+    let mut fields = params.split(';');
+    let mut codes = fields.next()?.split(':');
+    let code = parse_kitty_key_code(codes.next()?)?;
+    let alternate = match codes.nth(1) {
+        Some(base) if !base.is_empty() => Some(parse_kitty_key_code(base)?),
+        _ => None,
+    };
+    let mut mods_field = fields.next().unwrap_or("").split(':');
+    let mods = match mods_field.next() {
+        None | Some("") | Some("1") => None,
+        Some(m) => Some(parse_key_mods(m.parse().ok()?)?),
+    };
+    let kind = match mods_field.next() {
+        None | Some("1") => KeyEventKind::Press,
+        Some("2") => KeyEventKind::Repeat,
+        Some("3") => KeyEventKind::Release,
+        Some(_) => return None,
     };
-    Some(mods)
+    Some(Event::Key(Key {
+        code,
+        mods,
+        kind,
+        alternate: alternate.filter(|alternate| *alternate != code),
+    }))
+}
+
+/// The modifiers of an xterm or kitty modifier parameter, one more than the
+/// modifier bits.  Caps Lock and Num Lock are ignored.
+fn parse_key_mods(mods: u16) -> Option<KeyMod> {
+    KeyMod::from_bits(mods.checked_sub(1)? as u8)
 }
 
 /// Parses a CSI sequence, just after reading ^[
 ///
 /// Returns Result<Event, io::Error>, Event may be unsupported.
-fn parse_csi<I>(iter: &mut I) -> io::Result<Event>
+fn parse_csi<I>(iter: &mut I, options: &ParseOptions) -> io::Result<Event>
 where
     I: Iterator<Item = Result<u8, Error>>,
 {
     Ok(match iter.next() {
         Some(Ok(b'[')) => match iter.next() {
             Some(Ok(val @ b'A'..=b'E')) => Event::Key(Key::new(KeyCode::F(1 + val - b'A'))),
-            _ => return Err(Error::new(ErrorKind::Other, "Failed to parse csi code [")),
+            _ => return Err(ParseEventError::UnknownCsi.into()),
         },
         Some(Ok(b'D')) => Event::Key(Key::new(KeyCode::Left)),
         Some(Ok(b'C')) => Event::Key(Key::new(KeyCode::Right)),
@@ -347,36 +1581,54 @@ where
         Some(Ok(b'Z')) => Event::Key(Key::new(KeyCode::BackTab)),
         Some(Ok(b'M')) => {
             // X10 emulation mouse encoding: ESC [ CB Cx Cy (6 characters only).
-            if let (Some(cb), Some(cx), Some(cy)) =
-                (next_char(iter), next_char(iter), next_char(iter))
-            {
-                let cb = cb as i8 - 32;
-                let cx = cx.saturating_sub(32) as u16;
-                let cy = cy.saturating_sub(32) as u16;
-                Event::Mouse(match cb & 0b11 {
-                    0 => {
+            // If the terminal is using the UTF-8 extended mode (1005) then
+            // values over 95 are sent as two byte UTF-8 characters instead.
+            let mut pending = None;
+            let encoding = options.mouse_encoding;
+            if let (Some(cb), Some(cx), Some(cy)) = (
+                next_mouse_value(iter, &mut pending, encoding, true),
+                next_mouse_value(iter, &mut pending, encoding, true),
+                next_mouse_value(iter, &mut pending, encoding, false),
+            ) {
+                let cb = cb as i16 - 32;
+                let cx = cx.saturating_sub(32);
+                let cy = cy.saturating_sub(32);
+                let mods = mouse_mods(cb as u16);
+                // The motion bit is set while dragging with a button held.
+                let pressed = |button| {
+                    if cb & 0x60 == 0x20 {
+                        MouseEvent::Hold(button, cx, cy)
+                    } else {
+                        MouseEvent::Press(button, cx, cy)
+                    }
+                };
+                let event = match (cb & 0xC0, cb & 0b11) {
+                    (0x40, 2) => MouseEvent::Press(MouseButton::WheelLeft, cx, cy),
+                    (0x40, 3) => MouseEvent::Press(MouseButton::WheelRight, cx, cy),
+                    (0x80, 0) => MouseEvent::Press(MouseButton::Back, cx, cy),
+                    (0x80, 1) => MouseEvent::Press(MouseButton::Forward, cx, cy),
+                    (_, 0) => {
                         if cb & 0x40 != 0 {
                             MouseEvent::Press(MouseButton::WheelUp, cx, cy)
                         } else {
-                            MouseEvent::Press(MouseButton::Left, cx, cy)
+                            pressed(MouseButton::Left)
                         }
                     }
-                    1 => {
+                    (_, 1) => {
                         if cb & 0x40 != 0 {
                             MouseEvent::Press(MouseButton::WheelDown, cx, cy)
                         } else {
-                            MouseEvent::Press(MouseButton::Middle, cx, cy)
+                            pressed(MouseButton::Middle)
                         }
                     }
-                    2 => MouseEvent::Press(MouseButton::Right, cx, cy),
-                    3 => MouseEvent::Release(cx, cy),
-                    _ => return Err(Error::new(ErrorKind::Other, "Failed to parse csi code M")),
-                })
+                    (_, 2) => pressed(MouseButton::Right),
+                    (_, 3) if cb & 0x60 == 0x20 => MouseEvent::Moved(cx, cy),
+                    (_, 3) => MouseEvent::Release(None, cx, cy),
+                    _ => return Err(ParseEventError::InvalidMouse.into()),
+                };
+                Event::Mouse(event, mods)
             } else {
-                return Err(Error::new(
-                    ErrorKind::Other,
-                    "Failed to parse X10 emulation mouse encoding. Expected: ESC [ CB Cx Cy (6 characters only)."
-                ));
+                return Err(ParseEventError::InvalidMouse.into());
             }
         }
         Some(Ok(b'<')) => {
@@ -386,9 +1638,7 @@ where
             if let Some(mut c) = next_char(iter) {
                 while !matches!(c, b'm' | b'M') {
                     buf.push(c);
-                    if let Some(new_c) = next_char(iter) {
-                        c = new_c
-                    }
+                    c = next_char(iter).ok_or_else(truncated_error)?;
                 }
                 if !buf.is_empty() {
                     if let Ok(str_buf) = String::from_utf8(buf) {
@@ -399,48 +1649,32 @@ where
                             if let (Ok(cb), Ok(cx), Ok(cy)) =
                                 (cb.parse::<u16>(), cx.parse::<u16>(), cy.parse::<u16>())
                             {
-                                let event = match cb {
-                                    0..=2 | 64..=65 => {
-                                        let button = match cb {
-                                            0 => MouseButton::Left,
-                                            1 => MouseButton::Middle,
-                                            2 => MouseButton::Right,
-                                            64 => MouseButton::WheelUp,
-                                            65 => MouseButton::WheelDown,
-                                            _ => unreachable!(),
-                                        };
-
-                                        match c {
-                                            b'M' => MouseEvent::Press(button, cx, cy),
-                                            b'm' => MouseEvent::Release(cx, cy),
-                                            _ => {
-                                                return Err(Error::new(
-                                                    ErrorKind::Other,
-                                                    "Failed to parse csi code M or m after <",
-                                                ))
-                                            }
-                                        }
-                                    }
-                                    32 => MouseEvent::Hold(cx, cy),
-                                    3 => MouseEvent::Release(cx, cy),
-                                    _ => {
-                                        return Err(Error::new(
-                                            ErrorKind::Other,
-                                            "Failed to parse csi code as mouse event",
-                                        ))
-                                    }
-                                };
-
-                                return Ok(Event::Mouse(event));
+                                let event = parse_sgr_mouse(cb, cx, cy, c == b'm')?;
+                                return Ok(if options.sgr_pixels {
+                                    Event::MousePixels(event, mouse_mods(cb))
+                                } else {
+                                    Event::Mouse(event, mouse_mods(cb))
+                                });
                             }
                         }
                     }
                 }
             }
-            return Err(Error::new(
-                ErrorKind::Other,
-                "Failed to parse xterm mouse encoding. Expected: ESC [ < Cb ; Cx ; Cy (;) (M or m)",
-            ));
+            return Err(ParseEventError::InvalidMouse.into());
+        }
+        Some(Ok(private @ b'=')) | Some(Ok(private @ b'>')) | Some(Ok(private @ b'?')) => {
+            // Private parameter sequence, usually a reply to a query.
+            let mut buf = Vec::new();
+            let mut c = next_char(iter).ok_or_else(truncated_error)?;
+            while !(64..=126).contains(&c) {
+                buf.push(c);
+                c = next_char(iter).ok_or_else(truncated_error)?;
+            }
+            let csi = parse_csi_sequence(Some(private), &buf, c)?;
+            match csi.device_attributes() {
+                Some(attributes) => Event::DeviceAttributes(attributes),
+                None => Event::Csi(csi),
+            }
         }
         Some(Ok(c @ b'0'..=b'9')) => {
             // Numbered escape code.
@@ -450,8 +1684,11 @@ where
                 // let's keep reading anything else.
                 while !(64..=126).contains(&c) {
                     buf.push(c);
-                    if let Some(new_c) = next_char(iter) {
-                        c = new_c
+                    c = next_char(iter).ok_or_else(truncated_error)?;
+                }
+                if c == b'R' {
+                    if let Some((x, y)) = parse_cursor_report(&buf) {
+                        return Ok(Event::CursorPos(x, y));
                     }
                 }
                 match c {
@@ -463,17 +1700,11 @@ where
                                 return if let Some(code) = parse_special_key_code(to_int) {
                                     Ok(Event::Key(Key::new_mod(code, KeyMod::Ctrl)))
                                 } else {
-                                    Err(Error::new(
-                                        ErrorKind::Other,
-                                        "Unrecognized rxvt key encoding.",
-                                    ))
+                                    Err(ParseEventError::InvalidKey.into())
                                 };
                             }
                         }
-                        return Err(Error::new(
-                            ErrorKind::Other,
-                            "Failed to parse rxvt mod + special keys.",
-                        ));
+                        return Err(ParseEventError::InvalidKey.into());
                     }
                     // rxvt mouse encoding:
                     // ESC [ Cb ; Cx ; Cy ; M
@@ -486,31 +1717,47 @@ where
                                 if let (Ok(cb), Ok(cx), Ok(cy)) =
                                     (cb.parse::<u16>(), cx.parse::<u16>(), cy.parse::<u16>())
                                 {
-                                    let event = match cb {
+                                    // Without the modifier bits.
+                                    let event = match cb & !0b1_1100 {
                                         32 => MouseEvent::Press(MouseButton::Left, cx, cy),
                                         33 => MouseEvent::Press(MouseButton::Middle, cx, cy),
                                         34 => MouseEvent::Press(MouseButton::Right, cx, cy),
-                                        35 => MouseEvent::Release(cx, cy),
-                                        64 => MouseEvent::Hold(cx, cy),
-                                        96 | 97 => MouseEvent::Press(MouseButton::WheelUp, cx, cy),
-                                        _ => {
-                                            return Err(Error::new(
-                                                ErrorKind::Other,
-                                                "Failed to parse csi code 0-9 as mouse event",
-                                            ))
-                                        }
+                                        35 => MouseEvent::Release(None, cx, cy),
+                                        64 => MouseEvent::Hold(MouseButton::Left, cx, cy),
+                                        65 => MouseEvent::Hold(MouseButton::Middle, cx, cy),
+                                        66 => MouseEvent::Hold(MouseButton::Right, cx, cy),
+                                        67 => MouseEvent::Moved(cx, cy),
+                                        96 => MouseEvent::Press(MouseButton::WheelUp, cx, cy),
+                                        97 => MouseEvent::Press(MouseButton::WheelDown, cx, cy),
+                                        98 => MouseEvent::Press(MouseButton::WheelLeft, cx, cy),
+                                        99 => MouseEvent::Press(MouseButton::WheelRight, cx, cy),
+                                        160 => MouseEvent::Press(MouseButton::Back, cx, cy),
+                                        161 => MouseEvent::Press(MouseButton::Forward, cx, cy),
+                                        _ => return Err(ParseEventError::InvalidMouse.into()),
                                     };
-                                    return Ok(Event::Mouse(event));
+                                    return Ok(Event::Mouse(event, mouse_mods(cb)));
                                 }
                             }
                         }
-                        return Err(Error::new(
-                            ErrorKind::Other,
-                            "Failed to parse rxvt mouse encoding. Expected: ESC [ Cb ; Cx ; Cy ; M",
-                        ));
+                        return Err(ParseEventError::InvalidMouse.into());
                     }
                     // Special key code.
                     b'~' => {
+                        if buf.starts_with(b"27;") {
+                            // xterm modifyOtherKeys: ESC [ 27 ; mods ; code ~
+                            return str::from_utf8(&buf[3..])
+                                .ok()
+                                .and_then(|params| {
+                                    let mut fields = params.split(';');
+                                    match (fields.next(), fields.next(), fields.next()) {
+                                        (Some(mods), Some(code), None) => {
+                                            parse_csi_u(&format!("{};{}", code, mods))
+                                        }
+                                        _ => None,
+                                    }
+                                })
+                                .ok_or_else(|| Error::from(ParseEventError::InvalidKey));
+                        }
                         if let Ok(str_buf) = String::from_utf8(buf) {
                             // This CSI sequence can be a list of semicolon-separated
                             // numbers.
@@ -521,12 +1768,7 @@ where
                                 }
                             }
                             let event = match nums.len() {
-                                0 => {
-                                    return Err(Error::new(
-                                        ErrorKind::Other,
-                                        "Failed to parse csi ~, buffer is empty",
-                                    ))
-                                }
+                                0 => return Err(ParseEventError::UnknownCsi.into()),
                                 1 => {
                                     if let Some(code) = parse_special_key_code(nums[0]) {
                                         Event::Key(Key::new(code))
@@ -536,7 +1778,7 @@ where
                                 }
                                 2 => {
                                     if let Some(key_code) = parse_special_key_code(nums[0]) {
-                                        if let Some(mods) = parse_key_mods(nums[1]) {
+                                        if let Some(mods) = parse_key_mods(nums[1].into()) {
                                             Event::Key(Key::new_mod(key_code, mods))
                                         } else {
                                             Event::Unsupported(nums)
@@ -549,51 +1791,20 @@ where
                             };
                             return Ok(event);
                         }
-                        return Err(Error::new(
-                            ErrorKind::Other,
-                            "Failed to parse csi code ~ from buffer",
-                        ));
+                        return Err(ParseEventError::InvalidKey.into());
                     }
                     b'u' => {
-                        // libtickit specification:
-                        // http://www.leonerd.org.uk/hacks/fixterms/
-                        if let Ok(str_buf) = String::from_utf8(buf) {
-                            // This libtickit sequence can be a list of semicolon-separated
-                            // numbers.
-                            let mut nums: Vec<u8> = vec![];
-                            for i in str_buf.split(';') {
-                                if let Ok(c) = i.parse::<u8>() {
-                                    nums.push(c);
-                                }
-                            }
-                            let event =
-                                match nums.len() {
-                                    0 => return Err(Error::new(
-                                        ErrorKind::Other,
-                                        "Failed to parse libtickit escape code, buffer is empty",
-                                    )),
-                                    1 => Event::Unsupported(nums),
-                                    2 => {
-                                        let key_code = parse_libtickit_key_codes(nums[0]);
-                                        if let Some(mods) = parse_key_mods(nums[1]) {
-                                            Event::Key(Key::new_mod(key_code, mods))
-                                        } else {
-                                            Event::Unsupported(nums)
-                                        }
-                                    }
-                                    _ => Event::Unsupported(nums),
-                                };
-                            return Ok(event);
-                        } else {
-                            return Err(Error::new(
-                                ErrorKind::Other,
-                                "Failed to parse libtickit escape code",
-                            ));
-                        }
+                        // libtickit specification:
+                        // http://www.leonerd.org.uk/hacks/fixterms/
+                        // as extended by the kitty keyboard protocol.
+                        return str::from_utf8(&buf)
+                            .ok()
+                            .and_then(parse_csi_u)
+                            .ok_or_else(|| Error::from(ParseEventError::InvalidKey));
                     }
                     val => {
                         if let Some(key_code) = parse_other_special_key_code(val) {
-                            if let Ok(str_buf) = String::from_utf8(buf) {
+                            if let Ok(str_buf) = String::from_utf8(buf.clone()) {
                                 let mut nums: Vec<u8> = vec![];
                                 for i in str_buf.split(';') {
                                     if let Ok(c) = i.parse::<u8>() {
@@ -601,75 +1812,102 @@ where
                                     }
                                 }
                                 if nums.len() == 2 {
-                                    if let Some(mods) = parse_key_mods(nums[1]) {
+                                    if let Some(mods) = parse_key_mods(nums[1].into()) {
                                         return Ok(Event::Key(Key::new_mod(key_code, mods)));
                                     }
                                 }
                                 return Ok(Event::Unsupported(nums));
                             }
                         }
-                        return Err(Error::new(ErrorKind::Other, "Failed to parse csi code"));
+                        return Ok(Event::Csi(parse_csi_sequence(None, &buf, val)?));
                     }
                 };
             };
-            return Err(Error::new(
-                ErrorKind::Other,
-                "Failed to parse numbered escape code",
-            ));
-        }
-        _ => {
-            return Err(Error::new(
-                ErrorKind::Other,
-                "Failed to parse input as csi code, unexpected value",
-            ))
+            return Err(ParseEventError::UnknownCsi.into());
         }
+        _ => return Err(ParseEventError::UnknownCsi.into()),
     })
 }
 
-/// Parse `c` as either a single byte ASCII char or a variable size UTF-8 char.
-fn parse_utf8_char<I>(c: u8, iter: &mut I) -> io::Result<char>
+/// Parse the parameters of a cursor position report, `CSI row ; col R`,
+/// into (x, y).
+///
+/// `CSI 1 ; mods R` is F3 with modifiers so row 1 with a column that is a
+/// legacy modifier value (2 to 16) is not a report.
+fn parse_cursor_report(params: &[u8]) -> Option<(u16, u16)> {
+    let (row, col) = str::from_utf8(params).ok()?.split_once(';')?;
+    let (row, col): (u16, u16) = (row.parse().ok()?, col.parse().ok()?);
+    if row == 1 && (2..=16).contains(&col) {
+        return None;
+    }
+    Some((col, row))
+}
+
+/// Parse `c` as a char applying the invalid UTF-8 policy from `options`.
+///
+/// Returns Ok(Err(bytes)) if the policy wants the invalid bytes reported.
+fn parse_char<I>(
+    c: u8,
+    iter: &mut I,
+    options: &ParseOptions,
+    unread: &mut Option<u8>,
+) -> io::Result<Result<char, Vec<u8>>>
 where
     I: Iterator<Item = io::Result<u8>>,
 {
-    if c.is_ascii() {
-        Ok(c as char)
-    } else {
-        let bytes = &mut Vec::new();
-        bytes.push(c);
-
-        loop {
-            match iter.next() {
-                Some(Ok(next)) => {
-                    bytes.push(next);
-                    if let Ok(st) = str::from_utf8(bytes) {
-                        if let Some(c) = st.chars().next() {
-                            return Ok(c);
-                        }
-                    }
-                    if bytes.len() >= 4 {
-                        return Err(Error::new(
-                            ErrorKind::Other,
-                            "Input character is not valid UTF-8",
-                        ));
-                    }
-                }
-                _ => {
-                    return Err(Error::new(
-                        ErrorKind::Other,
-                        "Input character is not valid UTF-8",
-                    ))
-                }
+    let mut next = None;
+    match decode_utf8(c, iter, &mut next) {
+        Ok(ch) => Ok(Ok(ch)),
+        Err(bytes) => match options.utf8 {
+            Utf8Policy::Error => Err(ParseEventError::InvalidUtf8.into()),
+            Utf8Policy::Replace => {
+                *unread = next;
+                Ok(Ok(char::REPLACEMENT_CHARACTER))
+            }
+            Utf8Policy::Raw => {
+                *unread = next;
+                Ok(Err(bytes))
             }
+        },
+    }
+}
+
+/// Decode a UTF-8 char that starts with `c`.
+///
+/// On invalid input returns the invalid bytes, if the byte that ended the
+/// sequence early was not part of it then it is placed in `unread`.
+fn decode_utf8<I>(c: u8, iter: &mut I, unread: &mut Option<u8>) -> Result<char, Vec<u8>>
+where
+    I: Iterator<Item = io::Result<u8>>,
+{
+    let len = match c {
+        0x00..=0x7F => return Ok(c as char),
+        0xC2..=0xDF => 2,
+        0xE0..=0xEF => 3,
+        0xF0..=0xF4 => 4,
+        _ => return Err(vec![c]),
+    };
+    let mut bytes = vec![c];
+    while bytes.len() < len {
+        match next_char(iter) {
+            Some(next @ 0x80..=0xBF) => bytes.push(next),
+            Some(next) => {
+                *unread = Some(next);
+                return Err(bytes);
+            }
+            None => return Err(bytes),
         }
     }
+    match str::from_utf8(&bytes) {
+        Ok(st) => Ok(st.chars().next().unwrap_or(char::REPLACEMENT_CHARACTER)),
+        Err(_) => Err(bytes),
+    }
 }
 
 #[cfg(test)]
 mod test {
     use super::*;
-    use std::array::IntoIter;
     use std::collections::HashMap;
-    use std::iter::FromIterator;
 
     #[test]
     fn test_parse_utf8() {
@@ -678,10 +1916,286 @@ mod test {
         let chars = st.chars();
         for c in chars {
             let b = bytes.next().unwrap().unwrap();
-            assert!(c == parse_utf8_char(b, bytes).unwrap());
+            assert!(c == decode_utf8(b, bytes, &mut None).unwrap());
+        }
+    }
+
+    #[test]
+    fn test_parse_invalid_utf8_policy() {
+        fn parse(bytes: &[u8], utf8: Utf8Policy) -> (Event, Option<u8>) {
+            let mut iter = bytes[1..].iter().map(|x| Ok(*x));
+            let mut unread = None;
+            let options = ParseOptions {
+                utf8,
+                ..Default::default()
+            };
+            let event = parse_event_opts(bytes[0], &mut iter, &options, &mut unread).unwrap();
+            (event, unread)
+        }
+
+        // Latin-1 'é' followed by 'a'.
+        assert_eq!(
+            parse(b"\xE9a", Utf8Policy::Error),
+            (Event::Unsupported(vec![0xE9, b'a']), None)
+        );
+        assert_eq!(
+            parse(b"\xE9a", Utf8Policy::Replace),
+            (Event::Key(Key::new(KeyCode::Char('\u{FFFD}'))), Some(b'a'))
+        );
+        assert_eq!(
+            parse(b"\xE9a", Utf8Policy::Raw),
+            (Event::Unsupported(vec![0xE9]), Some(b'a'))
+        );
+        // Stray continuation byte.
+        assert_eq!(
+            parse(b"\x80a", Utf8Policy::Replace),
+            (Event::Key(Key::new(KeyCode::Char('\u{FFFD}'))), None)
+        );
+        assert_eq!(
+            parse(b"\x1B\xFF", Utf8Policy::Raw),
+            (Event::Unsupported(vec![0xFF]), None)
+        );
+        // Valid input is not affected.
+        assert_eq!(
+            parse("é".as_bytes(), Utf8Policy::Raw),
+            (Event::Key(Key::new(KeyCode::Char('é'))), None)
+        );
+    }
+
+    #[test]
+    fn test_parse_nul_as_ctrl_space() {
+        let options = ParseOptions {
+            nul_as_ctrl_space: true,
+            ..Default::default()
+        };
+        let mut iter = std::iter::empty();
+        assert_eq!(
+            parse_event_opts(b'\0', &mut iter, &options, &mut None).unwrap(),
+            Event::Key(Key::new_mod(KeyCode::Char(' '), KeyMod::Ctrl))
+        );
+        let mut iter = std::iter::once(Ok(b'\0'));
+        assert_eq!(
+            parse_event_opts(b'\x1B', &mut iter, &options, &mut None).unwrap(),
+            Event::Key(Key::new_mod(KeyCode::Char(' '), KeyMod::AltCtrl))
+        );
+        let mut iter = std::iter::empty();
+        assert_eq!(
+            parse_event(b'\0', &mut iter).unwrap(),
+            Event::Key(Key::new(KeyCode::Null))
+        );
+    }
+
+    #[test]
+    fn test_parse_c1_controls() {
+        fn parse(bytes: &[u8], c1: C1Mode) -> Event {
+            let mut iter = bytes[1..].iter().map(|x| Ok(*x));
+            let options = ParseOptions {
+                c1,
+                ..Default::default()
+            };
+            parse_event_opts(bytes[0], &mut iter, &options, &mut None).unwrap()
+        }
+
+        let up = Event::Key(Key::new(KeyCode::Up));
+        assert_eq!(parse(b"\x9BA", C1Mode::CsiOnly), up);
+        assert_eq!(parse(b"\x9BA", C1Mode::Controls), up);
+        assert_eq!(
+            parse(b"\x9BA", C1Mode::Data),
+            Event::Unsupported(vec![0x9B])
+        );
+        assert_eq!(
+            parse(b"\x8FP", C1Mode::Controls),
+            Event::Key(Key::new(KeyCode::F(1)))
+        );
+        assert_eq!(
+            parse(b"\x9D11;rgb:0000/0000/0000\x9C", C1Mode::Controls),
+            Event::Osc {
+                code: 11,
+                payload: b"rgb:0000/0000/0000".to_vec()
+            }
+        );
+        assert_eq!(
+            parse(b"\x9D10;rgb:ffff/ffff/ffff\x07", C1Mode::Controls),
+            Event::Osc {
+                code: 10,
+                payload: b"rgb:ffff/ffff/ffff".to_vec()
+            }
+        );
+        assert_eq!(
+            parse(b"\x90>|xterm(367)\x1B\\", C1Mode::Controls),
+            Event::Dcs(b">|xterm(367)".to_vec())
+        );
+        assert_eq!(
+            parse(b"\x8D", C1Mode::Controls),
+            Event::Unsupported(vec![0x8D])
+        );
+        // Not a control by default.
+        assert_eq!(
+            parse(b"\x90>|xterm(367)\x1B\\", C1Mode::CsiOnly),
+            Event::Unsupported(vec![0x90])
+        );
+    }
+
+    #[test]
+    fn test_parse_control_strings() {
+        let parse = |seq: &[u8]| {
+            let mut unread = None;
+            let mut iter = seq[1..].iter().map(|b| Ok(*b));
+            let event = parse_event_opts(seq[0], &mut iter, &ParseOptions::default(), &mut unread);
+            (event.unwrap(), unread)
+        };
+
+        assert_eq!(
+            parse(b"\x1B]11;rgb:0000/8080/ffff\x1B\\"),
+            (
+                Event::Osc {
+                    code: 11,
+                    payload: b"rgb:0000/8080/ffff".to_vec()
+                },
+                None
+            )
+        );
+        assert_eq!(
+            parse(b"\x1B]52;c;aGk=\x07"),
+            (
+                Event::Osc {
+                    code: 52,
+                    payload: b"c;aGk=".to_vec()
+                },
+                None
+            )
+        );
+        assert_eq!(
+            parse(b"\x1B]2\x07"),
+            (
+                Event::Osc {
+                    code: 2,
+                    payload: Vec::new()
+                },
+                None
+            )
+        );
+        assert_eq!(
+            parse(b"\x1BP>|kitty(0.26)\x1B\\"),
+            (Event::Dcs(b">|kitty(0.26)".to_vec()), None)
+        );
+        assert_eq!(
+            parse(b"\x1BP1$r0m\x1B\\"),
+            (Event::Dcs(b"1$r0m".to_vec()), None)
+        );
+        // Alt keys, not the start of a control string.
+        assert_eq!(
+            parse(b"\x1B]"),
+            (
+                Event::Key(Key::new_mod(KeyCode::Char(']'), KeyMod::Alt)),
+                None
+            )
+        );
+        assert_eq!(
+            parse(b"\x1B]x"),
+            (
+                Event::Key(Key::new_mod(KeyCode::Char(']'), KeyMod::Alt)),
+                Some(b'x')
+            )
+        );
+        assert_eq!(
+            parse(b"\x1BPp"),
+            (
+                Event::Key(Key::new_mod(KeyCode::Char('P'), KeyMod::AltShift)),
+                Some(b'p')
+            )
+        );
+        assert_eq!(
+            parse_events(b"\x1B]10;?\x1B\\\x1B[A").0,
+            vec![
+                Event::Osc {
+                    code: 10,
+                    payload: b"?".to_vec()
+                },
+                Event::Key(Key::new(KeyCode::Up))
+            ]
+        );
+    }
+
+    #[test]
+    fn test_key_names() {
+        let names = [
+            (
+                "Ctrl+Shift+F5",
+                Key::new_mod(KeyCode::F(5), KeyMod::CtrlShift),
+            ),
+            ("Alt+x", Key::new_mod(KeyCode::Char('x'), KeyMod::Alt)),
+            ("X", Key::new(KeyCode::Char('X'))),
+            ("Ctrl+Space", Key::new_mod(KeyCode::Char(' '), KeyMod::Ctrl)),
+            ("Enter", Key::new(KeyCode::Char('\n'))),
+            ("Alt+Plus", Key::new_mod(KeyCode::Char('+'), KeyMod::Alt)),
+            ("Super+PageUp", Key::new_mod(KeyCode::PageUp, KeyMod::Super)),
+            ("KpPlus", Key::new(KeyCode::Kp('+'))),
+            ("Kp7", Key::new(KeyCode::Kp('7'))),
+            (
+                "Ctrl+Alt+Shift+Esc",
+                Key::new_mod(KeyCode::Esc, KeyMod::AltCtrlShift),
+            ),
+            (
+                "Hyper+Meta+F35",
+                Key::new_mod(KeyCode::F(35), KeyMod::Hyper | KeyMod::Meta),
+            ),
+        ];
+        for (name, key) in names.iter() {
+            assert_eq!(key.to_string(), *name);
+            assert_eq!(name.parse::<Key>(), Ok(*key));
+        }
+
+        assert_eq!(
+            "shift+control+pgdn".parse::<Key>(),
+            Ok(Key::new_mod(KeyCode::PageDown, KeyMod::CtrlShift))
+        );
+        assert_eq!(
+            "Ctrl++".parse::<Key>(),
+            Ok(Key::new_mod(KeyCode::Char('+'), KeyMod::Ctrl))
+        );
+        assert_eq!(
+            "Ctrl+X".parse::<Key>(),
+            Ok(Key::new_mod(KeyCode::Char('x'), KeyMod::Ctrl))
+        );
+        assert_eq!("+".parse::<Key>(), Ok(Key::new(KeyCode::Char('+'))));
+        assert_eq!("f".parse::<Key>(), Ok(Key::new(KeyCode::Char('f'))));
+        let bad_names = [
+            "",
+            "Ctrl+",
+            "Cmd+x",
+            "F0",
+            "F36",
+            "Kpx",
+            "Kp\u{12E}",
+            "Foo",
+            "Ctrl+Foo",
+        ];
+        for bad in bad_names.iter() {
+            assert_eq!(bad.parse::<Key>(), Err(ParseKeyError(bad.to_string())));
         }
     }
 
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_serde() {
+        let events = vec![
+            Event::Key(Key::new_mod(KeyCode::F(5), KeyMod::CtrlShift)),
+            Event::Key(Key::new(KeyCode::Char('x'))),
+            Event::Mouse(
+                MouseEvent::Press(MouseButton::WheelUp, 3, 4),
+                Some(KeyMod::Alt),
+            ),
+            Event::Osc {
+                code: 11,
+                payload: b"rgb:0000/0000/0000".to_vec(),
+            },
+            Event::Resize(80, 24),
+        ];
+        let json = serde_json::to_string(&events).unwrap();
+        assert_eq!(serde_json::from_str::<Vec<Event>>(&json).unwrap(), events);
+    }
+
     fn test_parse_event_dynamic(item: u8, map: &mut HashMap<String, Event>) {
         for (key, val) in map.iter() {
             let mut iter = key.bytes().map(|x| Ok(x));
@@ -698,7 +2212,7 @@ mod test {
 
     #[test]
     fn test_parse_valid_csi_special_codes() {
-        let mut map = HashMap::<_, _>::from_iter(IntoIter::new([
+        let mut map = HashMap::from([
             ("[1~", Event::Key(Key::new(KeyCode::Home))),
             ("[7~", Event::Key(Key::new(KeyCode::Home))),
             ("[7^", Event::Key(Key::new_mod(KeyCode::Home, KeyMod::Ctrl))),
@@ -711,6 +2225,10 @@ mod test {
             ("[F", Event::Key(Key::new(KeyCode::End))),
             ("[D", Event::Key(Key::new(KeyCode::Left))),
             ("[Z", Event::Key(Key::new(KeyCode::BackTab))),
+            ("[24~", Event::Key(Key::new(KeyCode::F(12)))),
+            ("[25~", Event::Key(Key::new(KeyCode::F(13)))),
+            ("[29~", Event::Key(Key::new(KeyCode::F(16)))),
+            ("[34~", Event::Key(Key::new(KeyCode::F(20)))),
             (
                 "[1;2F",
                 Event::Key(Key::new_mod(KeyCode::End, KeyMod::Shift)),
@@ -851,7 +2369,7 @@ mod test {
                 "[24;2~",
                 Event::Key(Key::new_mod(KeyCode::F(12), KeyMod::Shift)),
             ),
-        ]));
+        ]);
 
         let item = b'\x1B';
         test_parse_event(item, &mut map);
@@ -859,60 +2377,177 @@ mod test {
 
     #[test]
     fn test_parse_x10_emulation_mouse_encoding() {
-        let mut map = HashMap::<_, _>::from_iter(IntoIter::new([
+        let mut map = HashMap::from([
             (
                 "[M\x00\x00\x00",
-                Event::Mouse(MouseEvent::Press(MouseButton::WheelUp, 0, 0)),
+                Event::Mouse(MouseEvent::Press(MouseButton::WheelUp, 0, 0), None),
             ),
             (
                 "[M\x40\x30\x32",
-                Event::Mouse(MouseEvent::Press(MouseButton::Left, 16, 18)),
+                Event::Mouse(MouseEvent::Hold(MouseButton::Left, 16, 18), None),
             ),
             (
                 "[M\x01\x00\x00",
-                Event::Mouse(MouseEvent::Press(MouseButton::WheelDown, 0, 0)),
+                Event::Mouse(MouseEvent::Press(MouseButton::WheelDown, 0, 0), None),
             ),
             (
                 "[M\x41\x29\x30",
-                Event::Mouse(MouseEvent::Press(MouseButton::Middle, 9, 16)),
+                Event::Mouse(MouseEvent::Hold(MouseButton::Middle, 9, 16), None),
             ),
             (
                 "[M\x02\x00\x30",
-                Event::Mouse(MouseEvent::Press(MouseButton::Right, 0, 16)),
+                Event::Mouse(MouseEvent::Press(MouseButton::Right, 0, 16), None),
+            ),
+            (
+                "[M\x03\x30\x7F",
+                Event::Mouse(MouseEvent::Release(None, 16, 95), None),
+            ),
+            (
+                "[M\x43\x30\x32",
+                Event::Mouse(MouseEvent::Moved(16, 18), None),
             ),
-            ("[M\x03\x30\x7F", Event::Mouse(MouseEvent::Release(16, 95))),
-        ]));
+            (
+                "[M\x3A\x30\x32",
+                Event::Mouse(
+                    MouseEvent::Press(MouseButton::Right, 16, 18),
+                    Some(KeyMod::AltCtrl),
+                ),
+            ),
+            // Dragging with the right button, the motion bit is set.
+            (
+                "[M\x42\x30\x32",
+                Event::Mouse(MouseEvent::Hold(MouseButton::Right, 16, 18), None),
+            ),
+        ]);
 
         let item = b'\x1B';
         test_parse_event(item, &mut map);
     }
 
+    #[test]
+    fn test_parse_utf8_extended_mouse_encoding() {
+        let cases: [(&[u8], Event); 5] = [
+            // Largest plain X10 coordinates.
+            (
+                b"[M\x20\xFF\xFF",
+                Event::Mouse(MouseEvent::Press(MouseButton::Left, 223, 223), None),
+            ),
+            // A plain X10 byte that looks like a UTF-8 lead byte.
+            (
+                b"[M\x20\xC5\x21",
+                Event::Mouse(MouseEvent::Press(MouseButton::Left, 165, 1), None),
+            ),
+            (
+                b"[M\x20\xC5\x8C\x21",
+                Event::Mouse(MouseEvent::Press(MouseButton::Left, 300, 1), None),
+            ),
+            (
+                b"[M\x23\x21\xC4\x9E",
+                Event::Mouse(MouseEvent::Release(None, 1, 254), None),
+            ),
+            (
+                b"[M\x22\xDF\xBF\xC2\x80",
+                Event::Mouse(MouseEvent::Press(MouseButton::Right, 2015, 96), None),
+            ),
+        ];
+        for (bytes, event) in cases.iter() {
+            let mut iter = bytes.iter().map(|x| Ok(*x));
+            assert_eq!(*event, parse_event(b'\x1B', &mut iter).unwrap());
+        }
+    }
+
+    #[test]
+    fn test_parse_known_mouse_encoding() {
+        let parse = |bytes: &[u8], mouse_encoding| {
+            let mut iter = bytes.iter().map(|x| Ok(*x));
+            let options = ParseOptions {
+                mouse_encoding,
+                ..Default::default()
+            };
+            parse_event_opts(b'\x1B', &mut iter, &options, &mut None).unwrap()
+        };
+        assert_eq!(
+            parse(b"[M\x20\xC5\x8C\x21", MouseEncoding::X10),
+            Event::Mouse(MouseEvent::Press(MouseButton::Left, 165, 108), None)
+        );
+        assert_eq!(
+            parse(b"[M\x20\xC5\x8C\xC5\x8C", MouseEncoding::Utf8),
+            Event::Mouse(MouseEvent::Press(MouseButton::Left, 300, 300), None)
+        );
+    }
+
+    #[test]
+    fn test_parse_sgr_pixels() {
+        let options = ParseOptions {
+            sgr_pixels: true,
+            ..Default::default()
+        };
+        let mut iter = b"[<0;1200;640M".iter().map(|x| Ok(*x));
+        assert_eq!(
+            parse_event_opts(b'\x1B', &mut iter, &options, &mut None).unwrap(),
+            Event::MousePixels(MouseEvent::Press(MouseButton::Left, 1200, 640), None)
+        );
+        // Only SGR reports are in pixels.
+        let mut iter = b"[32;65;8;M".iter().map(|x| Ok(*x));
+        assert_eq!(
+            parse_event_opts(b'\x1B', &mut iter, &options, &mut None).unwrap(),
+            Event::Mouse(MouseEvent::Press(MouseButton::Left, 65, 8), None)
+        );
+    }
+
     #[test]
     fn test_parse_rxvt_mouse_encoding() {
-        let mut map = HashMap::<_, _>::from_iter(IntoIter::new([
+        let mut map = HashMap::from([
             (
                 "[32;65;8;M",
-                Event::Mouse(MouseEvent::Press(MouseButton::Left, 65, 8)),
+                Event::Mouse(MouseEvent::Press(MouseButton::Left, 65, 8), None),
             ),
             (
                 "[33;5;2;M",
-                Event::Mouse(MouseEvent::Press(MouseButton::Middle, 5, 2)),
+                Event::Mouse(MouseEvent::Press(MouseButton::Middle, 5, 2), None),
             ),
             (
                 "[34;65;8;M",
-                Event::Mouse(MouseEvent::Press(MouseButton::Right, 65, 8)),
+                Event::Mouse(MouseEvent::Press(MouseButton::Right, 65, 8), None),
+            ),
+            (
+                "[35;65;8;M",
+                Event::Mouse(MouseEvent::Release(None, 65, 8), None),
+            ),
+            ("[67;65;8;M", Event::Mouse(MouseEvent::Moved(65, 8), None)),
+            (
+                "[99;65;8;M",
+                Event::Mouse(MouseEvent::Press(MouseButton::WheelRight, 65, 8), None),
+            ),
+            (
+                "[160;65;8;M",
+                Event::Mouse(MouseEvent::Press(MouseButton::Back, 65, 8), None),
+            ),
+            // Ctrl held while pressing the right button.
+            (
+                "[50;65;8;M",
+                Event::Mouse(
+                    MouseEvent::Press(MouseButton::Right, 65, 8),
+                    Some(KeyMod::Ctrl),
+                ),
+            ),
+            (
+                "[64;113;234;M",
+                Event::Mouse(MouseEvent::Hold(MouseButton::Left, 113, 234), None),
+            ),
+            (
+                "[66;113;234;M",
+                Event::Mouse(MouseEvent::Hold(MouseButton::Right, 113, 234), None),
             ),
-            ("[35;65;8;M", Event::Mouse(MouseEvent::Release(65, 8))),
-            ("[64;113;234;M", Event::Mouse(MouseEvent::Hold(113, 234))),
             (
                 "[96;65;8;M",
-                Event::Mouse(MouseEvent::Press(MouseButton::WheelUp, 65, 8)),
+                Event::Mouse(MouseEvent::Press(MouseButton::WheelUp, 65, 8), None),
             ),
             (
                 "[97;65;8;M",
-                Event::Mouse(MouseEvent::Press(MouseButton::WheelUp, 65, 8)),
+                Event::Mouse(MouseEvent::Press(MouseButton::WheelDown, 65, 8), None),
             ),
-        ]));
+        ]);
 
         let item = b'\x1B';
         test_parse_event(item, &mut map);
@@ -920,30 +2555,101 @@ mod test {
 
     #[test]
     fn test_parse_valid_csi_xterm_mouse() {
-        let mut map = HashMap::<_, _>::from_iter(IntoIter::new([
+        let mut map = HashMap::from([
             (
                 "[<0;65;8;M",
-                Event::Mouse(MouseEvent::Press(MouseButton::Left, 65, 8)),
+                Event::Mouse(MouseEvent::Press(MouseButton::Left, 65, 8), None),
             ),
             (
                 "[<1;5;2;M",
-                Event::Mouse(MouseEvent::Press(MouseButton::Middle, 5, 2)),
+                Event::Mouse(MouseEvent::Press(MouseButton::Middle, 5, 2), None),
             ),
             (
                 "[<2;65;8;M",
-                Event::Mouse(MouseEvent::Press(MouseButton::Right, 65, 8)),
+                Event::Mouse(MouseEvent::Press(MouseButton::Right, 65, 8), None),
             ),
             (
                 "[<64;65;8;M",
-                Event::Mouse(MouseEvent::Press(MouseButton::WheelUp, 65, 8)),
+                Event::Mouse(MouseEvent::Press(MouseButton::WheelUp, 65, 8), None),
             ),
             (
                 "[<65;82;1;M",
-                Event::Mouse(MouseEvent::Press(MouseButton::WheelDown, 82, 1)),
+                Event::Mouse(MouseEvent::Press(MouseButton::WheelDown, 82, 1), None),
+            ),
+            (
+                "[<3;65;8;m",
+                Event::Mouse(MouseEvent::Release(None, 65, 8), None),
+            ),
+            (
+                "[<0;65;8;m",
+                Event::Mouse(MouseEvent::Release(Some(MouseButton::Left), 65, 8), None),
+            ),
+            (
+                "[<1;65;8;m",
+                Event::Mouse(MouseEvent::Release(Some(MouseButton::Middle), 65, 8), None),
+            ),
+            // Ctrl held while releasing the right button.
+            (
+                "[<18;65;8;m",
+                Event::Mouse(
+                    MouseEvent::Release(Some(MouseButton::Right), 65, 8),
+                    Some(KeyMod::Ctrl),
+                ),
+            ),
+            (
+                "[<32;113;234;m",
+                Event::Mouse(MouseEvent::Hold(MouseButton::Left, 113, 234), None),
+            ),
+            (
+                "[<33;113;234;M",
+                Event::Mouse(MouseEvent::Hold(MouseButton::Middle, 113, 234), None),
+            ),
+            (
+                "[<34;113;234;M",
+                Event::Mouse(MouseEvent::Hold(MouseButton::Right, 113, 234), None),
+            ),
+            // Shift and ctrl held while dragging.
+            (
+                "[<36;4;5;M",
+                Event::Mouse(
+                    MouseEvent::Hold(MouseButton::Left, 4, 5),
+                    Some(KeyMod::Shift),
+                ),
+            ),
+            (
+                "[<50;4;5;M",
+                Event::Mouse(
+                    MouseEvent::Hold(MouseButton::Right, 4, 5),
+                    Some(KeyMod::Ctrl),
+                ),
+            ),
+            (
+                "[<96;4;5;M",
+                Event::Mouse(MouseEvent::Hold(MouseButton::WheelUp, 4, 5), None),
+            ),
+            (
+                "[<66;4;5;M",
+                Event::Mouse(MouseEvent::Press(MouseButton::WheelLeft, 4, 5), None),
+            ),
+            (
+                "[<67;4;5;M",
+                Event::Mouse(MouseEvent::Press(MouseButton::WheelRight, 4, 5), None),
+            ),
+            (
+                "[<128;4;5;M",
+                Event::Mouse(MouseEvent::Press(MouseButton::Back, 4, 5), None),
+            ),
+            (
+                "[<129;4;5;m",
+                Event::Mouse(MouseEvent::Release(Some(MouseButton::Forward), 4, 5), None),
+            ),
+            // Motion with no button, with and without shift.
+            ("[<35;10;3;M", Event::Mouse(MouseEvent::Moved(10, 3), None)),
+            (
+                "[<39;10;3;M",
+                Event::Mouse(MouseEvent::Moved(10, 3), Some(KeyMod::Shift)),
             ),
-            ("[<3;65;8;m", Event::Mouse(MouseEvent::Release(65, 8))),
-            ("[<32;113;234;m", Event::Mouse(MouseEvent::Hold(113, 234))),
-        ]));
+        ]);
 
         let item = b'\x1B';
         test_parse_event(item, &mut map);
@@ -952,80 +2658,67 @@ mod test {
     #[test]
     fn test_parse_ctrl_key_alphanumeric() {
         // a
-        let mut map = HashMap::<_, _>::from_iter(IntoIter::new([(
+        let mut map = HashMap::from([(
             "",
             Event::Key(Key::new_mod(KeyCode::Char('a'), KeyMod::Ctrl)),
-        )]));
+        )]);
 
         let item = b'\x01';
         test_parse_event(item, &mut map);
 
         // z
-        let mut map = HashMap::<_, _>::from_iter(IntoIter::new([(
+        let mut map = HashMap::from([(
             "",
             Event::Key(Key::new_mod(KeyCode::Char('z'), KeyMod::Ctrl)),
-        )]));
+        )]);
 
         let item = b'\x1A';
         test_parse_event(item, &mut map);
 
         // 4
-        let mut map = HashMap::<_, _>::from_iter(IntoIter::new([(
+        let mut map = HashMap::from([(
             "",
             Event::Key(Key::new_mod(KeyCode::Char('4'), KeyMod::Ctrl)),
-        )]));
+        )]);
 
         let item = b'\x1C';
         test_parse_event(item, &mut map);
 
         // 7
-        let mut map = HashMap::<_, _>::from_iter(IntoIter::new([(
+        let mut map = HashMap::from([(
             "",
             Event::Key(Key::new_mod(KeyCode::Char('7'), KeyMod::Ctrl)),
-        )]));
+        )]);
 
         let item = b'\x1F';
         test_parse_event(item, &mut map);
 
         // newline
-        let mut map = HashMap::<_, _>::from_iter(IntoIter::new([(
-            "",
-            Event::Key(Key::new(KeyCode::Char('\n'))),
-        )]));
+        let mut map = HashMap::from([("", Event::Key(Key::new(KeyCode::Char('\n'))))]);
 
         let item = b'\n';
         test_parse_event(item, &mut map);
 
         // carriage return
-        let mut map = HashMap::<_, _>::from_iter(IntoIter::new([(
-            "",
-            Event::Key(Key::new(KeyCode::Char('\n'))),
-        )]));
+        let mut map = HashMap::from([("", Event::Key(Key::new(KeyCode::Char('\n'))))]);
 
         let item = b'\r';
         test_parse_event(item, &mut map);
 
         // tab
-        let mut map = HashMap::<_, _>::from_iter(IntoIter::new([(
-            "",
-            Event::Key(Key::new(KeyCode::Char('\t'))),
-        )]));
+        let mut map = HashMap::from([("", Event::Key(Key::new(KeyCode::Char('\t'))))]);
 
         let item = b'\t';
         test_parse_event(item, &mut map);
 
         // backspace
-        let mut map = HashMap::<_, _>::from_iter(IntoIter::new([(
-            "",
-            Event::Key(Key::new(KeyCode::Backspace)),
-        )]));
+        let mut map = HashMap::from([("", Event::Key(Key::new(KeyCode::Backspace)))]);
 
         let item = b'\x7F';
         test_parse_event(item, &mut map);
 
         // null
-        let mut map =
-            HashMap::<_, _>::from_iter(IntoIter::new([("", Event::Key(Key::new(KeyCode::Null)))]));
+        let mut map = HashMap::from([("", Event::Key(Key::new(KeyCode::Null)))]);
 
         let item = b'\0';
         test_parse_event(item, &mut map);
@@ -1033,7 +2726,7 @@ mod test {
 
     #[test]
     fn test_parse_non_csi_escape_codes() {
-        let mut map = HashMap::<_, _>::from_iter(IntoIter::new([
+        let mut map = HashMap::from([
             ("OP", Event::Key(Key::new(KeyCode::F(1)))),
             ("OS", Event::Key(Key::new(KeyCode::F(4)))),
             (
@@ -1068,7 +2761,29 @@ mod test {
                 "\u{7a}",
                 Event::Key(Key::new_mod(KeyCode::Char('z'), KeyMod::Alt)),
             ),
-        ]));
+            (
+                "A",
+                Event::Key(Key::new_mod(KeyCode::Char('A'), KeyMod::AltShift)),
+            ),
+            (
+                "é",
+                Event::Key(Key::new_mod(KeyCode::Char('é'), KeyMod::Alt)),
+            ),
+            (
+                "É",
+                Event::Key(Key::new_mod(KeyCode::Char('É'), KeyMod::AltShift)),
+            ),
+            (
+                "€",
+                Event::Key(Key::new_mod(KeyCode::Char('€'), KeyMod::Alt)),
+            ),
+            ("\x1B", Event::Key(Key::new_mod(KeyCode::Esc, KeyMod::Alt))),
+            ("Op", Event::Key(Key::new(KeyCode::Kp('0')))),
+            ("Oy", Event::Key(Key::new(KeyCode::Kp('9')))),
+            ("Ok", Event::Key(Key::new(KeyCode::Kp('+')))),
+            ("Ol", Event::Key(Key::new(KeyCode::Kp(',')))),
+            ("OM", Event::Key(Key::new(KeyCode::KpEnter))),
+        ]);
         let item = b'\x1B';
         test_parse_event(item, &mut map);
     }
@@ -1083,13 +2798,187 @@ mod test {
         )
     }
 
+    #[test]
+    fn test_device_attributes() {
+        let parse = |seq: &str| {
+            let mut iter = seq.bytes().map(Ok);
+            match parse_event(b'\x1B', &mut iter).unwrap() {
+                Event::DeviceAttributes(attributes) => attributes,
+                event => panic!("not device attributes: {:?}", event),
+            }
+        };
+        let da1 = parse("[?64;1;4;6;22c");
+        assert_eq!(da1.level(), Some(4));
+        assert_eq!(da1.attributes(), &[1, 4, 6, 22]);
+        assert!(da1.columns_132() && da1.sixel() && da1.ansi_color());
+        assert!(!da1.rectangular_editing());
+        assert_eq!(da1.terminal_type(), None);
+        assert_eq!(parse("[?1;2c").level(), Some(1));
+        let da2 = parse("[>41;354;0c");
+        assert_eq!(da2.level(), None);
+        assert!(da2.attributes().is_empty());
+        assert_eq!(da2.terminal_type(), Some(41));
+        assert_eq!(da2.version(), Some(354));
+    }
+
+    #[test]
+    fn test_parse_csi_sequence() {
+        let mut map = HashMap::from([
+            (
+                "[?2026;2$y",
+                Event::Csi(CsiSequence {
+                    private: Some(b'?'),
+                    params: vec![2026, 2],
+                    intermediates: b"$".to_vec(),
+                    final_byte: b'y',
+                }),
+            ),
+            (
+                "[>1;10;0c",
+                Event::DeviceAttributes(DeviceAttributes {
+                    secondary: true,
+                    params: vec![1, 10, 0],
+                }),
+            ),
+            (
+                "[?u",
+                Event::Csi(CsiSequence {
+                    private: Some(b'?'),
+                    params: vec![],
+                    intermediates: vec![],
+                    final_byte: b'u',
+                }),
+            ),
+            (
+                "[4;2$y",
+                Event::Csi(CsiSequence {
+                    private: None,
+                    params: vec![4, 2],
+                    intermediates: b"$".to_vec(),
+                    final_byte: b'y',
+                }),
+            ),
+            (
+                "[8;;80t",
+                Event::Csi(CsiSequence {
+                    private: None,
+                    params: vec![8, 0, 80],
+                    intermediates: vec![],
+                    final_byte: b't',
+                }),
+            ),
+        ]);
+        test_parse_event(b'\x1B', &mut map);
+
+        let mut iter = "[?2026;2$y".bytes().map(|x| Ok(x));
+        if let Event::Csi(csi) = parse_event(b'\x1B', &mut iter).unwrap() {
+            assert_eq!(
+                csi.mode_report(),
+                Some(ModeReport {
+                    mode: 2026,
+                    private: true,
+                    state: ModeState::Reset,
+                })
+            );
+        } else {
+            panic!("Expected a csi sequence");
+        }
+
+        let mut iter = "[?20$26y".bytes().map(|x| Ok(x));
+        assert_eq!(
+            parse_event(b'\x1B', &mut iter).unwrap(),
+            Event::Unsupported(b"\x1B[?20$26y".to_vec())
+        );
+    }
+
+    #[test]
+    fn test_parse_cursor_report() {
+        let parse = |seq: &str| {
+            let mut iter = seq.bytes().map(Ok);
+            parse_event(b'\x1B', &mut iter).unwrap()
+        };
+        assert_eq!(parse("[12;40R"), Event::CursorPos(40, 12));
+        assert_eq!(parse("[300;1R"), Event::CursorPos(1, 300));
+        assert_eq!(parse("[1;20R"), Event::CursorPos(20, 1));
+        assert_eq!(
+            parse("[1;5R"),
+            Event::Key(Key::new_mod(KeyCode::F(3), KeyMod::Ctrl))
+        );
+    }
+
+    #[test]
+    fn test_event_parser() {
+        let mut parser = EventParser::new();
+        let mut events = Vec::new();
+        for byte in b"a\x1B[1;5Ab\x1B[<0;3;4M" {
+            events.extend(parser.advance(&[*byte]));
+        }
+        assert_eq!(
+            events,
+            vec![
+                Event::Key(Key::new(KeyCode::Char('a'))),
+                Event::Key(Key::new_mod(KeyCode::Up, KeyMod::Ctrl)),
+                Event::Key(Key::new(KeyCode::Char('b'))),
+                Event::Mouse(MouseEvent::Press(MouseButton::Left, 3, 4), None),
+            ]
+        );
+        assert_eq!(parser.advance(b"\x1B[1;"), vec![]);
+        assert_eq!(parser.pending(), b"\x1B[1;");
+        assert_eq!(
+            parser.flush(),
+            vec![Event::Unsupported(b"\x1B[1;".to_vec())]
+        );
+        assert_eq!(parser.pending(), b"");
+        assert_eq!(parser.flush(), vec![]);
+    }
+
+    #[test]
+    fn test_parse_event_error() {
+        let err = io::Error::from(ParseEventError::TruncatedSequence);
+        assert_eq!(err.kind(), ErrorKind::UnexpectedEof);
+        assert_eq!(
+            ParseEventError::from_io(&err),
+            Some(ParseEventError::TruncatedSequence)
+        );
+        let err = io::Error::from(ParseEventError::UnknownCsi);
+        assert_eq!(err.kind(), ErrorKind::InvalidData);
+        assert_eq!(
+            ParseEventError::from_io(&err),
+            Some(ParseEventError::UnknownCsi)
+        );
+        let err = io::Error::new(ErrorKind::Other, "other");
+        assert_eq!(ParseEventError::from_io(&err), None);
+    }
+
+    #[test]
+    fn test_parse_truncated() {
+        for seq in ["[<0;12;3", "[<", "[12;5", "[1"].iter() {
+            let mut iter = seq.bytes().map(|x| Ok(x));
+            let mut expected = vec![b'\x1B'];
+            expected.extend_from_slice(seq.as_bytes());
+            assert_eq!(
+                parse_event(b'\x1B', &mut iter).unwrap(),
+                Event::Unsupported(expected),
+            );
+        }
+
+        // A read that times out in the middle of a sequence.
+        let mut iter = b"[<0;1"
+            .iter()
+            .map(|x| Ok(*x))
+            .chain(std::iter::repeat_with(|| {
+                Err(Error::new(ErrorKind::WouldBlock, "timed out"))
+            }));
+        assert_eq!(
+            parse_event(b'\x1B', &mut iter).unwrap(),
+            Event::Unsupported(b"\x1B[<0;1".to_vec()),
+        );
+    }
+
     #[test]
     fn test_parse_libtickit_ascii() {
         let csi_sequences = vec![b'\x1b', b'\x9b'];
-        let mod_map = HashMap::<_, _>::from_iter(IntoIter::new([
-            ("6", KeyMod::CtrlShift),
-            ("8", KeyMod::AltCtrlShift),
-        ]));
+        let mod_map = HashMap::from([("6", KeyMod::CtrlShift), ("8", KeyMod::AltCtrlShift)]);
         let mut upper_letters = HashMap::new();
         for n in 65..91 {
             upper_letters.insert(format!("{}", n), KeyCode::Char((n as u8) as char));
@@ -1112,7 +3001,7 @@ mod test {
     #[test]
     fn test_parse_libtickit_special() {
         let csi_sequences = vec![b'\x1b', b'\x9b'];
-        let mod_map = HashMap::<_, _>::from_iter(IntoIter::new([
+        let mod_map = HashMap::from([
             ("2", KeyMod::Shift),
             ("3", KeyMod::Alt),
             ("4", KeyMod::AltShift),
@@ -1120,7 +3009,7 @@ mod test {
             ("6", KeyMod::CtrlShift),
             ("7", KeyMod::AltCtrl),
             ("8", KeyMod::AltCtrlShift),
-        ]));
+        ]);
         let mut special_key_codes = HashMap::new();
         special_key_codes.insert("27", KeyCode::Esc);
         special_key_codes.insert("127", KeyCode::Backspace);
@@ -1137,4 +3026,130 @@ mod test {
             test_parse_event_dynamic(*item, &mut map);
         }
     }
+
+    #[test]
+    fn test_parse_kitty_keys() {
+        let key = |seq: &str| {
+            let mut iter = seq.bytes().map(Ok);
+            parse_event(b'\x1B', &mut iter).unwrap()
+        };
+        let mut ctrl_z = Key::new_mod(KeyCode::Char('z'), KeyMod::Ctrl);
+        ctrl_z.alternate = Some(KeyCode::Char('y'));
+        assert_eq!(key("[122::121;5u"), Event::Key(ctrl_z));
+        assert_eq!(ctrl_z.layout_code(), KeyCode::Char('y'));
+        assert_eq!(
+            key("[97::97;5u"),
+            Event::Key(Key::new_mod(KeyCode::Char('a'), KeyMod::Ctrl))
+        );
+        assert_eq!(key("[1078u"), Event::Key(Key::new(KeyCode::Char('ж'))));
+        assert_eq!(key("[57376u"), Event::Key(Key::new(KeyCode::F(13))));
+        assert_eq!(key("[57387u"), Event::Key(Key::new(KeyCode::F(24))));
+        assert_eq!(key("[57399u"), Event::Key(Key::new(KeyCode::Kp('0'))));
+        assert_eq!(
+            key("[57413;5u"),
+            Event::Key(Key::new_mod(KeyCode::Kp('+'), KeyMod::Ctrl))
+        );
+        assert_eq!(key("[57414u"), Event::Key(Key::new(KeyCode::KpEnter)));
+        assert_eq!(
+            key("[97;9u"),
+            Event::Key(Key::new_mod(KeyCode::Char('a'), KeyMod::Super))
+        );
+        // Caps Lock is ignored.
+        let mods = KeyMod::Ctrl | KeyMod::Hyper;
+        assert_eq!(
+            key("[97;85u"),
+            Event::Key(Key::new_mod(KeyCode::Char('a'), mods))
+        );
+        assert_eq!(format!("{:?}", mods), "Ctrl+Hyper");
+        assert!(mods.contains(KeyMod::Ctrl));
+        assert!(!mods.contains(KeyMod::AltCtrl));
+        assert_eq!(mods.without(KeyMod::Hyper), Some(KeyMod::Ctrl));
+    }
+
+    #[test]
+    fn test_parse_modify_other_keys() {
+        let key = |seq: &str| {
+            let mut iter = seq.bytes().map(Ok);
+            parse_event(b'\x1B', &mut iter).unwrap()
+        };
+        assert_eq!(
+            key("[27;5;44~"),
+            Event::Key(Key::new_mod(KeyCode::Char(','), KeyMod::Ctrl))
+        );
+        assert_eq!(
+            key("[27;6;1078~"),
+            Event::Key(Key::new_mod(KeyCode::Char('ж'), KeyMod::CtrlShift))
+        );
+        assert_eq!(
+            key("[27;3;27~"),
+            Event::Key(Key::new_mod(KeyCode::Esc, KeyMod::Alt))
+        );
+        assert_eq!(key("[27;5~"), Event::Unsupported(b"\x1B[27;5~".to_vec()));
+        let mut repeat = Key::new_mod(KeyCode::Char('a'), KeyMod::Shift);
+        repeat.kind = KeyEventKind::Repeat;
+        assert_eq!(key("[97;2:2u"), Event::Key(repeat));
+        let mut release = Key::new(KeyCode::Char('a'));
+        release.kind = KeyEventKind::Release;
+        assert_eq!(key("[97;1:3u"), Event::Key(release));
+        assert_eq!(
+            key("[97;1:4u"),
+            Event::Unsupported(b"\x1B[97;1:4u".to_vec())
+        );
+    }
+
+    #[test]
+    fn test_parse_events() {
+        let (events, used) = parse_events(b"ab\x1B[<0;3;4M\x1B");
+        assert_eq!(events.len(), 3);
+        assert_eq!(used, 11);
+        assert_eq!(parse_events(b""), (Vec::new(), 0));
+        assert_eq!(parse_events(b"\x1B[1;5").1, 0);
+        // Invalid UTF-8 is reported, not left unused.
+        let (events, used) = parse_events(b"\xFFa");
+        assert_eq!(events.len(), 2);
+        assert_eq!(used, 2);
+
+        let options = ParseOptions {
+            resync: true,
+            ..Default::default()
+        };
+        let (events, used) = parse_events_opts(b"\xFF\x01\x02\x9Ea\x1B[\x00\x03", &options);
+        assert_eq!(
+            events,
+            vec![
+                Event::Garbled(b"\xFF\x01\x02\x9E".to_vec()),
+                Event::Key(Key::new(KeyCode::Char('a'))),
+                Event::Garbled(b"\x1B[\x00\x03".to_vec()),
+            ]
+        );
+        assert_eq!(used, 9);
+    }
+
+    #[test]
+    fn test_parse_events_arbitrary() {
+        // Bytes that start or shape sequences are more likely than others.
+        const INTERESTING: &[u8] =
+            b"\x1B[O]P\\;:<>?0123456789~uMmRtc\x07\x9B\x8F\x9D\x90\xC3\xE2\xF0\x80";
+        // xorshift, deterministic so failures can be reproduced.
+        let mut state = 0x2545_F491_4F6C_DD1Du64;
+        let mut next = move || {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            state
+        };
+        for _ in 0..20_000 {
+            let len = (next() % 24) as usize;
+            let bytes: Vec<u8> = (0..len)
+                .map(|_| match next() % 3 {
+                    0 => next() as u8,
+                    _ => INTERESTING[(next() % INTERESTING.len() as u64) as usize],
+                })
+                .collect();
+            let (events, used) = parse_events(&bytes);
+            assert!(used <= bytes.len(), "{:?}", bytes);
+            // The used bytes parse to the same events on their own.
+            assert_eq!(parse_events(&bytes[..used]), (events, used), "{:?}", bytes);
+        }
+    }
 }
@@ -0,0 +1,225 @@
+//! Frame rate limited output.
+//!
+//! A [`FrameLimiter`] buffers output and writes it at most a set number of
+//! times per second, so an application that redraws faster than the
+//! connection can carry (a slow SSH link for instance) does not fall further
+//! and further behind.  Frames given to [`FrameLimiter::present`] replace
+//! any frame still waiting, only the latest is drawn (as a [`diff`] against
+//! the last frame drawn), so the intermediate frames are never sent.
+//!
+//! # Example
+//!
+//! ```rust,no_run
+//! use sl_console::frame::FrameLimiter;
+//! use sl_console::grid::{CellStyle, Grid};
+//! use sl_console::*;
+//!
+//!     con_init().unwrap();
+//!     let mut out = FrameLimiter::new(conout(), 30);
+//!     let mut grid = Grid::new(20, 1);
+//!     for i in 0..1000 {
+//!         grid.put_str(0, 0, &i.to_string(), CellStyle::default());
+//!         out.present(&grid).unwrap();
+//!     }
+//!     // Draw the last frame even if it is early.
+//!     out.flush_now().unwrap();
+//! ```
+
+use std::io::{self, Write};
+use std::ops;
+use std::time::{Duration, Instant};
+
+use crate::clock::{Clock, SYSTEM_CLOCK};
+use crate::console::ConsoleWrite;
+use crate::grid::{apply_patches, diff, Grid};
+
+/// A writer that flushes at most a set number of times per second.
+pub struct FrameLimiter<W: Write> {
+    inner: W,
+    interval: Duration,
+    clock: &'static dyn Clock,
+    // When output was last written to inner.
+    last: Option<Instant>,
+    buf: Vec<u8>,
+    // The frame on the screen and the frame waiting to be drawn.
+    shown: Option<Grid>,
+    frame: Option<Grid>,
+}
+
+impl<W: Write> FrameLimiter<W> {
+    /// Limit the output to `inner` to `fps` frames per second, 0 does not
+    /// limit it.
+    pub fn new(inner: W, fps: u32) -> Self {
+        FrameLimiter::with_clock(inner, fps, &SYSTEM_CLOCK)
+    }
+
+    pub(crate) fn with_clock(inner: W, fps: u32, clock: &'static dyn Clock) -> Self {
+        let interval = match fps {
+            0 => Duration::ZERO,
+            fps => Duration::from_secs(1) / fps,
+        };
+        FrameLimiter {
+            inner,
+            interval,
+            clock,
+            last: None,
+            buf: Vec::new(),
+            shown: None,
+            frame: None,
+        }
+    }
+
+    /// Return the wrapped writer, output not yet written is dropped.
+    pub fn into_inner(self) -> W {
+        self.inner
+    }
+
+    /// Queue `grid` to be drawn, replacing a queued frame that has not been
+    /// drawn yet, and draw it if the next frame is due.
+    ///
+    /// Returns true if the frame was written.
+    pub fn present(&mut self, grid: &Grid) -> io::Result<bool> {
+        self.frame = Some(grid.clone());
+        self.poll()
+    }
+
+    /// Forget what is on the screen so the next frame is drawn in full, for
+    /// instance after the screen was cleared or resized.
+    pub fn invalidate(&mut self) {
+        self.shown = None;
+    }
+
+    /// True if there is output waiting to be written.
+    pub fn is_pending(&self) -> bool {
+        !self.buf.is_empty() || self.frame.is_some()
+    }
+
+    /// The time until waiting output can be written, None if nothing is
+    /// waiting.
+    ///
+    /// Event loops can use this as the timeout when waiting for input and
+    /// call [`poll`](FrameLimiter::poll) when it expires.
+    pub fn until_next(&self) -> Option<Duration> {
+        if !self.is_pending() {
+            return None;
+        }
+        Some(match self.last {
+            Some(last) => (last + self.interval).saturating_duration_since(self.clock.now()),
+            None => Duration::ZERO,
+        })
+    }
+
+    /// Write the waiting output if the next frame is due.
+    ///
+    /// Returns true if anything was written.
+    pub fn poll(&mut self) -> io::Result<bool> {
+        if self.until_next() == Some(Duration::ZERO) {
+            self.flush_now()?;
+            Ok(true)
+        } else {
+            Ok(false)
+        }
+    }
+
+    /// Write the waiting output now, even if the next frame is not due.
+    pub fn flush_now(&mut self) -> io::Result<()> {
+        if let Some(frame) = self.frame.take() {
+            // A grid of a different size draws every row.
+            let blank = Grid::new(0, 0);
+            let shown = self.shown.as_ref().unwrap_or(&blank);
+            apply_patches(&mut self.buf, &diff(shown, &frame))?;
+            self.shown = Some(frame);
+        }
+        if !self.buf.is_empty() {
+            self.inner.write_all(&self.buf)?;
+            self.buf.clear();
+        }
+        self.last = Some(self.clock.now());
+        self.inner.flush()
+    }
+}
+
+impl<W: Write> ops::Deref for FrameLimiter<W> {
+    type Target = W;
+
+    fn deref(&self) -> &W {
+        &self.inner
+    }
+}
+
+impl<W: Write> ops::DerefMut for FrameLimiter<W> {
+    fn deref_mut(&mut self) -> &mut W {
+        &mut self.inner
+    }
+}
+
+impl<W: Write> Write for FrameLimiter<W> {
+    /// Buffer `buf`, it is written by the next flush that is due.
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.buf.extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    /// Write the waiting output if the next frame is due, otherwise keep
+    /// it for a later flush or [`poll`](FrameLimiter::poll).
+    fn flush(&mut self) -> io::Result<()> {
+        self.poll().map(|_| ())
+    }
+}
+
+impl<W: ConsoleWrite> ConsoleWrite for FrameLimiter<W> {
+    fn set_raw_mode(&mut self, mode: bool) -> io::Result<bool> {
+        self.inner.set_raw_mode(mode)
+    }
+
+    fn is_raw_mode(&self) -> bool {
+        self.inner.is_raw_mode()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::clock::TestClock;
+    use crate::grid::CellStyle;
+    use crate::headless::Headless;
+
+    #[test]
+    fn test_frame_limiter() {
+        let clock: &'static TestClock = Box::leak(Box::new(TestClock::new()));
+        let mut out = FrameLimiter::with_clock(Headless::new(4, 1), 10, clock);
+        let mut grid = Grid::new(4, 1);
+        grid.put_str(0, 0, "a", CellStyle::default());
+        assert!(out.present(&grid).unwrap());
+        assert_eq!(out.grid().snapshot(), "\"a\"\n");
+
+        // Too soon, only the last frame is kept.
+        clock.advance(Duration::from_millis(40));
+        grid.put_str(1, 0, "b", CellStyle::default());
+        assert!(!out.present(&grid).unwrap());
+        grid.put_str(2, 0, "c", CellStyle::default());
+        assert!(!out.present(&grid).unwrap());
+        write!(out, "\x1B[1;4Hd").unwrap();
+        out.flush().unwrap();
+        assert_eq!(out.grid().snapshot(), "\"a\"\n");
+        assert_eq!(out.until_next(), Some(Duration::from_millis(60)));
+
+        clock.advance(Duration::from_millis(60));
+        assert!(out.poll().unwrap());
+        assert_eq!(out.grid().snapshot(), "\"abcd\"\n");
+        assert_eq!(out.until_next(), None);
+        assert!(!out.poll().unwrap());
+    }
+
+    #[test]
+    fn test_frame_limiter_diff() {
+        let mut out = FrameLimiter::new(Vec::new(), 0);
+        let mut grid = Grid::new(3, 1);
+        grid.put_str(0, 0, "abc", CellStyle::default());
+        out.present(&grid).unwrap();
+        out.clear();
+        grid.put_str(2, 0, "x", CellStyle::default());
+        out.present(&grid).unwrap();
+        assert_eq!(out.as_slice(), b"\x1B[1;3H\x1b[0mx\x1b[0m");
+    }
+}
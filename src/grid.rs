@@ -0,0 +1,647 @@
+//! Screen buffers and frame diffing.
+//!
+//! A [`Grid`] is a rectangle of styled [`Cell`]s.  [`diff`] compares two
+//! frames and returns the [`Patch`]es needed to turn the first into the
+//! second and [`apply_patches`] writes them with as few escape sequences as
+//! it can (cursor moves are skipped when the cursor is already in place and
+//! only the style changes between cells are written).  The patches are
+//! plain data so code with its own buffer type can build them directly and
+//! still use [`apply_patches`].
+//!
+//! Grid and patch coordinates are zero based, (0, 0) is the top left cell.
+//!
+//! # Example
+//!
+//! ```rust,no_run
+//! use sl_console::grid::{apply_patches, diff, CellStyle, Grid};
+//! use sl_console::*;
+//!
+//!     con_init().unwrap();
+//!     let prev = Grid::new(20, 2);
+//!     let mut next = prev.clone();
+//!     next.put_str(0, 0, "Hello", CellStyle::default());
+//!     apply_patches(&mut conout(), &diff(&prev, &next)).unwrap();
+//! ```
+
+use std::io::{self, Write};
+use std::ops;
+
+use crate::color::Rgb;
+use crate::cursor::Origin;
+use crate::headless::Headless;
+use crate::width::char_width;
+
+/// Equal cells between two changed runs shorter than this are rewritten
+/// instead of moving the cursor over them.
+const MERGE_GAP: usize = 4;
+
+/// Text attributes of a cell, combine with `|`.
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq, Hash)]
+pub struct Attrs(u16);
+
+impl Attrs {
+    /// No attributes.
+    pub const NONE: Attrs = Attrs(0);
+    /// Bold text.
+    pub const BOLD: Attrs = Attrs(1);
+    /// Faint text.
+    pub const FAINT: Attrs = Attrs(1 << 1);
+    /// Italic text.
+    pub const ITALIC: Attrs = Attrs(1 << 2);
+    /// Underlined text.
+    pub const UNDERLINE: Attrs = Attrs(1 << 3);
+    /// Blinking text.
+    pub const BLINK: Attrs = Attrs(1 << 4);
+    /// Inverted colors.
+    pub const INVERT: Attrs = Attrs(1 << 5);
+    /// Crossed out text.
+    pub const CROSSED_OUT: Attrs = Attrs(1 << 6);
+    /// Hidden text.
+    pub const CONCEAL: Attrs = Attrs(1 << 7);
+    /// Overlined text.
+    pub const OVERLINE: Attrs = Attrs(1 << 8);
+
+    /// The SGR parameters to turn each attribute on and off.
+    const SGR: [(Attrs, u8, u8); 9] = [
+        (Attrs::BOLD, 1, 22),
+        (Attrs::FAINT, 2, 22),
+        (Attrs::ITALIC, 3, 23),
+        (Attrs::UNDERLINE, 4, 24),
+        (Attrs::BLINK, 5, 25),
+        (Attrs::INVERT, 7, 27),
+        (Attrs::CROSSED_OUT, 9, 29),
+        (Attrs::CONCEAL, 8, 28),
+        (Attrs::OVERLINE, 53, 55),
+    ];
+
+    /// The snapshot name of each attribute.
+    const NAMES: [(Attrs, &'static str); 9] = [
+        (Attrs::BOLD, "bold"),
+        (Attrs::FAINT, "faint"),
+        (Attrs::ITALIC, "italic"),
+        (Attrs::UNDERLINE, "underline"),
+        (Attrs::BLINK, "blink"),
+        (Attrs::INVERT, "invert"),
+        (Attrs::CROSSED_OUT, "crossed_out"),
+        (Attrs::CONCEAL, "conceal"),
+        (Attrs::OVERLINE, "overline"),
+    ];
+
+    /// True if all the attributes in `other` are set.
+    pub fn contains(self, other: Attrs) -> bool {
+        self.0 & other.0 == other.0
+    }
+
+    /// True if no attributes are set.
+    pub fn is_empty(self) -> bool {
+        self.0 == 0
+    }
+
+    /// Turn off the attributes in `other`.
+    pub fn remove(&mut self, other: Attrs) {
+        self.0 &= !other.0;
+    }
+}
+
+impl ops::BitOr for Attrs {
+    type Output = Attrs;
+
+    fn bitor(self, rhs: Attrs) -> Attrs {
+        Attrs(self.0 | rhs.0)
+    }
+}
+
+impl ops::BitOrAssign for Attrs {
+    fn bitor_assign(&mut self, rhs: Attrs) {
+        self.0 |= rhs.0;
+    }
+}
+
+/// The color of a cell.
+#[derive(Debug, Copy, Clone, Default, PartialEq)]
+pub enum CellColor {
+    /// The terminal default.
+    #[default]
+    Default,
+    /// A 256 color palette index.
+    Ansi(u8),
+    /// A 24 bit color.
+    Rgb(Rgb),
+}
+
+impl CellColor {
+    /// Append the SGR parameters for this color, `fg` selects foreground
+    /// or background.
+    fn push_sgr(self, fg: bool, params: &mut Vec<String>) {
+        let base = if fg { 38 } else { 48 };
+        params.push(match self {
+            CellColor::Default => (base + 1).to_string(),
+            CellColor::Ansi(i) => format!("{};5;{}", base, i),
+            CellColor::Rgb(Rgb(r, g, b)) => format!("{};2;{};{};{}", base, r, g, b),
+        });
+    }
+
+    /// The color as written in a snapshot.
+    fn snapshot(self) -> String {
+        match self {
+            CellColor::Default => "default".to_string(),
+            CellColor::Ansi(i) => i.to_string(),
+            CellColor::Rgb(Rgb(r, g, b)) => format!("#{:02x}{:02x}{:02x}", r, g, b),
+        }
+    }
+}
+
+/// The colors and attributes of a cell.
+#[derive(Debug, Copy, Clone, Default, PartialEq)]
+pub struct CellStyle {
+    /// Foreground color.
+    pub fg: CellColor,
+    /// Background color.
+    pub bg: CellColor,
+    /// Text attributes.
+    pub attrs: Attrs,
+}
+
+impl CellStyle {
+    /// The SGR sequence to switch from `prev` (None if unknown) to this
+    /// style, empty if nothing changes.
+    ///
+    /// Attributes are turned off one at a time unless a full reset is
+    /// shorter.
+    pub(crate) fn transition(&self, prev: Option<&CellStyle>) -> String {
+        match prev {
+            Some(prev) if prev == self => String::new(),
+            Some(prev) => {
+                let reset = self.sgr_from(None);
+                let update = self.sgr_from(Some(prev));
+                if update.len() < reset.len() {
+                    update
+                } else {
+                    reset
+                }
+            }
+            None => self.sgr_from(None),
+        }
+    }
+
+    /// The SGR sequence to switch from `prev` to this style, None starts
+    /// with a reset.
+    fn sgr_from(&self, prev: Option<&CellStyle>) -> String {
+        let mut params = Vec::new();
+        let mut prev = match prev {
+            Some(prev) => *prev,
+            None => {
+                params.push("0".to_string());
+                CellStyle::default()
+            }
+        };
+        for (attr, _, off) in Attrs::SGR.iter() {
+            if prev.attrs.contains(*attr) && !self.attrs.contains(*attr) {
+                if !params.contains(&off.to_string()) {
+                    params.push(off.to_string());
+                }
+                // Normal intensity turns off both bold and faint.
+                if *off == 22 {
+                    prev.attrs.remove(Attrs::BOLD | Attrs::FAINT);
+                }
+                prev.attrs.remove(*attr);
+            }
+        }
+        for (attr, on, _) in Attrs::SGR.iter() {
+            if self.attrs.contains(*attr) && !prev.attrs.contains(*attr) {
+                params.push(on.to_string());
+            }
+        }
+        if self.fg != prev.fg {
+            self.fg.push_sgr(true, &mut params);
+        }
+        if self.bg != prev.bg {
+            self.bg.push_sgr(false, &mut params);
+        }
+        format!("\x1b[{}m", params.join(";"))
+    }
+
+    /// The style as written in a snapshot, for example `bold fg=1`.
+    fn snapshot(&self) -> String {
+        let mut parts: Vec<String> = Attrs::NAMES
+            .iter()
+            .filter(|(attr, _)| self.attrs.contains(*attr))
+            .map(|(_, name)| name.to_string())
+            .collect();
+        if self.fg != CellColor::Default {
+            parts.push(format!("fg={}", self.fg.snapshot()));
+        }
+        if self.bg != CellColor::Default {
+            parts.push(format!("bg={}", self.bg.snapshot()));
+        }
+        parts.join(" ")
+    }
+}
+
+/// One character cell of a grid.
+///
+/// A wide character is followed by a continuation cell (`ch` is
+/// [`Cell::CONTINUATION`]) that it covers on the screen.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Cell {
+    /// The character displayed.
+    pub ch: char,
+    /// The style of the character.
+    pub style: CellStyle,
+}
+
+impl Default for Cell {
+    /// A blank cell.
+    fn default() -> Self {
+        Cell {
+            ch: ' ',
+            style: CellStyle::default(),
+        }
+    }
+}
+
+impl Cell {
+    /// The character of the cell covered by a wide character.
+    pub const CONTINUATION: char = '\0';
+
+    /// A cell with `ch` in `style`.
+    pub fn new(ch: char, style: CellStyle) -> Self {
+        Cell { ch, style }
+    }
+}
+
+/// A rectangle of cells, one frame of a screen.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Grid {
+    width: u16,
+    height: u16,
+    cells: Vec<Cell>,
+}
+
+impl Grid {
+    /// A `width` by `height` grid of blank cells.
+    pub fn new(width: u16, height: u16) -> Self {
+        Grid {
+            width,
+            height,
+            cells: vec![Cell::default(); width as usize * height as usize],
+        }
+    }
+
+    /// The width in cells.
+    pub fn width(&self) -> u16 {
+        self.width
+    }
+
+    /// The height in cells.
+    pub fn height(&self) -> u16 {
+        self.height
+    }
+
+    /// The cell at (x, y), None if outside the grid.
+    pub fn get(&self, x: u16, y: u16) -> Option<&Cell> {
+        self.index(x, y).map(|i| &self.cells[i])
+    }
+
+    /// Set the cell at (x, y), ignored if outside the grid.
+    pub fn set(&mut self, x: u16, y: u16, cell: Cell) {
+        if let Some(i) = self.index(x, y) {
+            self.cells[i] = cell;
+        }
+    }
+
+    /// Write `text` starting at (x, y) in `style`, clipped at the end of
+    /// the row.
+    ///
+    /// Character widths follow the [`WidthPolicy`](crate::width::WidthPolicy),
+    /// zero width characters are skipped.  Returns the column after the
+    /// text.
+    pub fn put_str(&mut self, x: u16, y: u16, text: &str, style: CellStyle) -> u16 {
+        let mut x = x;
+        for ch in text.chars() {
+            let width = char_width(ch) as u16;
+            if width == 0 {
+                continue;
+            }
+            if x as u32 + width as u32 > self.width as u32 {
+                break;
+            }
+            // Do not leave half of a wide character behind.
+            if self.get(x, y).map(|c| c.ch) == Some(Cell::CONTINUATION) && x > 0 {
+                self.set(x - 1, y, Cell::default());
+            }
+            let end = x + width;
+            if self.get(end, y).map(|c| c.ch) == Some(Cell::CONTINUATION) {
+                self.set(end, y, Cell::default());
+            }
+            self.set(x, y, Cell::new(ch, style));
+            if width == 2 {
+                self.set(x + 1, y, Cell::new(Cell::CONTINUATION, style));
+            }
+            x = end;
+        }
+        x
+    }
+
+    /// Reset every cell to blank.
+    pub fn clear(&mut self) {
+        for cell in self.cells.iter_mut() {
+            *cell = Cell::default();
+        }
+    }
+
+    /// The cells of row `y`.
+    pub fn row(&self, y: u16) -> &[Cell] {
+        let start = y as usize * self.width as usize;
+        &self.cells[start..start + self.width as usize]
+    }
+
+    /// A text picture of the grid for comparing frames in tests.
+    ///
+    /// Each row is written in double quotes with trailing blanks removed.
+    /// Rows that have styled cells are followed by one indented line per run
+    /// of equally styled cells, giving the columns (zero based, end
+    /// exclusive) and the style, for example:
+    ///
+    /// ```text
+    /// "Hello world"
+    ///     0..5: bold fg=1
+    ///     6..11: bg=#102030
+    /// ```
+    ///
+    /// Attributes are named `bold`, `faint`, `italic`, `underline`,
+    /// `blink`, `invert`, `crossed_out`, `conceal` and `overline`, colors
+    /// are a palette index or `#rrggbb`.  See
+    /// [`assert_frame_eq!`](crate::assert_frame_eq).
+    pub fn snapshot(&self) -> String {
+        let mut out = String::new();
+        for y in 0..self.height {
+            let row = self.row(y);
+            let text: String = row
+                .iter()
+                .map(|cell| cell.ch)
+                .filter(|ch| *ch != Cell::CONTINUATION)
+                .collect();
+            out.push_str(&format!("\"{}\"\n", text.trim_end_matches(' ')));
+            let mut x = 0;
+            while x < row.len() {
+                let style = row[x].style;
+                let end = row[x..]
+                    .iter()
+                    .position(|cell| cell.style != style)
+                    .map_or(row.len(), |len| x + len);
+                if style != CellStyle::default() {
+                    out.push_str(&format!("    {}..{}: {}\n", x, end, style.snapshot()));
+                }
+                x = end;
+            }
+        }
+        out
+    }
+
+    fn index(&self, x: u16, y: u16) -> Option<usize> {
+        if x < self.width && y < self.height {
+            Some(y as usize * self.width as usize + x as usize)
+        } else {
+            None
+        }
+    }
+}
+
+/// A run of cells to write on one row.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Patch {
+    /// Column of the first cell.
+    pub x: u16,
+    /// Row of the cells.
+    pub y: u16,
+    /// The cells to write from (x, y) to the right.
+    pub cells: Vec<Cell>,
+}
+
+/// The patches that turn the screen showing `prev` into `next`.
+///
+/// If the grids are different sizes every row of `next` is returned.
+/// Changed runs separated by only a few unchanged cells are merged into
+/// one patch since rewriting the cells is shorter than moving the cursor.
+pub fn diff(prev: &Grid, next: &Grid) -> Vec<Patch> {
+    let mut patches = Vec::new();
+    let same_size = prev.width == next.width && prev.height == next.height;
+    for y in 0..next.height {
+        let row = next.row(y);
+        if !same_size {
+            patches.push(Patch {
+                x: 0,
+                y,
+                cells: row.to_vec(),
+            });
+            continue;
+        }
+        let old = prev.row(y);
+        let mut run: Option<(usize, usize)> = None;
+        for x in 0..row.len() {
+            if row[x] == old[x] {
+                continue;
+            }
+            // A change to a continuation cell has to redraw the wide
+            // character that covers it.
+            let first = if row[x].ch == Cell::CONTINUATION && x > 0 {
+                x - 1
+            } else {
+                x
+            };
+            run = match run {
+                Some((start, end)) if first <= end + MERGE_GAP => Some((start, x + 1)),
+                Some((start, end)) => {
+                    patches.push(Patch {
+                        x: start as u16,
+                        y,
+                        cells: row[start..end].to_vec(),
+                    });
+                    Some((first, x + 1))
+                }
+                None => Some((first, x + 1)),
+            };
+        }
+        if let Some((start, end)) = run {
+            patches.push(Patch {
+                x: start as u16,
+                y,
+                cells: row[start..end].to_vec(),
+            });
+        }
+    }
+    patches
+}
+
+/// Write `patches` to `out`.
+///
+/// The style is reset before the first cell and after the last one.  `out`
+/// is not flushed.
+pub fn apply_patches<W: Write>(out: &mut W, patches: &[Patch]) -> io::Result<()> {
+    let mut buf = String::new();
+    let mut style: Option<CellStyle> = None;
+    let mut cursor: Option<(u16, u16)> = None;
+    for patch in patches {
+        if patch.cells.is_empty() {
+            continue;
+        }
+        if cursor != Some((patch.x, patch.y)) {
+            // The grid is zero based whatever the origin is.
+            let (col, row) = Origin::Zero.to_terminal(patch.x, patch.y);
+            buf.push_str(&format!("\x1B[{};{}H", row, col));
+        }
+        for cell in &patch.cells {
+            // Already covered by the wide character before it.
+            if cell.ch == Cell::CONTINUATION {
+                continue;
+            }
+            buf.push_str(&cell.style.transition(style.as_ref()));
+            style = Some(cell.style);
+            buf.push(cell.ch);
+        }
+        cursor = Some((patch.x + patch.cells.len() as u16, patch.y));
+    }
+    if style.is_some() {
+        buf.push_str("\x1b[0m");
+    }
+    out.write_all(buf.as_bytes())
+}
+
+/// Draw `grid` on a blank [`Headless`] terminal of the same size with
+/// [`diff`] and [`apply_patches`] and return what the terminal shows.
+pub fn render_headless(grid: &Grid) -> Grid {
+    let blank = Grid::new(grid.width, grid.height);
+    let mut term = Headless::new(grid.width, grid.height);
+    apply_patches(&mut term, &diff(&blank, grid)).expect("write to a headless terminal");
+    term.into_grid()
+}
+
+/// Trim every line of a snapshot and drop the empty ones, so expected
+/// snapshots can be indented in the test source.
+pub fn normalize_snapshot(snapshot: &str) -> String {
+    snapshot
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty())
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn apply(patches: &[Patch]) -> String {
+        let mut out = Vec::new();
+        apply_patches(&mut out, patches).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn test_diff() {
+        let prev = Grid::new(20, 2);
+        let mut next = prev.clone();
+        assert!(diff(&prev, &next).is_empty());
+
+        next.put_str(0, 0, "ab", CellStyle::default());
+        next.put_str(5, 0, "c", CellStyle::default());
+        next.put_str(15, 0, "d", CellStyle::default());
+        next.put_str(18, 1, "xyz", CellStyle::default());
+        let patches = diff(&prev, &next);
+        assert_eq!(patches.len(), 3);
+        assert_eq!((patches[0].x, patches[0].y), (0, 0));
+        assert_eq!(patches[0].cells.len(), 6);
+        assert_eq!((patches[1].x, patches[1].cells.len()), (15, 1));
+        assert_eq!((patches[2].x, patches[2].y), (18, 1));
+        assert_eq!(patches[2].cells.len(), 2);
+
+        let mut wide = next.clone();
+        assert_eq!(wide.put_str(0, 1, "漢字", CellStyle::default()), 4);
+        assert_eq!(wide.get(1, 1).unwrap().ch, Cell::CONTINUATION);
+        let mut changed = wide.clone();
+        let bold = CellStyle {
+            attrs: Attrs::BOLD,
+            ..Default::default()
+        };
+        changed.set(3, 1, Cell::new(Cell::CONTINUATION, bold));
+        let patches = diff(&wide, &changed);
+        assert_eq!((patches[0].x, patches[0].cells.len()), (2, 2));
+        // Writing over half of a wide character blanks the other half.
+        changed.put_str(1, 1, "y", CellStyle::default());
+        assert_eq!(changed.get(0, 1).unwrap().ch, ' ');
+
+        let resized = Grid::new(10, 3);
+        assert_eq!(diff(&prev, &resized).len(), 3);
+    }
+
+    #[test]
+    fn test_apply_patches() {
+        assert_eq!(apply(&[]), "");
+        let bold = CellStyle {
+            attrs: Attrs::BOLD,
+            ..Default::default()
+        };
+        let red = CellStyle {
+            fg: CellColor::Ansi(1),
+            ..bold
+        };
+        let patches = [
+            Patch {
+                x: 0,
+                y: 0,
+                cells: vec![Cell::new('a', bold), Cell::new('b', red)],
+            },
+            Patch {
+                x: 2,
+                y: 0,
+                cells: vec![Cell::new('c', CellStyle::default())],
+            },
+            Patch {
+                x: 4,
+                y: 1,
+                cells: vec![
+                    Cell::new('漢', CellStyle::default()),
+                    Cell::new(Cell::CONTINUATION, CellStyle::default()),
+                    Cell::new('d', CellStyle::default()),
+                ],
+            },
+        ];
+        assert_eq!(
+            apply(&patches),
+            "\x1b[1;1H\x1b[0;1ma\x1b[38;5;1mb\x1b[0mc\x1b[2;5H漢d\x1b[0m"
+        );
+    }
+
+    #[test]
+    fn test_transition() {
+        let style = |attrs| CellStyle {
+            attrs,
+            ..Default::default()
+        };
+        let bold_italic = style(Attrs::BOLD | Attrs::ITALIC);
+        assert_eq!(
+            style(Attrs::ITALIC).transition(Some(&bold_italic)),
+            "\x1b[22m"
+        );
+        assert_eq!(
+            style(Attrs::FAINT | Attrs::ITALIC).transition(Some(&bold_italic)),
+            "\x1b[22;2m"
+        );
+        // A reset is shorter.
+        assert_eq!(
+            CellStyle::default().transition(Some(&bold_italic)),
+            "\x1b[0m"
+        );
+        let red = CellStyle {
+            fg: CellColor::Ansi(1),
+            ..bold_italic
+        };
+        let red_bold = CellStyle {
+            attrs: Attrs::BOLD,
+            ..red
+        };
+        assert_eq!(red_bold.transition(Some(&red)), "\x1b[23m");
+        assert_eq!(style(Attrs::BOLD).transition(Some(&red)), "\x1b[0;1m");
+    }
+}
@@ -0,0 +1,538 @@
+//! A terminal that draws into a grid instead of a screen.
+//!
+//! [`Headless`] implements `Write` and interprets the output the way a
+//! terminal would (text, cursor movement, clears, scrolling regions,
+//! inserted and deleted lines and characters and SGR styles) into a
+//! [`Grid`], so tests can check what rendering code would show without a
+//! terminal.  Sequences it does not know are ignored.
+//!
+//! # Example
+//!
+//! ```rust
+//! use sl_console::headless::Headless;
+//! use sl_console::{cursor, style};
+//! use std::io::Write;
+//!
+//! let mut term = Headless::new(10, 2);
+//! write!(term, "{}{}Hi", cursor::Goto(3, 2), style::Bold).unwrap();
+//! assert_eq!(term.grid().get(2, 1).unwrap().ch, 'H');
+//! assert_eq!(term.cursor(), (4, 1));
+//! ```
+
+use std::io::{self, Write};
+use std::str;
+
+use crate::color::Rgb;
+use crate::debug::{Decoder, Piece};
+use crate::grid::{Attrs, Cell, CellColor, CellStyle, Grid};
+use crate::width::char_width;
+
+/// A headless terminal.
+#[derive(Debug)]
+pub struct Headless {
+    grid: Grid,
+    // The column can be one past the last column until the next character
+    // wraps to the next row.
+    x: u16,
+    y: u16,
+    style: CellStyle,
+    // The scrolling region, first and last row.
+    top: u16,
+    bottom: u16,
+    // The cursor and style saved by ESC 7 or CSI s.
+    saved: (u16, u16, CellStyle),
+    decoder: Decoder,
+    // The start of a UTF-8 character split across writes.
+    partial: Vec<u8>,
+}
+
+impl Headless {
+    /// A blank `width` by `height` terminal with the cursor at the top left.
+    pub fn new(width: u16, height: u16) -> Self {
+        Headless {
+            grid: Grid::new(width, height),
+            x: 0,
+            y: 0,
+            style: CellStyle::default(),
+            top: 0,
+            bottom: height.saturating_sub(1),
+            saved: (0, 0, CellStyle::default()),
+            decoder: Decoder::default(),
+            partial: Vec::new(),
+        }
+    }
+
+    /// Change the size, keeping the top left of the screen.
+    ///
+    /// The scrolling region is reset to the whole screen and the cursor is
+    /// moved inside it.
+    pub fn resize(&mut self, width: u16, height: u16) {
+        let mut grid = Grid::new(width, height);
+        for y in 0..height.min(self.grid.height()) {
+            for x in 0..width.min(self.grid.width()) {
+                grid.set(x, y, self.grid.row(y)[x as usize]);
+            }
+        }
+        self.grid = grid;
+        self.top = 0;
+        self.bottom = height.saturating_sub(1);
+        let (x, y) = (self.x, self.y);
+        self.goto(x, y);
+    }
+
+    /// The screen contents.
+    pub fn grid(&self) -> &Grid {
+        &self.grid
+    }
+
+    /// Return the screen contents.
+    pub fn into_grid(self) -> Grid {
+        self.grid
+    }
+
+    /// The cursor position, zero based.
+    pub fn cursor(&self) -> (u16, u16) {
+        (self.x.min(self.grid.width().saturating_sub(1)), self.y)
+    }
+
+    /// The style new text is written in.
+    pub fn style(&self) -> CellStyle {
+        self.style
+    }
+
+    fn goto(&mut self, x: u16, y: u16) {
+        self.x = x.min(self.grid.width().saturating_sub(1));
+        self.y = y.min(self.grid.height().saturating_sub(1));
+    }
+
+    fn line_feed(&mut self) {
+        if self.y == self.bottom {
+            self.scroll(self.top, self.bottom, 1, true);
+        } else if self.y + 1 < self.grid.height() {
+            self.y += 1;
+        }
+    }
+
+    fn reverse_index(&mut self) {
+        if self.y == self.top {
+            self.scroll(self.top, self.bottom, 1, false);
+        } else {
+            self.y = self.y.saturating_sub(1);
+        }
+    }
+
+    /// Move rows `top..=bottom` `n` rows up (or down), the rows uncovered
+    /// are blanked.
+    fn scroll(&mut self, top: u16, bottom: u16, n: u16, up: bool) {
+        if top > bottom || bottom >= self.grid.height() {
+            return;
+        }
+        let n = n.min(bottom - top + 1);
+        let width = self.grid.width();
+        let rows: Vec<u16> = if up {
+            (top..=bottom).collect()
+        } else {
+            (top..=bottom).rev().collect()
+        };
+        for y in rows {
+            let from = if up {
+                Some(y + n).filter(|from| *from <= bottom)
+            } else {
+                y.checked_sub(n).filter(|from| *from >= top)
+            };
+            match from {
+                Some(from) => {
+                    for x in 0..width {
+                        let cell = self.grid.row(from)[x as usize];
+                        self.grid.set(x, y, cell);
+                    }
+                }
+                None => self.clear_row(y, 0, width),
+            }
+        }
+    }
+
+    /// Insert `n` blank cells at the cursor (or delete `n` cells), moving
+    /// the rest of the row right (or left).
+    fn shift_chars(&mut self, n: u16, insert: bool) {
+        let (x, y) = self.cursor();
+        let width = self.grid.width();
+        let n = n.min(width - x);
+        let row = self.grid.row(y).to_vec();
+        for col in x..width {
+            let from = if insert {
+                col.checked_sub(n).filter(|from| *from >= x)
+            } else {
+                Some(col + n).filter(|from| *from < width)
+            };
+            let cell = from.map_or_else(Cell::default, |from| row[from as usize]);
+            self.grid.set(col, y, cell);
+        }
+    }
+
+    fn clear_row(&mut self, y: u16, from: u16, to: u16) {
+        for x in from..to.min(self.grid.width()) {
+            self.grid.set(x, y, Cell::default());
+        }
+    }
+
+    fn put_char(&mut self, ch: char) {
+        match ch {
+            '\r' => self.x = 0,
+            '\n' => self.line_feed(),
+            '\x08' => self.x = self.x.saturating_sub(1),
+            '\t' => self.x = ((self.x / 8 + 1) * 8).min(self.grid.width().saturating_sub(1)),
+            _ if ch.is_control() => {}
+            _ => {
+                let width = char_width(ch) as u16;
+                if width == 0 {
+                    return;
+                }
+                if self.x + width > self.grid.width() {
+                    self.x = 0;
+                    self.line_feed();
+                }
+                let mut buf = [0; 4];
+                self.x = self
+                    .grid
+                    .put_str(self.x, self.y, ch.encode_utf8(&mut buf), self.style);
+            }
+        }
+    }
+
+    fn text(&mut self, bytes: &[u8]) {
+        let mut bytes = bytes;
+        let joined;
+        if !self.partial.is_empty() {
+            let mut partial = std::mem::take(&mut self.partial);
+            partial.extend_from_slice(bytes);
+            joined = partial;
+            bytes = &joined;
+        }
+        loop {
+            match str::from_utf8(bytes) {
+                Ok(text) => {
+                    text.chars().for_each(|ch| self.put_char(ch));
+                    return;
+                }
+                Err(err) => {
+                    let (valid, rest) = bytes.split_at(err.valid_up_to());
+                    // Checked by from_utf8.
+                    str::from_utf8(valid)
+                        .unwrap_or_default()
+                        .chars()
+                        .for_each(|ch| self.put_char(ch));
+                    match err.error_len() {
+                        Some(len) => {
+                            self.put_char(char::REPLACEMENT_CHARACTER);
+                            bytes = &rest[len..];
+                        }
+                        None => {
+                            self.partial = rest.to_vec();
+                            return;
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    fn sgr(&mut self, params: &[u16]) {
+        if params.is_empty() {
+            self.style = CellStyle::default();
+            return;
+        }
+        let mut iter = params.iter().copied();
+        while let Some(code) = iter.next() {
+            let attrs = &mut self.style.attrs;
+            match code {
+                0 => self.style = CellStyle::default(),
+                1 => *attrs |= Attrs::BOLD,
+                2 => *attrs |= Attrs::FAINT,
+                3 => *attrs |= Attrs::ITALIC,
+                4 => *attrs |= Attrs::UNDERLINE,
+                5 | 6 => *attrs |= Attrs::BLINK,
+                7 => *attrs |= Attrs::INVERT,
+                8 => *attrs |= Attrs::CONCEAL,
+                9 => *attrs |= Attrs::CROSSED_OUT,
+                22 => attrs.remove(Attrs::BOLD | Attrs::FAINT),
+                23 => attrs.remove(Attrs::ITALIC),
+                24 => attrs.remove(Attrs::UNDERLINE),
+                25 => attrs.remove(Attrs::BLINK),
+                27 => attrs.remove(Attrs::INVERT),
+                28 => attrs.remove(Attrs::CONCEAL),
+                29 => attrs.remove(Attrs::CROSSED_OUT),
+                53 => *attrs |= Attrs::OVERLINE,
+                55 => attrs.remove(Attrs::OVERLINE),
+                30..=37 => self.style.fg = CellColor::Ansi((code - 30) as u8),
+                90..=97 => self.style.fg = CellColor::Ansi((code - 90 + 8) as u8),
+                40..=47 => self.style.bg = CellColor::Ansi((code - 40) as u8),
+                100..=107 => self.style.bg = CellColor::Ansi((code - 100 + 8) as u8),
+                39 => self.style.fg = CellColor::Default,
+                49 => self.style.bg = CellColor::Default,
+                38 | 48 => {
+                    let color = match iter.next() {
+                        Some(5) => CellColor::Ansi(iter.next().unwrap_or(0) as u8),
+                        Some(2) => {
+                            let mut next = || iter.next().unwrap_or(0) as u8;
+                            CellColor::Rgb(Rgb(next(), next(), next()))
+                        }
+                        _ => continue,
+                    };
+                    if code == 38 {
+                        self.style.fg = color;
+                    } else {
+                        self.style.bg = color;
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+
+    fn csi(&mut self, seq: &[u8]) {
+        let body = &seq[2..seq.len() - 1];
+        // Private sequences (cursor visibility, modes) do not draw.
+        if body.first().is_some_and(|b| (b'<'..=b'?').contains(b))
+            || body.iter().any(|b| (0x20..=0x2F).contains(b))
+        {
+            return;
+        }
+        let params: Vec<u16> = if body.is_empty() {
+            Vec::new()
+        } else {
+            body.split(|b| *b == b';')
+                .map(|p| {
+                    str::from_utf8(p)
+                        .ok()
+                        .and_then(|p| p.parse().ok())
+                        .unwrap_or(0)
+                })
+                .collect()
+        };
+        // The count or position, 0 and missing mean 1.
+        let arg = |i: usize| params.get(i).copied().filter(|p| *p > 0).unwrap_or(1);
+        let (x, y) = self.cursor();
+        let (width, height) = (self.grid.width(), self.grid.height());
+        let in_region = (self.top..=self.bottom).contains(&y);
+        match seq[seq.len() - 1] {
+            b'H' | b'f' => self.goto(arg(1) - 1, arg(0) - 1),
+            b'A' => self.goto(x, y.saturating_sub(arg(0))),
+            b'B' => self.goto(x, y.saturating_add(arg(0))),
+            b'C' => self.goto(x.saturating_add(arg(0)), y),
+            b'D' => self.goto(x.saturating_sub(arg(0)), y),
+            b'E' => self.goto(0, y.saturating_add(arg(0))),
+            b'F' => self.goto(0, y.saturating_sub(arg(0))),
+            b'G' => self.goto(arg(0) - 1, y),
+            b'd' => self.goto(x, arg(0) - 1),
+            b'r' => {
+                let top = arg(0) - 1;
+                let bottom = params.get(1).copied().filter(|p| *p > 0).unwrap_or(height);
+                // An empty screen has no region to set.
+                let bottom = match bottom.checked_sub(1) {
+                    Some(bottom) => bottom,
+                    None => return,
+                };
+                if top < bottom && bottom < height {
+                    self.top = top;
+                    self.bottom = bottom;
+                    self.goto(0, 0);
+                }
+            }
+            b'S' => self.scroll(self.top, self.bottom, arg(0), true),
+            b'T' => self.scroll(self.top, self.bottom, arg(0), false),
+            b'L' if in_region => {
+                self.scroll(y, self.bottom, arg(0), false);
+                self.x = 0;
+            }
+            b'M' if in_region => {
+                self.scroll(y, self.bottom, arg(0), true);
+                self.x = 0;
+            }
+            b'@' => self.shift_chars(arg(0), true),
+            b'P' => self.shift_chars(arg(0), false),
+            b'X' => self.clear_row(y, x, x.saturating_add(arg(0))),
+            b's' => self.save_cursor(),
+            b'u' => self.restore_cursor(),
+            b'J' => match params.first().copied().unwrap_or(0) {
+                0 => {
+                    self.clear_row(y, x, width);
+                    (y + 1..height).for_each(|row| self.clear_row(row, 0, width));
+                }
+                1 => {
+                    (0..y).for_each(|row| self.clear_row(row, 0, width));
+                    self.clear_row(y, 0, x + 1);
+                }
+                2 | 3 => self.grid.clear(),
+                _ => {}
+            },
+            b'K' => match params.first().copied().unwrap_or(0) {
+                0 => self.clear_row(y, x, width),
+                1 => self.clear_row(y, 0, x + 1),
+                2 => self.clear_row(y, 0, width),
+                _ => {}
+            },
+            b'm' => self.sgr(&params),
+            _ => {}
+        }
+    }
+
+    fn save_cursor(&mut self) {
+        self.saved = (self.x, self.y, self.style);
+    }
+
+    fn restore_cursor(&mut self) {
+        let (x, y, style) = self.saved;
+        self.goto(x, y);
+        self.style = style;
+    }
+
+    /// Interpret an escape sequence that is not a CSI.
+    fn esc(&mut self, seq: &[u8]) {
+        match seq {
+            b"\x1B7" => self.save_cursor(),
+            b"\x1B8" => self.restore_cursor(),
+            b"\x1BD" => self.line_feed(),
+            b"\x1BE" => {
+                self.x = 0;
+                self.line_feed();
+            }
+            b"\x1BM" => self.reverse_index(),
+            b"\x1Bc" => *self = Headless::new(self.grid.width(), self.grid.height()),
+            _ => {}
+        }
+    }
+
+    /// Interpret one piece of output.
+    pub(crate) fn piece(&mut self, piece: Piece) {
+        match piece {
+            Piece::Text(text) => self.text(text),
+            Piece::Escape(seq) if seq.len() > 2 && seq[1] == b'[' => self.csi(seq),
+            Piece::Escape(seq) => self.esc(seq),
+        }
+    }
+}
+
+impl Write for Headless {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let mut decoder = std::mem::take(&mut self.decoder);
+        decoder.feed(buf, |piece| self.piece(piece));
+        self.decoder = decoder;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::color::{self, Bg, Fg, Rgb};
+    use crate::grid::render_headless;
+    use crate::{clear, cursor, style};
+
+    #[test]
+    fn test_headless() {
+        let mut term = Headless::new(6, 3);
+        write!(
+            term,
+            "ab{}{}c{}d{}",
+            Fg(color::Red),
+            style::Bold,
+            style::Reset,
+            Bg(Rgb(1, 2, 3))
+        )
+        .unwrap();
+        // Split in the middle of an escape and of a UTF-8 character.
+        term.write_all(b"\x1B[2;").unwrap();
+        term.write_all(b"3H\xE6\xBC").unwrap();
+        term.write_all(b"\xA2x\r\nyz").unwrap();
+        assert_eq!(
+            term.grid().snapshot(),
+            "\"abcd\"\n    2..3: bold fg=1\n\
+             \"  漢x\"\n    2..5: bg=#010203\n\
+             \"yz\"\n    0..2: bg=#010203\n"
+        );
+        assert_eq!(term.cursor(), (2, 2));
+
+        // Wrap at the end of the row and scroll at the bottom.
+        write!(term, "{}{}1234567", style::Reset, cursor::Goto(1, 3)).unwrap();
+        assert_eq!(term.grid().snapshot().lines().last(), Some("\"7\""));
+        assert_eq!(term.grid().row(1)[5].ch, '6');
+
+        write!(term, "{}", clear::All).unwrap();
+        assert_eq!(term.grid(), &Grid::new(6, 3));
+    }
+
+    #[test]
+    fn test_scrolling() {
+        let mut term = Headless::new(4, 4);
+        write!(term, "a\r\nb\r\nc\r\nd").unwrap();
+        // Scroll rows 2 and 3 only.
+        write!(term, "\x1B[2;3r\x1B[3;1H\nx").unwrap();
+        assert_eq!(term.grid().snapshot(), "\"a\"\n\"c\"\n\"x\"\n\"d\"\n");
+        // Reverse index at the top of the region.
+        write!(term, "\x1B[2;1H\x1BMy").unwrap();
+        assert_eq!(term.grid().snapshot(), "\"a\"\n\"y\"\n\"c\"\n\"d\"\n");
+        // Insert and delete lines in the region.
+        write!(term, "\x1B[r\x1B[2;1H\x1B[L").unwrap();
+        assert_eq!(term.grid().snapshot(), "\"a\"\n\"\"\n\"y\"\n\"c\"\n");
+        write!(term, "\x1B[2M").unwrap();
+        assert_eq!(term.grid().snapshot(), "\"a\"\n\"c\"\n\"\"\n\"\"\n");
+
+        // Insert, delete and erase characters.
+        write!(term, "\x1B[3;1Habcd\x1B[3;2H\x1B[2@").unwrap();
+        assert_eq!(
+            term.grid().row(2)[..4]
+                .iter()
+                .map(|c| c.ch)
+                .collect::<String>(),
+            "a  b"
+        );
+        write!(term, "\x1B[P\x1B[3;4H\x1B[X").unwrap();
+        assert_eq!(
+            term.grid().row(2)[..4]
+                .iter()
+                .map(|c| c.ch)
+                .collect::<String>(),
+            "a b "
+        );
+
+        // Saved cursor and resizing.
+        write!(term, "\x1B[2;2H\x1B7\x1B[4;4H\x1B8z").unwrap();
+        assert_eq!(term.grid().row(1)[1].ch, 'z');
+        term.resize(2, 2);
+        assert_eq!(term.grid().snapshot(), "\"a\"\n\"cz\"\n");
+        assert_eq!(term.cursor(), (1, 1));
+
+        // A screen with no rows has no scroll region.
+        let mut term = Headless::new(10, 0);
+        write!(term, "\x1B[r\x1B[1;2r").unwrap();
+        term.resize(4, 0);
+        write!(term, "\x1B[r").unwrap();
+    }
+
+    #[test]
+    fn test_attributes() {
+        let mut term = Headless::new(8, 1);
+        write!(
+            term,
+            "{}{}pw{}{}ab{}{}c",
+            style::Conceal,
+            style::Overline,
+            style::Reveal,
+            style::Bold,
+            style::NoOverline,
+            style::NoBold
+        )
+        .unwrap();
+        assert_eq!(
+            term.grid().snapshot(),
+            "\"pwabc\"\n    0..2: conceal overline\n    2..4: bold overline\n"
+        );
+        // Rendering the grid draws the same screen.
+        assert_eq!(&render_headless(term.grid()), term.grid());
+    }
+}
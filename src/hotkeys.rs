@@ -0,0 +1,174 @@
+//! Key bindings dispatched to callbacks.
+//!
+//! A [`Hotkeys`] registry maps keys to handlers.  Feed every event through
+//! [`Hotkeys::handle`], bound keys run their handler and are consumed, any
+//! other event is passed back to the caller.  Bindings can be changed at
+//! any time so shortcuts can be rebound while the application runs.
+//!
+//! # Example
+//!
+//! ```rust,no_run
+//! use std::sync::mpsc;
+//! use sl_console::event::{Key, KeyCode, KeyMod};
+//! use sl_console::hotkeys::Hotkeys;
+//! use sl_console::input::ConsoleReadExt;
+//! use sl_console::*;
+//!
+//! enum Command {
+//!     Quit,
+//! }
+//!
+//!     con_init().unwrap();
+//!     let (send, recv) = mpsc::channel();
+//!     let mut hotkeys = Hotkeys::new();
+//!     hotkeys.bind_message(Key::new_mod(KeyCode::Char('q'), KeyMod::Ctrl), send, || Command::Quit);
+//!     hotkeys.bind(Key::new(KeyCode::F(1)), |_| println!("help"));
+//!     let mut conin = conin();
+//!     while let Some(Ok(event)) = conin.get_event() {
+//!         if let Some(_event) = hotkeys.handle(event) {
+//!             // Not a hotkey.
+//!         }
+//!         if let Ok(Command::Quit) = recv.try_recv() {
+//!             break;
+//!         }
+//!     }
+//! ```
+
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::mpsc::Sender;
+
+use crate::event::{Event, Key, KeyCode, KeyEventKind, KeyMod};
+
+/// The part of a key that bindings match on.
+type Binding = (KeyCode, Option<KeyMod>);
+
+/// A registry of key bindings.
+///
+/// Keys match on their code and modifiers, presses and repeats run the
+/// handler and releases are passed back.  If
+/// the terminal reports a base layout key (`Key::alternate`) and the key
+/// itself is not bound then the base layout key is tried, so shortcuts
+/// work the same on any keyboard layout.
+#[derive(Default)]
+pub struct Hotkeys {
+    handlers: HashMap<Binding, Box<dyn FnMut(Key)>>,
+}
+
+impl fmt::Debug for Hotkeys {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Hotkeys")
+            .field("bindings", &self.handlers.keys().collect::<Vec<_>>())
+            .finish()
+    }
+}
+
+impl Hotkeys {
+    /// An empty registry.
+    pub fn new() -> Self {
+        Hotkeys::default()
+    }
+
+    /// Call `handler` when `key` is pressed, replaces any handler already
+    /// bound to `key`.
+    pub fn bind<F>(&mut self, key: Key, handler: F)
+    where
+        F: FnMut(Key) + 'static,
+    {
+        self.handlers
+            .insert((key.code, key.mods), Box::new(handler));
+    }
+
+    /// Send the message made by `message` on `sender` when `key` is
+    /// pressed.  Send errors (the receiver was dropped) are ignored.
+    pub fn bind_message<T, F>(&mut self, key: Key, sender: Sender<T>, message: F)
+    where
+        T: 'static,
+        F: Fn() -> T + 'static,
+    {
+        self.bind(key, move |_| {
+            let _ = sender.send(message());
+        });
+    }
+
+    /// Remove the binding for `key`, returns true if it was bound.
+    pub fn unbind(&mut self, key: &Key) -> bool {
+        self.handlers.remove(&(key.code, key.mods)).is_some()
+    }
+
+    /// True if `key` is bound.
+    pub fn is_bound(&self, key: &Key) -> bool {
+        self.handlers.contains_key(&(key.code, key.mods))
+    }
+
+    /// Remove all bindings.
+    pub fn clear(&mut self) {
+        self.handlers.clear();
+    }
+
+    /// Run the handler for `event` if it is a bound key and return None,
+    /// otherwise return the event unchanged.
+    pub fn handle(&mut self, event: Event) -> Option<Event> {
+        let key = match event {
+            Event::Key(key) if key.kind != KeyEventKind::Release => key,
+            event => return Some(event),
+        };
+        let mut bindings = vec![(key.code, key.mods)];
+        if let Some(alternate) = key.alternate {
+            bindings.push((alternate, key.mods));
+        }
+        for binding in bindings {
+            if let Some(handler) = self.handlers.get_mut(&binding) {
+                handler(key);
+                return None;
+            }
+        }
+        Some(Event::Key(key))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::cell::Cell;
+    use std::rc::Rc;
+    use std::sync::mpsc;
+
+    #[test]
+    fn test_hotkeys() {
+        let ctrl_z = Key::new_mod(KeyCode::Char('z'), KeyMod::Ctrl);
+        let count = Rc::new(Cell::new(0));
+        let mut hotkeys = Hotkeys::new();
+        let handler_count = count.clone();
+        hotkeys.bind(ctrl_z, move |_| handler_count.set(handler_count.get() + 1));
+        assert!(hotkeys.is_bound(&ctrl_z));
+
+        assert_eq!(hotkeys.handle(Event::Key(ctrl_z)), None);
+        // A German layout Ctrl+Y is on the US Z key.
+        let mut qwertz = Key::new_mod(KeyCode::Char('y'), KeyMod::Ctrl);
+        qwertz.alternate = Some(KeyCode::Char('z'));
+        assert_eq!(hotkeys.handle(Event::Key(qwertz)), None);
+        assert_eq!(count.get(), 2);
+
+        let plain_z = Event::Key(Key::new(KeyCode::Char('z')));
+        assert_eq!(hotkeys.handle(plain_z.clone()), Some(plain_z));
+        assert_eq!(hotkeys.handle(Event::Tick), Some(Event::Tick));
+        let mut release = ctrl_z;
+        release.kind = KeyEventKind::Release;
+        assert_eq!(
+            hotkeys.handle(Event::Key(release)),
+            Some(Event::Key(release))
+        );
+        assert_eq!(count.get(), 2);
+
+        let (send, recv) = mpsc::channel();
+        hotkeys.bind_message(ctrl_z, send, || "undo");
+        assert_eq!(hotkeys.handle(Event::Key(ctrl_z)), None);
+        assert_eq!(recv.try_recv(), Ok("undo"));
+        assert_eq!(count.get(), 2);
+
+        assert!(hotkeys.unbind(&ctrl_z));
+        assert!(!hotkeys.unbind(&ctrl_z));
+        assert_eq!(hotkeys.handle(Event::Key(ctrl_z)), Some(Event::Key(ctrl_z)));
+    }
+}
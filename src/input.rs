@@ -1,11 +1,19 @@
 //! User input
 
-use std::io::{self, Read, Write};
+use std::collections::VecDeque;
+use std::io::{self, BufRead, Read, Write};
 use std::ops;
-use std::time::Duration;
+use std::thread;
+use std::time::{Duration, Instant};
 
+use crate::caps::{overrides, query_modes};
+use crate::clock::{Clock, SYSTEM_CLOCK};
 use crate::console::{ConsoleRead, ConsoleWrite};
-use crate::event::{self, Event, Key, KeyCode};
+use crate::event::{
+    self, Event, Key, KeyCode, KeyEventKind, ModeReport, ModeState, MouseButton, MouseEncoding,
+    MouseEvent, ParseOptions,
+};
+use crate::width::char_width;
 
 /// An iterator over input events.
 pub struct EventsAndRaw<R> {
@@ -46,18 +54,190 @@ impl<R: ConsoleRead> Iterator for Events<R> {
     }
 }
 
+/// Flags key events that repeat the previous key within a threshold.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct RepeatDetector {
+    threshold: Option<Duration>,
+    last: Option<(Key, Instant)>,
+}
+
+impl RepeatDetector {
+    pub(crate) fn set_threshold(&mut self, threshold: Option<Duration>) {
+        self.threshold = threshold;
+        self.last = None;
+    }
+
+    pub(crate) fn threshold(&self) -> Option<Duration> {
+        self.threshold
+    }
+
+    /// Return `event` as a repeat if it is a press of the same key as the
+    /// last one and arrived within the threshold.
+    pub(crate) fn mark(&mut self, event: Event, now: Instant) -> Event {
+        let threshold = match self.threshold {
+            Some(threshold) => threshold,
+            None => return event,
+        };
+        match event {
+            Event::Key(mut key) if key.kind == KeyEventKind::Press => {
+                if let Some((last, at)) = self.last.replace((key, now)) {
+                    if last == key && now.duration_since(at) <= threshold {
+                        key.kind = KeyEventKind::Repeat;
+                    }
+                }
+                Event::Key(key)
+            }
+            event => event,
+        }
+    }
+}
+
+/// An event with the bytes that define it, as returned by get_event_and_raw.
+pub(crate) type RawEvent = Option<io::Result<(Event, Vec<u8>)>>;
+
+/// True if `key` is a plain character that combining marks can follow.
+pub(crate) fn is_combining_base(key: &Key) -> bool {
+    match key.code {
+        KeyCode::Char(ch) => {
+            key.mods.is_none()
+                && key.kind == KeyEventKind::Press
+                && !ch.is_control()
+                && char_width(ch) > 0
+        }
+        _ => false,
+    }
+}
+
+fn combining_mark(event: &Event) -> Option<char> {
+    match event {
+        Event::Key(Key {
+            code: KeyCode::Char(ch),
+            mods: None,
+            ..
+        }) if !ch.is_control() && char_width(*ch) == 0 => Some(*ch),
+        _ => None,
+    }
+}
+
+/// Merge the combining marks that follow `base` into one grapheme.
+///
+/// `next` reads the next event, it should give up quickly (a WouldBlock
+/// error) if nothing follows.  Returns the merged event, or `base`
+/// unchanged if no marks followed, and the event read after the marks that
+/// still needs to be delivered.
+pub(crate) fn merge_combining<F>(
+    base: (Event, Vec<u8>),
+    mut next: F,
+) -> ((Event, Vec<u8>), RawEvent)
+where
+    F: FnMut() -> RawEvent,
+{
+    let (event, mut raw) = base;
+    let mut text = match &event {
+        Event::Key(Key {
+            code: KeyCode::Char(ch),
+            ..
+        }) => ch.to_string(),
+        _ => return ((event, raw), None),
+    };
+    let pending = loop {
+        match next() {
+            Some(Ok((mark, mark_raw))) => match combining_mark(&mark) {
+                Some(ch) => {
+                    text.push(ch);
+                    raw.extend(mark_raw);
+                }
+                None => break Some(Ok((mark, mark_raw))),
+            },
+            Some(Err(err)) if err.kind() == io::ErrorKind::WouldBlock => break None,
+            res => break res,
+        }
+    };
+    if text.chars().count() == 1 {
+        ((event, raw), pending)
+    } else {
+        ((Event::Grapheme(text), raw), pending)
+    }
+}
+
+/// True if `next` can replace `prev` when mouse events are coalesced: both
+/// are drags with the same button, moves, or turns of the same wheel, with
+/// the same modifiers and coordinate kind.
+fn coalesces(prev: &Event, next: &Event) -> bool {
+    let (prev, next) = match (prev, next) {
+        (Event::Mouse(prev, prev_mods), Event::Mouse(next, next_mods))
+        | (Event::MousePixels(prev, prev_mods), Event::MousePixels(next, next_mods))
+            if prev_mods == next_mods =>
+        {
+            (prev, next)
+        }
+        _ => return false,
+    };
+    match (prev, next) {
+        (MouseEvent::Hold(prev, ..), MouseEvent::Hold(next, ..)) => prev == next,
+        (MouseEvent::Moved(..), MouseEvent::Moved(..)) => true,
+        (MouseEvent::Press(prev, ..), MouseEvent::Press(next, ..)) => {
+            prev == next
+                && matches!(
+                    prev,
+                    MouseButton::WheelUp
+                        | MouseButton::WheelDown
+                        | MouseButton::WheelLeft
+                        | MouseButton::WheelRight
+                )
+        }
+        _ => false,
+    }
+}
+
+/// Replace `first` with the mouse events of the same kind that directly
+/// follow it, keeping only the latest.
+///
+/// `next` reads the next event, it should give up at once (a WouldBlock
+/// error) if nothing is queued.  Returns the latest event and the event
+/// read after it that still needs to be delivered.
+pub(crate) fn coalesce_mouse<F>(
+    first: (Event, Vec<u8>),
+    mut next: F,
+) -> ((Event, Vec<u8>), RawEvent)
+where
+    F: FnMut() -> RawEvent,
+{
+    let mut latest = first;
+    let pending = loop {
+        match next() {
+            Some(Ok(event)) if coalesces(&latest.0, &event.0) => latest = event,
+            Some(Err(err)) if err.kind() == io::ErrorKind::WouldBlock => break None,
+            res => break res,
+        }
+    };
+    (latest, pending)
+}
+
 /// Get the next input event and the bytes that define it.
 pub(crate) fn event_and_raw(
     source: &mut dyn Read,
     leftover: &mut Option<u8>,
+    options: &ParseOptions,
 ) -> Option<Result<(Event, Vec<u8>), io::Error>> {
-    if let Some(c) = leftover {
+    let res = if let Some(c) = leftover.take() {
         // we have a leftover byte, use it
-        let ch = *c;
-        *leftover = None;
-        return Some(parse_event(ch, &mut source.bytes()));
-    }
+        parse_event(c, &mut source.bytes(), options, leftover)
+    } else {
+        read_event(source, leftover, options)?
+    };
+    Some(match res {
+        Ok((Event::Unsupported(_), raw)) if options.resync => Ok(resync(source, leftover, raw)),
+        res => res,
+    })
+}
 
+/// Read and parse the next event when there is no leftover byte.
+fn read_event(
+    source: &mut dyn Read,
+    leftover: &mut Option<u8>,
+    options: &ParseOptions,
+) -> Option<Result<(Event, Vec<u8>), io::Error>> {
     // Here we read two bytes at a time. We need to distinguish between single ESC key presses,
     // and escape sequences (which start with ESC or a x1B byte). The idea is that if this is
     // an escape sequence, we will read multiple bytes (the first byte being ESC) but if this
@@ -67,16 +247,17 @@ pub(crate) fn event_and_raw(
         Ok(0) => return None,
         Ok(1) => match buf[0] {
             b'\x1B' => Ok((Event::Key(Key::new(KeyCode::Esc)), vec![b'\x1B'])),
-            c => parse_event(c, &mut source.bytes()),
+            c => parse_event(c, &mut source.bytes(), options, leftover),
         },
         Ok(2) => {
             let option_iter = &mut Some(buf[1]).into_iter();
+            let mut unread = None;
             let result = {
                 let mut iter = option_iter.map(Ok).chain(source.bytes());
-                parse_event(buf[0], &mut iter)
+                parse_event(buf[0], &mut iter, options, &mut unread)
             };
             // If the option_iter wasn't consumed, keep the byte for later.
-            *leftover = option_iter.next();
+            *leftover = option_iter.next().or(unread);
             result
         }
         Ok(_) => unreachable!(),
@@ -86,17 +267,50 @@ pub(crate) fn event_and_raw(
     Some(res)
 }
 
-fn parse_event<I>(item: u8, iter: &mut I) -> io::Result<(Event, Vec<u8>)>
+/// Skip the garbage after the malformed input `raw`, up to the next byte
+/// that can start an event (left in `leftover`) or until no more input
+/// arrives, and return it all as one Garbled event.
+fn resync(source: &mut dyn Read, leftover: &mut Option<u8>, mut raw: Vec<u8>) -> (Event, Vec<u8>) {
+    match leftover.take() {
+        Some(byte) if event::is_resync_point(byte) => *leftover = Some(byte),
+        next => {
+            raw.extend(next);
+            let mut buf = [0u8; 1];
+            while let Ok(1) = source.read(&mut buf) {
+                if event::is_resync_point(buf[0]) {
+                    *leftover = Some(buf[0]);
+                    break;
+                }
+                raw.push(buf[0]);
+            }
+        }
+    }
+    (Event::Garbled(raw.clone()), raw)
+}
+
+fn parse_event<I>(
+    item: u8,
+    iter: &mut I,
+    options: &ParseOptions,
+    unread: &mut Option<u8>,
+) -> io::Result<(Event, Vec<u8>)>
 where
     I: Iterator<Item = io::Result<u8>>,
 {
     let mut buf = vec![item];
-    let mut iter = iter.inspect(|byte| {
-        if let Ok(byte) = *byte {
-            buf.push(byte);
-        }
-    });
-    event::parse_event(item, &mut iter)
+    let result = {
+        let mut iter = iter.inspect(|byte| {
+            if let Ok(byte) = *byte {
+                buf.push(byte);
+            }
+        });
+        event::parse_event_opts(item, &mut iter, options, unread)
+    };
+    if unread.is_some() {
+        // The unread byte belongs to the next event.
+        buf.pop();
+    }
+    result
         .or_else(|_| Ok(Event::Unsupported(buf.clone())))
         .map(|e| (e, buf))
 }
@@ -135,6 +349,29 @@ pub trait ConsoleReadExt {
     /// This version will block until an event is ready.
     /// Returns None if the Console has no more data.
     fn get_key(&mut self) -> Option<io::Result<Key>>;
+
+    /// Return the next input event without taking it, the next get_event
+    /// returns it again.
+    ///
+    /// Waits up to `timeout` like `get_event_timeout` (forever if None).
+    /// Needs a reader that can put events back (see
+    /// [`ConsoleRead::unget_event_and_raw`]), on other readers the event
+    /// read is lost and the error returned.
+    fn peek_event(&mut self, timeout: Option<Duration>) -> Option<io::Result<Event>>;
+
+    /// Put `event` back, the next get_event returns it before any new input.
+    ///
+    /// Events put back are returned last in, first out.  Returns an error of
+    /// kind Unsupported if the reader can not put events back.
+    fn unget_event(&mut self, event: Event) -> io::Result<()>;
+
+    /// Take all the events that are ready now without waiting, for example
+    /// to throw away type-ahead after an error or to handle a burst of
+    /// mouse motion at once.
+    ///
+    /// Stops at the first read that would block (or fails, the error is
+    /// dropped).
+    fn drain_events(&mut self) -> Vec<Event>;
 }
 
 impl<R: ConsoleRead> ConsoleReadExt for R {
@@ -166,6 +403,25 @@ impl<R: ConsoleRead> ConsoleReadExt for R {
         }
     }
 
+    fn peek_event(&mut self, timeout: Option<Duration>) -> Option<io::Result<Event>> {
+        match self.get_event_and_raw(timeout)? {
+            Ok((event, raw)) => Some(self.unget_event_and_raw(event.clone(), raw).map(|_| event)),
+            Err(err) => Some(Err(err)),
+        }
+    }
+
+    fn unget_event(&mut self, event: Event) -> io::Result<()> {
+        self.unget_event_and_raw(event, Vec::new())
+    }
+
+    fn drain_events(&mut self) -> Vec<Event> {
+        let mut events = Vec::new();
+        while let Some(Ok((event, _raw))) = self.get_event_and_raw(Some(Duration::ZERO)) {
+            events.push(event);
+        }
+        events
+    }
+
     fn get_key(&mut self) -> Option<io::Result<Key>> {
         loop {
             match self.get_event() {
@@ -178,11 +434,249 @@ impl<R: ConsoleRead> ConsoleReadExt for R {
     }
 }
 
-/// A sequence of escape codes to enable terminal mouse support.
-const ENTER_MOUSE_SEQUENCE: &str = csi!("?1000h\x1b[?1002h\x1b[?1015h\x1b[?1006h");
+/// The mouse modes in the order they are enabled.
+pub(crate) const MOUSE_MODES: [u16; 7] = [1000, 1002, 1003, 1005, 1015, 1006, 1016];
+
+/// A set of mouse tracking modes and report encodings to enable.
+///
+/// Build one with [`MouseCapture::builder`].  The default is what
+/// `mouse_on` enables: button presses and drags with every coordinate
+/// encoding the parser understands.
+///
+/// # Example
+///
+/// ```rust,no_run
+/// use sl_console::input::{ConsoleMouseExt, MouseCapture};
+/// use sl_console::*;
+///
+///     con_init().unwrap();
+///     let capture = MouseCapture::builder().buttons().drag().sgr().build();
+///     let mut conin = conin();
+///     let mut options = conin.lock().parse_options();
+///     options.mouse_encoding = capture.mouse_encoding();
+///     options.sgr_pixels = capture.sgr_pixels();
+///     conin.lock().set_parse_options(options);
+///     conout().mouse_capture(capture).unwrap();
+/// ```
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct MouseCapture {
+    buttons: bool,
+    drag: bool,
+    motion: bool,
+    utf8: bool,
+    urxvt: bool,
+    sgr: bool,
+    pixels: bool,
+}
+
+impl Default for MouseCapture {
+    fn default() -> Self {
+        MouseCapture::builder()
+            .buttons()
+            .drag()
+            .utf8()
+            .urxvt()
+            .sgr()
+            .build()
+    }
+}
+
+impl MouseCapture {
+    /// Start building a capture with nothing enabled.
+    pub fn builder() -> MouseCaptureBuilder {
+        MouseCaptureBuilder {
+            capture: MouseCapture {
+                buttons: false,
+                drag: false,
+                motion: false,
+                utf8: false,
+                urxvt: false,
+                sgr: false,
+                pixels: false,
+            },
+        }
+    }
+
+    /// The modes to set, tracking modes first then the encodings from the
+    /// oldest to the newest so the best one the terminal knows wins.
+    fn modes(&self) -> Vec<u16> {
+        [
+            self.buttons,
+            self.drag,
+            self.motion,
+            self.utf8,
+            self.urxvt,
+            self.sgr,
+            self.pixels,
+        ]
+        .iter()
+        .zip(MOUSE_MODES.iter())
+        .filter(|(on, _)| **on)
+        .map(|(_, mode)| *mode)
+        .collect()
+    }
+
+    /// The capture with the modes `reports` show as set.
+    pub(crate) fn from_reports(reports: &[ModeReport]) -> Self {
+        let mut capture = MouseCapture::builder().build();
+        for report in reports {
+            if report.state == ModeState::Set {
+                capture.set_mode(report.mode);
+            }
+        }
+        capture
+    }
+
+    fn set_mode(&mut self, mode: u16) {
+        match mode {
+            1000 => self.buttons = true,
+            1002 => self.drag = true,
+            1003 => self.motion = true,
+            1005 => self.utf8 = true,
+            1015 => self.urxvt = true,
+            1006 => self.sgr = true,
+            1016 => self.pixels = true,
+            _ => {}
+        }
+    }
+
+    /// The escape sequence that enables the capture.
+    pub fn enable_sequence(&self) -> String {
+        self.modes()
+            .iter()
+            .map(|mode| format!(csi!("?{}h"), mode))
+            .collect()
+    }
+
+    /// The escape sequence that disables the capture, in the reverse order
+    /// of [`enable_sequence`](MouseCapture::enable_sequence).
+    pub fn disable_sequence(&self) -> String {
+        self.modes()
+            .iter()
+            .rev()
+            .map(|mode| format!(csi!("?{}l"), mode))
+            .collect()
+    }
+
+    /// The encoding of `CSI M` reports with this capture, for
+    /// [`ParseOptions::mouse_encoding`](crate::event::ParseOptions::mouse_encoding).
+    ///
+    /// Terminals fall back to these reports when they do not know the
+    /// SGR or urxvt encodings.
+    pub fn mouse_encoding(&self) -> MouseEncoding {
+        if self.utf8 {
+            MouseEncoding::Utf8
+        } else {
+            MouseEncoding::X10
+        }
+    }
+
+    /// True if SGR reports are in pixels with this capture, for
+    /// [`ParseOptions::sgr_pixels`](crate::event::ParseOptions::sgr_pixels).
+    pub fn sgr_pixels(&self) -> bool {
+        self.pixels
+    }
+}
+
+/// How much mouse activity a [`MouseTerminal`] reports.
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq, Hash)]
+pub enum MouseMode {
+    /// Button presses and releases.
+    Buttons,
+    /// Presses, releases and motion while a button is held.
+    #[default]
+    Drag,
+    /// Presses, releases and all motion.
+    Motion,
+}
+
+impl MouseMode {
+    /// The capture for this mode using every encoding the parser
+    /// understands.
+    pub fn capture(self) -> MouseCapture {
+        let builder = MouseCapture::builder().buttons().utf8().urxvt().sgr();
+        match self {
+            MouseMode::Buttons => builder,
+            MouseMode::Drag => builder.drag(),
+            MouseMode::Motion => builder.motion(),
+        }
+        .build()
+    }
+}
+
+/// Ask the terminal which mouse modes are currently enabled (DECRQM).
+///
+/// See [`query_modes`] for how the console is used.  Returns an error if
+/// the terminal does not answer.
+pub fn query_mouse_capture<R, W>(conin: &mut R, conout: &mut W) -> io::Result<MouseCapture>
+where
+    R: ConsoleRead,
+    W: Write,
+{
+    Ok(MouseCapture::from_reports(&query_modes(
+        conin,
+        conout,
+        &MOUSE_MODES,
+    )?))
+}
+
+/// Builder for [`MouseCapture`].
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct MouseCaptureBuilder {
+    capture: MouseCapture,
+}
 
-/// A sequence of escape codes to disable terminal mouse support.
-const EXIT_MOUSE_SEQUENCE: &str = csi!("?1006l\x1b[?1015l\x1b[?1002l\x1b[?1000l");
+impl MouseCaptureBuilder {
+    /// Report button presses and releases (mode 1000).
+    pub fn buttons(mut self) -> Self {
+        self.capture.buttons = true;
+        self
+    }
+
+    /// Report motion while a button is held (mode 1002).
+    pub fn drag(mut self) -> Self {
+        self.capture.drag = true;
+        self
+    }
+
+    /// Report all motion, with or without a button held (mode 1003).
+    pub fn motion(mut self) -> Self {
+        self.capture.motion = true;
+        self
+    }
+
+    /// Encode coordinates over 95 as UTF-8 (mode 1005).
+    pub fn utf8(mut self) -> Self {
+        self.capture.utf8 = true;
+        self
+    }
+
+    /// Use the urxvt decimal encoding (mode 1015).
+    pub fn urxvt(mut self) -> Self {
+        self.capture.urxvt = true;
+        self
+    }
+
+    /// Use the SGR encoding (mode 1006), recommended for any terminal
+    /// that supports it.
+    pub fn sgr(mut self) -> Self {
+        self.capture.sgr = true;
+        self
+    }
+
+    /// Report SGR coordinates in pixels instead of cells (mode 1016), the
+    /// parser needs [`ParseOptions::sgr_pixels`](crate::event::ParseOptions::sgr_pixels)
+    /// to report them as [`Event::MousePixels`](crate::event::Event::MousePixels).
+    pub fn pixels(mut self) -> Self {
+        self.capture.pixels = true;
+        self
+    }
+
+    /// Finish building.
+    pub fn build(self) -> MouseCapture {
+        self.capture
+    }
+}
 
 /// Extension trait for ConsoleWrite to turn mouse support on or off for the console.
 pub trait ConsoleMouseExt {
@@ -191,39 +685,190 @@ pub trait ConsoleMouseExt {
 
     /// Turn mouse support off for the console.
     fn mouse_off(&mut self) -> io::Result<()>;
+
+    /// Turn mouse support on for the console reporting `mode` activity,
+    /// [`MouseMode::Motion`] also reports moves with no button held.
+    fn mouse_mode_on(&mut self, mode: MouseMode) -> io::Result<()>;
+
+    /// Turn mouse support turned on with `mode` off.
+    fn mouse_mode_off(&mut self, mode: MouseMode) -> io::Result<()>;
+
+    /// Enable the modes in `capture`.
+    fn mouse_capture(&mut self, capture: MouseCapture) -> io::Result<()>;
+
+    /// Disable the modes in `capture`.
+    fn mouse_release(&mut self, capture: MouseCapture) -> io::Result<()>;
 }
 
 impl<W: ConsoleWrite> ConsoleMouseExt for W {
     fn mouse_on(&mut self) -> io::Result<()> {
-        self.write_all(ENTER_MOUSE_SEQUENCE.as_bytes())?;
-        Ok(())
+        self.mouse_capture(MouseCapture::default())
     }
 
     fn mouse_off(&mut self) -> io::Result<()> {
-        self.write_all(EXIT_MOUSE_SEQUENCE.as_bytes())?;
+        self.mouse_release(MouseCapture::default())
+    }
+
+    fn mouse_mode_on(&mut self, mode: MouseMode) -> io::Result<()> {
+        self.mouse_capture(mode.capture())
+    }
+
+    fn mouse_mode_off(&mut self, mode: MouseMode) -> io::Result<()> {
+        self.mouse_release(mode.capture())
+    }
+
+    fn mouse_capture(&mut self, capture: MouseCapture) -> io::Result<()> {
+        if !overrides().no_mouse {
+            self.write_all(capture.enable_sequence().as_bytes())?;
+        }
+        Ok(())
+    }
+
+    fn mouse_release(&mut self, capture: MouseCapture) -> io::Result<()> {
+        if !overrides().no_mouse {
+            self.write_all(capture.disable_sequence().as_bytes())?;
+        }
         Ok(())
     }
 }
 
+/// Kitty keyboard protocol enhancements, combine with `|`.
+///
+/// See <https://sw.kovidgoyal.net/kitty/keyboard-protocol/>.
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq, Hash)]
+pub struct KeyboardFlags(u8);
+
+impl KeyboardFlags {
+    /// Report keys that are ambiguous in the legacy encoding (Esc,
+    /// Alt+key, Ctrl+key) as `CSI ... u`.
+    pub const DISAMBIGUATE: KeyboardFlags = KeyboardFlags(1);
+    /// Report repeat (and release) events.
+    pub const REPORT_EVENTS: KeyboardFlags = KeyboardFlags(1 << 1);
+    /// Report the base layout key, see `Key::alternate`.
+    pub const ALTERNATE_KEYS: KeyboardFlags = KeyboardFlags(1 << 2);
+
+    /// The flags as the number sent to the terminal.
+    pub fn bits(self) -> u8 {
+        self.0
+    }
+}
+
+impl ops::BitOr for KeyboardFlags {
+    type Output = KeyboardFlags;
+
+    fn bitor(self, rhs: KeyboardFlags) -> KeyboardFlags {
+        KeyboardFlags(self.0 | rhs.0)
+    }
+}
+
+/// Extension trait for ConsoleWrite to switch kitty keyboard protocol
+/// enhancements and xterm's modifyOtherKeys on or off.
+///
+/// Terminals that do not support the protocol ignore these, see
+/// [`TerminalCaps::kitty_keyboard`](crate::caps::TerminalCaps::kitty_keyboard).
+pub trait ConsoleKeyboardExt {
+    /// Push `flags` on the terminal's keyboard mode stack.
+    fn push_keyboard_flags(&mut self, flags: KeyboardFlags) -> io::Result<()>;
+
+    /// Pop the last pushed flags, restoring the previous mode.
+    fn pop_keyboard_flags(&mut self) -> io::Result<()>;
+
+    /// Set xterm's modifyOtherKeys `level`: 0 turns it off, 1 reports
+    /// modified keys that would otherwise be lost (Ctrl+comma) and 2 all
+    /// modified keys, as `CSI 27 ; mods ; code ~`.
+    fn set_modify_other_keys(&mut self, level: u8) -> io::Result<()>;
+}
+
+impl<W: ConsoleWrite> ConsoleKeyboardExt for W {
+    fn push_keyboard_flags(&mut self, flags: KeyboardFlags) -> io::Result<()> {
+        write!(self, csi!(">{}u"), flags.bits())
+    }
+
+    fn pop_keyboard_flags(&mut self) -> io::Result<()> {
+        self.write_all(csi!("<u").as_bytes())
+    }
+
+    fn set_modify_other_keys(&mut self, level: u8) -> io::Result<()> {
+        write!(self, csi!(">4;{}m"), level)
+    }
+}
+
 /// A terminal with added mouse support.
 ///
-/// This can be obtained through the `From` implementations.
-/// You can use this if you want an RAII guard around terminal mouse support.
+/// This can be obtained through [`MouseTerminal::try_new`] or the `From`
+/// implementations.  You can use this if you want an RAII guard around
+/// terminal mouse support.
 pub struct MouseTerminal<W: ConsoleWrite> {
     term: W,
+    capture: MouseCapture,
+    previous: Option<MouseCapture>,
+}
+
+impl<W: ConsoleWrite> MouseTerminal<W> {
+    /// Enable mouse reporting in `mode` on `term`.
+    ///
+    /// The mouse modes that were enabled before are queried first through
+    /// `conin`, the input of the same terminal (this briefly puts `term` in
+    /// raw mode and waits up to 200ms for a terminal that does not answer),
+    /// and restored when the guard is dropped.  Returns an error if the
+    /// mouse modes could not be written.
+    pub fn try_new<R: ConsoleRead>(
+        mut term: W,
+        conin: &mut R,
+        mode: MouseMode,
+    ) -> io::Result<Self> {
+        let previous = query_previous(&mut term, conin).ok();
+        let capture = mode.capture();
+        term.mouse_capture(capture)?;
+        term.flush()?;
+        Ok(MouseTerminal {
+            term,
+            capture,
+            previous,
+        })
+    }
+
+    /// The mouse modes enabled before this terminal was created, None if
+    /// the terminal did not report them.
+    pub fn previous(&self) -> Option<MouseCapture> {
+        self.previous
+    }
 }
 
+/// Query the mouse modes with `term` in raw mode.
+fn query_previous<W, R>(term: &mut W, conin: &mut R) -> io::Result<MouseCapture>
+where
+    W: ConsoleWrite,
+    R: ConsoleRead,
+{
+    let was_raw = term.set_raw_mode(true)?;
+    let capture = query_mouse_capture(conin, term);
+    term.set_raw_mode(was_raw)?;
+    capture
+}
+
+/// Enables the default mouse modes.  A write error is ignored, as when the
+/// guard is dropped, use [`MouseTerminal::try_new`] to see it.
 impl<W: ConsoleWrite> From<W> for MouseTerminal<W> {
     fn from(mut from: W) -> MouseTerminal<W> {
-        from.write_all(ENTER_MOUSE_SEQUENCE.as_bytes()).unwrap();
+        let _ = from.mouse_on();
 
-        MouseTerminal { term: from }
+        MouseTerminal {
+            term: from,
+            capture: MouseCapture::default(),
+            previous: None,
+        }
     }
 }
 
 impl<W: ConsoleWrite> Drop for MouseTerminal<W> {
     fn drop(&mut self) {
-        self.term.write_all(EXIT_MOUSE_SEQUENCE.as_bytes()).unwrap();
+        // Errors can not be reported from drop, the terminal is likely gone.
+        let _ = self.term.mouse_release(self.capture);
+        if let Some(previous) = self.previous {
+            let _ = self.term.mouse_capture(previous);
+        }
+        let _ = self.term.flush();
     }
 }
 
@@ -261,6 +906,362 @@ impl<W: ConsoleWrite> ConsoleWrite for MouseTerminal<W> {
     }
 }
 
+/// A console reader that records the input read through it.
+///
+/// Every chunk of bytes read is written to the recording as a line with the
+/// seconds since the recorder was made and the bytes in hex, such as
+/// `1.250000 1b5b41`.  A [`Player`] reads the recording back as console
+/// input, which makes bug reports reproducible and scripted input for
+/// integration tests easy to capture.
+pub struct Recorder<R: ConsoleRead, W: Write> {
+    inner: R,
+    recording: W,
+    clock: &'static dyn Clock,
+    start: Instant,
+}
+
+impl<R: ConsoleRead, W: Write> Recorder<R, W> {
+    /// Read from `inner` and record the input to `recording`.
+    pub fn new(inner: R, recording: W) -> Self {
+        Recorder::with_clock(inner, recording, &SYSTEM_CLOCK)
+    }
+
+    pub(crate) fn with_clock(inner: R, recording: W, clock: &'static dyn Clock) -> Self {
+        Recorder {
+            inner,
+            recording,
+            clock,
+            start: clock.now(),
+        }
+    }
+
+    /// The wrapped input.
+    pub fn get_ref(&self) -> &R {
+        &self.inner
+    }
+
+    /// The wrapped input, reading from it directly is not recorded.
+    pub fn get_mut(&mut self) -> &mut R {
+        &mut self.inner
+    }
+
+    /// Return the wrapped input and the recording.
+    pub fn into_inner(self) -> (R, W) {
+        (self.inner, self.recording)
+    }
+
+    fn record(&mut self, bytes: &[u8]) -> io::Result<()> {
+        if bytes.is_empty() {
+            return Ok(());
+        }
+        let elapsed = self.clock.now().saturating_duration_since(self.start);
+        let hex: String = bytes.iter().map(|b| format!("{:02x}", b)).collect();
+        writeln!(self.recording, "{:.6} {}", elapsed.as_secs_f64(), hex)?;
+        self.recording.flush()
+    }
+}
+
+/// Failing to write the recording is returned as the error of the read.
+impl<R: ConsoleRead, W: Write> ConsoleRead for Recorder<R, W> {
+    fn get_event_and_raw(
+        &mut self,
+        timeout: Option<Duration>,
+    ) -> Option<io::Result<(Event, Vec<u8>)>> {
+        let res = self.inner.get_event_and_raw(timeout);
+        if let Some(Ok((_, raw))) = &res {
+            if let Err(err) = self.record(raw) {
+                return Some(Err(err));
+            }
+        }
+        res
+    }
+
+    fn poll(&mut self, timeout: Option<Duration>) -> bool {
+        self.inner.poll(timeout)
+    }
+
+    fn read_timeout(&mut self, buf: &mut [u8], timeout: Option<Duration>) -> io::Result<usize> {
+        let len = self.inner.read_timeout(buf, timeout)?;
+        self.record(&buf[..len])?;
+        Ok(len)
+    }
+}
+
+impl<R: ConsoleRead, W: Write> Read for Recorder<R, W> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let len = self.inner.read(buf)?;
+        self.record(&buf[..len])?;
+        Ok(len)
+    }
+}
+
+/// Console input played back from a [`Recorder`] recording.
+///
+/// By default the whole recording is available at once, with
+/// [`set_timing`](Player::set_timing) each chunk arrives when it did while
+/// recording.  Blank lines and lines starting with `#` are skipped, so a
+/// recording can be written by hand.
+pub struct Player<B: BufRead> {
+    lines: io::Lines<B>,
+    // The chunk being read.
+    buf: VecDeque<u8>,
+    // The next chunk and when it was recorded.
+    next: Option<(Duration, Vec<u8>)>,
+    eof: bool,
+    timing: bool,
+    clock: &'static dyn Clock,
+    start: Instant,
+    leftover: Option<u8>,
+    options: ParseOptions,
+}
+
+impl<B: BufRead> Player<B> {
+    /// Play back `recording`.
+    pub fn new(recording: B) -> Self {
+        Player::with_clock(recording, &SYSTEM_CLOCK)
+    }
+
+    pub(crate) fn with_clock(recording: B, clock: &'static dyn Clock) -> Self {
+        Player {
+            lines: recording.lines(),
+            buf: VecDeque::new(),
+            next: None,
+            eof: false,
+            timing: false,
+            clock,
+            start: clock.now(),
+            leftover: None,
+            options: ParseOptions::default(),
+        }
+    }
+
+    /// Play the input with its recorded timing, counted from now.  Reads
+    /// wait for the next chunk (up to their timeout).
+    pub fn set_timing(&mut self, timing: bool) {
+        self.timing = timing;
+        self.start = self.clock.now();
+    }
+
+    /// True if the input is played with its recorded timing.
+    pub fn timing(&self) -> bool {
+        self.timing
+    }
+
+    /// Set the options used to parse input into events.
+    pub fn set_parse_options(&mut self, options: ParseOptions) {
+        self.options = options;
+    }
+
+    /// Return the options used to parse input into events.
+    pub fn parse_options(&self) -> ParseOptions {
+        self.options
+    }
+
+    /// Read the next chunk from the recording into `next`.
+    fn next_chunk(&mut self) -> io::Result<()> {
+        while self.next.is_none() && !self.eof {
+            let line = match self.lines.next() {
+                Some(line) => line?,
+                None => {
+                    self.eof = true;
+                    break;
+                }
+            };
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            self.next = Some(parse_recorded(line).ok_or_else(|| {
+                io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("Invalid input recording line: {}", line),
+                )
+            })?);
+        }
+        Ok(())
+    }
+
+    /// Move the next chunk to the buffer once it is due.  Returns an error
+    /// of kind WouldBlock if `timeout` passes first.
+    fn fill(&mut self, timeout: Option<Duration>) -> io::Result<()> {
+        if !self.buf.is_empty() {
+            return Ok(());
+        }
+        self.next_chunk()?;
+        let (time, bytes) = match self.next.take() {
+            Some(next) => next,
+            None => return Ok(()),
+        };
+        if self.timing {
+            let wait = (self.start + time).saturating_duration_since(self.clock.now());
+            match timeout {
+                Some(timeout) if timeout < wait => {
+                    self.next = Some((time, bytes));
+                    thread::sleep(timeout);
+                    return Err(io::Error::new(
+                        io::ErrorKind::WouldBlock,
+                        "Timed out on playback read.",
+                    ));
+                }
+                _ => thread::sleep(wait),
+            }
+        }
+        self.buf.extend(bytes);
+        Ok(())
+    }
+}
+
+/// Parse a recording line, the time and the bytes.
+fn parse_recorded(line: &str) -> Option<(Duration, Vec<u8>)> {
+    let (time, hex) = line.split_once(' ')?;
+    let time = Duration::from_secs_f64(time.parse::<f64>().ok()?.max(0.0));
+    let hex = hex.trim().as_bytes();
+    if hex.len() % 2 != 0 {
+        return None;
+    }
+    let bytes = hex
+        .chunks(2)
+        .map(|pair| u8::from_str_radix(std::str::from_utf8(pair).ok()?, 16).ok())
+        .collect::<Option<Vec<u8>>>()?;
+    Some((time, bytes))
+}
+
+/// Reader used while parsing a single event, the first read waits up to
+/// the caller's timeout and the rest of a sequence must already be due.
+struct PlayerReader<'a, B: BufRead> {
+    input: &'a mut Player<B>,
+    timeout: Option<Duration>,
+    started: bool,
+}
+
+impl<'a, B: BufRead> Read for PlayerReader<'a, B> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let timeout = if self.started {
+            Some(Duration::from_millis(0))
+        } else {
+            self.timeout
+        };
+        self.started = true;
+        self.input.read_timeout(buf, timeout)
+    }
+}
+
+impl<B: BufRead> ConsoleRead for Player<B> {
+    fn get_event_and_raw(
+        &mut self,
+        timeout: Option<Duration>,
+    ) -> Option<io::Result<(Event, Vec<u8>)>> {
+        let mut leftover = self.leftover.take();
+        let options = self.options;
+        let res = {
+            let mut reader = PlayerReader {
+                started: leftover.is_some(),
+                input: self,
+                timeout,
+            };
+            event_and_raw(&mut reader, &mut leftover, &options)
+        };
+        self.leftover = leftover;
+        res
+    }
+
+    fn poll(&mut self, timeout: Option<Duration>) -> bool {
+        self.leftover.is_some() || (self.fill(timeout).is_ok() && !self.buf.is_empty())
+    }
+
+    fn read_timeout(&mut self, buf: &mut [u8], timeout: Option<Duration>) -> io::Result<usize> {
+        self.fill(timeout)?;
+        let len = buf.len().min(self.buf.len());
+        for (dst, src) in buf.iter_mut().zip(self.buf.drain(..len)) {
+            *dst = src;
+        }
+        Ok(len)
+    }
+}
+
+impl<B: BufRead> Read for Player<B> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.read_timeout(buf, None)
+    }
+}
+
+/// An async stream of the events read from a console reader.
+///
+/// Polling the stream reads without blocking, when no input is waiting the
+/// task is woken once the reader's descriptor becomes readable.  One shared
+/// thread watches the descriptors of all streams, none is needed per
+/// reader.  Events sent with a [`Waker`](crate::console::Waker) are only
+/// returned when input arrives, wake the task some other way if they
+/// matter.  Read the stream from one task at a time.
+///
+/// Requires the `async` feature and is only available on unix.
+///
+/// # Example
+///
+/// ```rust,no_run
+/// use futures_core::Stream;
+/// use sl_console::input::EventStream;
+/// use sl_console::*;
+///
+/// fn events() -> impl Stream<Item = std::io::Result<event::Event>> {
+///     con_init().unwrap();
+///     EventStream::new(conin())
+/// }
+/// ```
+#[cfg(all(unix, feature = "async"))]
+pub struct EventStream<R> {
+    input: R,
+}
+
+#[cfg(all(unix, feature = "async"))]
+impl<R: ConsoleRead + std::os::unix::io::AsRawFd> EventStream<R> {
+    /// Stream the events read from `input`.
+    pub fn new(input: R) -> Self {
+        EventStream { input }
+    }
+
+    /// The wrapped input.
+    pub fn get_ref(&self) -> &R {
+        &self.input
+    }
+
+    /// The wrapped input.
+    pub fn get_mut(&mut self) -> &mut R {
+        &mut self.input
+    }
+
+    /// Return the wrapped input.
+    pub fn into_inner(self) -> R {
+        self.input
+    }
+}
+
+#[cfg(all(unix, feature = "async"))]
+impl<R> futures_core::Stream for EventStream<R>
+where
+    R: ConsoleRead + std::os::unix::io::AsRawFd + Unpin,
+{
+    type Item = io::Result<Event>;
+
+    fn poll_next(
+        self: std::pin::Pin<&mut Self>,
+        cx: &mut std::task::Context<'_>,
+    ) -> std::task::Poll<Option<Self::Item>> {
+        use std::task::Poll;
+
+        let input = &mut self.get_mut().input;
+        match input.get_event_and_raw(Some(Duration::ZERO)) {
+            Some(Err(err)) if err.kind() == io::ErrorKind::WouldBlock => {
+                match crate::sys::ready::wake_when_readable(input.as_raw_fd(), cx.waker()) {
+                    Ok(()) => Poll::Pending,
+                    Err(err) => Poll::Ready(Some(Err(err))),
+                }
+            }
+            res => Poll::Ready(res.map(|res| res.map(|(event, _)| event))),
+        }
+    }
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
@@ -274,7 +1275,9 @@ mod test {
             &mut self,
             _timeout: Option<Duration>,
         ) -> Option<io::Result<(Event, Vec<u8>)>> {
-            LEFTOVER.with(|leftover| event_and_raw(self, &mut leftover.borrow_mut()))
+            LEFTOVER.with(|leftover| {
+                event_and_raw(self, &mut leftover.borrow_mut(), &ParseOptions::default())
+            })
         }
 
         fn poll(&mut self, _timeout: Option<Duration>) -> bool {
@@ -305,6 +1308,29 @@ mod test {
         assert!(i.next().is_none());
     }
 
+    #[test]
+    fn test_resync() {
+        let options = ParseOptions {
+            resync: true,
+            ..Default::default()
+        };
+        let mut input = &b"\x1B[\x00\x01\x02\xFE\x1B[Dx\xFF"[..];
+        let mut leftover = None;
+        let mut next =
+            || event_and_raw(&mut input, &mut leftover, &options).map(|res| res.unwrap());
+        assert_eq!(
+            next(),
+            Some((
+                Event::Garbled(b"\x1B[\x00\x01\x02\xFE".to_vec()),
+                b"\x1B[\x00\x01\x02\xFE".to_vec()
+            ))
+        );
+        assert_eq!(next().unwrap().0, Event::Key(Key::new(KeyCode::Left)));
+        assert_eq!(next().unwrap().0, Event::Key(Key::new(KeyCode::Char('x'))));
+        assert_eq!(next().unwrap().0, Event::Garbled(b"\xFF".to_vec()));
+        assert_eq!(next(), None);
+    }
+
     #[test]
     fn test_events() {
         let mut i = b"\x1B[\x00bc\x7F\x1B[D\
@@ -333,23 +1359,23 @@ mod test {
         );
         assert_eq!(
             i.next().unwrap().unwrap(),
-            Event::Mouse(MouseEvent::Press(MouseButton::WheelUp, 2, 4))
+            Event::Mouse(MouseEvent::Press(MouseButton::WheelUp, 2, 4), None)
         );
         assert_eq!(
             i.next().unwrap().unwrap(),
-            Event::Mouse(MouseEvent::Press(MouseButton::Left, 2, 4))
+            Event::Mouse(MouseEvent::Press(MouseButton::Left, 2, 4), None)
         );
         assert_eq!(
             i.next().unwrap().unwrap(),
-            Event::Mouse(MouseEvent::Press(MouseButton::Left, 2, 4))
+            Event::Mouse(MouseEvent::Press(MouseButton::Left, 2, 4), None)
         );
         assert_eq!(
             i.next().unwrap().unwrap(),
-            Event::Mouse(MouseEvent::Release(2, 4))
+            Event::Mouse(MouseEvent::Release(Some(MouseButton::Left), 2, 4), None)
         );
         assert_eq!(
             i.next().unwrap().unwrap(),
-            Event::Mouse(MouseEvent::Release(2, 4))
+            Event::Mouse(MouseEvent::Release(None, 2, 4), None)
         );
         assert_eq!(
             i.next().unwrap().unwrap(),
@@ -382,18 +1408,24 @@ mod test {
             assert_eq!(i.next().unwrap(), Event::Key(Key::new(KeyCode::Left)));
             assert_eq!(
                 i.next().unwrap(),
-                Event::Mouse(MouseEvent::Press(MouseButton::WheelUp, 2, 4))
+                Event::Mouse(MouseEvent::Press(MouseButton::WheelUp, 2, 4), None)
+            );
+            assert_eq!(
+                i.next().unwrap(),
+                Event::Mouse(MouseEvent::Press(MouseButton::Left, 2, 4), None)
+            );
+            assert_eq!(
+                i.next().unwrap(),
+                Event::Mouse(MouseEvent::Press(MouseButton::Left, 2, 4), None)
             );
             assert_eq!(
                 i.next().unwrap(),
-                Event::Mouse(MouseEvent::Press(MouseButton::Left, 2, 4))
+                Event::Mouse(MouseEvent::Release(Some(MouseButton::Left), 2, 4), None)
             );
             assert_eq!(
                 i.next().unwrap(),
-                Event::Mouse(MouseEvent::Press(MouseButton::Left, 2, 4))
+                Event::Mouse(MouseEvent::Release(None, 2, 4), None)
             );
-            assert_eq!(i.next().unwrap(), Event::Mouse(MouseEvent::Release(2, 4)));
-            assert_eq!(i.next().unwrap(), Event::Mouse(MouseEvent::Release(2, 4)));
             assert_eq!(i.next().unwrap(), Event::Key(Key::new(KeyCode::Char('b'))));
             assert!(i.next().is_none());
         }
@@ -436,4 +1468,261 @@ mod test {
         assert_eq!(st.next().unwrap().unwrap(), Key::new(KeyCode::Esc));
         assert!(st.next().is_none());
     }
+
+    #[test]
+    fn test_repeat_detector() {
+        let a = Event::Key(Key::new(KeyCode::Char('a')));
+        let b = Event::Key(Key::new(KeyCode::Char('b')));
+        let repeat = |event: &Event| match event {
+            Event::Key(key) => key.kind == KeyEventKind::Repeat,
+            _ => false,
+        };
+        let start = Instant::now();
+        let at = |ms| start + Duration::from_millis(ms);
+
+        let mut detector = RepeatDetector::default();
+        assert!(!repeat(&detector.mark(a.clone(), at(0))));
+        assert!(!repeat(&detector.mark(a.clone(), at(10))));
+
+        detector.set_threshold(Some(Duration::from_millis(50)));
+        assert!(!repeat(&detector.mark(a.clone(), at(0))));
+        assert!(repeat(&detector.mark(a.clone(), at(30))));
+        assert!(repeat(&detector.mark(a.clone(), at(60))));
+        assert!(!repeat(&detector.mark(a.clone(), at(200))));
+        assert!(!repeat(&detector.mark(b.clone(), at(210))));
+        assert!(!repeat(&detector.mark(a, at(220))));
+        assert!(!repeat(&detector.mark(b, at(230))));
+    }
+
+    #[test]
+    fn test_merge_combining() {
+        let key = |ch| {
+            Some(Ok((
+                Event::Key(Key::new(KeyCode::Char(ch))),
+                ch.to_string().into_bytes(),
+            )))
+        };
+        let would_block = || Some(Err(io::Error::new(io::ErrorKind::WouldBlock, "timeout")));
+
+        let mut input = vec![key('\u{301}'), key('\u{308}'), key('x')].into_iter();
+        let base = key('e').unwrap().unwrap();
+        let ((event, raw), pending) = merge_combining(base, || input.next().unwrap());
+        assert_eq!(event, Event::Grapheme("e\u{301}\u{308}".to_string()));
+        assert_eq!(raw, "e\u{301}\u{308}".as_bytes());
+        assert_eq!(
+            pending.unwrap().unwrap().0,
+            Event::Key(Key::new(KeyCode::Char('x')))
+        );
+
+        let base = key('e').unwrap().unwrap();
+        let ((event, _), pending) = merge_combining(base.clone(), would_block);
+        assert_eq!(event, base.0);
+        assert!(pending.is_none());
+
+        assert!(is_combining_base(&Key::new(KeyCode::Char('a'))));
+        assert!(!is_combining_base(&Key::new(KeyCode::Char('\u{301}'))));
+        assert!(!is_combining_base(&Key::new_mod(
+            KeyCode::Char('a'),
+            KeyMod::Alt
+        )));
+    }
+
+    #[test]
+    fn test_mouse_capture() {
+        assert_eq!(
+            MouseCapture::default().enable_sequence(),
+            "\x1b[?1000h\x1b[?1002h\x1b[?1005h\x1b[?1015h\x1b[?1006h"
+        );
+        assert_eq!(
+            MouseCapture::default().disable_sequence(),
+            "\x1b[?1006l\x1b[?1015l\x1b[?1005l\x1b[?1002l\x1b[?1000l"
+        );
+        let capture = MouseCapture::builder().motion().sgr().pixels().build();
+        assert_eq!(
+            capture.enable_sequence(),
+            "\x1b[?1003h\x1b[?1006h\x1b[?1016h"
+        );
+        assert_eq!(capture.mouse_encoding(), MouseEncoding::X10);
+        assert_eq!(
+            MouseCapture::default().mouse_encoding(),
+            MouseEncoding::Utf8
+        );
+        assert_eq!(MouseCapture::builder().build().enable_sequence(), "");
+        assert_eq!(
+            MouseMode::Motion.capture().enable_sequence(),
+            "\x1b[?1000h\x1b[?1003h\x1b[?1005h\x1b[?1015h\x1b[?1006h"
+        );
+
+        let replies = b"\x1b[?1000;1$y\x1b[?1002;2$y\x1b[?1006;1$y\x1b[?1016;0$y\x1b[?62;22c";
+        LEFTOVER.with(|leftover| *leftover.borrow_mut() = None);
+        let mut out = Vec::new();
+        let capture = query_mouse_capture(&mut &replies[..], &mut out).unwrap();
+        assert_eq!(capture, MouseCapture::builder().buttons().sgr().build());
+        assert!(out.ends_with(b"\x1b[?1016$p\x1b[c"));
+    }
+
+    /// Output that is never in raw mode, shared to be read after a guard
+    /// is dropped.
+    struct Term(std::rc::Rc<RefCell<Vec<u8>>>);
+
+    impl Write for Term {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.borrow_mut().write(buf)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    impl ConsoleWrite for Term {
+        fn set_raw_mode(&mut self, _mode: bool) -> io::Result<bool> {
+            Ok(false)
+        }
+
+        fn is_raw_mode(&self) -> bool {
+            false
+        }
+    }
+
+    #[test]
+    fn test_mouse_terminal() {
+        let replies = b"\x1b[?1000;1$y\x1b[?62;22c";
+        LEFTOVER.with(|leftover| *leftover.borrow_mut() = None);
+        let output = std::rc::Rc::default();
+        let term = Term(std::rc::Rc::clone(&output));
+        let mouse = MouseTerminal::try_new(term, &mut &replies[..], MouseMode::Buttons).unwrap();
+        assert_eq!(
+            mouse.previous(),
+            Some(MouseCapture::builder().buttons().build())
+        );
+        drop(mouse);
+        let enabled = MouseMode::Buttons.capture();
+        let expected = format!(
+            "{}{}\x1b[?1000h",
+            enabled.enable_sequence(),
+            enabled.disable_sequence()
+        );
+        assert!(output.borrow().ends_with(expected.as_bytes()));
+    }
+
+    #[test]
+    fn test_recorder_and_player() {
+        use crate::clock::TestClock;
+
+        let clock: &'static TestClock = Box::leak(Box::new(TestClock::new()));
+        LEFTOVER.with(|leftover| *leftover.borrow_mut() = None);
+        let mut rec = Recorder::with_clock(&b"a\x1B[A"[..], Vec::new(), clock);
+        let a = Event::Key(Key::new(KeyCode::Char('a')));
+        let up = Event::Key(Key::new(KeyCode::Up));
+        assert_eq!(rec.get_event_and_raw(None).unwrap().unwrap().0, a);
+        clock.advance(Duration::from_millis(1250));
+        assert_eq!(rec.get_event_and_raw(None).unwrap().unwrap().0, up);
+        let (_, recording) = rec.into_inner();
+        assert_eq!(recording, b"0.000000 61\n1.250000 1b5b41\n");
+
+        let recording = b"# comment\n0.000000 61\n\n1.250000 1b5b41\n1.5 1b\n";
+        let mut player = Player::with_clock(&recording[..], clock);
+        let mut next = || player.get_event_and_raw(None).map(|res| res.unwrap().0);
+        assert_eq!(next(), Some(a.clone()));
+        assert_eq!(next(), Some(up.clone()));
+        // A chunk is not joined with the next one.
+        assert_eq!(next(), Some(Event::Key(Key::new(KeyCode::Esc))));
+        assert_eq!(next(), None);
+
+        let mut player = Player::with_clock(&recording[..], clock);
+        player.set_timing(true);
+        assert_eq!(player.get_event_and_raw(None).unwrap().unwrap().0, a);
+        let timeout = Some(Duration::from_millis(1));
+        let err = player.get_event_and_raw(timeout).unwrap().unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::WouldBlock);
+        assert!(!player.poll(timeout));
+        clock.advance(Duration::from_secs(2));
+        assert!(player.poll(timeout));
+        assert_eq!(player.get_event_and_raw(timeout).unwrap().unwrap().0, up);
+
+        let mut player = Player::new(&b"0.1 6"[..]);
+        let err = player.get_event_and_raw(None).unwrap().unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[cfg(all(unix, feature = "async"))]
+    #[test]
+    fn test_event_stream() {
+        use futures_core::Stream;
+        use std::os::unix::io::{AsRawFd, RawFd};
+        use std::os::unix::net::UnixStream;
+        use std::pin::Pin;
+        use std::sync::Arc;
+        use std::task::{Context, Poll, Wake};
+
+        struct PipeIn(UnixStream, Option<u8>);
+
+        impl Read for PipeIn {
+            fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+                self.0.read(buf)
+            }
+        }
+
+        impl AsRawFd for PipeIn {
+            fn as_raw_fd(&self) -> RawFd {
+                self.0.as_raw_fd()
+            }
+        }
+
+        impl ConsoleRead for PipeIn {
+            fn get_event_and_raw(&mut self, _timeout: Option<Duration>) -> RawEvent {
+                let mut leftover = self.1.take();
+                let res = event_and_raw(&mut self.0, &mut leftover, &ParseOptions::default());
+                self.1 = leftover;
+                res
+            }
+
+            fn poll(&mut self, _timeout: Option<Duration>) -> bool {
+                false
+            }
+
+            fn read_timeout(
+                &mut self,
+                buf: &mut [u8],
+                _timeout: Option<Duration>,
+            ) -> io::Result<usize> {
+                self.read(buf)
+            }
+        }
+
+        struct Unpark(thread::Thread);
+
+        impl Wake for Unpark {
+            fn wake(self: Arc<Self>) {
+                self.0.unpark();
+            }
+        }
+
+        let (read, mut write) = UnixStream::pair().unwrap();
+        read.set_nonblocking(true).unwrap();
+        let mut stream = EventStream::new(PipeIn(read, None));
+        let waker = Arc::new(Unpark(thread::current())).into();
+        let mut cx = Context::from_waker(&waker);
+        assert!(Pin::new(&mut stream).poll_next(&mut cx).is_pending());
+        let writer = thread::spawn(move || {
+            thread::sleep(Duration::from_millis(20));
+            write.write_all(b"a").unwrap();
+        });
+        let event = loop {
+            match Pin::new(&mut stream).poll_next(&mut cx) {
+                Poll::Ready(event) => break event,
+                Poll::Pending => thread::park(),
+            }
+        };
+        assert_eq!(
+            event.unwrap().unwrap(),
+            Event::Key(Key::new(KeyCode::Char('a')))
+        );
+        writer.join().unwrap();
+        match Pin::new(&mut stream).poll_next(&mut cx) {
+            Poll::Ready(None) => {}
+            _ => panic!("expected the end of the stream"),
+        }
+    }
 }
@@ -0,0 +1,268 @@
+//! Keyboard macros.
+//!
+//! A [`MacroRecorder`] wraps a [`ConsoleRead`] and is read in its place.
+//! While recording, every key read through it is kept; stopping the
+//! recording stores the keys as a named macro.  Playing a macro queues its
+//! keys, they are returned by the following reads before any new input, so
+//! the application handles them exactly as if they had been typed.  This
+//! is what an editor needs for vim style `q`/`@` macros.
+//!
+//! Only [`Event::Key`] events are recorded.  Keys are replayed with the raw
+//! bytes they were read from (empty for macros made with
+//! [`MacroRecorder::insert`]).
+//!
+//! # Example
+//!
+//! ```rust,no_run
+//! use sl_console::event::{Event, Key, KeyCode};
+//! use sl_console::keymacro::MacroRecorder;
+//! use sl_console::*;
+//!
+//!     con_init().unwrap();
+//!     let mut conin = MacroRecorder::new(conin());
+//!     while let Some(Ok((event, _))) = conin.get_event_and_raw(None) {
+//!         match event {
+//!             Event::Key(Key { code: KeyCode::F(2), .. }) if conin.is_recording() => {
+//!                 conin.stop_recording();
+//!             }
+//!             Event::Key(Key { code: KeyCode::F(2), .. }) => conin.start_recording("m"),
+//!             Event::Key(Key { code: KeyCode::F(3), .. }) => {
+//!                 conin.play("m", 1);
+//!             }
+//!             Event::Key(Key { code: KeyCode::Esc, .. }) => break,
+//!             event => println!("{:?}", event),
+//!         }
+//!     }
+//! ```
+
+use std::collections::{HashMap, VecDeque};
+use std::io::{self, Read};
+use std::time::Duration;
+
+use crate::console::ConsoleRead;
+use crate::event::{Event, Key};
+
+/// Keys and the bytes they were read from.
+type Keys = Vec<(Key, Vec<u8>)>;
+
+/// A console reader that records and plays back keyboard macros.
+#[derive(Debug)]
+pub struct MacroRecorder<R> {
+    input: R,
+    macros: HashMap<String, Keys>,
+    // The name and keys of the macro being recorded.
+    recording: Option<(String, Keys)>,
+    // True if the last event returned was a recorded key.
+    recorded_last: bool,
+    // Keys being played back.
+    queue: VecDeque<(Key, Vec<u8>)>,
+}
+
+impl<R: ConsoleRead> MacroRecorder<R> {
+    /// Record macros from the keys read from `input`.
+    pub fn new(input: R) -> Self {
+        MacroRecorder {
+            input,
+            macros: HashMap::new(),
+            recording: None,
+            recorded_last: false,
+            queue: VecDeque::new(),
+        }
+    }
+
+    /// Start recording the macro `name`, ending any recording in progress
+    /// without storing it.
+    pub fn start_recording(&mut self, name: &str) {
+        self.recording = Some((name.to_string(), Vec::new()));
+        self.recorded_last = false;
+    }
+
+    /// Stop recording and store the macro, replacing any macro with the
+    /// same name.
+    ///
+    /// If the event read just before was a key then that key, the one that
+    /// made the application stop, is not part of the macro.  Returns the
+    /// name of the macro, None if nothing was being recorded.
+    pub fn stop_recording(&mut self) -> Option<String> {
+        let (name, mut keys) = self.recording.take()?;
+        if self.recorded_last {
+            keys.pop();
+        }
+        self.macros.insert(name.clone(), keys);
+        Some(name)
+    }
+
+    /// Stop recording without storing the macro.
+    pub fn cancel_recording(&mut self) {
+        self.recording = None;
+    }
+
+    /// True while a macro is being recorded.
+    pub fn is_recording(&self) -> bool {
+        self.recording.is_some()
+    }
+
+    /// The name of the macro being recorded.
+    pub fn recording_name(&self) -> Option<&str> {
+        self.recording.as_ref().map(|(name, _)| name.as_str())
+    }
+
+    /// Queue the keys of macro `name` `count` times, they are returned by
+    /// the following reads.  Returns false if there is no such macro.
+    pub fn play(&mut self, name: &str, count: usize) -> bool {
+        match self.macros.get(name) {
+            Some(keys) => {
+                for _ in 0..count {
+                    self.queue.extend(keys.iter().cloned());
+                }
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// True while keys of a macro are waiting to be read.
+    pub fn is_playing(&self) -> bool {
+        !self.queue.is_empty()
+    }
+
+    /// Drop the keys still waiting to be played back.
+    pub fn stop_playing(&mut self) {
+        self.queue.clear();
+    }
+
+    /// The keys of macro `name`.
+    pub fn keys(&self, name: &str) -> Option<Vec<Key>> {
+        self.macros
+            .get(name)
+            .map(|keys| keys.iter().map(|(key, _)| *key).collect())
+    }
+
+    /// Store `keys` as macro `name`, replacing any macro with the same
+    /// name.
+    pub fn insert(&mut self, name: &str, keys: Vec<Key>) {
+        let keys = keys.into_iter().map(|key| (key, Vec::new())).collect();
+        self.macros.insert(name.to_string(), keys);
+    }
+
+    /// Remove macro `name`, returns true if there was one.
+    pub fn remove(&mut self, name: &str) -> bool {
+        self.macros.remove(name).is_some()
+    }
+
+    /// The names of the stored macros, in no particular order.
+    pub fn names(&self) -> Vec<&str> {
+        self.macros.keys().map(|name| name.as_str()).collect()
+    }
+
+    /// The wrapped input.
+    pub fn get_ref(&self) -> &R {
+        &self.input
+    }
+
+    /// The wrapped input, reading from it directly bypasses recording and
+    /// playback.
+    pub fn get_mut(&mut self) -> &mut R {
+        &mut self.input
+    }
+
+    /// Return the wrapped input, dropping the macros.
+    pub fn into_inner(self) -> R {
+        self.input
+    }
+}
+
+impl<R: ConsoleRead> ConsoleRead for MacroRecorder<R> {
+    fn get_event_and_raw(
+        &mut self,
+        timeout: Option<Duration>,
+    ) -> Option<io::Result<(Event, Vec<u8>)>> {
+        let res = match self.queue.pop_front() {
+            Some((key, raw)) => Some(Ok((Event::Key(key), raw))),
+            None => self.input.get_event_and_raw(timeout),
+        };
+        self.recorded_last = false;
+        if let (Some((_, keys)), Some(Ok((Event::Key(key), raw)))) = (&mut self.recording, &res) {
+            keys.push((*key, raw.clone()));
+            self.recorded_last = true;
+        }
+        res
+    }
+
+    fn poll(&mut self, timeout: Option<Duration>) -> bool {
+        !self.queue.is_empty() || self.input.poll(timeout)
+    }
+
+    fn read_timeout(&mut self, buf: &mut [u8], timeout: Option<Duration>) -> io::Result<usize> {
+        self.input.read_timeout(buf, timeout)
+    }
+}
+
+/// Reads the wrapped input's bytes, bypassing recording and playback.
+impl<R: ConsoleRead> Read for MacroRecorder<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.input.read(buf)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::event::KeyCode;
+
+    fn next(conin: &mut MacroRecorder<&[u8]>) -> Option<Event> {
+        conin.get_event_and_raw(None).map(|res| res.unwrap().0)
+    }
+
+    fn key(ch: char) -> Event {
+        Event::Key(Key::new(KeyCode::Char(ch)))
+    }
+
+    #[test]
+    fn test_macro_recorder() {
+        let mut conin = MacroRecorder::new(&b"qaxyqz"[..]);
+        assert_eq!(next(&mut conin), Some(key('q')));
+        assert_eq!(next(&mut conin), Some(key('a')));
+        conin.start_recording("a");
+        assert_eq!(conin.recording_name(), Some("a"));
+        assert_eq!(next(&mut conin), Some(key('x')));
+        assert_eq!(next(&mut conin), Some(key('y')));
+        assert_eq!(next(&mut conin), Some(key('q')));
+        assert_eq!(conin.stop_recording(), Some("a".to_string()));
+        assert!(!conin.is_recording());
+        assert_eq!(
+            conin.keys("a"),
+            Some(vec![
+                Key::new(KeyCode::Char('x')),
+                Key::new(KeyCode::Char('y'))
+            ])
+        );
+
+        assert!(conin.play("a", 2));
+        assert!(!conin.play("b", 1));
+        assert!(conin.is_playing());
+        let (event, raw) = conin.get_event_and_raw(None).unwrap().unwrap();
+        assert_eq!((event, raw), (key('x'), b"x".to_vec()));
+        // Replayed keys are recorded too.
+        conin.start_recording("b");
+        assert_eq!(next(&mut conin), Some(key('y')));
+        assert_eq!(next(&mut conin), Some(key('x')));
+        assert_eq!(next(&mut conin), Some(key('y')));
+        assert!(!conin.is_playing());
+        assert_eq!(next(&mut conin), Some(key('z')));
+        assert_eq!(next(&mut conin), None);
+        // The last event was not a key, nothing is dropped.
+        conin.stop_recording();
+        assert_eq!(conin.keys("b").unwrap().len(), 4);
+
+        conin.insert("c", vec![Key::new(KeyCode::Char('\n'))]);
+        let mut names = conin.names();
+        names.sort_unstable();
+        assert_eq!(names, vec!["a", "b", "c"]);
+        conin.play("c", 3);
+        conin.stop_playing();
+        assert!(!conin.is_playing());
+        assert!(conin.remove("c"));
+        assert!(!conin.remove("c"));
+    }
+}
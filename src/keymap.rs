@@ -0,0 +1,368 @@
+//! Key sequence bindings.
+//!
+//! A [`KeyMap`] maps sequences of keys, such as `Ctrl+X Ctrl+C`, to
+//! actions.  It is a trie: every bound sequence is a path from the root and
+//! a key that starts a longer binding keeps the map waiting for the next
+//! key.  Feed it keys with [`KeyMap::feed`] or let [`KeyMap::read`] read
+//! them from the console, it returns the actions of completed sequences and
+//! passes back the keys and events that are not bound.
+//!
+//! When a sequence is a binding and also the start of a longer one (`g` and
+//! `g g` in vim) the map waits for the next key up to the
+//! [timeout](KeyMap::set_timeout) and then runs the shorter binding.  A key
+//! that does not continue a pending sequence ends it: the longest bound
+//! part runs (or the first key is passed back unbound) and the keys after
+//! it are matched again.
+//!
+//! Keys match on their code and modifiers, like
+//! [`Hotkeys`](crate::hotkeys::Hotkeys), and the base layout key is tried
+//! when the key itself does not match.
+//!
+//! # Example
+//!
+//! ```rust,no_run
+//! use sl_console::keymap::{Dispatch, KeyMap};
+//! use sl_console::*;
+//!
+//! #[derive(Clone)]
+//! enum Command {
+//!     Save,
+//!     Quit,
+//! }
+//!
+//!     con_init().unwrap();
+//!     let mut keymap = KeyMap::new();
+//!     keymap.bind_str("Ctrl+X Ctrl+S", Command::Save).unwrap();
+//!     keymap.bind_str("Ctrl+X Ctrl+C", Command::Quit).unwrap();
+//!     let mut conin = conin();
+//!     while let Some(Ok(dispatch)) = keymap.read(&mut conin) {
+//!         match dispatch {
+//!             Dispatch::Action(Command::Save) => println!("save"),
+//!             Dispatch::Action(Command::Quit) => break,
+//!             Dispatch::Key(key) => println!("{}", key),
+//!             Dispatch::Event(_) => {}
+//!         }
+//!     }
+//! ```
+
+use std::collections::{HashMap, VecDeque};
+use std::io;
+use std::time::Duration;
+
+use crate::console::ConsoleRead;
+use crate::event::{Event, Key, KeyCode, KeyEventKind, KeyMod, ParseKeyError};
+use crate::input::ConsoleReadExt;
+
+/// The part of a key that bindings match on.
+type Binding = (KeyCode, Option<KeyMod>);
+
+/// How long to wait for the next key of a sequence by default.
+const DEFAULT_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// What a [`KeyMap`] makes of the input.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Dispatch<T> {
+    /// A bound key sequence was typed.
+    Action(T),
+    /// A key that is not part of a binding.
+    Key(Key),
+    /// An event that is not a key press, returned by [`KeyMap::read`] only.
+    Event(Event),
+}
+
+#[derive(Debug)]
+struct Node<T> {
+    action: Option<T>,
+    children: HashMap<Binding, Node<T>>,
+}
+
+impl<T> Node<T> {
+    fn new() -> Self {
+        Node {
+            action: None,
+            children: HashMap::new(),
+        }
+    }
+
+    fn child(&self, key: &Key) -> Option<&Node<T>> {
+        self.children
+            .get(&(key.code, key.mods))
+            .or_else(|| self.children.get(&(key.alternate?, key.mods)))
+    }
+
+    /// Remove the binding for `keys` below this node, returns the action
+    /// and drops the nodes left with nothing in them.
+    fn remove(&mut self, keys: &[Key]) -> Option<T> {
+        let (first, rest) = match keys.split_first() {
+            Some(split) => split,
+            None => return self.action.take(),
+        };
+        let binding = (first.code, first.mods);
+        let child = self.children.get_mut(&binding)?;
+        let action = child.remove(rest);
+        if child.action.is_none() && child.children.is_empty() {
+            self.children.remove(&binding);
+        }
+        action
+    }
+}
+
+/// A trie of key sequences bound to actions.
+#[derive(Debug)]
+pub struct KeyMap<T> {
+    root: Node<T>,
+    timeout: Option<Duration>,
+    // The keys of the sequence typed so far.
+    pending: Vec<Key>,
+    // Results not returned by read yet.
+    ready: VecDeque<Dispatch<T>>,
+}
+
+impl<T> Default for KeyMap<T> {
+    fn default() -> Self {
+        KeyMap::new()
+    }
+}
+
+impl<T> KeyMap<T> {
+    /// An empty key map waiting one second for the next key of a sequence.
+    pub fn new() -> Self {
+        KeyMap {
+            root: Node::new(),
+            timeout: Some(DEFAULT_TIMEOUT),
+            pending: Vec::new(),
+            ready: VecDeque::new(),
+        }
+    }
+
+    /// Set how long [`read`](KeyMap::read) waits for the next key of a
+    /// sequence before ending it, None waits for ever.
+    pub fn set_timeout(&mut self, timeout: Option<Duration>) {
+        self.timeout = timeout;
+    }
+
+    /// How long to wait for the next key of a sequence.
+    pub fn timeout(&self) -> Option<Duration> {
+        self.timeout
+    }
+
+    /// Bind the sequence `keys` to `action`, returns the action it was bound
+    /// to before.  An empty sequence is not bound.
+    pub fn bind(&mut self, keys: &[Key], action: T) -> Option<T> {
+        if keys.is_empty() {
+            return None;
+        }
+        let mut node = &mut self.root;
+        for key in keys {
+            node = node
+                .children
+                .entry((key.code, key.mods))
+                .or_insert_with(Node::new);
+        }
+        node.action.replace(action)
+    }
+
+    /// Bind the sequence of key names separated by spaces, such as
+    /// `"Ctrl+X Ctrl+C"`, to `action`.  See [`Key`]'s `FromStr` for the
+    /// names, a space key is `Space`.
+    pub fn bind_str(&mut self, keys: &str, action: T) -> Result<Option<T>, ParseKeyError> {
+        let keys = keys
+            .split_whitespace()
+            .map(str::parse)
+            .collect::<Result<Vec<Key>, _>>()?;
+        Ok(self.bind(&keys, action))
+    }
+
+    /// Remove the binding for `keys`, returns its action.  Longer sequences
+    /// that start with `keys` stay bound.
+    pub fn unbind(&mut self, keys: &[Key]) -> Option<T> {
+        if keys.is_empty() {
+            return None;
+        }
+        self.root.remove(keys)
+    }
+
+    /// The action bound to `keys`.
+    pub fn get(&self, keys: &[Key]) -> Option<&T> {
+        self.node(keys)?.action.as_ref()
+    }
+
+    /// Remove all bindings and drop any pending keys.
+    pub fn clear(&mut self) {
+        self.root = Node::new();
+        self.pending.clear();
+        self.ready.clear();
+    }
+
+    /// The keys of the sequence typed so far, empty unless the map is
+    /// waiting for the next key.
+    pub fn pending(&self) -> &[Key] {
+        &self.pending
+    }
+
+    fn node(&self, keys: &[Key]) -> Option<&Node<T>> {
+        keys.iter()
+            .try_fold(&self.root, |node, key| node.child(key))
+    }
+}
+
+impl<T: Clone> KeyMap<T> {
+    /// Match `key` after the keys typed before it.
+    ///
+    /// Returns nothing while the keys so far start a longer binding, the
+    /// action once a sequence completes and the keys that are not bound.
+    /// A key that ends a pending sequence without completing it can return
+    /// several results.  Key releases should not be fed.
+    pub fn feed(&mut self, key: Key) -> Vec<Dispatch<T>> {
+        self.pending.push(key);
+        self.resolve(false)
+    }
+
+    /// End the pending sequence, because the timeout passed or there is no
+    /// more input: a bound sequence runs even if it starts a longer one.
+    pub fn flush(&mut self) -> Vec<Dispatch<T>> {
+        self.resolve(true)
+    }
+
+    fn resolve(&mut self, flush: bool) -> Vec<Dispatch<T>> {
+        let mut out = Vec::new();
+        while !self.pending.is_empty() {
+            match self.node(&self.pending) {
+                Some(node) if !node.children.is_empty() && !flush => break,
+                Some(Node {
+                    action: Some(action),
+                    ..
+                }) => {
+                    out.push(Dispatch::Action(action.clone()));
+                    self.pending.clear();
+                }
+                _ => {
+                    // The longest bound start of the keys runs and the rest
+                    // are matched again, without one the first key is not
+                    // bound.
+                    let bound = (1..self.pending.len()).rev().find_map(|len| {
+                        self.get(&self.pending[..len])
+                            .map(|action| (len, action.clone()))
+                    });
+                    match bound {
+                        Some((len, action)) => {
+                            out.push(Dispatch::Action(action));
+                            self.pending.drain(..len);
+                        }
+                        None => out.push(Dispatch::Key(self.pending.remove(0))),
+                    }
+                }
+            }
+        }
+        out
+    }
+
+    /// Read from `conin` until there is something to return.
+    ///
+    /// While a sequence is pending waits up to the timeout for the next key
+    /// and then ends the sequence.  Events other than key presses are
+    /// returned as they arrive, also in the middle of a sequence.  Returns
+    /// None when `conin` has no more input and nothing is left to return.
+    pub fn read<R: ConsoleRead>(&mut self, conin: &mut R) -> Option<io::Result<Dispatch<T>>> {
+        loop {
+            if let Some(dispatch) = self.ready.pop_front() {
+                return Some(Ok(dispatch));
+            }
+            let res = match self.timeout {
+                Some(timeout) if !self.pending.is_empty() => conin.get_event_timeout(timeout),
+                _ => conin.get_event(),
+            };
+            match res {
+                Some(Ok(Event::Key(key))) if key.kind != KeyEventKind::Release => {
+                    let out = self.feed(key);
+                    self.ready.extend(out);
+                }
+                Some(Ok(event)) => return Some(Ok(Dispatch::Event(event))),
+                Some(Err(err)) if err.kind() == io::ErrorKind::WouldBlock => {
+                    let out = self.flush();
+                    self.ready.extend(out);
+                }
+                Some(Err(err)) => return Some(Err(err)),
+                None if self.pending.is_empty() => return None,
+                None => {
+                    let out = self.flush();
+                    self.ready.extend(out);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn keys(names: &str) -> Vec<Key> {
+        names
+            .split_whitespace()
+            .map(|name| name.parse().unwrap())
+            .collect()
+    }
+
+    fn key(name: &str) -> Dispatch<&'static str> {
+        Dispatch::Key(name.parse().unwrap())
+    }
+
+    #[test]
+    fn test_keymap_feed() {
+        let mut keymap = KeyMap::new();
+        assert_eq!(keymap.bind_str("Ctrl+X Ctrl+C", "quit"), Ok(None));
+        keymap.bind_str("Ctrl+X Ctrl+S", "save").unwrap();
+        keymap.bind_str("g", "go").unwrap();
+        keymap.bind_str("g g", "top").unwrap();
+        assert_eq!(keymap.get(&keys("Ctrl+X Ctrl+S")), Some(&"save"));
+        assert_eq!(keymap.get(&keys("Ctrl+X")), None);
+        assert!(keymap.bind_str("Ctrl+Foo", "bad").is_err());
+
+        let mut feed = |names: &str| {
+            keys(names)
+                .into_iter()
+                .flat_map(|key| keymap.feed(key))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(feed("Ctrl+X"), vec![]);
+        assert_eq!(feed("Ctrl+C"), vec![Dispatch::Action("quit")]);
+        assert_eq!(
+            feed("a Ctrl+X Ctrl+S"),
+            vec![key("a"), Dispatch::Action("save")]
+        );
+        assert_eq!(feed("g g"), vec![Dispatch::Action("top")]);
+        // The pending g runs when the next key does not continue it.
+        assert_eq!(feed("g x"), vec![Dispatch::Action("go"), key("x")]);
+        // Ctrl+X is not bound on its own, g is matched again.
+        assert_eq!(feed("Ctrl+X g"), vec![key("Ctrl+X")]);
+        assert_eq!(keymap.pending(), &keys("g")[..]);
+        assert_eq!(keymap.flush(), vec![Dispatch::Action("go")]);
+        assert_eq!(keymap.flush(), vec![]);
+
+        assert_eq!(keymap.unbind(&keys("g")), Some("go"));
+        assert_eq!(keymap.get(&keys("g g")), Some(&"top"));
+        assert_eq!(keymap.unbind(&keys("g g")), Some("top"));
+        assert_eq!(keymap.feed(keys("g")[0]), vec![key("g")]);
+    }
+
+    #[test]
+    fn test_keymap_read() {
+        let mut keymap = KeyMap::new();
+        keymap.bind_str("Ctrl+X Ctrl+C", "quit").unwrap();
+        keymap.bind_str("g", "go").unwrap();
+        keymap.bind_str("g g", "top").unwrap();
+        let mut conin = &b"\x18\x1B[<0;1;1M\x03xggg"[..];
+        let mut read = || keymap.read(&mut conin).map(|res| res.unwrap());
+        assert!(matches!(read(), Some(Dispatch::Event(Event::Mouse(..)))));
+        assert_eq!(read(), Some(Dispatch::Action("quit")));
+        assert_eq!(read(), Some(key("x")));
+        assert_eq!(read(), Some(Dispatch::Action("top")));
+        // The input ended, the pending g runs.
+        assert_eq!(read(), Some(Dispatch::Action("go")));
+        assert_eq!(read(), None);
+
+        let mut conin = &b"\x18"[..];
+        assert_eq!(keymap.read(&mut conin).unwrap().unwrap(), key("Ctrl+X"));
+    }
+}
@@ -0,0 +1,221 @@
+//! An Emacs style kill ring for line editors.
+//!
+//! A [`KillRing`] keeps the text removed by kill commands so it can be
+//! yanked back.  An editor maps the usual keys onto it:
+//!
+//! * Ctrl-K (kill to the end of line) and Ctrl-U (kill to the start of line)
+//!   call [`KillRing::kill`], appending or prepending when kills follow each
+//!   other.
+//! * Ctrl-Y calls [`KillRing::yank`] and inserts the text.
+//! * Alt-Y right after a yank calls [`KillRing::yank_pop`] and replaces the
+//!   yanked text with the older entry.
+//! * Any other command calls [`KillRing::end_kill`].
+//!
+//! With [`KillRing::set_clipboard`] on, every kill is also put on the
+//! system clipboard of the user's terminal with OSC 52 (see
+//! [`SetClipboard`]), which works over SSH where the terminal supports it.
+//!
+//! # Example
+//!
+//! ```rust
+//! use sl_console::killring::{KillRing, KillTo};
+//!
+//! let mut ring = KillRing::new(10);
+//! ring.kill("world", KillTo::End);
+//! ring.end_kill();
+//! ring.kill("hello ", KillTo::End);
+//! assert_eq!(ring.yank(), Some("hello "));
+//! assert_eq!(ring.yank_pop(), Some("world"));
+//! ```
+
+use std::collections::VecDeque;
+use std::fmt;
+use std::io::{self, Write};
+
+const BASE64: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+/// Put text on the terminal's clipboard (OSC 52).
+///
+/// Terminals may refuse (many need it enabled) and some limit the length.
+/// Inside tmux or screen wrap it in a
+/// [`Passthrough`](crate::passthrough::Passthrough) to reach the terminal.
+#[derive(Copy, Clone, PartialEq, Eq)]
+pub struct SetClipboard<'a>(pub &'a str);
+
+impl<'a> fmt::Display for SetClipboard<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("\x1B]52;c;")?;
+        for chunk in self.0.as_bytes().chunks(3) {
+            let bits = chunk
+                .iter()
+                .enumerate()
+                .fold(0u32, |bits, (i, b)| bits | (*b as u32) << (16 - 8 * i));
+            let mut out = [b'='; 4];
+            for (i, out) in out.iter_mut().enumerate().take(chunk.len() + 1) {
+                *out = BASE64[(bits >> (18 - 6 * i) & 0x3F) as usize];
+            }
+            // The alphabet and padding are ASCII.
+            f.write_str(std::str::from_utf8(&out).unwrap_or_default())?;
+        }
+        f.write_str("\x07")
+    }
+}
+
+/// Where a kill joins the previous one when kills follow each other.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum KillTo {
+    /// Killed forwards (Ctrl-K), append the text.
+    End,
+    /// Killed backwards (Ctrl-U), prepend the text.
+    Start,
+}
+
+/// A ring of killed text.
+#[derive(Debug, Clone)]
+pub struct KillRing {
+    // The newest kill first.
+    entries: VecDeque<String>,
+    capacity: usize,
+    // True while kills follow each other and join.
+    killing: bool,
+    // The entry last yanked, while yank_pop can follow.
+    yanked: Option<usize>,
+    clipboard: bool,
+    // A kill not yet sent to the clipboard.
+    clipboard_pending: bool,
+}
+
+impl KillRing {
+    /// A ring that keeps the last `capacity` kills (at least one).
+    pub fn new(capacity: usize) -> Self {
+        KillRing {
+            entries: VecDeque::new(),
+            capacity: capacity.max(1),
+            killing: false,
+            yanked: None,
+            clipboard: false,
+            clipboard_pending: false,
+        }
+    }
+
+    /// Also put each kill on the terminal's clipboard, written by
+    /// [`sync_clipboard`](KillRing::sync_clipboard).  Off by default.
+    pub fn set_clipboard(&mut self, clipboard: bool) {
+        self.clipboard = clipboard;
+    }
+
+    /// True if kills are put on the terminal's clipboard.
+    pub fn clipboard(&self) -> bool {
+        self.clipboard
+    }
+
+    /// Add killed `text`, joined with the previous kill if nothing ended
+    /// the kill sequence since.
+    pub fn kill(&mut self, text: &str, to: KillTo) {
+        if text.is_empty() {
+            return;
+        }
+        match self.entries.front_mut() {
+            Some(last) if self.killing => match to {
+                KillTo::End => last.push_str(text),
+                KillTo::Start => last.insert_str(0, text),
+            },
+            _ => {
+                self.entries.push_front(text.to_string());
+                self.entries.truncate(self.capacity);
+            }
+        }
+        self.killing = true;
+        self.yanked = None;
+        self.clipboard_pending = self.clipboard;
+    }
+
+    /// End the kill sequence, the next kill starts a new entry.  Call it
+    /// for every command that is not a kill or yank.
+    pub fn end_kill(&mut self) {
+        self.killing = false;
+        self.yanked = None;
+    }
+
+    /// The text to insert for a yank, the newest kill.
+    pub fn yank(&mut self) -> Option<&str> {
+        self.killing = false;
+        let entry = self.entries.front()?;
+        self.yanked = Some(0);
+        Some(entry)
+    }
+
+    /// The text to replace the last yank with, the kill before it.
+    ///
+    /// Returns None (and nothing should change) unless the last command
+    /// was a yank or yank pop.
+    pub fn yank_pop(&mut self) -> Option<&str> {
+        let next = (self.yanked? + 1) % self.entries.len();
+        self.yanked = Some(next);
+        Some(&self.entries[next])
+    }
+
+    /// The kills, newest first.
+    pub fn entries(&self) -> impl Iterator<Item = &str> {
+        self.entries.iter().map(|entry| entry.as_str())
+    }
+
+    /// Put the newest kill on the terminal's clipboard if the clipboard is
+    /// on and it changed since the last call.
+    pub fn sync_clipboard<W: Write>(&mut self, out: &mut W) -> io::Result<()> {
+        if !self.clipboard_pending {
+            return Ok(());
+        }
+        self.clipboard_pending = false;
+        if let Some(entry) = self.entries.front() {
+            write!(out, "{}", SetClipboard(entry))?;
+            out.flush()?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_kill_ring() {
+        let mut ring = KillRing::new(2);
+        assert_eq!(ring.yank(), None);
+        assert_eq!(ring.yank_pop(), None);
+        ring.kill("b", KillTo::End);
+        ring.kill("c", KillTo::End);
+        ring.kill("a", KillTo::Start);
+        ring.end_kill();
+        ring.kill("x", KillTo::End);
+        ring.end_kill();
+        ring.kill("y", KillTo::End);
+        assert_eq!(ring.entries().collect::<Vec<_>>(), vec!["y", "x"]);
+
+        assert_eq!(ring.yank(), Some("y"));
+        assert_eq!(ring.yank_pop(), Some("x"));
+        assert_eq!(ring.yank_pop(), Some("y"));
+        ring.end_kill();
+        assert_eq!(ring.yank_pop(), None);
+    }
+
+    #[test]
+    fn test_clipboard() {
+        assert_eq!(SetClipboard("").to_string(), "\x1B]52;c;\x07");
+        assert_eq!(SetClipboard("hi!").to_string(), "\x1B]52;c;aGkh\x07");
+        assert_eq!(SetClipboard("hi").to_string(), "\x1B]52;c;aGk=\x07");
+        assert_eq!(SetClipboard("h").to_string(), "\x1B]52;c;aA==\x07");
+
+        let mut ring = KillRing::new(4);
+        let mut out = Vec::new();
+        ring.kill("a", KillTo::End);
+        ring.sync_clipboard(&mut out).unwrap();
+        assert!(out.is_empty());
+        ring.set_clipboard(true);
+        ring.kill("b", KillTo::End);
+        ring.sync_clipboard(&mut out).unwrap();
+        ring.sync_clipboard(&mut out).unwrap();
+        assert_eq!(out, b"\x1B]52;c;YWI=\x07");
+    }
+}
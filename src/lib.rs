@@ -19,25 +19,55 @@ mod sys;
 mod sys;
 
 pub use console::{con_init, conin, conout, ConsoleRead, ConsoleWrite};
+pub use cursor::PrintAtExt;
 pub use input::ConsoleReadExt;
 pub use raw::RawModeExt;
 pub use sys::size::terminal_size;
-#[cfg(unix)]
-pub use sys::size::terminal_size_pixels;
 pub use sys::tty::is_tty;
+pub use window::terminal_size_pixels;
 
 #[macro_use]
 mod macros;
+pub mod backend;
+pub mod bell;
+pub mod caps;
+pub mod cast;
+#[cfg(all(unix, feature = "pty"))]
+pub mod child;
 pub mod clear;
+mod clock;
 pub mod color;
 pub mod console;
 pub mod cursor;
+pub mod debug;
+pub mod diagnostics;
+pub mod escape;
 pub mod event;
+pub mod frame;
+pub mod grid;
+pub mod headless;
+pub mod hotkeys;
 pub mod input;
+pub mod keymacro;
+pub mod keymap;
+pub mod killring;
+pub mod metrics;
+pub mod multi;
+pub mod passthrough;
+pub mod quirks;
 pub mod raw;
+pub mod resize;
+pub mod scrape;
 pub mod screen;
 pub mod scroll;
 pub mod style;
+#[cfg(all(unix, feature = "testing"))]
+pub mod testing;
+pub mod text;
+pub mod theme;
+pub mod transport;
+pub mod width;
+pub mod window;
 
 #[cfg(test)]
 mod test {
@@ -1,31 +1,249 @@
 /// Create a CSI-introduced sequence.
+///
+/// Expands to a `&'static str` literal, the arguments are concatenated after
+/// `ESC [`.
+///
+/// ```rust
+/// use sl_console::csi;
+///
+/// assert_eq!(csi!("?25l"), "\x1B[?25l");
+/// ```
+#[macro_export]
 macro_rules! csi {
     ($( $l:expr ),*) => { concat!("\x1B[", $( $l ),*) };
 }
 
-/// Derive a CSI sequence struct.
-macro_rules! derive_csi_sequence {
-    ($doc:expr, $name:ident, $value:expr) => {
-        #[doc = $doc]
+/// Define a unit struct for a parameterless CSI sequence.
+///
+/// The struct implements `Display` (writing the sequence),
+/// `AsRef<[u8]>`/`AsRef<str>` and [`Escape`](crate::escape::Escape) and has
+/// the sequence in its `STR` and `BYTES` constants, the same way as the
+/// sequences this crate provides, so it can be used to cover terminal
+/// specific sequences the crate does not.  The attributes (including doc
+/// comments) and visibility are applied to the struct and the value is the
+/// part after `ESC [`.
+///
+/// ```rust
+/// use sl_console::define_csi_sequence;
+///
+/// define_csi_sequence!(
+///     /// Enable the focus in/out reports.
+///     pub FocusReportingOn,
+///     "?1004h"
+/// );
+///
+/// assert_eq!(FocusReportingOn.to_string(), "\x1B[?1004h");
+/// assert_eq!(FocusReportingOn::BYTES, b"\x1B[?1004h");
+/// ```
+#[macro_export]
+macro_rules! define_csi_sequence {
+    ($(#[$meta:meta])* $vis:vis $name:ident, $value:expr) => {
+        $crate::define_csi_sequence!(@when true; $(#[$meta])* $vis $name, $value);
+    };
+    // Display writes the sequence only if $when is true.
+    (@when $when:expr; $(#[$meta:meta])* $vis:vis $name:ident, $value:expr) => {
+        $(#[$meta])*
         #[derive(Copy, Clone)]
-        pub struct $name;
+        $vis struct $name;
+
+        impl $name {
+            /// The escape sequence.
+            pub const STR: &'static str = $crate::csi!($value);
+            /// The escape sequence as bytes.
+            pub const BYTES: &'static [u8] = Self::STR.as_bytes();
+        }
 
-        impl fmt::Display for $name {
-            fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-                write!(f, csi!($value))
+        impl ::std::fmt::Display for $name {
+            fn fmt(&self, f: &mut ::std::fmt::Formatter) -> ::std::fmt::Result {
+                if $when {
+                    f.write_str(Self::STR)
+                } else {
+                    Ok(())
+                }
             }
         }
 
         impl AsRef<[u8]> for $name {
             fn as_ref(&self) -> &'static [u8] {
-                csi!($value).as_bytes()
+                Self::BYTES
             }
         }
 
         impl AsRef<str> for $name {
             fn as_ref(&self) -> &'static str {
-                csi!($value)
+                Self::STR
             }
         }
+
+        impl $crate::escape::Escape for $name {
+            fn len_hint(&self) -> usize {
+                Self::BYTES.len()
+            }
+
+            fn write_into(&self, buf: &mut [u8]) -> usize {
+                buf[..Self::BYTES.len()].copy_from_slice(Self::BYTES);
+                Self::BYTES.len()
+            }
+
+            fn as_bytes(&self) -> ::std::borrow::Cow<'static, [u8]> {
+                ::std::borrow::Cow::Borrowed(Self::BYTES)
+            }
+        }
+    };
+}
+
+/// Derive a CSI sequence struct.
+macro_rules! derive_csi_sequence {
+    ($doc:expr, $name:ident, $value:expr) => {
+        define_csi_sequence!(#[doc = $doc] pub $name, $value);
+    };
+}
+
+/// Derive a CSI sequence struct for an SGR (style) sequence, displayed only
+/// if the [style policy](crate::style::set_policy) allows styles.
+macro_rules! derive_sgr_sequence {
+    ($doc:expr, $name:ident, $value:expr) => {
+        define_csi_sequence!(@when $crate::style::enabled(); #[doc = $doc] pub $name, $value);
+    };
+}
+
+/// Write escape sequences and formatted text with one write and one flush.
+///
+/// The writer comes first followed by `;` separated items, each item is
+/// either a value implementing `Display` (such as the sequences in this
+/// crate) or a format string with its arguments as for `write!`.  The items
+/// are formatted into one buffer which is then written with a single
+/// `write_all` followed by a `flush`, instead of a system call per `write!`.
+/// Evaluates to an `io::Result<()>`.
+///
+/// ```rust
+/// use sl_console::{cursor, style, write_console};
+///
+/// let mut out = Vec::new();
+/// let name = "world";
+/// write_console!(out; cursor::Goto(1, 1); style::Bold; "Hello {}!", name; style::Reset).unwrap();
+/// assert_eq!(out, b"\x1B[1;1H\x1B[1mHello world!\x1B[m");
+/// ```
+#[macro_export]
+macro_rules! write_console {
+    (@push $buf:ident;) => {};
+    (@push $buf:ident; $fmt:literal $(, $arg:expr)* $(; $($rest:tt)*)?) => {
+        {
+            use ::std::fmt::Write;
+            // Writing to a String can not fail.
+            let _ = write!($buf, $fmt $(, $arg)*);
+        }
+        $crate::write_console!(@push $buf; $($($rest)*)?);
+    };
+    (@push $buf:ident; $item:expr $(; $($rest:tt)*)?) => {
+        {
+            use ::std::fmt::Write;
+            let _ = write!($buf, "{}", $item);
+        }
+        $crate::write_console!(@push $buf; $($($rest)*)?);
+    };
+    ($out:expr; $($items:tt)*) => {{
+        use ::std::io::Write;
+        let mut buf = String::new();
+        $crate::write_console!(@push buf; $($items)*);
+        let out = &mut $out;
+        out.write_all(buf.as_bytes()).and_then(|_| out.flush())
+    }};
+}
+
+/// Assert that a [`Grid`](crate::grid::Grid) shows the expected frame.
+///
+/// The grid is drawn on a blank [`Headless`](crate::headless::Headless)
+/// terminal with [`diff`](crate::grid::diff) and
+/// [`apply_patches`](crate::grid::apply_patches), so the escape sequences the
+/// renderer writes are checked as well as the grid, and the result is
+/// compared with the expected [`snapshot`](crate::grid::Grid::snapshot).
+/// Lines of the expected snapshot are trimmed and blank lines ignored.  On
+/// a mismatch both frames are printed.
+///
+/// ```rust
+/// use sl_console::assert_frame_eq;
+/// use sl_console::grid::{Attrs, CellStyle, Grid};
+///
+/// let mut grid = Grid::new(12, 2);
+/// let bold = CellStyle {
+///     attrs: Attrs::BOLD,
+///     ..Default::default()
+/// };
+/// grid.put_str(0, 0, "Hello", bold);
+/// grid.put_str(6, 0, "world", CellStyle::default());
+/// assert_frame_eq!(
+///     grid,
+///     r#"
+///     "Hello world"
+///         0..5: bold
+///     ""
+///     "#
+/// );
+/// ```
+#[macro_export]
+macro_rules! assert_frame_eq {
+    ($grid:expr, $expected:expr $(,)?) => {{
+        let actual =
+            $crate::grid::normalize_snapshot(&$crate::grid::render_headless(&$grid).snapshot());
+        let expected = $crate::grid::normalize_snapshot($expected);
+        if actual != expected {
+            panic!(
+                "frames differ\n--- expected\n{}\n--- actual\n{}\n",
+                expected, actual
+            );
+        }
+    }};
+}
+
+/// Print styled text to the console, atomically.
+///
+/// Takes the same `;` separated items as [`write_console!`] without the
+/// writer.  The items and a [`Reset`](crate::style::Reset) are formatted
+/// (following the [style policy](crate::style::set_policy)) into one buffer
+/// and written with one write and flush while holding the console lock, so
+/// output from several threads never interleaves and a style never leaks
+/// into the next print.  Evaluates to an `io::Result<()>`, an error if the
+/// console could not be opened.
+///
+/// ```rust,no_run
+/// use sl_console::{color, cprint, style};
+///
+/// sl_console::con_init().unwrap();
+/// cprint!(color::Fg(color::Red); style::Bold; "error: ").unwrap();
+/// cprint!("{} files", 3).unwrap();
+/// ```
+#[macro_export]
+macro_rules! cprint {
+    (@end $end:expr; $($items:tt)*) => {{
+        let mut buf = String::new();
+        $crate::write_console!(@push buf; $($items)*);
+        $crate::write_console!(@push buf; $crate::style::Reset; $end);
+        $crate::console::conout_r().and_then(|conout| {
+            use ::std::io::Write;
+            let mut out = conout.lock();
+            out.write_all(buf.as_bytes()).and_then(|_| out.flush())
+        })
+    }};
+    ($($items:tt)*) => {
+        $crate::cprint!(@end ""; $($items)*)
+    };
+}
+
+/// Print styled text and a newline to the console, atomically.
+///
+/// As [`cprint!`], the newline follows the reset.
+///
+/// ```rust,no_run
+/// use sl_console::{color, cprintln};
+///
+/// sl_console::con_init().unwrap();
+/// cprintln!(color::Fg(color::Green); "done in {}ms", 12).unwrap();
+/// cprintln!().unwrap();
+/// ```
+#[macro_export]
+macro_rules! cprintln {
+    ($($items:tt)*) => {
+        $crate::cprint!(@end "\n"; $($items)*)
     };
 }
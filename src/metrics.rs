@@ -0,0 +1,250 @@
+//! Input parsing metrics.
+//!
+//! When enabled with [`set_enabled`] the console input counts the events it
+//! parses, the bytes they used, the parse failures and the time from the
+//! first byte of an event arriving to the event being returned.
+//! [`metrics`] returns a snapshot, so benchmarks can detect regressions in
+//! the reading, parsing and locking path.  Only events parsed from input are
+//! counted, not ticks, wake ups or IO readiness.  Disabled by default, when
+//! disabled the cost is one atomic load per event.
+//!
+//! # Example
+//!
+//! ```rust,no_run
+//! use sl_console::*;
+//!
+//!     con_init().unwrap();
+//!     metrics::set_enabled(true);
+//!     for event in conin().events().take(100) {
+//!         let _ = event;
+//!     }
+//!     let stats = metrics::metrics();
+//!     println!(
+//!         "{:.1} events/s, mean latency {:?}",
+//!         stats.events_per_sec(),
+//!         stats.mean_latency()
+//!     );
+//! ```
+
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+
+use lazy_static::lazy_static;
+use parking_lot::Mutex;
+
+use crate::event::Event;
+
+static COUNTERS: Counters = Counters::new();
+
+lazy_static! {
+    static ref STARTED: Mutex<Instant> = Mutex::new(Instant::now());
+}
+
+/// A snapshot of the input metrics.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Metrics {
+    /// Events parsed from input.
+    pub events: u64,
+    /// Input bytes used by the events.
+    pub bytes: u64,
+    /// Input that could not be parsed, unsupported sequences and read
+    /// errors.
+    pub parse_failures: u64,
+    /// The sum of the times from the first byte arriving to the event being
+    /// returned.
+    pub total_latency: Duration,
+    /// The longest time from the first byte arriving to the event being
+    /// returned.
+    pub max_latency: Duration,
+    /// The time since the metrics were enabled or reset.
+    pub elapsed: Duration,
+}
+
+impl Metrics {
+    /// Events per second over [`elapsed`](Metrics::elapsed).
+    pub fn events_per_sec(&self) -> f64 {
+        match self.elapsed.as_secs_f64() {
+            secs if secs > 0.0 => self.events as f64 / secs,
+            _ => 0.0,
+        }
+    }
+
+    /// The average number of input bytes per event.
+    pub fn bytes_per_event(&self) -> f64 {
+        match self.events {
+            0 => 0.0,
+            events => self.bytes as f64 / events as f64,
+        }
+    }
+
+    /// The average time from the first byte arriving to the event being
+    /// returned.
+    pub fn mean_latency(&self) -> Duration {
+        match self.events {
+            0 => Duration::ZERO,
+            events => self.total_latency / events.min(u32::MAX as u64) as u32,
+        }
+    }
+}
+
+/// The counters behind the metrics, [`COUNTERS`] for the console input.
+struct Counters {
+    enabled: AtomicBool,
+    events: AtomicU64,
+    bytes: AtomicU64,
+    failures: AtomicU64,
+    latency_total: AtomicU64,
+    latency_max: AtomicU64,
+}
+
+impl Counters {
+    const fn new() -> Self {
+        Counters {
+            enabled: AtomicBool::new(false),
+            events: AtomicU64::new(0),
+            bytes: AtomicU64::new(0),
+            failures: AtomicU64::new(0),
+            latency_total: AtomicU64::new(0),
+            latency_max: AtomicU64::new(0),
+        }
+    }
+
+    fn set_enabled(&self, enabled: bool) {
+        if enabled {
+            self.reset();
+        }
+        self.enabled.store(enabled, Ordering::Relaxed);
+    }
+
+    fn is_enabled(&self) -> bool {
+        self.enabled.load(Ordering::Relaxed)
+    }
+
+    fn reset(&self) {
+        for counter in [
+            &self.events,
+            &self.bytes,
+            &self.failures,
+            &self.latency_total,
+            &self.latency_max,
+        ] {
+            counter.store(0, Ordering::Relaxed);
+        }
+    }
+
+    fn snapshot(&self, elapsed: Duration) -> Metrics {
+        Metrics {
+            events: self.events.load(Ordering::Relaxed),
+            bytes: self.bytes.load(Ordering::Relaxed),
+            parse_failures: self.failures.load(Ordering::Relaxed),
+            total_latency: Duration::from_nanos(self.latency_total.load(Ordering::Relaxed)),
+            max_latency: Duration::from_nanos(self.latency_max.load(Ordering::Relaxed)),
+            elapsed,
+        }
+    }
+
+    fn record_event(&self, event: &Event, raw: &[u8], latency: Duration) {
+        if !self.is_enabled() {
+            return;
+        }
+        self.events.fetch_add(1, Ordering::Relaxed);
+        self.bytes.fetch_add(raw.len() as u64, Ordering::Relaxed);
+        if let Event::Unsupported(_) | Event::Garbled(_) = event {
+            self.failures.fetch_add(1, Ordering::Relaxed);
+        }
+        let nanos = latency.as_nanos().min(u64::MAX as u128) as u64;
+        self.latency_total.fetch_add(nanos, Ordering::Relaxed);
+        self.latency_max.fetch_max(nanos, Ordering::Relaxed);
+    }
+
+    fn record_failure(&self) {
+        if self.is_enabled() {
+            self.failures.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+}
+
+/// Turn collecting metrics on or off, turning them on resets them.
+pub fn set_enabled(enabled: bool) {
+    COUNTERS.set_enabled(enabled);
+}
+
+/// True if metrics are being collected.
+pub fn is_enabled() -> bool {
+    COUNTERS.is_enabled()
+}
+
+/// Zero the metrics and restart the elapsed time.
+pub fn reset() {
+    COUNTERS.reset();
+    *STARTED.lock() = Instant::now();
+}
+
+/// Return the current metrics.
+pub fn metrics() -> Metrics {
+    COUNTERS.snapshot(STARTED.lock().elapsed())
+}
+
+/// Count an event parsed from `raw`, `latency` is the time since its first
+/// byte arrived.
+pub(crate) fn record_event(event: &Event, raw: &[u8], latency: Duration) {
+    COUNTERS.record_event(event, raw, latency);
+}
+
+/// Count input that failed to read.
+pub(crate) fn record_failure() {
+    COUNTERS.record_failure();
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::event::{Key, KeyCode};
+
+    #[test]
+    fn test_metrics() {
+        let counters = Counters::new();
+        let key = Event::Key(Key::new(KeyCode::Up));
+        counters.record_event(&key, b"\x1B[A", Duration::from_micros(30));
+        assert_eq!(counters.snapshot(Duration::ZERO), Metrics::default());
+
+        counters.set_enabled(true);
+        assert!(counters.is_enabled());
+        counters.record_event(&key, b"\x1B[A", Duration::from_micros(30));
+        counters.record_event(
+            &Event::Unsupported(vec![0x1B]),
+            b"\x1B[9",
+            Duration::from_micros(10),
+        );
+        counters.record_failure();
+        let elapsed = Duration::from_secs(1);
+        assert_eq!(
+            counters.snapshot(elapsed),
+            Metrics {
+                events: 2,
+                bytes: 6,
+                parse_failures: 2,
+                total_latency: Duration::from_micros(40),
+                max_latency: Duration::from_micros(30),
+                elapsed,
+            }
+        );
+        counters.set_enabled(false);
+        assert!(!counters.is_enabled());
+        counters.record_failure();
+        assert_eq!(counters.snapshot(elapsed).parse_failures, 2);
+        counters.set_enabled(true);
+        assert_eq!(counters.snapshot(elapsed).events, 0);
+
+        let stats = Metrics {
+            events: 2,
+            bytes: 6,
+            total_latency: Duration::from_micros(40),
+            elapsed: Duration::from_millis(500),
+            ..Default::default()
+        };
+        assert_eq!(stats.events_per_sec(), 4.0);
+        assert_eq!(stats.bytes_per_event(), 3.0);
+        assert_eq!(stats.mean_latency(), Duration::from_micros(20));
+    }
+}
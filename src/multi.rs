@@ -0,0 +1,240 @@
+//! Read events from several inputs at once.
+//!
+//! A [`MultiConsole`] holds any number of [`ConsoleRead`] sources (the
+//! console, [`TransportIn`](crate::transport::TransportIn) streams or any
+//! other implementation) and returns the next event from whichever has
+//! one, tagged with the [`SourceId`] it came from, so a multiplexer can
+//! serve several terminals from one loop.
+//!
+//! The sources are checked in turn (starting after the one that produced
+//! the last event so a busy source can not starve the others) and each
+//! waits a short slice of the timeout, an event can take up to
+//! [`POLL_SLICE`] to be noticed.
+//!
+//! # Example
+//!
+//! ```rust,no_run
+//! use sl_console::multi::{MultiConsole, MultiEvent};
+//! use sl_console::*;
+//!
+//!     con_init().unwrap();
+//!     let mut multi = MultiConsole::new();
+//!     let local = multi.add(conin());
+//!     while let Some(event) = multi.next_event(None) {
+//!         match event {
+//!             MultiEvent::Event(id, event, _) if id == local => println!("local {:?}", event),
+//!             MultiEvent::Event(id, event, _) => println!("{:?} {:?}", id, event),
+//!             MultiEvent::Closed(id) | MultiEvent::Error(id, _) => println!("{:?} done", id),
+//!         }
+//!     }
+//! ```
+
+use std::io;
+use std::time::Duration;
+
+use crate::clock::{Clock, Deadline, SYSTEM_CLOCK};
+use crate::console::ConsoleRead;
+use crate::event::Event;
+
+/// The longest a round over the sources waits for input.
+pub const POLL_SLICE: Duration = Duration::from_millis(10);
+
+/// Identifies a source added to a [`MultiConsole`].
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct SourceId(pub u64);
+
+/// What [`MultiConsole::next_event`] found.
+#[derive(Debug)]
+pub enum MultiEvent {
+    /// An event and the bytes that made it.
+    Event(SourceId, Event, Vec<u8>),
+    /// The source reached the end of its input and was removed.
+    Closed(SourceId),
+    /// Reading the source failed, it is not removed.
+    Error(SourceId, io::Error),
+}
+
+struct Source<'a> {
+    id: SourceId,
+    input: Box<dyn ConsoleRead + 'a>,
+}
+
+/// Several input sources read as one.
+pub struct MultiConsole<'a> {
+    sources: Vec<Source<'a>>,
+    // The index of the source to check first.
+    next: usize,
+    next_id: u64,
+    clock: &'a dyn Clock,
+}
+
+impl<'a> Default for MultiConsole<'a> {
+    fn default() -> Self {
+        MultiConsole::new()
+    }
+}
+
+impl<'a> MultiConsole<'a> {
+    /// A MultiConsole with no sources.
+    pub fn new() -> Self {
+        MultiConsole {
+            sources: Vec::new(),
+            next: 0,
+            next_id: 0,
+            clock: &SYSTEM_CLOCK,
+        }
+    }
+
+    /// Add `input` and return the id its events are tagged with.
+    pub fn add<R: ConsoleRead + 'a>(&mut self, input: R) -> SourceId {
+        let id = SourceId(self.next_id);
+        self.next_id += 1;
+        self.sources.push(Source {
+            id,
+            input: Box::new(input),
+        });
+        id
+    }
+
+    /// Remove the source `id` and return it, None if there is no such
+    /// source.
+    pub fn remove(&mut self, id: SourceId) -> Option<Box<dyn ConsoleRead + 'a>> {
+        let index = self.sources.iter().position(|source| source.id == id)?;
+        if index < self.next {
+            self.next -= 1;
+        }
+        Some(self.sources.remove(index).input)
+    }
+
+    /// The ids of the sources, in the order they were added.
+    pub fn ids(&self) -> Vec<SourceId> {
+        self.sources.iter().map(|source| source.id).collect()
+    }
+
+    /// The number of sources.
+    pub fn len(&self) -> usize {
+        self.sources.len()
+    }
+
+    /// True if there are no sources.
+    pub fn is_empty(&self) -> bool {
+        self.sources.is_empty()
+    }
+
+    /// Return the next event from any source.
+    ///
+    /// If timeout is None waits until a source has an event.  Returns None
+    /// if the timeout passes first or there are no sources.
+    pub fn next_event(&mut self, timeout: Option<Duration>) -> Option<MultiEvent> {
+        let deadline = timeout.map(|timeout| Deadline::new(self.clock, timeout));
+        loop {
+            if self.sources.is_empty() {
+                return None;
+            }
+            let slice = match &deadline {
+                Some(deadline) => deadline.remaining().unwrap_or(Duration::ZERO),
+                None => POLL_SLICE,
+            }
+            .min(POLL_SLICE);
+            let wait = slice / self.sources.len() as u32;
+            for _ in 0..self.sources.len() {
+                let index = self.next % self.sources.len();
+                self.next = index + 1;
+                let source = &mut self.sources[index];
+                match source.input.get_event_and_raw(Some(wait)) {
+                    Some(Ok((event, raw))) => {
+                        return Some(MultiEvent::Event(source.id, event, raw))
+                    }
+                    Some(Err(err)) if err.kind() == io::ErrorKind::WouldBlock => {}
+                    Some(Err(err)) => return Some(MultiEvent::Error(source.id, err)),
+                    None => {
+                        let id = source.id;
+                        self.sources.remove(index);
+                        self.next = index;
+                        return Some(MultiEvent::Closed(id));
+                    }
+                }
+            }
+            if deadline
+                .as_ref()
+                .is_some_and(|deadline| deadline.remaining().is_none())
+            {
+                return None;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::clock::{SilentConsole, TestClock};
+    use crate::event::{Key, KeyCode};
+    use std::collections::VecDeque;
+    use std::io::Read;
+
+    /// A source with a list of events, then the end of input.
+    struct Events(VecDeque<Event>);
+
+    impl ConsoleRead for Events {
+        fn get_event_and_raw(
+            &mut self,
+            _timeout: Option<Duration>,
+        ) -> Option<io::Result<(Event, Vec<u8>)>> {
+            self.0.pop_front().map(|event| Ok((event, Vec::new())))
+        }
+
+        fn poll(&mut self, _timeout: Option<Duration>) -> bool {
+            !self.0.is_empty()
+        }
+
+        fn read_timeout(
+            &mut self,
+            _buf: &mut [u8],
+            _timeout: Option<Duration>,
+        ) -> io::Result<usize> {
+            Ok(0)
+        }
+    }
+
+    impl Read for Events {
+        fn read(&mut self, _buf: &mut [u8]) -> io::Result<usize> {
+            Ok(0)
+        }
+    }
+
+    fn key(ch: char) -> Event {
+        Event::Key(Key::new(KeyCode::Char(ch)))
+    }
+
+    #[test]
+    fn test_multi_console() {
+        let clock = TestClock::new();
+        let mut multi = MultiConsole::new();
+        multi.clock = &clock;
+        let a = multi.add(Events(vec![key('a'), key('b')].into()));
+        let silent = multi.add(SilentConsole { clock: &clock });
+        let c = multi.add(Events(vec![key('c')].into()));
+        assert_eq!(multi.ids(), vec![a, silent, c]);
+
+        let mut next = || match multi.next_event(Some(Duration::from_millis(50))) {
+            Some(MultiEvent::Event(id, event, _)) => Some((id, Some(event))),
+            Some(MultiEvent::Closed(id)) => Some((id, None)),
+            Some(MultiEvent::Error(_, err)) => panic!("{}", err),
+            None => None,
+        };
+        // Taken in turn.
+        assert_eq!(next(), Some((a, Some(key('a')))));
+        assert_eq!(next(), Some((c, Some(key('c')))));
+        assert_eq!(next(), Some((a, Some(key('b')))));
+        assert_eq!(next(), Some((c, None)));
+        assert_eq!(next(), Some((a, None)));
+        let start = clock.now();
+        assert_eq!(next(), None);
+        assert_eq!(clock.now() - start, Duration::from_millis(50));
+
+        assert!(multi.remove(silent).is_some());
+        assert!(multi.is_empty());
+        assert!(multi.next_event(None).is_none());
+    }
+}
@@ -0,0 +1,131 @@
+//! Pass escape sequences through tmux and screen.
+//!
+//! A terminal multiplexer interprets the application's output itself and
+//! drops the sequences it does not know, so the clipboard (OSC 52) and
+//! image protocols (sixel, kitty graphics) do not reach the real terminal.
+//! Both tmux and screen pass on a sequence wrapped in a special DCS,
+//! [`Passthrough`] does that wrapping for the multiplexer the application
+//! runs in (see [`multiplexer`]) and writes the sequence unchanged outside
+//! of one.  tmux only passes sequences on with its `allow-passthrough`
+//! option on.
+//!
+//! A [`CapabilityWriter`](crate::caps::CapabilityWriter) wraps the
+//! sequences the multiplexer is known to drop by itself, this is for
+//! output written without one.
+//!
+//! # Example
+//!
+//! ```rust,no_run
+//! use sl_console::killring::SetClipboard;
+//! use sl_console::passthrough::Passthrough;
+//! use sl_console::*;
+//! use std::io::Write;
+//!
+//!     con_init().unwrap();
+//!     write!(conout(), "{}", Passthrough::new(SetClipboard("copied"))).unwrap();
+//! ```
+
+use std::fmt;
+
+use crate::caps::{cached, Dialect, TerminalCaps};
+
+/// The multiplexer the application runs in, None if it is not in one.
+///
+/// Taken from the cached capabilities if they were detected, otherwise
+/// from the environment (`TMUX`, `STY`, `TERM` and the
+/// `SL_CONSOLE_DIALECT` override).
+pub fn multiplexer() -> Option<Dialect> {
+    let dialect = match cached() {
+        Some(caps) => caps.dialect,
+        None => TerminalCaps::from_env().dialect,
+    };
+    match dialect {
+        Dialect::Screen | Dialect::Tmux => Some(dialect),
+        Dialect::Xterm | Dialect::Vt100 => None,
+    }
+}
+
+/// Wrap the escape sequence `seq` for `dialect`'s passthrough, `seq` is
+/// returned unchanged for a dialect that is not a multiplexer.
+pub fn wrap(seq: &[u8], dialect: Dialect) -> Vec<u8> {
+    let mut out = Vec::with_capacity(seq.len() + 16);
+    // Writing to a Vec can not fail.
+    let _ = dialect.write_passthrough(&mut out, seq);
+    out
+}
+
+/// An escape sequence wrapped to pass through a multiplexer.
+///
+/// Displays the wrapped sequence.  The sequence should be one complete
+/// escape sequence.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Passthrough<T> {
+    seq: T,
+    dialect: Option<Dialect>,
+}
+
+impl<T: fmt::Display> Passthrough<T> {
+    /// Wrap `seq` for the multiplexer the application runs in, see
+    /// [`multiplexer`].
+    pub fn new(seq: T) -> Self {
+        Passthrough {
+            seq,
+            dialect: multiplexer(),
+        }
+    }
+
+    /// Wrap `seq` for `dialect`.
+    pub fn for_dialect(seq: T, dialect: Dialect) -> Self {
+        Passthrough {
+            seq,
+            dialect: Some(dialect),
+        }
+    }
+}
+
+impl<T: fmt::Display> fmt::Display for Passthrough<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.dialect {
+            Some(dialect) => {
+                let wrapped = wrap(self.seq.to_string().as_bytes(), dialect);
+                // Only ESC and ASCII are added to the UTF-8 of the sequence.
+                f.write_str(&String::from_utf8_lossy(&wrapped))
+            }
+            None => self.seq.fmt(f),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::killring::SetClipboard;
+
+    #[test]
+    fn test_passthrough() {
+        let copy = SetClipboard("hi");
+        assert_eq!(
+            Passthrough::for_dialect(copy, Dialect::Tmux).to_string(),
+            "\x1bPtmux;\x1b\x1b]52;c;aGk=\x07\x1b\\"
+        );
+        assert_eq!(
+            Passthrough::for_dialect(copy, Dialect::Screen).to_string(),
+            "\x1bP\x1b\x1b\\\x1bP]52;c;aGk=\x07\x1b\\"
+        );
+        assert_eq!(
+            Passthrough::for_dialect(copy, Dialect::Xterm).to_string(),
+            copy.to_string()
+        );
+        // A long string is split into pieces screen accepts.
+        let long = format!("\x1bPq{}\x1b\\", "~".repeat(1000));
+        let wrapped = wrap(long.as_bytes(), Dialect::Screen);
+        assert_eq!(
+            String::from_utf8(wrapped).unwrap(),
+            format!(
+                "\x1bP\x1b\x1b\\\x1bPPq{}\x1b\\\x1bP{}\x1b\x1b\\\x1bP\\\x1b\\",
+                "~".repeat(758),
+                "~".repeat(242)
+            )
+        );
+    }
+}
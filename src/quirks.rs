@@ -0,0 +1,181 @@
+//! Terminal specific key sequence quirks.
+//!
+//! Terminals do not agree on the sequences for some keys, for instance
+//! Shift+Tab can arrive as `CSI Z`, `CSI 0 Z` or `CSI 1 ; 2 Z`.  A
+//! [`KeyQuirks`] table maps the raw bytes of such sequences to the key they
+//! should be reported as.  The console input builds one for the terminal in
+//! `$TERM` and applies it to every event, applications can add their own
+//! entries for terminals that need it.
+//!
+//! # Example
+//!
+//! ```rust,no_run
+//! use sl_console::event::{Key, KeyCode};
+//! use sl_console::quirks::KeyQuirks;
+//! use sl_console::*;
+//!
+//!     con_init().unwrap();
+//!     let mut quirks = KeyQuirks::from_env();
+//!     // This terminal sends ESC TAB for Shift+Tab.
+//!     quirks.insert(b"\x1b\t".to_vec(), Key::new(KeyCode::BackTab));
+//!     conin().lock().set_key_quirks(quirks);
+//! ```
+
+use std::collections::HashMap;
+use std::env;
+
+use crate::caps::overrides;
+use crate::event::{Event, Key, KeyCode, KeyMod};
+
+/// A raw sequence and the key it should be reported as.
+type QuirkEntry = (&'static [u8], KeyCode, Option<KeyMod>);
+
+/// Sequences that are normalized for every terminal.
+const GENERIC_QUIRKS: &[QuirkEntry] = &[
+    (b"\x1b[0Z", KeyCode::BackTab, None),
+    (b"\x1b[1Z", KeyCode::BackTab, None),
+    (b"\x1b[1;2Z", KeyCode::BackTab, None),
+];
+
+/// rxvt sends its own sequences for shifted and ctrl arrow keys.
+const RXVT_QUIRKS: &[QuirkEntry] = &[
+    (b"\x1b[a", KeyCode::Up, Some(KeyMod::Shift)),
+    (b"\x1b[b", KeyCode::Down, Some(KeyMod::Shift)),
+    (b"\x1b[c", KeyCode::Right, Some(KeyMod::Shift)),
+    (b"\x1b[d", KeyCode::Left, Some(KeyMod::Shift)),
+    (b"\x1bOa", KeyCode::Up, Some(KeyMod::Ctrl)),
+    (b"\x1bOb", KeyCode::Down, Some(KeyMod::Ctrl)),
+    (b"\x1bOc", KeyCode::Right, Some(KeyMod::Ctrl)),
+    (b"\x1bOd", KeyCode::Left, Some(KeyMod::Ctrl)),
+];
+
+/// Built in quirk tables keyed by the prefix of the terminal name.
+const TERMINAL_QUIRKS: &[(&str, &[QuirkEntry])] = &[("rxvt", RXVT_QUIRKS)];
+
+/// A table mapping raw input sequences to the key they represent.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct KeyQuirks {
+    map: HashMap<Vec<u8>, Key>,
+}
+
+impl KeyQuirks {
+    /// Create an empty table, no events will be changed.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Create the built in table for the terminal named `term` (a `$TERM`
+    /// value).
+    ///
+    /// This always contains the generic entries (used for every terminal)
+    /// plus any entries specific to `term`.
+    pub fn for_terminal(term: &str) -> Self {
+        let mut quirks = Self::new();
+        quirks.extend(GENERIC_QUIRKS);
+        for (prefix, table) in TERMINAL_QUIRKS {
+            if term.starts_with(prefix) {
+                quirks.extend(table);
+            }
+        }
+        quirks
+    }
+
+    /// Create the built in table for the terminal in `$TERM`.
+    ///
+    /// `SL_CONSOLE_TERM_QUIRKS` overrides the terminal name, if it is `none`
+    /// then the table is empty (see [`crate::caps::overrides`]).
+    pub fn from_env() -> Self {
+        match &overrides().term_quirks {
+            Some(term) if term == "none" => Self::new(),
+            Some(term) => Self::for_terminal(term),
+            None => Self::for_terminal(&env::var("TERM").unwrap_or_default()),
+        }
+    }
+
+    fn extend(&mut self, table: &[QuirkEntry]) {
+        for (seq, code, mods) in table {
+            let key = match mods {
+                Some(mods) => Key::new_mod(*code, *mods),
+                None => Key::new(*code),
+            };
+            self.insert(seq.to_vec(), key);
+        }
+    }
+
+    /// Report `seq` as `key`.
+    ///
+    /// Returns the key `seq` was previously mapped to, if any.  Note that
+    /// `seq` has to be all the bytes the parser reads for the event, a
+    /// sequence the parser does not recognize will be matched as long as it
+    /// does not run into the following input.
+    pub fn insert(&mut self, seq: Vec<u8>, key: Key) -> Option<Key> {
+        self.map.insert(seq, key)
+    }
+
+    /// Stop remapping `seq`, returns the key it was mapped to.
+    pub fn remove(&mut self, seq: &[u8]) -> Option<Key> {
+        self.map.remove(seq)
+    }
+
+    /// Return the key `seq` is mapped to.
+    pub fn get(&self, seq: &[u8]) -> Option<Key> {
+        self.map.get(seq).copied()
+    }
+
+    /// True if there are no entries in the table.
+    pub fn is_empty(&self) -> bool {
+        self.map.is_empty()
+    }
+
+    /// Return `event` with any quirks applied, `raw` is the bytes that made
+    /// the event.
+    pub fn normalize(&self, event: Event, raw: &[u8]) -> Event {
+        match self.get(raw) {
+            Some(key) => Event::Key(key),
+            None => event,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::event::parse_event;
+
+    fn parse(quirks: &KeyQuirks, raw: &[u8]) -> Event {
+        let mut iter = raw[1..].iter().map(|b| Ok(*b));
+        let event = parse_event(raw[0], &mut iter).unwrap();
+        quirks.normalize(event, raw)
+    }
+
+    #[test]
+    fn test_back_tab() {
+        let quirks = KeyQuirks::for_terminal("xterm-256color");
+        let back_tab = Event::Key(Key::new(KeyCode::BackTab));
+        assert_eq!(parse(&quirks, b"\x1b[Z"), back_tab);
+        assert_eq!(parse(&quirks, b"\x1b[0Z"), back_tab);
+        assert_eq!(parse(&quirks, b"\x1b[1;2Z"), back_tab);
+        assert_eq!(
+            parse(&quirks, b"\x1b\t"),
+            Event::Key(Key::new_mod(KeyCode::Char('i'), KeyMod::AltCtrl))
+        );
+
+        let mut quirks = quirks;
+        quirks.insert(b"\x1b\t".to_vec(), Key::new(KeyCode::BackTab));
+        assert_eq!(parse(&quirks, b"\x1b\t"), back_tab);
+    }
+
+    #[test]
+    fn test_terminal_tables() {
+        let shift_up = Event::Key(Key::new_mod(KeyCode::Up, KeyMod::Shift));
+        let rxvt = KeyQuirks::for_terminal("rxvt-unicode-256color");
+        assert_eq!(parse(&rxvt, b"\x1b[a"), shift_up);
+        assert_eq!(
+            parse(&rxvt, b"\x1bOd"),
+            Event::Key(Key::new_mod(KeyCode::Left, KeyMod::Ctrl))
+        );
+        let xterm = KeyQuirks::for_terminal("xterm");
+        assert_ne!(parse(&xterm, b"\x1b[a"), shift_up);
+        assert!(KeyQuirks::new().is_empty());
+    }
+}
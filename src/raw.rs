@@ -23,7 +23,18 @@
 use std::io::{self, Write};
 use std::ops;
 
+use crate::caps::query_modes;
 use crate::console::*;
+use crate::cursor;
+use crate::event::ModeState;
+use crate::input::{ConsoleMouseExt, MouseCapture, MOUSE_MODES};
+use crate::screen;
+
+/// Cursor visibility (DECTCEM).
+const CURSOR_VISIBLE_MODE: u16 = 25;
+
+/// The alternate screen buffer.
+const ALTERNATE_SCREEN_MODE: u16 = 1049;
 
 /// A terminal restorer, which keeps the previous state of the terminal, and restores it, when
 /// dropped.
@@ -126,6 +137,55 @@ pub trait RawModeExt: ConsoleWrite + Sized {
     /// the program). Furthermore, the input isn't canonicalised or buffered (that is, you can
     /// read from stdin one byte of a time). The output is neither modified in any way.
     fn into_raw_mode(self) -> io::Result<RawTerminal<Self>>;
+
+    /// Run `f` with the terminal back in its normal state and restore the
+    /// current state afterwards.
+    ///
+    /// This is for shelling out from a full screen program, for instance to
+    /// run `$EDITOR` or a pager.  Raw mode is turned off, mouse reporting
+    /// is disabled, the main screen is shown and the cursor is made visible
+    /// while `f` runs.  The terminal is asked which of these are currently
+    /// active (waiting up to 200ms) and only those are changed, if it does
+    /// not answer then only raw mode is switched.  The alternate screen is
+    /// usually cleared by the program run so redraw after this returns.
+    fn cooked_scope<F, T>(&mut self, f: F) -> io::Result<T>
+    where
+        F: FnOnce() -> T,
+    {
+        let was_raw = self.set_raw_mode(true)?;
+        let mut modes = MOUSE_MODES.to_vec();
+        modes.extend([CURSOR_VISIBLE_MODE, ALTERNATE_SCREEN_MODE]);
+        let reports = conin_r()
+            .and_then(|conin| query_modes(&mut conin.lock(), self, &modes))
+            .unwrap_or_default();
+        let is = |mode, state| reports.iter().any(|r| r.mode == mode && r.state == state);
+        let mouse = MouseCapture::from_reports(&reports);
+        let alternate = is(ALTERNATE_SCREEN_MODE, ModeState::Set);
+        let hidden = is(CURSOR_VISIBLE_MODE, ModeState::Reset);
+
+        self.mouse_release(mouse)?;
+        if alternate {
+            write!(self, "{}", screen::ToMainScreen)?;
+        }
+        if hidden {
+            write!(self, "{}", cursor::Show)?;
+        }
+        self.flush()?;
+        self.set_raw_mode(false)?;
+
+        let result = f();
+
+        self.set_raw_mode(was_raw)?;
+        if alternate {
+            write!(self, "{}", screen::ToAlternateScreen)?;
+        }
+        if hidden {
+            write!(self, "{}", cursor::Hide)?;
+        }
+        self.mouse_capture(mouse)?;
+        self.flush()?;
+        Ok(result)
+    }
 }
 
 impl<W: ConsoleWrite> RawModeExt for W {
@@ -0,0 +1,87 @@
+//! Terminal resize notifications.
+//!
+//! Handlers registered here are called from a background thread whenever
+//! the terminal changes size (SIGWINCH on unix, on Windows the console size
+//! is polled), independent of the console input.  This suits programs that
+//! render from a different thread than the one reading input, programs
+//! that handle everything in their input loop can have the resizes
+//! delivered as events instead, see
+//! [`ConsoleIn::set_resize_events`](crate::console::ConsoleIn::set_resize_events).
+//!
+//! # Example
+//!
+//! ```rust,no_run
+//! use sl_console::resize;
+//! use sl_console::*;
+//!
+//!     con_init().unwrap();
+//!     let resized = resize::resize_channel().unwrap();
+//!     resize::on_resize(|cols, rows| eprintln!("now {}x{}", cols, rows)).unwrap();
+//!     for (_cols, _rows) in resized {
+//!         // Redraw at the new size.
+//!     }
+//! ```
+
+use std::io;
+use std::sync::mpsc::{self, Receiver};
+
+use lazy_static::lazy_static;
+use parking_lot::Mutex;
+
+use crate::sys::resize::start_watcher;
+use crate::sys::size::terminal_size;
+
+/// A registered handler, returns false once it should be removed.
+pub(crate) type Handler = Box<dyn FnMut(u16, u16) -> bool + Send>;
+
+lazy_static! {
+    static ref HANDLERS: Mutex<Vec<Handler>> = Mutex::new(Vec::new());
+    // The watcher is started on the first registration and runs for the
+    // life of the program.
+    static ref WATCHER: io::Result<()> = start_watcher(dispatch);
+}
+
+/// Call every handler with the new size.
+fn dispatch() {
+    if let Ok((cols, rows)) = terminal_size() {
+        HANDLERS.lock().retain_mut(|handler| handler(cols, rows));
+    }
+}
+
+/// Register `handler` to be called with the new size after every resize
+/// until it returns false.  Returns an error if the watcher could not be
+/// started.
+pub(crate) fn register(handler: Handler) -> io::Result<()> {
+    if let Err(err) = &*WATCHER {
+        return Err(io::Error::new(err.kind(), err));
+    }
+    HANDLERS.lock().push(handler);
+    Ok(())
+}
+
+/// Call `callback` with the new size (columns, rows) after every resize.
+///
+/// The callback runs on the resize watcher thread, keep it short.
+/// Returns an error if the watcher could not be started.
+pub fn on_resize<F>(mut callback: F) -> io::Result<()>
+where
+    F: FnMut(u16, u16) + Send + 'static,
+{
+    register(Box::new(move |cols, rows| {
+        callback(cols, rows);
+        true
+    }))
+}
+
+/// Return a channel that receives the new size (columns, rows) after every
+/// resize.
+///
+/// The sender is removed after the receiver is dropped.  Returns an error
+/// if the watcher could not be started.
+pub fn resize_channel() -> io::Result<Receiver<(u16, u16)>> {
+    let (sender, receiver) = mpsc::channel();
+    register(Box::new(move |cols, rows| {
+        sender.send((cols, rows)).is_ok()
+    }))?;
+    Ok(receiver)
+}
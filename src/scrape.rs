@@ -0,0 +1,204 @@
+//! Follow what a child process shows on its terminal.
+//!
+//! A [`Scraper`] is fed the output of a program running in a pseudo
+//! terminal (for instance [`testing::Pty`](crate::testing)) and keeps the
+//! screen that program would be showing: the [`Headless`] interpretation
+//! of text, cursor movement, clears, scrolling and styles plus the
+//! alternate screen, cursor visibility and window title.  Recorders, screen
+//! readers and test runners can then inspect the screen instead of the raw
+//! byte stream.
+//!
+//! # Example
+//!
+//! ```rust
+//! use sl_console::scrape::Scraper;
+//! use std::io::Write;
+//!
+//! let mut screen = Scraper::new(20, 3);
+//! // Output as read from the child.
+//! screen.write_all(b"\x1B]2;top\x07\x1B[?1049h\x1B[2;3Hload 0.1").unwrap();
+//! assert_eq!(screen.title(), "top");
+//! assert!(screen.is_alternate());
+//! assert_eq!(screen.lines(), vec!["", "  load 0.1", ""]);
+//! ```
+
+use std::io::{self, Write};
+
+use crate::debug::{Decoder, Piece};
+use crate::grid::{Cell, Grid};
+use crate::headless::Headless;
+
+/// The screen of a child process, built from its output.
+#[derive(Debug)]
+pub struct Scraper {
+    main: Headless,
+    // The alternate screen while it is in use.
+    alternate: Option<Headless>,
+    cursor_visible: bool,
+    title: String,
+    decoder: Decoder,
+}
+
+impl Scraper {
+    /// A blank `width` by `height` screen.
+    pub fn new(width: u16, height: u16) -> Self {
+        Scraper {
+            main: Headless::new(width, height),
+            alternate: None,
+            cursor_visible: true,
+            title: String::new(),
+            decoder: Decoder::default(),
+        }
+    }
+
+    /// The terminal in use, the alternate screen if the child switched to
+    /// it.
+    pub fn terminal(&self) -> &Headless {
+        self.alternate.as_ref().unwrap_or(&self.main)
+    }
+
+    fn terminal_mut(&mut self) -> &mut Headless {
+        self.alternate.as_mut().unwrap_or(&mut self.main)
+    }
+
+    /// The screen contents.
+    pub fn screen(&self) -> &Grid {
+        self.terminal().grid()
+    }
+
+    /// The text of each row with trailing blanks removed.
+    pub fn lines(&self) -> Vec<String> {
+        let grid = self.screen();
+        (0..grid.height())
+            .map(|y| {
+                let line: String = grid
+                    .row(y)
+                    .iter()
+                    .map(|cell| cell.ch)
+                    .filter(|ch| *ch != Cell::CONTINUATION)
+                    .collect();
+                line.trim_end().to_string()
+            })
+            .collect()
+    }
+
+    /// The cursor position, zero based.
+    pub fn cursor(&self) -> (u16, u16) {
+        self.terminal().cursor()
+    }
+
+    /// False if the child hid the cursor.
+    pub fn cursor_visible(&self) -> bool {
+        self.cursor_visible
+    }
+
+    /// The window title the child set, empty if it set none.
+    pub fn title(&self) -> &str {
+        &self.title
+    }
+
+    /// True while the child is using the alternate screen.
+    pub fn is_alternate(&self) -> bool {
+        self.alternate.is_some()
+    }
+
+    /// Change the screen size, call it along with resizing the child's
+    /// terminal.
+    pub fn resize(&mut self, width: u16, height: u16) {
+        self.main.resize(width, height);
+        if let Some(alternate) = &mut self.alternate {
+            alternate.resize(width, height);
+        }
+    }
+
+    /// Handle a private mode set (h) or reset (l).
+    fn private_mode(&mut self, seq: &[u8]) {
+        let set = seq[seq.len() - 1] == b'h';
+        let params = String::from_utf8_lossy(&seq[3..seq.len() - 1]).into_owned();
+        for mode in params.split(';') {
+            match mode {
+                "25" => self.cursor_visible = set,
+                "47" | "1047" | "1049" if set => {
+                    let (width, height) = (self.main.grid().width(), self.main.grid().height());
+                    self.alternate
+                        .get_or_insert_with(|| Headless::new(width, height));
+                }
+                "47" | "1047" | "1049" => self.alternate = None,
+                _ => {}
+            }
+        }
+    }
+
+    /// Handle an operating system command, the window title is kept.
+    fn osc(&mut self, seq: &[u8]) {
+        let end = if seq.ends_with(b"\x1B\\") { 2 } else { 1 };
+        let body = String::from_utf8_lossy(&seq[2..seq.len() - end]).into_owned();
+        if let Some((code, text)) = body.split_once(';') {
+            if code == "0" || code == "2" {
+                self.title = text.to_string();
+            }
+        }
+    }
+
+    fn piece(&mut self, piece: Piece) {
+        match piece {
+            Piece::Escape(seq)
+                if seq.starts_with(b"\x1B[?") && (seq.ends_with(b"h") || seq.ends_with(b"l")) =>
+            {
+                self.private_mode(seq)
+            }
+            Piece::Escape(seq) if seq.starts_with(b"\x1B]") => self.osc(seq),
+            piece => self.terminal_mut().piece(piece),
+        }
+    }
+}
+
+impl Write for Scraper {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let mut decoder = std::mem::take(&mut self.decoder);
+        decoder.feed(buf, |piece| self.piece(piece));
+        self.decoder = decoder;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{cursor, screen, style};
+
+    #[test]
+    fn test_scraper() {
+        let mut term = Scraper::new(8, 2);
+        write!(term, "shell$ {}", style::Bold).unwrap();
+        write!(
+            term,
+            "{}{}{}edit",
+            screen::ToAlternateScreen,
+            cursor::Hide,
+            cursor::Goto(2, 2)
+        )
+        .unwrap();
+        assert!(term.is_alternate());
+        assert!(!term.cursor_visible());
+        assert_eq!(term.lines(), vec!["", " edit"]);
+        assert_eq!(term.cursor(), (5, 1));
+
+        // Back on the main term the shell's output is still there.
+        write!(term, "{}{}", screen::ToMainScreen, cursor::Show).unwrap();
+        assert_eq!(term.lines(), vec!["shell$", ""]);
+        assert_eq!(term.cursor(), (7, 0));
+
+        // A title split across writes, ended by ST.
+        term.write_all(b"\x1B]0;vi").unwrap();
+        term.write_all(b"m\x1B\\").unwrap();
+        assert_eq!(term.title(), "vim");
+
+        term.resize(4, 1);
+        assert_eq!(term.lines(), vec!["shel"]);
+    }
+}
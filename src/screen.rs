@@ -18,29 +18,21 @@
 //!     println!("Writing to main screen.");
 //! ```
 
-use std::fmt;
 use std::io::{self, Write};
 use std::ops;
 
 use crate::console::ConsoleWrite;
 
-/// Switch to the main screen buffer of the terminal.
-pub struct ToMainScreen;
-
-impl fmt::Display for ToMainScreen {
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, csi!("?1049l"))
-    }
-}
-
-/// Switch to the alternate screen buffer of the terminal.
-pub struct ToAlternateScreen;
-
-impl fmt::Display for ToAlternateScreen {
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, csi!("?1049h"))
-    }
-}
+derive_csi_sequence!(
+    "Switch to the main screen buffer of the terminal.",
+    ToMainScreen,
+    "?1049l"
+);
+derive_csi_sequence!(
+    "Switch to the alternate screen buffer of the terminal.",
+    ToAlternateScreen,
+    "?1049h"
+);
 
 /// A terminal restorer, which wraps a type implementing Write, and causes all writes to be written
 /// to an alternate screen.
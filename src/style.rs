@@ -1,24 +1,138 @@
 //! Text styling management.
+//!
+//! # Style policy
+//!
+//! The `Display` implementations of the styles here and of the colors in
+//! [`color`](crate::color) write nothing when styles are turned off with
+//! [`set_policy`], so a `--color=always|never|auto` flag is one call:
+//!
+//! ```rust
+//! use sl_console::style::{self, StylePolicy};
+//!
+//! style::set_policy(StylePolicy::Never);
+//! assert_eq!(format!("{}bold{}", style::Bold, style::Reset), "bold");
+//! style::set_policy(StylePolicy::Auto);
+//! ```
 
+use std::env;
 use std::fmt;
 
-derive_csi_sequence!("Reset SGR parameters.", Reset, "m");
-derive_csi_sequence!("Bold text.", Bold, "1m");
-derive_csi_sequence!("Fainted text (not widely supported).", Faint, "2m");
-derive_csi_sequence!("Italic text.", Italic, "3m");
-derive_csi_sequence!("Underlined text.", Underline, "4m");
-derive_csi_sequence!("Blinking text (not widely supported).", Blink, "5m");
-derive_csi_sequence!("Inverted colors (negative mode).", Invert, "7m");
-derive_csi_sequence!("Crossed out text (not widely supported).", CrossedOut, "9m");
-derive_csi_sequence!("Undo bold text.", NoBold, "21m");
-derive_csi_sequence!("Undo fainted text (not widely supported).", NoFaint, "22m");
-derive_csi_sequence!("Undo italic text.", NoItalic, "23m");
-derive_csi_sequence!("Undo underlined text.", NoUnderline, "24m");
-derive_csi_sequence!("Undo blinking text (not widely supported).", NoBlink, "25m");
-derive_csi_sequence!("Undo inverted colors (negative mode).", NoInvert, "27m");
-derive_csi_sequence!(
+use lazy_static::lazy_static;
+use parking_lot::Mutex;
+
+lazy_static! {
+    static ref POLICY: Mutex<StylePolicy> = Mutex::new(StylePolicy::default());
+    static ref NO_COLOR: bool = env::var_os("NO_COLOR").is_some_and(|v| !v.is_empty());
+}
+
+/// When styles and colors are written.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, Default)]
+pub enum StylePolicy {
+    /// Unless the `NO_COLOR` environment variable is set (and not empty).
+    #[default]
+    Auto,
+    /// Always.
+    Always,
+    /// Never, only the text is written.
+    Never,
+}
+
+/// Set when styles and colors are written, the default is
+/// [`StylePolicy::Auto`].
+///
+/// `Auto` can not know where the text goes, an application that writes to
+/// standard output and wants no styles when it is piped should check with
+/// [`is_tty`](crate::is_tty) and choose `Never` itself.
+pub fn set_policy(policy: StylePolicy) {
+    *POLICY.lock() = policy;
+}
+
+/// Return when styles and colors are written.
+pub fn policy() -> StylePolicy {
+    *POLICY.lock()
+}
+
+/// True if styles and colors are written under the current policy.
+pub fn enabled() -> bool {
+    match policy() {
+        StylePolicy::Auto => !*NO_COLOR,
+        StylePolicy::Always => true,
+        StylePolicy::Never => false,
+    }
+}
+
+derive_sgr_sequence!("Reset SGR parameters.", Reset, "m");
+derive_sgr_sequence!("Bold text.", Bold, "1m");
+derive_sgr_sequence!("Fainted text (not widely supported).", Faint, "2m");
+derive_sgr_sequence!("Italic text.", Italic, "3m");
+derive_sgr_sequence!("Underlined text.", Underline, "4m");
+derive_sgr_sequence!("Blinking text (not widely supported).", Blink, "5m");
+derive_sgr_sequence!(
+    "Rapidly blinking text (not widely supported, often the same as `Blink`).",
+    RapidBlink,
+    "6m"
+);
+derive_sgr_sequence!("Inverted colors (negative mode).", Invert, "7m");
+derive_sgr_sequence!(
+    "Hidden text, for instance a password (not widely supported).",
+    Conceal,
+    "8m"
+);
+derive_sgr_sequence!("Crossed out text (not widely supported).", CrossedOut, "9m");
+derive_sgr_sequence!("Undo bold text, also undoes faint text.", NoBold, "22m");
+derive_sgr_sequence!(
+    "Undo fainted text (not widely supported), also undoes bold text.",
+    NoFaint,
+    "22m"
+);
+derive_sgr_sequence!("Undo italic text.", NoItalic, "23m");
+derive_sgr_sequence!("Undo underlined text.", NoUnderline, "24m");
+derive_sgr_sequence!(
+    "Undo blinking and rapidly blinking text (not widely supported).",
+    NoBlink,
+    "25m"
+);
+derive_sgr_sequence!("Undo inverted colors (negative mode).", NoInvert, "27m");
+derive_sgr_sequence!("Show hidden text.", Reveal, "28m");
+derive_sgr_sequence!(
     "Undo crossed out text (not widely supported).",
     NoCrossedOut,
     "29m"
 );
-derive_csi_sequence!("Framed text (not widely supported).", Framed, "51m");
+derive_sgr_sequence!("Framed text (not widely supported).", Framed, "51m");
+derive_sgr_sequence!("Overlined text (not widely supported).", Overline, "53m");
+derive_sgr_sequence!(
+    "Undo overlined text (not widely supported).",
+    NoOverline,
+    "55m"
+);
+
+/// Select font `n`, 0 is the primary font and 1 to 9 (larger values are 9)
+/// are alternate fonts (not widely supported, see
+/// [`TerminalCaps::alternate_fonts`](crate::caps::TerminalCaps::alternate_fonts)).
+///
+/// What the alternate fonts look like is up to the terminal, some use 9
+/// (SGR 19) for Fraktur or let the user configure them.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct Font(pub u8);
+
+impl From<Font> for String {
+    fn from(this: Font) -> String {
+        this.to_string()
+    }
+}
+
+impl fmt::Display for Font {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if !enabled() {
+            return Ok(());
+        }
+        write!(f, "\x1B[{}m", 10 + self.0.min(9))
+    }
+}
+
+derive_sgr_sequence!(
+    "Select the primary font, the same as `Font(0)`.",
+    PrimaryFont,
+    "10m"
+);
@@ -4,10 +4,15 @@ use libc::{self, fd_set, suseconds_t, time_t, timeval};
 use std::fs::{File, OpenOptions};
 use std::io::{self, Read, Write};
 use std::os::unix::fs::OpenOptionsExt;
-use std::os::unix::io::{AsRawFd, RawFd};
+use std::os::unix::io::{AsRawFd, FromRawFd, RawFd};
+use std::os::unix::process::{CommandExt, ExitStatusExt};
+use std::process::{Command, ExitStatus};
+use std::sync::Arc;
 use std::time::Duration;
+use std::{mem, ptr};
 
-use super::Termios;
+use super::{cvt, Termios};
+use crate::backend::{SysBackend, Wake};
 use crate::sys::attr::{get_terminal_attr_fd, raw_terminal_attr, set_terminal_attr_fd};
 
 /// Open and return the read side of a tty.
@@ -16,7 +21,29 @@ pub fn open_syscon_in() -> io::Result<SysConsoleIn> {
         .read(true)
         .custom_flags(libc::O_NONBLOCK)
         .open("/dev/tty")?;
-    Ok(SysConsoleIn { tty })
+    let (wake_read, wake_write) = wake_pipe()?;
+    Ok(SysConsoleIn {
+        tty,
+        wake_read,
+        wake_write: Arc::new(wake_write),
+        watched: Vec::new(),
+        ready: Vec::new(),
+    })
+}
+
+/// Create the non-blocking self-pipe used to wake a blocked poll.
+pub(super) fn wake_pipe() -> io::Result<(File, File)> {
+    let mut fds: [libc::c_int; 2] = [0; 2];
+    unsafe {
+        if libc::pipe(fds.as_mut_ptr()) == -1 {
+            return Err(io::Error::last_os_error());
+        }
+        for fd in fds.iter() {
+            libc::fcntl(*fd, libc::F_SETFL, libc::O_NONBLOCK);
+            libc::fcntl(*fd, libc::F_SETFD, libc::FD_CLOEXEC);
+        }
+        Ok((File::from_raw_fd(fds[0]), File::from_raw_fd(fds[1])))
+    }
 }
 
 /// Open and return the write side of a tty.
@@ -28,6 +55,19 @@ pub fn open_syscon_out() -> io::Result<SysConsoleOut> {
     Ok(SysConsoleOut { tty, prev_ios })
 }
 
+/// Open the write side of a tty non-blocking, for writers that wait until
+/// it is writable.
+///
+/// The tty is opened again, a duplicate of the console's descriptor would
+/// share the flag and make its writes fail with WouldBlock.
+#[cfg(feature = "tokio")]
+pub fn open_syscon_out_nonblock() -> io::Result<File> {
+    OpenOptions::new()
+        .write(true)
+        .custom_flags(libc::O_NONBLOCK)
+        .open("/dev/tty")
+}
+
 /// Represents system specific part of a tty/console output.
 pub struct SysConsoleOut {
     tty: File,
@@ -55,6 +95,69 @@ impl SysConsoleOut {
         set_terminal_attr_fd(tty_fd, &ios)?;
         Ok(())
     }
+
+    /// Run `command` in its own process group as the foreground job of the
+    /// tty, wait for it to exit or stop and take the tty back with the
+    /// modes it had before.
+    ///
+    /// If we are not the foreground job ourselves the command is just run.
+    pub fn run_child(&self, command: &mut Command) -> io::Result<ExitStatus> {
+        let tty = self.tty.as_raw_fd();
+        if unsafe { libc::tcgetpgrp(tty) != libc::getpgrp() } {
+            return command.status();
+        }
+        let ios = get_terminal_attr_fd(tty)?;
+        let child = command.process_group(0).spawn()?;
+        let pid = child.id() as libc::pid_t;
+        let _ = set_foreground(tty, pid);
+        let status = wait_job(pid);
+        set_foreground(tty, unsafe { libc::getpgrp() })?;
+        set_terminal_attr_fd(tty, &ios)?;
+        status
+    }
+}
+
+/// Wait for the foreground job `pid` to exit or be stopped.
+///
+/// The child can touch the tty before it is made the foreground job, it is
+/// then stopped by SIGTTIN or SIGTTOU and continued here.
+fn wait_job(pid: libc::pid_t) -> io::Result<ExitStatus> {
+    loop {
+        let mut status = 0;
+        if unsafe { libc::waitpid(pid, &mut status, libc::WUNTRACED) } == -1 {
+            let err = io::Error::last_os_error();
+            if err.kind() == io::ErrorKind::Interrupted {
+                continue;
+            }
+            return Err(err);
+        }
+        if libc::WIFSTOPPED(status)
+            && matches!(libc::WSTOPSIG(status), libc::SIGTTIN | libc::SIGTTOU)
+        {
+            unsafe {
+                libc::kill(-pid, libc::SIGCONT);
+            }
+            continue;
+        }
+        return Ok(ExitStatus::from_raw(status));
+    }
+}
+
+/// Make `pgrp` the foreground process group of `tty`.
+///
+/// A background process calling tcsetpgrp gets SIGTTOU, which stops it by
+/// default, so the signal is blocked for the call.
+fn set_foreground(tty: RawFd, pgrp: libc::pid_t) -> io::Result<()> {
+    unsafe {
+        let mut block: libc::sigset_t = mem::zeroed();
+        let mut old: libc::sigset_t = mem::zeroed();
+        libc::sigemptyset(&mut block);
+        libc::sigaddset(&mut block, libc::SIGTTOU);
+        libc::pthread_sigmask(libc::SIG_BLOCK, &block, &mut old);
+        let res = cvt(libc::tcsetpgrp(tty, pgrp));
+        libc::pthread_sigmask(libc::SIG_SETMASK, &old, ptr::null_mut());
+        res.map(|_| ())
+    }
 }
 
 impl Write for SysConsoleOut {
@@ -70,6 +173,30 @@ impl Write for SysConsoleOut {
 /// Represents system specific part of a tty/console input.
 pub struct SysConsoleIn {
     tty: File,
+    /// Read end of the self-pipe used by SysWaker.
+    wake_read: File,
+    wake_write: Arc<File>,
+    /// Extra descriptors to wait on with their tokens.
+    watched: Vec<(RawFd, u64)>,
+    /// Tokens of watched descriptors found readable by the last poll.
+    ready: Vec<u64>,
+}
+
+/// Wakes a SysConsoleIn blocked in poll_or_wake from any thread.
+#[derive(Clone)]
+pub struct SysWaker {
+    wake_write: Arc<File>,
+}
+
+impl SysWaker {
+    /// Make the current or next poll_or_wake return.
+    pub fn wake(&self) -> io::Result<()> {
+        match (&*self.wake_write).write(&[1]) {
+            // The pipe is full so a wake up is already pending.
+            Err(err) if err.kind() == io::ErrorKind::WouldBlock => Ok(()),
+            res => res.map(|_| ()),
+        }
+    }
 }
 
 impl SysConsoleIn {
@@ -119,6 +246,95 @@ impl SysConsoleIn {
         }
     }
 
+    /// Wait for input, a wake up or a watched descriptor, with an optional
+    /// timeout.
+    ///
+    /// Returns true if input is ready, false if woken, timed out or only
+    /// watched descriptors are ready (see take_ready).
+    pub fn poll_or_wake(&mut self, timeout: Option<Duration>) -> bool {
+        let tty_fd = self.tty.as_raw_fd();
+        let wake_fd = self.wake_read.as_raw_fd();
+        let mut max_fd = tty_fd.max(wake_fd);
+        let mut rfdset: fd_set = unsafe { std::mem::MaybeUninit::zeroed().assume_init() };
+        unsafe {
+            libc::FD_ZERO(&mut rfdset);
+            libc::FD_SET(tty_fd, &mut rfdset);
+            libc::FD_SET(wake_fd, &mut rfdset);
+            for (fd, _) in &self.watched {
+                libc::FD_SET(*fd, &mut rfdset);
+                max_fd = max_fd.max(*fd);
+            }
+        }
+        let mut tv = timeout.map(|timeout| timeval {
+            tv_sec: timeout.as_secs() as time_t,
+            tv_usec: timeout.subsec_micros() as suseconds_t,
+        });
+        let tv_ptr = match &mut tv {
+            Some(tv) => tv as *mut timeval,
+            None => std::ptr::null_mut(),
+        };
+        unsafe {
+            if libc::select(
+                max_fd + 1,
+                &mut rfdset,
+                std::ptr::null_mut(),
+                std::ptr::null_mut(),
+                tv_ptr,
+            ) <= 0
+            {
+                return false;
+            }
+            for (fd, token) in &self.watched {
+                if libc::FD_ISSET(*fd, &rfdset) && !self.ready.contains(token) {
+                    self.ready.push(*token);
+                }
+            }
+            libc::FD_ISSET(tty_fd, &rfdset)
+        }
+    }
+
+    /// Wait for `fd` to be readable in poll_or_wake, reported as `token`.
+    pub fn watch(&mut self, fd: RawFd, token: u64) -> io::Result<()> {
+        if fd < 0 || fd as usize >= libc::FD_SETSIZE {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "File descriptor can not be watched.",
+            ));
+        }
+        self.unwatch(token);
+        self.watched.push((fd, token));
+        Ok(())
+    }
+
+    /// Stop watching the descriptor registered as `token`.
+    pub fn unwatch(&mut self, token: u64) {
+        self.watched.retain(|(_, t)| *t != token);
+        self.ready.retain(|t| *t != token);
+    }
+
+    /// Return and clear the tokens of the watched descriptors that were
+    /// ready.
+    pub fn take_ready(&mut self) -> Vec<u64> {
+        std::mem::take(&mut self.ready)
+    }
+
+    /// Return a waker that interrupts poll_or_wake.
+    pub fn waker(&self) -> SysWaker {
+        SysWaker {
+            wake_write: self.wake_write.clone(),
+        }
+    }
+
+    /// Discard pending wake ups.
+    pub fn clear_wake(&mut self) {
+        let mut buf = [0_u8; 64];
+        while let Ok(n) = self.wake_read.read(&mut buf) {
+            if n == 0 {
+                break;
+            }
+        }
+    }
+
     /// Read from the byte stream.
     ///
     /// This version blocks, the read from the Read trait does not.
@@ -145,3 +361,55 @@ impl AsRawFd for SysConsoleIn {
         self.tty.as_raw_fd()
     }
 }
+
+impl Wake for SysWaker {
+    fn wake(&self) -> io::Result<()> {
+        SysWaker::wake(self)
+    }
+}
+
+impl SysBackend for SysConsoleIn {
+    type Out = SysConsoleOut;
+
+    fn open() -> io::Result<(Self, SysConsoleOut)> {
+        Ok((open_syscon_in()?, open_syscon_out()?))
+    }
+
+    fn poll(&mut self, timeout: Option<Duration>) -> bool {
+        match timeout {
+            Some(timeout) => self.poll_timeout(timeout),
+            None => {
+                SysConsoleIn::poll(self);
+                true
+            }
+        }
+    }
+
+    fn poll_or_wake(&mut self, timeout: Option<Duration>) -> bool {
+        SysConsoleIn::poll_or_wake(self, timeout)
+    }
+
+    fn read_block(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        SysConsoleIn::read_block(self, buf)
+    }
+
+    fn waker(&self) -> Arc<dyn Wake> {
+        Arc::new(SysConsoleIn::waker(self))
+    }
+
+    fn clear_wake(&mut self) {
+        SysConsoleIn::clear_wake(self)
+    }
+
+    fn take_ready(&mut self) -> Vec<u64> {
+        SysConsoleIn::take_ready(self)
+    }
+
+    fn set_raw_mode(&self, out: &mut SysConsoleOut, mode: bool) -> io::Result<()> {
+        if mode {
+            out.activate_raw_mode(self)
+        } else {
+            out.suspend_raw_mode(self)
+        }
+    }
+}
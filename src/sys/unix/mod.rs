@@ -4,6 +4,11 @@ pub use libc::termios as Termios;
 
 pub mod attr;
 pub mod console;
+#[cfg(feature = "pty")]
+pub mod pty;
+#[cfg(feature = "async")]
+pub mod ready;
+pub mod resize;
 pub mod size;
 pub mod tty;
 
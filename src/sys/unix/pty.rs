@@ -0,0 +1,132 @@
+//! Pseudo terminals, used by the testing and child modules.
+
+use std::ffi::CStr;
+use std::fs::{File, OpenOptions};
+use std::io::{self, Read};
+use std::os::unix::fs::OpenOptionsExt;
+use std::os::unix::io::{AsRawFd, FromRawFd, RawFd};
+use std::os::unix::process::CommandExt;
+use std::process::{Child, Command, Stdio};
+use std::time::Duration;
+
+use lazy_static::lazy_static;
+use parking_lot::Mutex;
+
+lazy_static! {
+    // ptsname() returns a static buffer.
+    static ref PTSNAME: Mutex<()> = Mutex::new(());
+}
+
+/// Spawn `command` with a new pseudo terminal of `cols` x `rows` as its
+/// stdin, stdout, stderr and controlling terminal (so `/dev/tty`).
+///
+/// Returns the master side of the terminal and the child.
+pub fn spawn(command: &mut Command, cols: u16, rows: u16) -> io::Result<(File, Child)> {
+    let (master, slave) = open_pty()?;
+    set_size(master.as_raw_fd(), cols, rows)?;
+    command
+        .stdin(Stdio::from(slave.try_clone()?))
+        .stdout(Stdio::from(slave.try_clone()?))
+        .stderr(Stdio::from(slave));
+    unsafe {
+        command.pre_exec(|| {
+            // A new session so the pty can become the controlling terminal.
+            if libc::setsid() == -1 {
+                return Err(io::Error::last_os_error());
+            }
+            if libc::ioctl(0, libc::TIOCSCTTY as _, 0) == -1 {
+                return Err(io::Error::last_os_error());
+            }
+            Ok(())
+        });
+    }
+    let child = command.spawn();
+    // Close the parent's copies of the slave so the master sees EOF when
+    // the child exits.
+    command
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null());
+    Ok((master, child?))
+}
+
+/// Read the available output from `master` into `output`, waiting at most
+/// `timeout` for some.
+///
+/// Returns false once the child side is closed.
+pub fn read_master(
+    master: &mut File,
+    timeout: Option<Duration>,
+    output: &mut Vec<u8>,
+) -> io::Result<bool> {
+    let mut fds = [libc::pollfd {
+        fd: master.as_raw_fd(),
+        events: libc::POLLIN,
+        revents: 0,
+    }];
+    let timeout = timeout.map_or(-1, |timeout| timeout.as_millis() as libc::c_int);
+    let res = unsafe { libc::poll(fds.as_mut_ptr(), 1, timeout) };
+    if res == -1 {
+        let err = io::Error::last_os_error();
+        return if err.kind() == io::ErrorKind::Interrupted {
+            Ok(true)
+        } else {
+            Err(err)
+        };
+    }
+    if res == 0 {
+        return Ok(true);
+    }
+    let mut buf = [0; 4096];
+    match master.read(&mut buf) {
+        Ok(0) => return Ok(false),
+        Ok(len) => output.extend_from_slice(&buf[..len]),
+        // Linux reports EIO once the slave side is closed.
+        Err(err) if err.raw_os_error() == Some(libc::EIO) => return Ok(false),
+        Err(err) if err.kind() == io::ErrorKind::Interrupted => {}
+        Err(err) => return Err(err),
+    }
+    Ok(true)
+}
+
+/// Open a new pseudo terminal, returns the master and slave sides.
+fn open_pty() -> io::Result<(File, File)> {
+    unsafe {
+        let master = libc::posix_openpt(libc::O_RDWR | libc::O_NOCTTY | libc::O_CLOEXEC);
+        if master == -1 {
+            return Err(io::Error::last_os_error());
+        }
+        let master = File::from_raw_fd(master);
+        if libc::grantpt(master.as_raw_fd()) == -1 || libc::unlockpt(master.as_raw_fd()) == -1 {
+            return Err(io::Error::last_os_error());
+        }
+        let path = {
+            let _lock = PTSNAME.lock();
+            let name = libc::ptsname(master.as_raw_fd());
+            if name.is_null() {
+                return Err(io::Error::last_os_error());
+            }
+            CStr::from_ptr(name).to_string_lossy().into_owned()
+        };
+        let slave = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .custom_flags(libc::O_NOCTTY)
+            .open(path)?;
+        Ok((master, slave))
+    }
+}
+
+/// Set the size of the terminal `fd`, its foreground job gets SIGWINCH.
+pub fn set_size(fd: RawFd, cols: u16, rows: u16) -> io::Result<()> {
+    let size = libc::winsize {
+        ws_row: rows,
+        ws_col: cols,
+        ws_xpixel: 0,
+        ws_ypixel: 0,
+    };
+    if unsafe { libc::ioctl(fd, libc::TIOCSWINSZ, &size) } == -1 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
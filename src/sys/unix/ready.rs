@@ -0,0 +1,108 @@
+//! Wake async tasks when a descriptor becomes readable.
+//!
+//! One watcher thread, started on first use, polls every registered
+//! descriptor and wakes its task once it is readable.  A self-pipe
+//! interrupts the poll when a new descriptor is registered.
+
+use std::fs::File;
+use std::io::{self, Read, Write};
+use std::os::unix::io::{AsRawFd, RawFd};
+use std::task::Waker;
+use std::thread;
+
+use lazy_static::lazy_static;
+use parking_lot::Mutex;
+
+use super::console::wake_pipe;
+
+struct Watcher {
+    waiting: Mutex<Vec<(RawFd, Waker)>>,
+    wake_write: File,
+}
+
+lazy_static! {
+    static ref WATCHER: io::Result<Watcher> = start();
+}
+
+fn start() -> io::Result<Watcher> {
+    let (wake_read, wake_write) = wake_pipe()?;
+    thread::Builder::new()
+        .name("sl-console-ready".to_string())
+        .spawn(move || watch(wake_read))?;
+    Ok(Watcher {
+        waiting: Mutex::new(Vec::new()),
+        wake_write,
+    })
+}
+
+fn watch(mut wake_read: File) {
+    let mut fds = Vec::new();
+    loop {
+        fds.clear();
+        fds.push(libc::pollfd {
+            fd: wake_read.as_raw_fd(),
+            events: libc::POLLIN,
+            revents: 0,
+        });
+        if let Ok(watcher) = &*WATCHER {
+            fds.extend(watcher.waiting.lock().iter().map(|(fd, _)| libc::pollfd {
+                fd: *fd,
+                events: libc::POLLIN,
+                revents: 0,
+            }));
+        }
+        if unsafe { libc::poll(fds.as_mut_ptr(), fds.len() as libc::nfds_t, -1) } <= 0 {
+            continue;
+        }
+        if fds[0].revents != 0 {
+            let mut buf = [0u8; 64];
+            while let Ok(n) = wake_read.read(&mut buf) {
+                if n == 0 {
+                    break;
+                }
+            }
+        }
+        let ready: Vec<RawFd> = fds[1..]
+            .iter()
+            .filter(|pollfd| pollfd.revents != 0)
+            .map(|pollfd| pollfd.fd)
+            .collect();
+        if ready.is_empty() {
+            continue;
+        }
+        if let Ok(watcher) = &*WATCHER {
+            let mut woken = Vec::new();
+            watcher.waiting.lock().retain(|(fd, waker)| {
+                if ready.contains(fd) {
+                    woken.push(waker.clone());
+                    false
+                } else {
+                    true
+                }
+            });
+            woken.into_iter().for_each(Waker::wake);
+        }
+    }
+}
+
+/// Wake `waker` once `fd` is readable (or closed or in error).
+pub fn wake_when_readable(fd: RawFd, waker: &Waker) -> io::Result<()> {
+    let watcher = match &*WATCHER {
+        Ok(watcher) => watcher,
+        Err(err) => return Err(io::Error::new(err.kind(), err.to_string())),
+    };
+    {
+        let mut waiting = watcher.waiting.lock();
+        if waiting
+            .iter()
+            .any(|(waiting_fd, waiting)| *waiting_fd == fd && waiting.will_wake(waker))
+        {
+            return Ok(());
+        }
+        waiting.push((fd, waker.clone()));
+    }
+    match (&watcher.wake_write).write(&[1]) {
+        Err(err) if err.kind() != io::ErrorKind::WouldBlock => Err(err),
+        _ => Ok(()),
+    }
+}
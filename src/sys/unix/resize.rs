@@ -0,0 +1,76 @@
+use std::fs::File;
+use std::io::{self, Read};
+use std::os::unix::io::FromRawFd;
+use std::sync::atomic::{AtomicI32, AtomicUsize, Ordering};
+use std::{mem, thread};
+
+use super::cvt;
+
+/// Write end of the pipe the SIGWINCH handler signals on.
+static SIGNAL_FD: AtomicI32 = AtomicI32::new(-1);
+
+/// The handler that was installed before ours (0 if none), called after ours.
+static PREVIOUS: AtomicUsize = AtomicUsize::new(0);
+
+extern "C" fn on_sigwinch(signal: libc::c_int) {
+    // Only async-signal-safe calls in here, write(2) is one.
+    let fd = SIGNAL_FD.load(Ordering::Relaxed);
+    if fd >= 0 {
+        unsafe {
+            libc::write(fd, b"w".as_ptr() as *const libc::c_void, 1);
+        }
+    }
+    let previous = PREVIOUS.load(Ordering::Relaxed);
+    if previous != 0 {
+        let previous: extern "C" fn(libc::c_int) = unsafe { mem::transmute(previous) };
+        previous(signal);
+    }
+}
+
+/// Install a SIGWINCH handler and start a thread that calls `notify` after
+/// every resize.
+pub fn start_watcher(notify: fn()) -> io::Result<()> {
+    let mut fds = [0; 2];
+    unsafe {
+        cvt(libc::pipe(fds.as_mut_ptr()))?;
+        for fd in fds.iter() {
+            cvt(libc::fcntl(*fd, libc::F_SETFD, libc::FD_CLOEXEC))?;
+        }
+        // Never block in the signal handler, a full pipe already means a
+        // resize is pending.
+        let flags = cvt(libc::fcntl(fds[1], libc::F_GETFL))?;
+        cvt(libc::fcntl(fds[1], libc::F_SETFL, flags | libc::O_NONBLOCK))?;
+    }
+    let mut read = unsafe { File::from_raw_fd(fds[0]) };
+    SIGNAL_FD.store(fds[1], Ordering::Relaxed);
+
+    unsafe {
+        let mut action: libc::sigaction = mem::zeroed();
+        action.sa_sigaction = on_sigwinch as extern "C" fn(libc::c_int) as libc::sighandler_t;
+        action.sa_flags = libc::SA_RESTART;
+        libc::sigemptyset(&mut action.sa_mask);
+        let mut old: libc::sigaction = mem::zeroed();
+        cvt(libc::sigaction(libc::SIGWINCH, &action, &mut old))?;
+        if old.sa_sigaction != libc::SIG_DFL
+            && old.sa_sigaction != libc::SIG_IGN
+            && old.sa_flags & libc::SA_SIGINFO == 0
+        {
+            PREVIOUS.store(old.sa_sigaction, Ordering::Relaxed);
+        }
+    }
+
+    thread::Builder::new()
+        .name("sl-console-resize".to_string())
+        .spawn(move || {
+            let mut buf = [0u8; 64];
+            loop {
+                match read.read(&mut buf) {
+                    Ok(0) => break,
+                    Ok(_) => notify(),
+                    Err(err) if err.kind() == io::ErrorKind::Interrupted => {}
+                    Err(_) => break,
+                }
+            }
+        })?;
+    Ok(())
+}
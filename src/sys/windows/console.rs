@@ -7,7 +7,9 @@ use std::iter::once;
 use std::os::windows::ffi::OsStrExt;
 use std::os::windows::io::FromRawHandle;
 use std::os::windows::io::{AsRawHandle, RawHandle};
+use std::process::{Command, ExitStatus};
 use std::ptr::null_mut;
+use std::sync::Arc;
 use std::thread;
 use std::time::Duration;
 
@@ -20,6 +22,7 @@ use winapi::um::wincon::{
     ENABLE_VIRTUAL_TERMINAL_PROCESSING,
 };
 
+use crate::backend::{SysBackend, Wake};
 use crate::sys::attr::{handle_result, result};
 
 const RAW_MODE_IN_MASK: u32 = ENABLE_LINE_INPUT | ENABLE_ECHO_INPUT | ENABLE_PROCESSED_INPUT;
@@ -46,18 +49,31 @@ pub fn open_syscon_in() -> io::Result<SysConsoleIn> {
     let tty = unsafe { File::from_raw_handle(handle as *mut std::ffi::c_void) };
 
     let (send, recv) = unbounded();
+    #[cfg(feature = "mio")]
+    let notify: Arc<parking_lot::Mutex<Option<mio::Waker>>> = Arc::default();
+    #[cfg(feature = "mio")]
+    let thread_notify = notify.clone();
     thread::spawn(move || {
         for i in tty.bytes() {
             if send.send(i).is_err() {
                 return;
             }
+            #[cfg(feature = "mio")]
+            if let Some(waker) = &*thread_notify.lock() {
+                let _ = waker.wake();
+            }
         }
     });
     let handle = handle as usize;
+    let (wake_send, wake_recv) = unbounded();
     Ok(SysConsoleIn {
         recv,
         normal_mode,
         handle,
+        wake_send,
+        wake_recv,
+        #[cfg(feature = "mio")]
+        notify,
     })
 }
 
@@ -105,6 +121,28 @@ pub struct SysConsoleIn {
     normal_mode: u32,
     /// Handle to CONIN$
     handle: usize,
+    /// Used by SysWaker to interrupt a poll.
+    wake_send: Sender<()>,
+    wake_recv: Receiver<()>,
+    /// Woken by the reader thread for each byte read, set while registered
+    /// with a mio poll.
+    #[cfg(feature = "mio")]
+    notify: Arc<parking_lot::Mutex<Option<mio::Waker>>>,
+}
+
+/// Wakes a SysConsoleIn blocked in poll_or_wake from any thread.
+#[derive(Clone)]
+pub struct SysWaker {
+    wake_send: Sender<()>,
+}
+
+impl SysWaker {
+    /// Make the current or next poll_or_wake return.
+    pub fn wake(&self) -> io::Result<()> {
+        self.wake_send
+            .send(())
+            .map_err(|err| io::Error::new(io::ErrorKind::Other, err))
+    }
 }
 
 impl SysConsoleOut {
@@ -126,6 +164,14 @@ impl SysConsoleOut {
         result(unsafe { SetConsoleMode(handle, raw_mode) })?;
         Ok(())
     }
+
+    /// Run `command` and wait for it.
+    ///
+    /// The console has no foreground process group to hand over, the child
+    /// simply shares it.
+    pub fn run_child(&self, command: &mut Command) -> io::Result<ExitStatus> {
+        command.status()
+    }
 }
 
 impl SysConsoleIn {
@@ -149,6 +195,44 @@ impl SysConsoleIn {
         sel.ready_timeout(timeout).is_ok()
     }
 
+    /// Wait for input or a wake up, with an optional timeout.
+    ///
+    /// Returns true if input is ready, false if woken or timed out.
+    pub fn poll_or_wake(&mut self, timeout: Option<Duration>) -> bool {
+        let mut sel = Select::new();
+        let input = sel.recv(&self.recv);
+        sel.recv(&self.wake_recv);
+        let ready = match timeout {
+            Some(timeout) => sel.ready_timeout(timeout).ok(),
+            None => Some(sel.ready()),
+        };
+        ready == Some(input)
+    }
+
+    /// Return a waker that interrupts poll_or_wake.
+    pub fn waker(&self) -> SysWaker {
+        SysWaker {
+            wake_send: self.wake_send.clone(),
+        }
+    }
+
+    /// Discard pending wake ups.
+    pub fn clear_wake(&mut self) {
+        while self.wake_recv.try_recv().is_ok() {}
+    }
+
+    /// Wake `waker` whenever input arrives, None to stop.
+    #[cfg(feature = "mio")]
+    pub(crate) fn set_notify(&self, waker: Option<mio::Waker>) {
+        *self.notify.lock() = waker;
+    }
+
+    /// Watching other handles is not supported on Windows, nothing is ever
+    /// ready.
+    pub fn take_ready(&mut self) -> Vec<u64> {
+        Vec::new()
+    }
+
     /// Read from the byte stream.
     ///
     /// This version blocks, the read from the Read trait does not.
@@ -254,3 +338,55 @@ impl AsRawHandle for SysConsoleIn {
         self.handle as RawHandle
     }
 }
+
+impl Wake for SysWaker {
+    fn wake(&self) -> io::Result<()> {
+        SysWaker::wake(self)
+    }
+}
+
+impl SysBackend for SysConsoleIn {
+    type Out = SysConsoleOut;
+
+    fn open() -> io::Result<(Self, SysConsoleOut)> {
+        Ok((open_syscon_in()?, open_syscon_out()?))
+    }
+
+    fn poll(&mut self, timeout: Option<Duration>) -> bool {
+        match timeout {
+            Some(timeout) => self.poll_timeout(timeout),
+            None => {
+                SysConsoleIn::poll(self);
+                true
+            }
+        }
+    }
+
+    fn poll_or_wake(&mut self, timeout: Option<Duration>) -> bool {
+        SysConsoleIn::poll_or_wake(self, timeout)
+    }
+
+    fn read_block(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        SysConsoleIn::read_block(self, buf)
+    }
+
+    fn waker(&self) -> Arc<dyn Wake> {
+        Arc::new(SysConsoleIn::waker(self))
+    }
+
+    fn clear_wake(&mut self) {
+        SysConsoleIn::clear_wake(self)
+    }
+
+    fn take_ready(&mut self) -> Vec<u64> {
+        SysConsoleIn::take_ready(self)
+    }
+
+    fn set_raw_mode(&self, out: &mut SysConsoleOut, mode: bool) -> io::Result<()> {
+        if mode {
+            out.activate_raw_mode(self)
+        } else {
+            out.suspend_raw_mode(self)
+        }
+    }
+}
@@ -3,5 +3,6 @@ pub struct Termios(u32, u32); // (input flags, output flags)
 
 pub mod attr;
 pub mod console;
+pub mod resize;
 pub mod size;
 pub mod tty;
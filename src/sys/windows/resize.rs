@@ -0,0 +1,25 @@
+use std::io;
+use std::thread;
+use std::time::Duration;
+
+use super::size::terminal_size;
+
+/// How often to check the console size, the console does not signal
+/// resizes outside of its input records.
+const POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+/// Start a thread that calls `notify` after every resize.
+pub fn start_watcher(notify: fn()) -> io::Result<()> {
+    let mut last = terminal_size().ok();
+    thread::Builder::new()
+        .name("sl-console-resize".to_string())
+        .spawn(move || loop {
+            thread::sleep(POLL_INTERVAL);
+            let size = terminal_size().ok();
+            if size != last {
+                last = size;
+                notify();
+            }
+        })?;
+    Ok(())
+}
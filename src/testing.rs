@@ -0,0 +1,177 @@
+//! Run code under a real pseudo terminal in tests.
+//!
+//! Most of this crate talks to `/dev/tty` so its behaviour (raw mode, mouse
+//! reporting, cursor queries) can not be tested with a pipe.  A [`Pty`]
+//! spawns a process with a new pseudo terminal as its controlling terminal,
+//! feeds it scripted input and captures everything it writes, so these
+//! tests also run in CI where there is no interactive terminal.
+//!
+//! [`Pty::spawn_test`] re-runs a single test of the current test binary in
+//! the pseudo terminal, the test uses [`is_pty_child`] to tell which side it
+//! is on.
+//!
+//! Requires the `testing` feature and is only available on unix.
+//!
+//! # Example
+//!
+//! ```rust,no_run
+//! use sl_console::testing::{is_pty_child, Pty};
+//! use sl_console::*;
+//! use std::time::Duration;
+//!
+//! #[test]
+//! fn cursor_pos() {
+//!     if is_pty_child() {
+//!         let pos = cursor::cursor_pos().unwrap();
+//!         println!("pos {:?}", pos);
+//!         return;
+//!     }
+//!     let mut pty = Pty::spawn_test("cursor_pos").unwrap();
+//!     pty.expect(b"\x1B[6n", Duration::from_secs(5)).unwrap();
+//!     pty.send(b"\x1B[5;7R").unwrap();
+//!     pty.expect(b"pos (7, 5)", Duration::from_secs(5)).unwrap();
+//!     assert!(pty.wait().unwrap().success());
+//! }
+//! ```
+
+use std::env;
+use std::fs::File;
+use std::io::{self, Write};
+use std::os::unix::io::AsRawFd;
+use std::process::{Child, Command, ExitStatus};
+use std::time::Duration;
+
+use crate::clock::{Deadline, SYSTEM_CLOCK};
+use crate::sys::pty::{read_master, set_size, spawn};
+
+/// Set in the environment of a test spawned by [`Pty::spawn_test`].
+const PTY_CHILD_VAR: &str = "SL_CONSOLE_PTY_CHILD";
+
+/// True when running as the child side of [`Pty::spawn_test`].
+pub fn is_pty_child() -> bool {
+    env::var_os(PTY_CHILD_VAR).is_some()
+}
+
+/// A process running with a pseudo terminal as its controlling terminal.
+pub struct Pty {
+    master: File,
+    child: Child,
+    output: Vec<u8>,
+    // Start of the output not yet matched by expect().
+    unmatched: usize,
+    eof: bool,
+}
+
+impl Pty {
+    /// Spawn `command` with a new pseudo terminal of `cols` x `rows` as its
+    /// stdin, stdout, stderr and controlling terminal (so `/dev/tty`).
+    pub fn spawn_size(command: &mut Command, cols: u16, rows: u16) -> io::Result<Pty> {
+        let (master, child) = spawn(command, cols, rows)?;
+        Ok(Pty {
+            master,
+            child,
+            output: Vec::new(),
+            unmatched: 0,
+            eof: false,
+        })
+    }
+
+    /// Spawn `command` in an 80 x 24 pseudo terminal.
+    pub fn spawn(command: &mut Command) -> io::Result<Pty> {
+        Pty::spawn_size(command, 80, 24)
+    }
+
+    /// Run the test named `test` from the current test binary in a pseudo
+    /// terminal.
+    ///
+    /// The name is matched exactly so it must include the module path
+    /// for tests inside modules.  In the child [`is_pty_child`] returns true.
+    pub fn spawn_test(test: &str) -> io::Result<Pty> {
+        let mut command = Command::new(env::current_exe()?);
+        command
+            .args([test, "--exact", "--nocapture", "--test-threads=1"])
+            .env(PTY_CHILD_VAR, test);
+        Pty::spawn(&mut command)
+    }
+
+    /// Write `input` to the terminal as if it was typed.
+    pub fn send(&mut self, input: &[u8]) -> io::Result<()> {
+        self.master.write_all(input)?;
+        self.master.flush()
+    }
+
+    /// Read output until `needle` appears after the previous match, waiting
+    /// at most `timeout`.
+    ///
+    /// Returns a `TimedOut` error containing the unmatched output if the
+    /// needle does not show up in time, or an `UnexpectedEof` error if the
+    /// child closes the terminal first.  An empty needle always matches.
+    pub fn expect(&mut self, needle: &[u8], timeout: Duration) -> io::Result<()> {
+        if needle.is_empty() {
+            return Ok(());
+        }
+        let deadline = Deadline::new(&SYSTEM_CLOCK, timeout);
+        loop {
+            if let Some(pos) = self.output[self.unmatched..]
+                .windows(needle.len())
+                .position(|window| window == needle)
+            {
+                self.unmatched += pos + needle.len();
+                return Ok(());
+            }
+            if self.eof {
+                return Err(self.expect_error(io::ErrorKind::UnexpectedEof, needle));
+            }
+            match deadline.remaining() {
+                Some(remaining) => self.read_output(Some(remaining))?,
+                None => return Err(self.expect_error(io::ErrorKind::TimedOut, needle)),
+            }
+        }
+    }
+
+    fn expect_error(&self, kind: io::ErrorKind, needle: &[u8]) -> io::Error {
+        io::Error::new(
+            kind,
+            format!(
+                "expected {:?}, got {:?}",
+                String::from_utf8_lossy(needle),
+                String::from_utf8_lossy(&self.output[self.unmatched..])
+            ),
+        )
+    }
+
+    /// Everything the child has written that has been read so far.
+    pub fn output(&self) -> &[u8] {
+        &self.output
+    }
+
+    /// Change the terminal size, the child receives SIGWINCH.
+    pub fn resize(&self, cols: u16, rows: u16) -> io::Result<()> {
+        set_size(self.master.as_raw_fd(), cols, rows)
+    }
+
+    /// Wait for the child to exit, reading the rest of its output.
+    pub fn wait(&mut self) -> io::Result<ExitStatus> {
+        while !self.eof {
+            self.read_output(None)?;
+        }
+        self.child.wait()
+    }
+
+    /// Read the available output, waiting at most `timeout` for some.
+    fn read_output(&mut self, timeout: Option<Duration>) -> io::Result<()> {
+        if !read_master(&mut self.master, timeout, &mut self.output)? {
+            self.eof = true;
+        }
+        Ok(())
+    }
+}
+
+impl Drop for Pty {
+    fn drop(&mut self) {
+        if let Ok(None) = self.child.try_wait() {
+            let _ = self.child.kill();
+            let _ = self.child.wait();
+        }
+    }
+}
@@ -0,0 +1,195 @@
+//! Cursor motion over text for line editors.
+//!
+//! The functions take a text and a byte index into it and return the byte
+//! index the cursor moves to, always on a grapheme boundary so the cursor
+//! never lands inside a character with combining marks, an emoji sequence
+//! joined with ZWJ or a flag.  [`column`] gives the screen column of an
+//! index using the crate's [width policy](crate::width).
+//!
+//! Graphemes are found with a simplified version of the Unicode rules:
+//! zero width characters (combining marks, variation selectors, ZWJ) join
+//! the character before them, the character after a ZWJ joins it, regional
+//! indicators pair up into flags and CR LF is one grapheme.  Words are runs
+//! of alphanumeric characters and underscores.
+//!
+//! # Example
+//!
+//! ```rust
+//! use sl_console::text;
+//!
+//! let line = "cafe\u{301} au lait";
+//! // The accent belongs to the e.
+//! assert_eq!(text::prev_grapheme(line, 6), 3);
+//! assert_eq!(text::next_word(line, 0), 6);
+//! assert_eq!(text::prev_word(line, line.len()), 10);
+//! assert_eq!(text::column(line, 6), 4);
+//! ```
+
+use crate::width::{char_width, str_width};
+
+const ZWJ: char = '\u{200D}';
+
+fn is_regional_indicator(ch: char) -> bool {
+    ('\u{1F1E6}'..='\u{1F1FF}').contains(&ch)
+}
+
+/// The byte indexes where graphemes start in `text`, followed by its
+/// length.
+fn boundaries(text: &str) -> Vec<usize> {
+    let mut bounds = Vec::new();
+    let mut prev: Option<char> = None;
+    // Regional indicators in the current run, a flag is a pair.
+    let mut indicators = 0;
+    for (i, ch) in text.char_indices() {
+        let joins = match prev {
+            None => false,
+            Some('\r') => ch == '\n',
+            Some(prev) if prev.is_control() => false,
+            Some(prev) => {
+                (!ch.is_control() && (char_width(ch) == 0 || prev == ZWJ))
+                    || (is_regional_indicator(prev)
+                        && is_regional_indicator(ch)
+                        && indicators % 2 == 1)
+            }
+        };
+        if is_regional_indicator(ch) {
+            indicators += 1;
+        } else {
+            indicators = 0;
+        }
+        if !joins {
+            bounds.push(i);
+        }
+        prev = Some(ch);
+    }
+    bounds.push(text.len());
+    bounds
+}
+
+/// The start of the grapheme after the one at `idx`, `text.len()` at the
+/// end of the text.
+pub fn next_grapheme(text: &str, idx: usize) -> usize {
+    boundaries(text)
+        .into_iter()
+        .find(|bound| *bound > idx)
+        .unwrap_or(text.len())
+}
+
+/// The start of the grapheme before `idx`, 0 at the start of the text.
+pub fn prev_grapheme(text: &str, idx: usize) -> usize {
+    boundaries(text)
+        .into_iter()
+        .rev()
+        .find(|bound| *bound < idx)
+        .unwrap_or(0)
+}
+
+/// The graphemes of `text` as (start, end, is word) ranges.
+fn graphemes(text: &str) -> Vec<(usize, usize, bool)> {
+    boundaries(text)
+        .windows(2)
+        .map(|pair| {
+            let word = text[pair[0]..]
+                .chars()
+                .next()
+                .is_some_and(|ch| ch.is_alphanumeric() || ch == '_');
+            (pair[0], pair[1], word)
+        })
+        .collect()
+}
+
+/// The end of the word at or after `idx`, `text.len()` if there is none.
+pub fn next_word(text: &str, idx: usize) -> usize {
+    let mut in_word = false;
+    for (start, end, word) in graphemes(text) {
+        if end <= idx {
+            continue;
+        }
+        if word {
+            in_word = true;
+        } else if in_word {
+            return start;
+        }
+    }
+    text.len()
+}
+
+/// The start of the word before `idx`, 0 if there is none.
+pub fn prev_word(text: &str, idx: usize) -> usize {
+    let mut in_word = false;
+    for (start, end, word) in graphemes(text).into_iter().rev() {
+        if start >= idx {
+            continue;
+        }
+        if word {
+            in_word = true;
+        } else if in_word {
+            return end;
+        }
+    }
+    0
+}
+
+/// The screen column (zero based) of byte index `idx` of `text`.
+///
+/// An index past the end gives the width of the text, one inside a
+/// character counts from the start of that character.
+pub fn column(text: &str, idx: usize) -> usize {
+    let mut idx = idx.min(text.len());
+    while !text.is_char_boundary(idx) {
+        idx -= 1;
+    }
+    str_width(&text[..idx])
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_graphemes() {
+        // e + combining acute, a ZWJ family, a flag pair, CR LF.
+        let text = "e\u{301}\u{1F468}\u{200D}\u{1F467}\u{1F1EB}\u{1F1F7}\u{1F1E9}\r\nx";
+        let mut stops = vec![0];
+        while *stops.last().unwrap() < text.len() {
+            stops.push(next_grapheme(text, *stops.last().unwrap()));
+        }
+        assert_eq!(stops, vec![0, 3, 14, 22, 26, 28, 29]);
+        let mut back = vec![text.len()];
+        while *back.last().unwrap() > 0 {
+            back.push(prev_grapheme(text, *back.last().unwrap()));
+        }
+        back.reverse();
+        assert_eq!(back, stops);
+        // From inside a grapheme.
+        assert_eq!(next_grapheme(text, 1), 3);
+        assert_eq!(prev_grapheme(text, 1), 0);
+        assert_eq!(next_grapheme("", 0), 0);
+        assert_eq!(prev_grapheme("", 0), 0);
+    }
+
+    #[test]
+    fn test_words() {
+        let text = "  let foo_bar = baz(1);";
+        assert_eq!(next_word(text, 0), 5);
+        assert_eq!(next_word(text, 5), 13);
+        assert_eq!(next_word(text, 8), 13);
+        assert_eq!(next_word(text, 19), 21);
+        assert_eq!(next_word(text, 21), text.len());
+        assert_eq!(prev_word(text, text.len()), 20);
+        assert_eq!(prev_word(text, 19), 16);
+        assert_eq!(prev_word(text, 8), 6);
+        assert_eq!(prev_word(text, 2), 0);
+    }
+
+    #[test]
+    fn test_column() {
+        let text = "a漢e\u{301}b";
+        assert_eq!(column(text, 0), 0);
+        assert_eq!(column(text, 1), 1);
+        assert_eq!(column(text, 2), 1);
+        assert_eq!(column(text, 4), 3);
+        assert_eq!(column(text, 7), 4);
+        assert_eq!(column(text, 100), 5);
+    }
+}
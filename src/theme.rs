@@ -0,0 +1,338 @@
+//! Semantic color themes.
+//!
+//! A [`Theme`] maps roles (error, warning, selection, ...) to concrete colors
+//! with a light and a dark variant so applications can ask for "the error
+//! color" instead of hard coding one that may be unreadable on the user's
+//! background.  [`Theme::detect`] picks the variant by asking the terminal
+//! for its background color.
+//!
+//! # Example
+//!
+//! ```rust,no_run
+//! use sl_console::theme::{Role, Theme};
+//! use sl_console::*;
+//!
+//!     con_init().unwrap();
+//!     let theme = Theme::detect();
+//!     println!("{}error:{} bad input", theme.style(Role::Error), theme.reset());
+//! ```
+
+use std::fmt;
+use std::io::{self, Error, ErrorKind, Write};
+use std::time::Duration;
+
+use crate::clock::{Deadline, SYSTEM_CLOCK};
+use crate::color::{self, Rgb};
+use crate::console::*;
+
+/// How long to wait for the terminal to report its background color.
+const QUERY_TIMEOUT: Duration = Duration::from_millis(100);
+
+/// The number of roles in a theme.
+const ROLE_COUNT: usize = 6;
+
+/// The purpose of some text, used to look up its colors in a [`Theme`].
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum Role {
+    /// Normal text.
+    Text,
+    /// Error messages.
+    Error,
+    /// Warnings.
+    Warning,
+    /// Hints and other low priority information.
+    Hint,
+    /// Selected or highlighted items.
+    Selection,
+    /// Borders and separators.
+    Border,
+}
+
+impl Role {
+    fn index(self) -> usize {
+        match self {
+            Role::Text => 0,
+            Role::Error => 1,
+            Role::Warning => 2,
+            Role::Hint => 3,
+            Role::Selection => 4,
+            Role::Border => 5,
+        }
+    }
+}
+
+/// The background a theme is for.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum Variant {
+    /// Dark text on a light background.
+    Light,
+    /// Light text on a dark background.
+    Dark,
+}
+
+impl Variant {
+    /// The variant to use on the background color `bg`.
+    pub fn for_background(bg: Rgb) -> Variant {
+        let Rgb(r, g, b) = bg;
+        // Perceived brightness (ITU-R BT.601 weights).
+        let brightness = (299 * r as u32 + 587 * g as u32 + 114 * b as u32) / 1000;
+        if brightness < 128 {
+            Variant::Dark
+        } else {
+            Variant::Light
+        }
+    }
+}
+
+/// The colors for a role, None leaves the terminal default.
+#[derive(Debug, Copy, Clone, Default, PartialEq)]
+pub struct RoleColors {
+    /// Foreground color.
+    pub fg: Option<Rgb>,
+    /// Background color.
+    pub bg: Option<Rgb>,
+}
+
+impl RoleColors {
+    /// Colors with only a foreground.
+    pub const fn fg(fg: Rgb) -> Self {
+        RoleColors {
+            fg: Some(fg),
+            bg: None,
+        }
+    }
+
+    /// Colors with a foreground and background.
+    pub const fn fg_bg(fg: Rgb, bg: Rgb) -> Self {
+        RoleColors {
+            fg: Some(fg),
+            bg: Some(bg),
+        }
+    }
+}
+
+impl fmt::Display for RoleColors {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if let Some(fg) = self.fg {
+            write!(f, "{}", color::Fg(fg))?;
+        }
+        if let Some(bg) = self.bg {
+            write!(f, "{}", color::Bg(bg))?;
+        }
+        Ok(())
+    }
+}
+
+const DEFAULT_LIGHT: [RoleColors; ROLE_COUNT] = [
+    RoleColors { fg: None, bg: None },
+    RoleColors::fg(Rgb(0xb0, 0x00, 0x20)),
+    RoleColors::fg(Rgb(0x8a, 0x5a, 0x00)),
+    RoleColors::fg(Rgb(0x50, 0x50, 0x50)),
+    RoleColors::fg_bg(Rgb(0x00, 0x00, 0x00), Rgb(0xb4, 0xd5, 0xfe)),
+    RoleColors::fg(Rgb(0x90, 0x90, 0x90)),
+];
+
+const DEFAULT_DARK: [RoleColors; ROLE_COUNT] = [
+    RoleColors { fg: None, bg: None },
+    RoleColors::fg(Rgb(0xff, 0x6b, 0x6b)),
+    RoleColors::fg(Rgb(0xff, 0xc8, 0x57)),
+    RoleColors::fg(Rgb(0xa0, 0xa0, 0xa0)),
+    RoleColors::fg_bg(Rgb(0xff, 0xff, 0xff), Rgb(0x26, 0x4f, 0x78)),
+    RoleColors::fg(Rgb(0x60, 0x60, 0x60)),
+];
+
+/// Colors for each [`Role`] in a light and dark variant.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Theme {
+    variant: Variant,
+    light: [RoleColors; ROLE_COUNT],
+    dark: [RoleColors; ROLE_COUNT],
+}
+
+impl Default for Theme {
+    /// The built in colors using the dark variant.
+    fn default() -> Self {
+        Theme::new(Variant::Dark)
+    }
+}
+
+impl Theme {
+    /// The built in colors using `variant`.
+    pub fn new(variant: Variant) -> Self {
+        Theme {
+            variant,
+            light: DEFAULT_LIGHT,
+            dark: DEFAULT_DARK,
+        }
+    }
+
+    /// The built in colors with the variant for the terminal background.
+    ///
+    /// If the terminal does not report its background then the dark
+    /// variant is used.
+    pub fn detect() -> Self {
+        let variant = query_background()
+            .map(Variant::for_background)
+            .unwrap_or(Variant::Dark);
+        Theme::new(variant)
+    }
+
+    /// The variant in use.
+    pub fn variant(&self) -> Variant {
+        self.variant
+    }
+
+    /// Switch to `variant`.
+    pub fn set_variant(&mut self, variant: Variant) {
+        self.variant = variant;
+    }
+
+    /// Set the colors of `role` in `variant`.
+    pub fn set(&mut self, variant: Variant, role: Role, colors: RoleColors) {
+        match variant {
+            Variant::Light => self.light[role.index()] = colors,
+            Variant::Dark => self.dark[role.index()] = colors,
+        }
+    }
+
+    /// The colors of `role` in the current variant.
+    pub fn colors(&self, role: Role) -> RoleColors {
+        match self.variant {
+            Variant::Light => self.light[role.index()],
+            Variant::Dark => self.dark[role.index()],
+        }
+    }
+
+    /// The escape sequence to switch to the colors of `role`.
+    pub fn style(&self, role: Role) -> RoleColors {
+        self.colors(role)
+    }
+
+    /// The escape sequence to go back to the default colors.
+    pub fn reset(&self) -> ResetColors {
+        ResetColors
+    }
+}
+
+/// Writes the sequence to go back to the default colors (following the
+/// [style policy](crate::style::set_policy)), see [`Theme::reset`].
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq)]
+pub struct ResetColors;
+
+impl fmt::Display for ResetColors {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}{}", color::Fg(color::Reset), color::Bg(color::Reset))
+    }
+}
+
+/// Parse a color from an OSC color report (`rgb:RRRR/GGGG/BBBB`, each
+/// component 1 to 4 hex digits).
+pub fn parse_color_report(report: &str) -> Option<Rgb> {
+    let mut parts = report.strip_prefix("rgb:")?.split('/');
+    let mut component = || -> Option<u8> {
+        let hex = parts.next()?;
+        if hex.is_empty() || hex.len() > 4 {
+            return None;
+        }
+        let value = u32::from_str_radix(hex, 16).ok()?;
+        let max = (1u32 << (4 * hex.len())) - 1;
+        Some(((value * 255 + max / 2) / max) as u8)
+    };
+    let rgb = Rgb(component()?, component()?, component()?);
+    if parts.next().is_some() {
+        return None;
+    }
+    Some(rgb)
+}
+
+/// Ask the terminal for its background color (OSC 11).
+///
+/// The console is put in raw mode while waiting for the reply.  Returns an
+/// error if the terminal does not answer.
+pub fn query_background() -> io::Result<Rgb> {
+    let mut conout = conout_r()?.lock();
+    let mut conin = conin_r()?.lock();
+    let was_raw = conout.set_raw_mode(true)?;
+    let reply = read_background(&mut conin, &mut conout);
+    conout.set_raw_mode(was_raw)?;
+    let reply = reply?;
+
+    // The answer will look like `ESC ] 11 ; rgb:.... ST`.
+    let reply = String::from_utf8_lossy(&reply);
+    reply
+        .find("11;")
+        .and_then(|start| parse_color_report(&reply[start + 3..]))
+        .ok_or_else(|| Error::new(ErrorKind::InvalidData, "Invalid background color report."))
+}
+
+/// Send the background query and return the reply without its terminator.
+fn read_background<R, W>(conin: &mut R, conout: &mut W) -> io::Result<Vec<u8>>
+where
+    R: ConsoleRead,
+    W: Write,
+{
+    write!(conout, "\x1B]11;?\x1B\\")?;
+    conout.flush()?;
+
+    let mut buf: [u8; 1] = [0];
+    let mut read_chars = Vec::new();
+    let deadline = Deadline::new(&SYSTEM_CLOCK, QUERY_TIMEOUT);
+    while let Some(remaining) = deadline.remaining() {
+        match conin.read_timeout(&mut buf, Some(remaining)) {
+            Ok(1) => {
+                read_chars.push(buf[0]);
+                if buf[0] == b'\x07' {
+                    read_chars.pop();
+                    return Ok(read_chars);
+                }
+                if read_chars.ends_with(b"\x1B\\") {
+                    read_chars.truncate(read_chars.len() - 2);
+                    return Ok(read_chars);
+                }
+            }
+            Ok(0) => {
+                return Err(Error::new(ErrorKind::UnexpectedEof, "Unexpected EOF."));
+            }
+            Ok(_) => {}
+            Err(err) if err.kind() == ErrorKind::WouldBlock => {}
+            Err(err) => return Err(err),
+        }
+    }
+    Err(Error::new(
+        ErrorKind::TimedOut,
+        "Background color query timed out.",
+    ))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_parse_color_report() {
+        assert_eq!(
+            parse_color_report("rgb:ffff/8080/0000"),
+            Some(Rgb(255, 128, 0))
+        );
+        assert_eq!(parse_color_report("rgb:f/0/8"), Some(Rgb(255, 0, 136)));
+        assert_eq!(parse_color_report("rgb:ff/00"), None);
+        assert_eq!(parse_color_report("#ffffff"), None);
+    }
+
+    #[test]
+    fn test_variants() {
+        assert_eq!(Variant::for_background(Rgb(0, 0, 0)), Variant::Dark);
+        assert_eq!(Variant::for_background(Rgb(250, 250, 240)), Variant::Light);
+
+        let mut theme = Theme::new(Variant::Light);
+        let light_error = theme.colors(Role::Error);
+        theme.set_variant(Variant::Dark);
+        assert_ne!(theme.colors(Role::Error), light_error);
+        theme.set(Variant::Dark, Role::Error, RoleColors::fg(Rgb(1, 2, 3)));
+        assert_eq!(
+            theme.style(Role::Error).to_string(),
+            "\x1b[38;2;1;2;3m".to_string()
+        );
+        assert_eq!(theme.style(Role::Text).to_string(), "");
+    }
+}
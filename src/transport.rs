@@ -0,0 +1,325 @@
+//! Console input and output over any byte stream.
+//!
+//! [`TransportIn`] and [`TransportOut`] implement [`ConsoleRead`] and
+//! [`ConsoleWrite`] over a plain `Read` and `Write` (an SSH channel, a TCP
+//! stream to a telnet client, a pipe) so server side programs can run the
+//! same TUI code they run on the local console.  The reader is read on a
+//! background thread so reads can time out like they do on the console.
+//!
+//! There is no terminal driver on the other end of the stream, so raw mode
+//! is only recorded: the client (or protocol, for instance telnet option
+//! negotiation) decides whether input is line buffered or echoed.
+//!
+//! # Example
+//!
+//! ```rust,no_run
+//! use sl_console::transport::transport;
+//! use sl_console::*;
+//! use std::io::Write;
+//! use std::net::TcpListener;
+//!
+//!     let listener = TcpListener::bind("127.0.0.1:2323").unwrap();
+//!     let (stream, _) = listener.accept().unwrap();
+//!     let (mut conin, mut conout) = transport(stream.try_clone().unwrap(), stream);
+//!     write!(conout, "{}Press a key", clear::All).unwrap();
+//!     conout.flush().unwrap();
+//!     if let Some(Ok(key)) = conin.keys().next() {
+//!         writeln!(conout, "\r\n{:?}", key).unwrap();
+//!     }
+//! ```
+
+use std::collections::VecDeque;
+use std::io::{self, Read, Write};
+use std::ops;
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError};
+use std::thread;
+use std::time::Duration;
+
+use crate::console::{ConsoleRead, ConsoleWrite};
+use crate::event::{Event, ParseOptions};
+use crate::input::event_and_raw;
+
+/// The default time to wait for the rest of an escape sequence, longer than
+/// the console's since the bytes cross a network.
+const SEQUENCE_TIMEOUT: Duration = Duration::from_millis(100);
+
+/// Console input read from a byte stream.
+pub struct TransportIn {
+    data: Receiver<io::Result<Vec<u8>>>,
+    buf: VecDeque<u8>,
+    eof: bool,
+    leftover: Option<u8>,
+    options: ParseOptions,
+    sequence_timeout: Duration,
+    // Events put back, returned next (from the front).
+    unread: VecDeque<(Event, Vec<u8>)>,
+}
+
+impl TransportIn {
+    /// Read console input from `reader`.
+    ///
+    /// `reader` is moved to a background thread that reads it until the
+    /// end of input or an error, the thread blocks in `read` until then
+    /// even if this is dropped.
+    pub fn new<R: Read + Send + 'static>(mut reader: R) -> Self {
+        let (tx, data) = mpsc::channel();
+        thread::spawn(move || {
+            let mut buf = [0u8; 4096];
+            loop {
+                let res = match reader.read(&mut buf) {
+                    Ok(len) => Ok(buf[..len].to_vec()),
+                    Err(err) if err.kind() == io::ErrorKind::Interrupted => continue,
+                    Err(err) => Err(err),
+                };
+                let done = !matches!(&res, Ok(data) if !data.is_empty());
+                if tx.send(res).is_err() || done {
+                    break;
+                }
+            }
+        });
+        TransportIn {
+            data,
+            buf: VecDeque::new(),
+            eof: false,
+            leftover: None,
+            options: ParseOptions::default(),
+            sequence_timeout: SEQUENCE_TIMEOUT,
+            unread: VecDeque::new(),
+        }
+    }
+
+    /// Set how long to wait for the rest of a partially read escape
+    /// sequence.
+    pub fn set_sequence_timeout(&mut self, timeout: Duration) {
+        self.sequence_timeout = timeout;
+    }
+
+    /// Return the escape sequence timeout.
+    pub fn sequence_timeout(&self) -> Duration {
+        self.sequence_timeout
+    }
+
+    /// Set the options used to parse input into events.
+    pub fn set_parse_options(&mut self, options: ParseOptions) {
+        self.options = options;
+    }
+
+    /// Return the options used to parse input into events.
+    pub fn parse_options(&self) -> ParseOptions {
+        self.options
+    }
+
+    /// Wait for more data from the reader thread.  Returns an error of
+    /// kind WouldBlock if `timeout` passes first.
+    fn fill(&mut self, timeout: Option<Duration>) -> io::Result<()> {
+        if !self.buf.is_empty() || self.eof {
+            return Ok(());
+        }
+        let res = match timeout {
+            Some(timeout) => self.data.recv_timeout(timeout),
+            None => self.data.recv().map_err(|_| RecvTimeoutError::Disconnected),
+        };
+        match res {
+            Ok(Ok(data)) if data.is_empty() => self.eof = true,
+            Ok(Ok(data)) => self.buf.extend(data),
+            Ok(Err(err)) => {
+                self.eof = true;
+                return Err(err);
+            }
+            Err(RecvTimeoutError::Timeout) => {
+                return Err(io::Error::new(
+                    io::ErrorKind::WouldBlock,
+                    "Timed out on transport read.",
+                ))
+            }
+            Err(RecvTimeoutError::Disconnected) => self.eof = true,
+        }
+        Ok(())
+    }
+}
+
+/// Reader used while parsing a single event, the first read waits up to
+/// the caller's timeout and the rest up to the sequence timeout.
+struct EventReader<'a> {
+    input: &'a mut TransportIn,
+    timeout: Option<Duration>,
+    started: bool,
+}
+
+impl<'a> Read for EventReader<'a> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let timeout = if self.started {
+            Some(self.input.sequence_timeout)
+        } else {
+            self.timeout
+        };
+        self.started = true;
+        self.input.read_timeout(buf, timeout)
+    }
+}
+
+impl ConsoleRead for TransportIn {
+    fn get_event_and_raw(
+        &mut self,
+        timeout: Option<Duration>,
+    ) -> Option<io::Result<(Event, Vec<u8>)>> {
+        if let Some(unread) = self.unread.pop_front() {
+            return Some(Ok(unread));
+        }
+        let mut leftover = self.leftover.take();
+        let options = self.options;
+        let res = {
+            let mut reader = EventReader {
+                started: leftover.is_some(),
+                input: self,
+                timeout,
+            };
+            event_and_raw(&mut reader, &mut leftover, &options)
+        };
+        self.leftover = leftover;
+        res
+    }
+
+    fn poll(&mut self, timeout: Option<Duration>) -> bool {
+        !self.unread.is_empty()
+            || self.leftover.is_some()
+            || (self.fill(timeout).is_ok() && !self.buf.is_empty())
+    }
+
+    fn read_timeout(&mut self, buf: &mut [u8], timeout: Option<Duration>) -> io::Result<usize> {
+        self.fill(timeout)?;
+        let len = buf.len().min(self.buf.len());
+        for (dst, src) in buf.iter_mut().zip(self.buf.drain(..len)) {
+            *dst = src;
+        }
+        Ok(len)
+    }
+
+    fn unget_event_and_raw(&mut self, event: Event, raw: Vec<u8>) -> io::Result<()> {
+        self.unread.push_front((event, raw));
+        Ok(())
+    }
+}
+
+impl Read for TransportIn {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.read_timeout(buf, None)
+    }
+}
+
+/// Console output written to a byte stream.
+pub struct TransportOut<W: Write> {
+    inner: W,
+    raw_mode: bool,
+}
+
+impl<W: Write> TransportOut<W> {
+    /// Write console output to `inner`.
+    pub fn new(inner: W) -> Self {
+        TransportOut {
+            inner,
+            raw_mode: false,
+        }
+    }
+
+    /// Return the wrapped writer.
+    pub fn into_inner(self) -> W {
+        self.inner
+    }
+}
+
+impl<W: Write> ops::Deref for TransportOut<W> {
+    type Target = W;
+
+    fn deref(&self) -> &W {
+        &self.inner
+    }
+}
+
+impl<W: Write> ops::DerefMut for TransportOut<W> {
+    fn deref_mut(&mut self) -> &mut W {
+        &mut self.inner
+    }
+}
+
+impl<W: Write> Write for TransportOut<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.inner.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+impl<W: Write> ConsoleWrite for TransportOut<W> {
+    /// Record the mode, nothing is sent to the other end.
+    fn set_raw_mode(&mut self, mode: bool) -> io::Result<bool> {
+        Ok(std::mem::replace(&mut self.raw_mode, mode))
+    }
+
+    fn is_raw_mode(&self) -> bool {
+        self.raw_mode
+    }
+}
+
+/// Console input and output over `reader` and `writer`, see
+/// [`TransportIn::new`] and [`TransportOut::new`].
+pub fn transport<R, W>(reader: R, writer: W) -> (TransportIn, TransportOut<W>)
+where
+    R: Read + Send + 'static,
+    W: Write,
+{
+    (TransportIn::new(reader), TransportOut::new(writer))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::event::{Key, KeyCode};
+    use std::sync::mpsc::Sender;
+
+    /// A reader fed through a channel, blocks until data is sent.
+    struct ChannelReader(Receiver<Vec<u8>>);
+
+    impl Read for ChannelReader {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            let data = self.0.recv().unwrap_or_default();
+            buf[..data.len()].copy_from_slice(&data);
+            Ok(data.len())
+        }
+    }
+
+    fn channel() -> (Sender<Vec<u8>>, TransportIn) {
+        let (tx, rx) = mpsc::channel();
+        (tx, TransportIn::new(ChannelReader(rx)))
+    }
+
+    #[test]
+    fn test_transport_in() {
+        let (tx, mut conin) = channel();
+        let err = conin
+            .get_event_and_raw(Some(Duration::from_millis(10)))
+            .unwrap()
+            .unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::WouldBlock);
+
+        // An escape sequence split across reads.
+        tx.send(b"a\x1B[".to_vec()).unwrap();
+        tx.send(b"A".to_vec()).unwrap();
+        let mut next = || conin.get_event_and_raw(None).map(|res| res.unwrap().0);
+        assert_eq!(next(), Some(Event::Key(Key::new(KeyCode::Char('a')))));
+        assert_eq!(next(), Some(Event::Key(Key::new(KeyCode::Up))));
+        drop(tx);
+        assert_eq!(next(), None);
+    }
+
+    #[test]
+    fn test_transport_out() {
+        let (_, mut conout) = transport(io::empty(), Vec::new());
+        assert!(!conout.set_raw_mode(true).unwrap());
+        assert!(conout.is_raw_mode());
+        write!(conout, "hi").unwrap();
+        assert_eq!(conout.into_inner(), b"hi");
+    }
+}
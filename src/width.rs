@@ -0,0 +1,127 @@
+//! Display width of text.
+//!
+//! Some characters (mostly from East Asian scripts, such as `±` or `Ω`)
+//! have an ambiguous width, terminals show them as one or two cells
+//! depending on their configuration.  The [`WidthPolicy`] decides which
+//! width is used, every width computed by the crate (for instance when
+//! writing text into a [`Grid`](crate::grid::Grid)) goes through the
+//! functions in this module and so follows the policy set with
+//! [`set_width_policy`].
+
+use std::env;
+use std::sync::atomic::{AtomicU8, Ordering};
+
+use lazy_static::lazy_static;
+use unicode_width::{UnicodeWidthChar, UnicodeWidthStr};
+
+lazy_static! {
+    // The locale does not change, only look it up once.
+    static ref AUTO_POLICY: WidthPolicy = {
+        let locale = ["LC_ALL", "LC_CTYPE", "LANG"]
+            .iter()
+            .filter_map(|name| env::var(name).ok())
+            .find(|v| !v.is_empty())
+            .unwrap_or_default();
+        WidthPolicy::for_locale(&locale)
+    };
+}
+
+static WIDTH_POLICY: AtomicU8 = AtomicU8::new(WidthPolicy::Auto as u8);
+
+/// How wide ambiguous width characters are.
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq, Hash)]
+pub enum WidthPolicy {
+    /// Ambiguous characters are one cell wide (the usual setting outside
+    /// of CJK locales).
+    Narrow = 0,
+    /// Ambiguous characters are two cells wide.
+    Wide = 1,
+    /// Wide in Chinese, Japanese and Korean locales, otherwise narrow.
+    #[default]
+    Auto = 2,
+}
+
+impl WidthPolicy {
+    /// Resolve Auto to Narrow or Wide using the locale, the other policies
+    /// are returned unchanged.
+    pub fn resolve(self) -> WidthPolicy {
+        match self {
+            WidthPolicy::Auto => *AUTO_POLICY,
+            policy => policy,
+        }
+    }
+
+    /// The policy Auto uses for `locale` (a value like `ja_JP.UTF-8`).
+    pub fn for_locale(locale: &str) -> WidthPolicy {
+        let lang = locale.split(['_', '.']).next().unwrap_or("");
+        match lang {
+            "ja" | "ko" | "zh" => WidthPolicy::Wide,
+            _ => WidthPolicy::Narrow,
+        }
+    }
+
+    fn from_u8(value: u8) -> WidthPolicy {
+        match value {
+            0 => WidthPolicy::Narrow,
+            1 => WidthPolicy::Wide,
+            _ => WidthPolicy::Auto,
+        }
+    }
+}
+
+/// Set the policy used for ambiguous width characters.
+pub fn set_width_policy(policy: WidthPolicy) {
+    WIDTH_POLICY.store(policy as u8, Ordering::Relaxed);
+}
+
+/// The policy used for ambiguous width characters.
+pub fn width_policy() -> WidthPolicy {
+    WidthPolicy::from_u8(WIDTH_POLICY.load(Ordering::Relaxed))
+}
+
+/// The number of cells `ch` takes up with `policy`, control characters
+/// and combining marks are 0.
+pub fn char_width_with(ch: char, policy: WidthPolicy) -> usize {
+    let width = match policy.resolve() {
+        WidthPolicy::Wide => ch.width_cjk(),
+        _ => ch.width(),
+    };
+    width.unwrap_or(0)
+}
+
+/// The number of cells `text` takes up with `policy`.
+pub fn str_width_with(text: &str, policy: WidthPolicy) -> usize {
+    match policy.resolve() {
+        WidthPolicy::Wide => text.width_cjk(),
+        _ => text.width(),
+    }
+}
+
+/// The number of cells `ch` takes up using the current policy.
+pub fn char_width(ch: char) -> usize {
+    char_width_with(ch, width_policy())
+}
+
+/// The number of cells `text` takes up using the current policy.
+pub fn str_width(text: &str) -> usize {
+    str_width_with(text, width_policy())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_widths() {
+        assert_eq!(char_width_with('a', WidthPolicy::Wide), 1);
+        assert_eq!(char_width_with('漢', WidthPolicy::Narrow), 2);
+        assert_eq!(char_width_with('\u{301}', WidthPolicy::Narrow), 0);
+        assert_eq!(char_width_with('±', WidthPolicy::Narrow), 1);
+        assert_eq!(char_width_with('±', WidthPolicy::Wide), 2);
+        assert_eq!(str_width_with("a±漢", WidthPolicy::Narrow), 4);
+        assert_eq!(str_width_with("a±漢", WidthPolicy::Wide), 5);
+        assert_eq!(WidthPolicy::for_locale("ja_JP.UTF-8"), WidthPolicy::Wide);
+        assert_eq!(WidthPolicy::for_locale("en_US.UTF-8"), WidthPolicy::Narrow);
+        assert_eq!(WidthPolicy::for_locale("C"), WidthPolicy::Narrow);
+    }
+}
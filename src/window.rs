@@ -0,0 +1,211 @@
+//! Window operations (XTWINOPS, `CSI ... t`).
+//!
+//! Sequences to iconify the terminal window, save and restore its title and
+//! ask for its state, position and size.  The replies arrive as console
+//! input and are read with
+//! [`CsiSequence::window_report`](crate::event::CsiSequence::window_report),
+//! or by the query functions here which send the request and wait for the
+//! reply.  Many terminals ignore some or all of these (window managers often
+//! refuse to iconify, the position is meaningless over SSH), the queries
+//! return an `Unsupported` error when the terminal does not answer.
+//!
+//! # Example
+//!
+//! ```rust,no_run
+//! use sl_console::window;
+//! use sl_console::*;
+//!
+//!     con_init().unwrap();
+//!     let mut conout = conout().lock();
+//!     conout.raw_mode_on().unwrap();
+//!     let size = window::query_size_pixels(&mut conin().lock(), &mut conout);
+//!     conout.raw_mode_off().unwrap();
+//!     println!("{:?}", size);
+//! ```
+
+use std::io::{self, Write};
+
+use crate::caps::query_until_da1;
+use crate::clock::{Clock, SYSTEM_CLOCK};
+use crate::console::{conin_r, conout_r, ConsoleRead, ConsoleWrite};
+use crate::event::{Event, WindowReport};
+#[cfg(unix)]
+use crate::sys::size::terminal_size_pixels as driver_size_pixels;
+
+derive_csi_sequence!("De-iconify (restore) the window.", Deiconify, "1t");
+derive_csi_sequence!("Iconify (minimize) the window.", Iconify, "2t");
+derive_csi_sequence!(
+    "Ask whether the window is iconified, see [`query_iconified`].",
+    ReportState,
+    "11t"
+);
+derive_csi_sequence!(
+    "Ask for the window position in pixels, see [`query_position`].",
+    ReportPosition,
+    "13t"
+);
+derive_csi_sequence!(
+    "Ask for the text area size in pixels, see [`query_size_pixels`].",
+    ReportSizePixels,
+    "14t"
+);
+derive_csi_sequence!(
+    "Ask for the text area size in characters, see [`query_size_chars`].",
+    ReportSizeChars,
+    "18t"
+);
+derive_csi_sequence!(
+    "Save the window and icon titles on the terminal's title stack.",
+    PushTitle,
+    "22;0t"
+);
+derive_csi_sequence!(
+    "Restore the window and icon titles saved by [`PushTitle`].",
+    PopTitle,
+    "23;0t"
+);
+
+/// Ask whether the window is iconified.
+///
+/// The query is written to `conout` and the reply read from `conin`, the
+/// console should be in raw mode.  Other input that arrives while waiting
+/// is discarded.
+pub fn query_iconified<R: ConsoleRead, W: Write>(
+    conin: &mut R,
+    conout: &mut W,
+) -> io::Result<bool> {
+    match query(conin, conout, ReportState::STR, &SYSTEM_CLOCK)? {
+        WindowReport::Iconified(iconified) => Ok(iconified),
+        _ => Err(unsupported()),
+    }
+}
+
+/// Ask for the position of the window's top left corner on the screen in
+/// pixels (x, y), see [`query_iconified`] for how the console is used.
+pub fn query_position<R: ConsoleRead, W: Write>(
+    conin: &mut R,
+    conout: &mut W,
+) -> io::Result<(u16, u16)> {
+    match query(conin, conout, ReportPosition::STR, &SYSTEM_CLOCK)? {
+        WindowReport::Position(x, y) => Ok((x, y)),
+        _ => Err(unsupported()),
+    }
+}
+
+/// Ask for the size of the text area in pixels (width, height), see
+/// [`query_iconified`] for how the console is used.
+pub fn query_size_pixels<R: ConsoleRead, W: Write>(
+    conin: &mut R,
+    conout: &mut W,
+) -> io::Result<(u16, u16)> {
+    match query(conin, conout, ReportSizePixels::STR, &SYSTEM_CLOCK)? {
+        WindowReport::SizePixels(width, height) => Ok((width, height)),
+        _ => Err(unsupported()),
+    }
+}
+
+/// Ask for the size of the text area in characters (columns, rows), see
+/// [`query_iconified`] for how the console is used.
+pub fn query_size_chars<R: ConsoleRead, W: Write>(
+    conin: &mut R,
+    conout: &mut W,
+) -> io::Result<(u16, u16)> {
+    match query(conin, conout, ReportSizeChars::STR, &SYSTEM_CLOCK)? {
+        WindowReport::SizeChars(cols, rows) => Ok((cols, rows)),
+        _ => Err(unsupported()),
+    }
+}
+
+/// Get the size of the terminal, in pixels (width, height).
+///
+/// The size comes from the terminal driver (TIOCGWINSZ) on unix.  Many
+/// terminal emulators leave the pixel size there zero, and Windows has no
+/// such call, so then the terminal is asked with [`ReportSizePixels`]
+/// through the console (put in raw mode for the query if it is not
+/// already), waiting up to 200ms for the reply.
+pub fn terminal_size_pixels() -> io::Result<(u16, u16)> {
+    match driver_size_pixels() {
+        Ok((width, height)) if width > 0 && height > 0 => Ok((width, height)),
+        _ => {
+            let mut conout = conout_r()?.lock();
+            let mut conin = conin_r()?.lock();
+            let was_raw = conout.set_raw_mode(true)?;
+            let size = query_size_pixels(&mut conin, &mut conout);
+            conout.set_raw_mode(was_raw)?;
+            size
+        }
+    }
+}
+
+#[cfg(windows)]
+fn driver_size_pixels() -> io::Result<(u16, u16)> {
+    Ok((0, 0))
+}
+
+fn unsupported() -> io::Error {
+    io::Error::new(
+        io::ErrorKind::Unsupported,
+        "The terminal does not report this window property.",
+    )
+}
+
+/// Send `request` and return the window report that answers it.
+///
+/// A DA1 reply with no report before it means the request is not
+/// supported.
+fn query<R, W>(
+    conin: &mut R,
+    conout: &mut W,
+    request: &str,
+    clock: &dyn Clock,
+) -> io::Result<WindowReport>
+where
+    R: ConsoleRead,
+    W: Write,
+{
+    let mut report = None;
+    let answered = query_until_da1(conin, conout, request, clock, |event, _| {
+        if let Event::Csi(reply) = event {
+            report = report.or_else(|| reply.window_report());
+        }
+    })?;
+    match report {
+        Some(report) => Ok(report),
+        None if answered => Err(unsupported()),
+        None => Err(io::Error::new(
+            io::ErrorKind::TimedOut,
+            "Window query timed out.",
+        )),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::clock::{SilentConsole, TestClock};
+
+    #[test]
+    fn test_query() {
+        let clock = TestClock::new();
+        let mut out = Vec::new();
+        let mut conin = &b"x\x1b[4;600;800t\x1b[?62c"[..];
+        let report = query(&mut conin, &mut out, ReportSizePixels::STR, &clock).unwrap();
+        assert_eq!(report, WindowReport::SizePixels(800, 600));
+        assert_eq!(out, b"\x1b[14t\x1b[c");
+
+        let mut conin = &b"\x1b[8;24;80t\x1b[?62c"[..];
+        assert_eq!(query_size_chars(&mut conin, &mut out).unwrap(), (80, 24));
+        let mut conin = &b"\x1b[3;10;20t\x1b[?62c"[..];
+        assert_eq!(query_position(&mut conin, &mut out).unwrap(), (20, 10));
+        let mut conin = &b"\x1b[1t\x1b[?62c"[..];
+        assert!(!query_iconified(&mut conin, &mut out).unwrap());
+
+        let mut conin = &b"\x1b[?62c"[..];
+        let err = query_size_pixels(&mut conin, &mut out).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::Unsupported);
+
+        let mut conin = SilentConsole { clock: &clock };
+        let err = query(&mut conin, &mut out, ReportState::STR, &clock).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::TimedOut);
+    }
+}
@@ -0,0 +1,256 @@
+//! Console tests that need a terminal, run in a pseudo terminal.
+
+use std::io::Write;
+use std::time::Duration;
+
+use sl_console::input::ConsoleMouseExt;
+use sl_console::testing::{is_pty_child, Pty};
+use sl_console::*;
+
+const TIMEOUT: Duration = Duration::from_secs(5);
+
+#[test]
+fn raw_mode() {
+    if is_pty_child() {
+        let mut conout = conout();
+        conout.raw_mode_on().unwrap();
+        write!(conout, "ready").unwrap();
+        conout.flush().unwrap();
+        let key = conin().get_key().unwrap().unwrap();
+        conout.raw_mode_off().unwrap();
+        println!("got {}", key.code == event::KeyCode::Char('q'));
+        return;
+    }
+    let mut pty = Pty::spawn_test("raw_mode").unwrap();
+    pty.expect(b"", TIMEOUT).unwrap();
+    pty.expect(b"ready", TIMEOUT).unwrap();
+    // No newline, in raw mode the key is read on its own.
+    pty.send(b"q").unwrap();
+    pty.expect(b"got true", TIMEOUT).unwrap();
+    assert!(pty.wait().unwrap().success());
+}
+
+#[test]
+fn cursor_pos() {
+    if is_pty_child() {
+        conout().raw_mode_on().unwrap();
+        let pos = cursor::cursor_pos();
+        conout().raw_mode_off().unwrap();
+        println!("pos {:?}", pos.unwrap());
+        return;
+    }
+    let mut pty = Pty::spawn_test("cursor_pos").unwrap();
+    pty.expect(b"\x1B[6n", TIMEOUT).unwrap();
+    pty.send(b"\x1B[5;7R").unwrap();
+    pty.expect(b"pos (7, 5)", TIMEOUT).unwrap();
+    assert!(pty.wait().unwrap().success());
+}
+
+#[test]
+fn mouse_on() {
+    if is_pty_child() {
+        let mut conout = conout();
+        conout.mouse_on().unwrap();
+        conout.mouse_off().unwrap();
+        return;
+    }
+    let mut pty = Pty::spawn_test("mouse_on").unwrap();
+    pty.expect(b"\x1B[?1000h", TIMEOUT).unwrap();
+    pty.expect(b"\x1B[?1000l", TIMEOUT).unwrap();
+    assert!(pty.wait().unwrap().success());
+}
+
+#[test]
+fn resize_events() {
+    if is_pty_child() {
+        let mut conin = conin().lock();
+        conin.set_resize_events(true).unwrap();
+        assert!(conin.resize_events());
+        println!("ready");
+        let event = conin.get_event_and_raw(Some(TIMEOUT)).unwrap().unwrap().0;
+        conin.set_resize_events(false).unwrap();
+        println!("got {:?}", event);
+        return;
+    }
+    let mut pty = Pty::spawn_test("resize_events").unwrap();
+    pty.expect(b"ready", TIMEOUT).unwrap();
+    pty.resize(50, 20).unwrap();
+    pty.expect(b"got Resize(50, 20)", TIMEOUT).unwrap();
+    assert!(pty.wait().unwrap().success());
+}
+
+#[test]
+fn resize_channel() {
+    if is_pty_child() {
+        let resized = resize::resize_channel().unwrap();
+        println!("ready");
+        let (cols, rows) = resized.recv_timeout(TIMEOUT).unwrap();
+        println!("resized {} {}", cols, rows);
+        return;
+    }
+    let mut pty = Pty::spawn_test("resize_channel").unwrap();
+    pty.expect(b"ready", TIMEOUT).unwrap();
+    pty.resize(60, 15).unwrap();
+    pty.expect(b"resized 60 15", TIMEOUT).unwrap();
+    assert!(pty.wait().unwrap().success());
+}
+
+#[test]
+fn run_child() {
+    use std::os::unix::io::AsRawFd;
+    use std::os::unix::process::ExitStatusExt;
+    use std::process::Command;
+
+    if is_pty_child() {
+        let status = console::run_child(&mut Command::new("true")).unwrap();
+        println!("exited {}", status.success());
+        // Stops itself as Ctrl-Z would.
+        let mut stop = Command::new("sh");
+        stop.args(["-c", "kill -TSTP $$"]);
+        let status = console::run_child(&mut stop).unwrap();
+        println!("stopped {:?}", status.stopped_signal());
+        let tty = conout().as_raw_fd();
+        println!("foreground {}", unsafe {
+            libc::tcgetpgrp(tty) == libc::getpgrp()
+        });
+        return;
+    }
+    let mut pty = Pty::spawn_test("run_child").unwrap();
+    pty.expect(b"exited true", TIMEOUT).unwrap();
+    let stopped = format!("stopped Some({})", libc::SIGTSTP);
+    pty.expect(stopped.as_bytes(), TIMEOUT).unwrap();
+    pty.expect(b"foreground true", TIMEOUT).unwrap();
+    assert!(pty.wait().unwrap().success());
+}
+
+#[cfg(feature = "tokio")]
+#[test]
+fn tokio_console() {
+    use ::tokio::io::AsyncWriteExt;
+    use ::tokio::time::timeout;
+
+    if is_pty_child() {
+        let runtime = ::tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .unwrap();
+        runtime.block_on(async {
+            let mut conout = console::tokio::conout().unwrap();
+            let mut conin = console::tokio::conin().unwrap();
+            conout.write_all(b"ready\n").await.unwrap();
+            let event = conin.get_event().await.unwrap().unwrap();
+            println!("got {:?}", event);
+            // Not read until the parent sees the result, so the write has
+            // to wait without blocking the timer.
+            let big = vec![b'x'; 256 * 1024];
+            let res = timeout(Duration::from_millis(200), conout.write_all(&big)).await;
+            println!("write {}", if res.is_err() { "pending" } else { "done" });
+        });
+        return;
+    }
+    let mut pty = Pty::spawn_test("tokio_console").unwrap();
+    pty.expect(b"ready", TIMEOUT).unwrap();
+    pty.send(b"q\n").unwrap();
+    pty.expect(b"got Key(", TIMEOUT).unwrap();
+    std::thread::sleep(Duration::from_secs(1));
+    pty.expect(b"write pending", TIMEOUT).unwrap();
+    assert!(pty.wait().unwrap().success());
+}
+
+#[test]
+fn waker() {
+    if is_pty_child() {
+        let mut conin = conin().lock();
+        let waker = conin.waker();
+        let handle = std::thread::spawn(move || {
+            std::thread::sleep(Duration::from_millis(10));
+            waker.wake(event::Event::Custom(7)).unwrap();
+        });
+        let event = conin.get_event_and_raw(Some(TIMEOUT)).unwrap().unwrap().0;
+        handle.join().unwrap();
+        println!("got {:?}", event);
+        return;
+    }
+    let mut pty = Pty::spawn_test("waker").unwrap();
+    pty.expect(b"got Custom(7)", TIMEOUT).unwrap();
+    assert!(pty.wait().unwrap().success());
+}
+
+#[test]
+fn watch_fd() {
+    use std::os::unix::io::AsRawFd;
+    use std::os::unix::net::UnixStream;
+
+    if is_pty_child() {
+        let (a, mut b) = UnixStream::pair().unwrap();
+        let mut conin = conin().lock();
+        conin.watch_fd(a.as_raw_fd(), 42).unwrap();
+        b.write_all(b"x").unwrap();
+        let event = conin.get_event_and_raw(Some(TIMEOUT)).unwrap().unwrap().0;
+        conin.unwatch_fd(42);
+        println!("got {:?}", event);
+        return;
+    }
+    let mut pty = Pty::spawn_test("watch_fd").unwrap();
+    pty.expect(b"got IoReady(42)", TIMEOUT).unwrap();
+    assert!(pty.wait().unwrap().success());
+}
+
+#[test]
+fn cooked_scope() {
+    if is_pty_child() {
+        let mut out = conout().into_raw_mode().unwrap();
+        let was_raw = out.cooked_scope(|| conout().is_raw_mode()).unwrap();
+        let is_raw = out.is_raw_mode();
+        drop(out);
+        println!("raw in scope {}, after {}", was_raw, is_raw);
+        return;
+    }
+    let mut pty = Pty::spawn_test("cooked_scope").unwrap();
+    pty.expect(b"raw in scope false, after true", TIMEOUT)
+        .unwrap();
+    assert!(pty.wait().unwrap().success());
+}
+
+#[test]
+fn cancel_blocked_read() {
+    if is_pty_child() {
+        let token = console::CancelToken::new();
+        let cancel = token.clone();
+        let canceller = std::thread::spawn(move || {
+            std::thread::sleep(Duration::from_millis(20));
+            cancel.cancel();
+        });
+        let res = conin().get_event_cancellable(&token).unwrap();
+        canceller.join().unwrap();
+        println!("got {:?}", res.map_err(|err| err.kind()));
+        return;
+    }
+    let mut pty = Pty::spawn_test("cancel_blocked_read").unwrap();
+    pty.expect(b"got Err(Interrupted)", TIMEOUT).unwrap();
+    assert!(pty.wait().unwrap().success());
+}
+
+#[cfg(feature = "mio")]
+#[test]
+fn mio_source() {
+    if is_pty_child() {
+        let mut poll = mio::Poll::new().unwrap();
+        let mut conin = conin();
+        poll.registry()
+            .register(&mut conin, mio::Token(7), mio::Interest::READABLE)
+            .unwrap();
+        println!("ready");
+        let mut events = mio::Events::with_capacity(4);
+        poll.poll(&mut events, Some(TIMEOUT)).unwrap();
+        let tokens: Vec<_> = events.iter().map(|event| event.token().0).collect();
+        poll.registry().deregister(&mut conin).unwrap();
+        println!("tokens {:?}", tokens);
+        return;
+    }
+    let mut pty = Pty::spawn_test("mio_source").unwrap();
+    pty.expect(b"ready", TIMEOUT).unwrap();
+    pty.send(b"x\n").unwrap();
+    pty.expect(b"tokens [7]", TIMEOUT).unwrap();
+    assert!(pty.wait().unwrap().success());
+}